@@ -0,0 +1,76 @@
+//! Packetization and fan-out cost: contiguous `Vec<u8>` packets versus
+//! shared [`RtpPacket`]s whose payloads reference the frame.
+//!
+//! Run with `cargo bench -p rtsp-rs --bench packetize`.
+
+use std::hint::black_box;
+
+use bytes::Bytes;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rtsp::Packetizer;
+use rtsp::media::h264::H264Packetizer;
+use rtsp::media::packet::RtpPacket;
+
+/// Roughly one 4K IDR frame: SPS, PPS and a 400 KB slice.
+fn frame() -> Vec<u8> {
+    let mut frame = vec![
+        0, 0, 0, 1, 0x67, 0x64, 0x00, 0x33, 0, 0, 0, 1, 0x68, 0xee, 0x3c, 0x80,
+    ];
+    frame.extend_from_slice(&[0, 0, 0, 1, 0x65]);
+    frame.extend((0..400_000u32).map(|i| (i % 251) as u8 | 1));
+    frame
+}
+
+fn packetize(c: &mut Criterion) {
+    let frame = frame();
+    let shared = Bytes::from(frame.clone());
+    let mut group = c.benchmark_group("h264_packetize");
+    group.throughput(Throughput::Bytes(frame.len() as u64));
+
+    let mut p = H264Packetizer::new(96, 0x1234);
+    group.bench_function("vec", |b| b.iter(|| p.packetize(black_box(&frame), 3000)));
+
+    let mut p = H264Packetizer::new(96, 0x1234);
+    group.bench_function("bytes", |b| {
+        b.iter(|| p.packetize_bytes(black_box(shared.clone()), 3000))
+    });
+    group.finish();
+}
+
+/// Per-viewer cost of handing a frame's packets to `viewers` senders.
+fn fan_out(c: &mut Criterion) {
+    let frame = frame();
+    let mut group = c.benchmark_group("h264_fan_out");
+    group.throughput(Throughput::Bytes(frame.len() as u64));
+
+    for viewers in [1usize, 8, 32] {
+        let mut p = H264Packetizer::new(96, 0x1234);
+        group.bench_with_input(BenchmarkId::new("vec", viewers), &viewers, |b, &n| {
+            b.iter(|| {
+                let packets = p.packetize(&frame, 3000);
+                for _ in 0..n {
+                    for packet in &packets {
+                        black_box(packet.clone());
+                    }
+                }
+            })
+        });
+
+        let mut p = H264Packetizer::new(96, 0x1234);
+        let shared = Bytes::from(frame.clone());
+        group.bench_with_input(BenchmarkId::new("bytes", viewers), &viewers, |b, &n| {
+            b.iter(|| {
+                let packets: Vec<RtpPacket> = p.packetize_bytes(shared.clone(), 3000);
+                for _ in 0..n {
+                    for packet in &packets {
+                        black_box(packet.clone());
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, packetize, fan_out);
+criterion_main!(benches);
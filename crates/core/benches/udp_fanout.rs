@@ -0,0 +1,80 @@
+//! Loopback fan-out of one H.264 frame to many viewers through each
+//! [`UdpTransport`] send path: a `sendmsg` per packet, batched `sendmmsg`,
+//! and batched `sendmmsg` with UDP GSO (Linux).
+//!
+//! Run with `cargo bench -p rtsp-rs --bench udp_fanout`. Receivers are
+//! never drained, so the kernel drops datagrams once their buffers fill;
+//! the numbers measure the sender's cost.
+
+use std::hint::black_box;
+use std::net::{SocketAddr, UdpSocket};
+
+use bytes::Bytes;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rtsp::Packetizer;
+use rtsp::media::h264::H264Packetizer;
+use rtsp::media::packet::RtpPacket;
+use rtsp::transport::UdpTransport;
+
+/// One ~100 KB slice, fragmented into ~70 FU-A packets.
+fn packets() -> Vec<RtpPacket> {
+    let mut frame = vec![0, 0, 0, 1, 0x65];
+    frame.extend((0..100_000u32).map(|i| (i % 251) as u8 | 1));
+    H264Packetizer::new(96, 0x1234).packetize_bytes(Bytes::from(frame), 3000)
+}
+
+fn viewers(n: usize) -> (Vec<UdpSocket>, Vec<SocketAddr>) {
+    let sockets: Vec<UdpSocket> = (0..n)
+        .map(|_| UdpSocket::bind("127.0.0.1:0").unwrap())
+        .collect();
+    let addrs = sockets.iter().map(|s| s.local_addr().unwrap()).collect();
+    (sockets, addrs)
+}
+
+fn fan_out(c: &mut Criterion) {
+    let packets = packets();
+    let frame_bytes: usize = packets.iter().map(RtpPacket::len).sum();
+
+    let plain = UdpTransport::bind().unwrap();
+    let gso = UdpTransport::bind().unwrap();
+    let gso_available = gso.enable_gso();
+
+    let mut group = c.benchmark_group("udp_fan_out");
+    group.sample_size(20);
+    for n in [1usize, 100, 500] {
+        let (_receivers, addrs) = viewers(n);
+        group.throughput(Throughput::Bytes((frame_bytes * n) as u64));
+
+        group.bench_with_input(BenchmarkId::new("sendmsg", n), &addrs, |b, addrs| {
+            b.iter(|| {
+                for &addr in addrs {
+                    for packet in &packets {
+                        black_box(plain.send_packet(packet, addr).unwrap());
+                    }
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batched", n), &addrs, |b, addrs| {
+            b.iter(|| {
+                for &addr in addrs {
+                    black_box(plain.send_packets(&packets, addr).unwrap());
+                }
+            })
+        });
+
+        if gso_available {
+            group.bench_with_input(BenchmarkId::new("batched_gso", n), &addrs, |b, addrs| {
+                b.iter(|| {
+                    for &addr in addrs {
+                        black_box(gso.send_packets(&packets, addr).unwrap());
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fan_out);
+criterion_main!(benches);
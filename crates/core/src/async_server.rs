@@ -0,0 +1,626 @@
+//! Tokio front end for the RTSP server (`tokio` feature).
+//!
+//! [`AsyncServer`] shares the protocol, session and mount layers — and RTP
+//! delivery itself — with [`Server`](crate::Server); only the I/O driving
+//! it differs:
+//!
+//! - RTSP connections are accepted asynchronously and each runs as a task,
+//!   so thousands of control connections cost no threads.
+//! - The `send_*` methods are `async`, but RTP and RTCP still go through
+//!   the blocking UDP sockets [`Server`](crate::Server) uses, not tokio
+//!   sockets: UDP fan-out (batched `sendmmsg`, GSO,
+//!   [pacing](crate::Pacing), [send queues](crate::SendQueueConfig)) runs
+//!   on tokio's blocking pool so a slow socket never stalls a runtime
+//!   worker, and RTCP feedback is received by a task on that pool.
+//!   Interleaved TCP viewers are written by their connection's task.
+//!
+//! ```no_run
+//! # async fn run() -> rtsp::Result<()> {
+//! use rtsp::AsyncServer;
+//!
+//! let mut server = AsyncServer::new("0.0.0.0:8554");
+//! server.start().await?;
+//! // server.send_frame_to("/stream", &h264_data, 3000).await?;
+//! server.stop().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::error::{Result, RtspError};
+use crate::hls::{HlsConfig, HlsServer};
+use crate::media::Packetizer;
+use crate::media::h264::H264Packetizer;
+use crate::mount::{DEFAULT_MOUNT_PATH, Mount, MountRegistry, Track};
+use crate::playback::{MediaFile, MediaSource};
+use crate::record::RecordingConfig;
+use crate::send_queue::FrameTiming;
+use crate::server::{
+    self, Delivery, SendQueues, ServerConfig, Viewer, find_mount, find_track, parse_bind_addr,
+};
+use crate::session::{RandomSessionIds, SessionIdGenerator, SessionManager};
+use crate::transport::{async_tcp, tcp};
+
+/// RTSP server running on a tokio runtime.
+///
+/// Mirrors [`Server`](crate::Server): mounts, tracks, configuration and
+/// viewers work the same way; [`start`](Self::start),
+/// [`stop`](Self::stop), [`stop_recording`](Self::stop_recording) and the
+/// `send_*`/`broadcast_*` methods are `async` and must be called within a
+/// tokio runtime.
+pub struct AsyncServer {
+    session_manager: SessionManager,
+    mounts: MountRegistry,
+    bind_addr: String,
+    config: Arc<ServerConfig>,
+    delivery: Option<Arc<Delivery>>,
+    send_queues: Arc<SendQueues>,
+    local_addr: Option<SocketAddr>,
+    /// Turns `true` to stop the accept and connection tasks.
+    shutdown: Option<watch::Sender<bool>>,
+    tasks: Vec<JoinHandle<()>>,
+    /// Player threads of seekable mounts and the RTCP receiver, which run
+    /// while `running` is set.
+    players: Vec<thread::JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+    hls: Option<HlsServer>,
+}
+
+impl AsyncServer {
+    /// Create a server with a default H.264 mount at `/stream`.
+    ///
+    /// `bind_addr` must be `host:port`; port 0 picks a free port (see
+    /// [`local_addr`](Self::local_addr)).
+    pub fn new(bind_addr: &str) -> Self {
+        Self::with_config(bind_addr, ServerConfig::default())
+    }
+
+    /// Create a server with custom protocol/SDP configuration.
+    /// A default H.264 mount at `/stream` is created automatically.
+    pub fn with_config(bind_addr: &str, config: ServerConfig) -> Self {
+        Self::with_packetizer_and_config(
+            bind_addr,
+            Box::new(H264Packetizer::with_random_ssrc(96)),
+            config,
+        )
+    }
+
+    /// Create a server with a custom packetizer and protocol/SDP configuration.
+    pub fn with_packetizer_and_config(
+        bind_addr: &str,
+        packetizer: Box<dyn Packetizer>,
+        config: ServerConfig,
+    ) -> Self {
+        let mounts = MountRegistry::new();
+        mounts.add(DEFAULT_MOUNT_PATH, packetizer);
+        mounts.set_default(DEFAULT_MOUNT_PATH);
+
+        Self {
+            session_manager: SessionManager::with_id_generator(RandomSessionIds::new(
+                config.session_id_length,
+            )),
+            mounts,
+            bind_addr: bind_addr.to_string(),
+            config: Arc::new(config),
+            delivery: None,
+            send_queues: Arc::new(SendQueues::default()),
+            local_addr: None,
+            shutdown: None,
+            tasks: Vec::new(),
+            players: Vec::new(),
+            running: Arc::new(AtomicBool::new(false)),
+            hls: None,
+        }
+    }
+
+    /// Register a named mount with its own packetizer (see
+    /// [`Server::add_mount`](crate::Server::add_mount)).
+    pub fn add_mount(&self, path: &str, packetizer: Box<dyn Packetizer>) -> Arc<Mount> {
+        self.mounts.add(path, packetizer)
+    }
+
+    /// Register a seekable mount (see
+    /// [`Server::add_seekable_mount`](crate::Server::add_seekable_mount)).
+    pub fn add_seekable_mount(
+        &self,
+        path: &str,
+        packetizer: Box<dyn Packetizer>,
+        source: Box<dyn MediaSource>,
+    ) -> Arc<Mount> {
+        let mount = self.mounts.add(path, packetizer);
+        mount.set_source(source);
+        mount
+    }
+
+    /// Register a seekable mount that plays a media file (see
+    /// [`Server::add_file_mount`](crate::Server::add_file_mount)).
+    pub fn add_file_mount(&self, path: &str, file: MediaFile) -> Arc<Mount> {
+        file.mount(&self.mounts, path)
+    }
+
+    /// Add a track (audio, metadata, ...) to an existing mount (see
+    /// [`Server::add_track`](crate::Server::add_track)).
+    pub fn add_track(
+        &self,
+        mount_path: &str,
+        packetizer: Box<dyn Packetizer>,
+    ) -> Result<Arc<Track>> {
+        Ok(find_mount(&self.mounts, mount_path)?.add_track(packetizer))
+    }
+
+    /// Start recording a mount (see
+    /// [`Server::start_recording`](crate::Server::start_recording)).
+    pub fn start_recording(&self, mount_path: &str, config: RecordingConfig) -> Result<()> {
+        find_mount(&self.mounts, mount_path)?.start_recording(config)
+    }
+
+    /// Stop recording a mount, waiting off the runtime for the writer to
+    /// flush. Returns the segment files written.
+    pub async fn stop_recording(&self, mount_path: &str) -> Result<Vec<PathBuf>> {
+        let mount = find_mount(&self.mounts, mount_path)?;
+        blocking(move || mount.stop_recording()).await
+    }
+
+    /// Bind the RTSP listener and RTP/RTCP sockets and spawn the accept
+    /// task on the current runtime and the RTCP receiver on its blocking
+    /// pool.
+    pub async fn start(&mut self) -> Result<()> {
+        if self.is_running() {
+            return Err(RtspError::AlreadyRunning);
+        }
+
+        parse_bind_addr(&self.bind_addr)?;
+        let delivery = Delivery::bind(&self.session_manager, &self.config)?;
+        let (server_rtp_port, server_rtcp_port) = delivery.udp().local_ports()?;
+
+        let listener = TcpListener::bind(&self.bind_addr).await?;
+        let local_addr = listener.local_addr()?;
+        if let Some(hls) = &self.config.hls {
+            self.hls = Some(HlsServer::start(hls.clone(), self.mounts.clone())?);
+        }
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        self.tasks.push(tokio::spawn(async_tcp::accept_loop(
+            listener,
+            self.session_manager.clone(),
+            self.mounts.clone(),
+            self.config.clone(),
+            shutdown_rx,
+        )));
+
+        self.running.store(true, Ordering::SeqCst);
+        let rtcp_udp = delivery.udp().clone();
+        let session_manager = self.session_manager.clone();
+        let mounts = self.mounts.clone();
+        let running = self.running.clone();
+        self.tasks.push(tokio::task::spawn_blocking(move || {
+            server::rtcp_loop(rtcp_udp, session_manager, mounts, running);
+        }));
+
+        tracing::info!(
+            addr = %local_addr,
+            server_rtp_port,
+            server_rtcp_port,
+            "RTSP server listening (tokio)"
+        );
+
+        self.players = server::spawn_players(
+            &self.mounts,
+            &delivery,
+            &self.send_queues,
+            &self.session_manager,
+            self.running.clone(),
+        );
+
+        self.delivery = Some(delivery);
+        self.local_addr = Some(local_addr);
+        self.shutdown = Some(shutdown);
+        Ok(())
+    }
+
+    /// Shut the server down the way [`Server::stop`](crate::Server::stop)
+    /// does: flush send queues and pacers, send viewers an RTCP BYE, close
+    /// every connection once its queued output is written, and remove all
+    /// sessions. The server may be started again afterwards.
+    pub async fn stop(&mut self) {
+        let Some(shutdown) = self.shutdown.take() else {
+            return;
+        };
+        tracing::info!("server stopping");
+
+        self.running.store(false, Ordering::SeqCst);
+        let players = std::mem::take(&mut self.players);
+        if let Some(delivery) = self.delivery.take() {
+            let send_queues = self.send_queues.clone();
+            let mounts = self.mounts.clone();
+            let hls = self.hls.take();
+            let _ = tokio::task::spawn_blocking(move || {
+                if let Some(hls) = hls {
+                    hls.stop();
+                }
+                for player in players {
+                    if !tcp::join_timeout(player, server::SHUTDOWN_TIMEOUT) {
+                        tracing::warn!("player thread did not exit in time, detaching it");
+                    }
+                }
+                delivery.flush(&send_queues);
+                delivery.send_bye(&mounts);
+            })
+            .await;
+        }
+        shutdown.send_replace(true);
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+
+        self.local_addr = None;
+        self.mounts.clear_subscriptions();
+        let removed = self.session_manager.clear();
+        tracing::info!(removed, "server stopped");
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    /// Address the RTSP listener is bound to; `None` while not running.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Serve every mount as HLS over HTTP, or stop doing so, from the next
+    /// [`start`](Self::start) on (see
+    /// [`Server::set_hls`](crate::Server::set_hls)).
+    pub fn set_hls(&mut self, config: Option<HlsConfig>) {
+        Arc::make_mut(&mut self.config).hls = config;
+    }
+
+    /// Address the HLS HTTP server is bound to (see
+    /// [`Server::hls_addr`](crate::Server::hls_addr)).
+    pub fn hls_addr(&self) -> Option<SocketAddr> {
+        self.hls.as_ref().map(HlsServer::local_addr)
+    }
+
+    /// Send a raw encoded frame to the default mount (`/stream`) (see
+    /// [`Server::send_frame`](crate::Server::send_frame)).
+    pub async fn send_frame(&self, data: &[u8], timestamp_increment: u32) -> Result<usize> {
+        self.send_frame_to(DEFAULT_MOUNT_PATH, data, timestamp_increment)
+            .await
+    }
+
+    /// Send a raw encoded frame to a specific mount (see
+    /// [`Server::send_frame_to`](crate::Server::send_frame_to)).
+    pub async fn send_frame_to(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        timestamp_increment: u32,
+    ) -> Result<usize> {
+        self.send_frame_bytes(
+            mount_path,
+            Bytes::copy_from_slice(data),
+            timestamp_increment,
+        )
+        .await
+    }
+
+    /// Like [`send_frame_to`](Self::send_frame_to), taking ownership of a
+    /// shared buffer.
+    pub async fn send_frame_bytes(
+        &self,
+        mount_path: &str,
+        data: Bytes,
+        timestamp_increment: u32,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            0,
+            data,
+            FrameTiming::Increment(timestamp_increment),
+        )
+        .await
+    }
+
+    /// Send a raw encoded frame to one track of a mount (see
+    /// [`Server::send_track_frame`](crate::Server::send_track_frame)).
+    pub async fn send_track_frame(
+        &self,
+        mount_path: &str,
+        track: usize,
+        data: &[u8],
+        timestamp_increment: u32,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            track,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Increment(timestamp_increment),
+        )
+        .await
+    }
+
+    /// Send a raw encoded frame stamped with its presentation timestamp
+    /// (see [`Server::send_frame_with_pts`](crate::Server::send_frame_with_pts)).
+    pub async fn send_frame_with_pts(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send_frame_bytes_with_pts(mount_path, Bytes::copy_from_slice(data), pts)
+            .await
+    }
+
+    /// Like [`send_frame_with_pts`](Self::send_frame_with_pts), taking
+    /// ownership of a shared buffer.
+    pub async fn send_frame_bytes_with_pts(
+        &self,
+        mount_path: &str,
+        data: Bytes,
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send(mount_path, 0, data, FrameTiming::Pts { pts, dts: None })
+            .await
+    }
+
+    /// Send a raw encoded frame stamped with both its presentation and
+    /// decode timestamps (see
+    /// [`Server::send_frame_with_pts_dts`](crate::Server::send_frame_with_pts_dts)).
+    pub async fn send_frame_with_pts_dts(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        pts: Duration,
+        dts: Duration,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            0,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Pts {
+                pts,
+                dts: Some(dts),
+            },
+        )
+        .await
+    }
+
+    /// Send a frame to one track of a mount, stamped with its presentation
+    /// timestamp (see
+    /// [`Server::send_track_frame_with_pts`](crate::Server::send_track_frame_with_pts)).
+    pub async fn send_track_frame_with_pts(
+        &self,
+        mount_path: &str,
+        track: usize,
+        data: &[u8],
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            track,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Pts { pts, dts: None },
+        )
+        .await
+    }
+
+    async fn send(
+        &self,
+        mount_path: &str,
+        track_index: usize,
+        data: Bytes,
+        timing: FrameTiming,
+    ) -> Result<usize> {
+        let delivery = self.delivery.clone().ok_or(RtspError::NotStarted)?;
+        let mount = find_mount(&self.mounts, mount_path)?;
+        let track = find_track(&mount, track_index)?;
+        let send_queues = self.send_queues.clone();
+        blocking(move || Ok(delivery.submit(&send_queues, &mount, track, data, timing))).await
+    }
+
+    /// Send a pre-packetized RTP packet to a specific session (see
+    /// [`Server::send_rtp_packet`](crate::Server::send_rtp_packet)).
+    pub async fn send_rtp_packet(&self, session_id: &str, payload: &[u8]) -> Result<usize> {
+        let delivery = self.delivery.clone().ok_or(RtspError::NotStarted)?;
+        let session_manager = self.session_manager.clone();
+        let session_id = session_id.to_string();
+        let payload = payload.to_vec();
+        blocking(move || {
+            server::send_rtp_packet(delivery.udp(), &session_manager, &session_id, &payload)
+        })
+        .await
+    }
+
+    /// Broadcast a pre-packetized RTP packet to all playing sessions on the
+    /// default mount (see
+    /// [`Server::broadcast_rtp_packet`](crate::Server::broadcast_rtp_packet)).
+    pub async fn broadcast_rtp_packet(&self, payload: &[u8]) -> Result<usize> {
+        let delivery = self.delivery.clone().ok_or(RtspError::NotStarted)?;
+        let mount = find_mount(&self.mounts, DEFAULT_MOUNT_PATH)?;
+        let session_manager = self.session_manager.clone();
+        let payload = payload.to_vec();
+        blocking(move || {
+            Ok(server::broadcast_rtp_packet(
+                delivery.udp(),
+                &session_manager,
+                &mount,
+                &payload,
+            ))
+        })
+        .await
+    }
+
+    pub fn get_viewers(&self) -> Vec<Viewer> {
+        server::playing_viewers(&self.session_manager)
+    }
+
+    pub fn session_manager(&self) -> &SessionManager {
+        &self.session_manager
+    }
+
+    /// See [`Server::set_session_id_generator`](crate::Server::set_session_id_generator).
+    pub fn set_session_id_generator(&self, generator: impl SessionIdGenerator + 'static) {
+        self.session_manager.set_id_generator(generator);
+    }
+
+    /// Returns the mount registry (used by adapters that need mount access).
+    pub fn mounts(&self) -> &MountRegistry {
+        &self.mounts
+    }
+
+    /// Returns the server's protocol configuration.
+    pub fn config(&self) -> Arc<ServerConfig> {
+        self.config.clone()
+    }
+}
+
+impl Drop for AsyncServer {
+    /// Signal the tasks to stop; they finish on the runtime. Call
+    /// [`stop`](Self::stop) to wait for them.
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send_replace(true);
+        }
+    }
+}
+
+/// Run `f` on tokio's blocking pool, re-raising a panic in the caller.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(RtspError::Io(std::io::Error::other(e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    async fn request(stream: &mut BufReader<TcpStream>, text: &str) -> String {
+        stream.get_mut().write_all(text.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            response.push_str(&line);
+            if line == "\r\n" || line.is_empty() {
+                return response;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_interleaved_viewer_and_stops() {
+        let mut server = AsyncServer::new("127.0.0.1:0");
+        server.start().await.unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(matches!(
+            server.start().await,
+            Err(RtspError::AlreadyRunning)
+        ));
+
+        let mut stream = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let uri = format!("rtsp://{addr}/stream");
+        let setup = request(
+            &mut stream,
+            &format!(
+                "SETUP {uri} RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP/TCP;unicast;interleaved=0-1\r\n\r\n"
+            ),
+        )
+        .await;
+        assert!(setup.starts_with("RTSP/1.0 200 OK"), "{setup}");
+        let session = setup
+            .lines()
+            .find_map(|l| l.strip_prefix("Session: "))
+            .and_then(|v| v.split(';').next())
+            .unwrap()
+            .to_string();
+
+        let play = request(
+            &mut stream,
+            &format!("PLAY {uri} RTSP/1.0\r\nCSeq: 2\r\nSession: {session}\r\n\r\n"),
+        )
+        .await;
+        assert!(play.starts_with("RTSP/1.0 200 OK"), "{play}");
+
+        let idr = [0, 0, 0, 1, 0x65, 0x88, 0x84];
+        assert_eq!(
+            server.send_frame_to("/stream", &idr, 3000).await.unwrap(),
+            1
+        );
+        let mut prefix = [0u8; 4];
+        stream.read_exact(&mut prefix).await.unwrap();
+        assert_eq!(prefix, [b'$', 0, 0, 15]);
+        let mut packet = [0u8; 15];
+        stream.read_exact(&mut packet).await.unwrap();
+        assert_eq!(&packet[12..], &idr[4..]);
+
+        assert_eq!(server.broadcast_rtp_packet(&packet).await.unwrap(), 1);
+        assert_eq!(
+            server.send_rtp_packet(&session, &packet).await.unwrap(),
+            packet.len()
+        );
+        for _ in 0..2 {
+            let mut echoed = [0u8; 4 + 15];
+            stream.read_exact(&mut echoed).await.unwrap();
+            assert_eq!(echoed[..4], [b'$', 0, 0, 15]);
+            assert_eq!(echoed[4..], packet);
+        }
+
+        server.stop().await;
+        assert!(!server.is_running());
+        assert!(server.session_manager().get_session(&session).is_none());
+        // An RTCP BYE on the RTCP channel, then the connection closes.
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest[..2], [b'$', 1]);
+        assert_eq!(rest[4 + 9], 203);
+    }
+
+    #[tokio::test]
+    async fn serves_http_tunnels() {
+        use base64::prelude::{BASE64_STANDARD, Engine as _};
+
+        let mut server = AsyncServer::new("127.0.0.1:0");
+        server.start().await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let mut get = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let head = request(
+            &mut get,
+            "GET /stream HTTP/1.0\r\nx-sessioncookie: k4Tz\r\nAccept: application/x-rtsp-tunnelled\r\n\r\n",
+        )
+        .await;
+        assert!(head.starts_with("HTTP/1.0 200 OK"), "{head}");
+
+        let mut post = TcpStream::connect(addr).await.unwrap();
+        let options = BASE64_STANDARD.encode("OPTIONS * RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+        let body = format!(
+            "POST /stream HTTP/1.0\r\nx-sessioncookie: k4Tz\r\nContent-Type: application/x-rtsp-tunnelled\r\n\r\n{options}"
+        );
+        post.write_all(body.as_bytes()).await.unwrap();
+        let response = request(&mut get, "").await;
+        assert!(response.starts_with("RTSP/1.0 200 OK"), "{response}");
+
+        // Stopping closes both channels.
+        server.stop().await;
+        let mut rest = Vec::new();
+        get.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(post.read_to_end(&mut rest).await.unwrap(), 0);
+    }
+}
@@ -10,18 +10,26 @@ use std::fmt;
 /// - **Transport**: [`Io`](Self::Io) — socket/network failures.
 /// - **Session**: [`SessionNotFound`](Self::SessionNotFound),
 ///   [`SessionNotPlaying`](Self::SessionNotPlaying),
-///   [`TransportNotConfigured`](Self::TransportNotConfigured).
+///   [`TransportNotConfigured`](Self::TransportNotConfigured),
+///   [`InvalidSessionId`](Self::InvalidSessionId),
+///   [`DuplicateSessionId`](Self::DuplicateSessionId).
 /// - **Server**: [`NotStarted`](Self::NotStarted),
 ///   [`AlreadyRunning`](Self::AlreadyRunning).
-/// - **Mount**: [`MountNotFound`](Self::MountNotFound).
-/// - **Server**: [`InvalidBindAddress`](Self::InvalidBindAddress) — bind address must have an explicit non-zero port.
+/// - **Mount**: [`MountNotFound`](Self::MountNotFound),
+///   [`TrackNotFound`](Self::TrackNotFound),
+///   [`InvalidRange`](Self::InvalidRange),
+///   [`InvalidMediaFile`](Self::InvalidMediaFile).
+/// - **Recording**: [`AlreadyRecording`](Self::AlreadyRecording),
+///   [`NotRecording`](Self::NotRecording), [`Recording`](Self::Recording).
+/// - **HLS**: [`Hls`](Self::Hls).
+/// - **Server**: [`InvalidBindAddress`](Self::InvalidBindAddress) — bind address must be `host:port`.
 #[derive(Debug, thiserror::Error)]
 pub enum RtspError {
     /// Underlying I/O or socket error.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
-    /// Bind address must be `host:port` with an explicit non-zero port (port 0 is not allowed).
+    /// Bind address must be `host:port` (port 0 picks a free port).
     #[error("invalid bind address: {0}")]
     InvalidBindAddress(String),
 
@@ -29,6 +37,15 @@ pub enum RtspError {
     #[error("session not found: {0}")]
     SessionNotFound(String),
 
+    /// A [`SessionIdGenerator`](crate::session::SessionIdGenerator) produced
+    /// an ID that cannot be sent in a `Session` header (RFC 2326 §3.4).
+    #[error("invalid session ID: {0}")]
+    InvalidSessionId(String),
+
+    /// The session ID generator kept producing IDs already in use.
+    #[error("session ID already in use: {0}")]
+    DuplicateSessionId(String),
+
     /// SETUP has not been completed for this session (no UDP ports negotiated).
     #[error("transport not configured for session: {0}")]
     TransportNotConfigured(String),
@@ -56,6 +73,38 @@ pub enum RtspError {
     /// No mount registered at the requested path.
     #[error("mount not found: {0}")]
     MountNotFound(String),
+
+    /// A PLAY `Range` lies outside a seekable mount's presentation
+    /// (RFC 2326 §11.3.14, 457 Invalid Range).
+    #[error("invalid range: {0}")]
+    InvalidRange(String),
+
+    /// A file given to [`MediaFile`](crate::playback::MediaFile) is not a
+    /// supported format, or is malformed.
+    #[error("invalid media file: {0}")]
+    InvalidMediaFile(String),
+
+    /// [`Mount::start_recording`](crate::Mount::start_recording) was called
+    /// on a mount that is already recording.
+    #[error("mount already recording: {0}")]
+    AlreadyRecording(String),
+
+    /// [`Mount::stop_recording`](crate::Mount::stop_recording) was called on
+    /// a mount that is not recording.
+    #[error("mount not recording: {0}")]
+    NotRecording(String),
+
+    /// A mount cannot be recorded (no recordable track).
+    #[error("recording error: {0}")]
+    Recording(String),
+
+    /// A mount cannot be served as HLS (no track in a supported codec).
+    #[error("HLS error: {0}")]
+    Hls(String),
+
+    /// The mount has no track at the requested index.
+    #[error("track {track} not found on mount {mount}")]
+    TrackNotFound { mount: String, track: usize },
 }
 
 /// Specific kind of RTSP parse failure.
@@ -0,0 +1,326 @@
+//! The HLS HTTP/1.1 server: a nonblocking accept loop and one thread per
+//! connection, with keep-alive.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use super::segmenter::Resource;
+use super::stream::Unavailable;
+use super::{HlsConfig, HlsFormat, IDLE_TIMEOUT};
+use crate::error::Result;
+use crate::mount::MountRegistry;
+use crate::server::SHUTDOWN_TIMEOUT;
+use crate::transport::tcp;
+
+/// How often blocked reads and the accept loop check for shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Idle keep-alive connections are closed after this long.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest request head accepted.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// A running HLS HTTP server.
+pub(crate) struct HlsServer {
+    running: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+    mounts: MountRegistry,
+    accept: Option<JoinHandle<()>>,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl HlsServer {
+    /// Bind `config.bind_addr` and start serving the mounts of `mounts`.
+    pub(crate) fn start(config: HlsConfig, mounts: MountRegistry) -> Result<Self> {
+        let listener = TcpListener::bind(&config.bind_addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let connections = Arc::new(Mutex::new(Vec::new()));
+
+        let accept = {
+            let running = running.clone();
+            let mounts = mounts.clone();
+            let connections = connections.clone();
+            thread::Builder::new()
+                .name("hls accept".to_string())
+                .spawn(move || accept_loop(listener, config, mounts, running, connections))?
+        };
+        tracing::info!(addr = %local_addr, "HLS server listening");
+        Ok(Self {
+            running,
+            local_addr,
+            mounts,
+            accept: Some(accept),
+            connections,
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting, stop segmenting every mount, and wait (bounded) for
+    /// the connection threads.
+    pub(crate) fn stop(mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+        for mount in self.mounts.all() {
+            mount.stop_hls();
+        }
+        for connection in self.connections.lock().drain(..) {
+            if !tcp::join_timeout(connection, SHUTDOWN_TIMEOUT) {
+                tracing::warn!("HLS connection thread did not exit in time, detaching it");
+            }
+        }
+        tracing::info!("HLS server stopped");
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    config: HlsConfig,
+    mounts: MountRegistry,
+    running: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+) {
+    let config = Arc::new(config);
+    let mut last_sweep = Instant::now();
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer_addr)) => {
+                if stream.set_nonblocking(false).is_err() {
+                    continue;
+                }
+                let config = config.clone();
+                let mounts = mounts.clone();
+                let running = running.clone();
+                let mut connections = connections.lock();
+                connections.retain(|c| !c.is_finished());
+                connections.push(thread::spawn(move || {
+                    serve(stream, peer_addr, &config, &mounts, &running);
+                }));
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "HLS accept error");
+            }
+        }
+        if last_sweep.elapsed() >= Duration::from_secs(1) {
+            last_sweep = Instant::now();
+            for mount in mounts.all() {
+                if mount
+                    .hls()
+                    .is_some_and(|hls| hls.idle_for() >= IDLE_TIMEOUT)
+                {
+                    tracing::debug!(mount = mount.path(), "no HLS requests, segmenting stopped");
+                    mount.stop_hls();
+                }
+            }
+        }
+    }
+    tracing::debug!("HLS accept loop exited");
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    /// `Cache-Control` value.
+    cache: &'static str,
+    body: Bytes,
+}
+
+impl Response {
+    fn error(status: u16) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            cache: "no-cache",
+            body: Bytes::from(format!("{} {}\n", status, reason(status))),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
+
+/// Serve requests on one connection until the client closes it, it idles
+/// out, or the server stops.
+fn serve(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    config: &HlsConfig,
+    mounts: &MountRegistry,
+    running: &AtomicBool,
+) {
+    if stream.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 2048];
+    let mut last_activity = Instant::now();
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..end]).into_owned();
+            buf.drain(..end + 4);
+            let Some((response, head_only, keep_alive)) = handle(&head, config, mounts) else {
+                break;
+            };
+            if write_response(&mut stream, &response, head_only, keep_alive).is_err() || !keep_alive
+            {
+                break;
+            }
+            last_activity = Instant::now();
+            continue;
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            let _ = write_response(&mut stream, &Response::error(400), false, false);
+            break;
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(ref e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if !running.load(Ordering::SeqCst) || last_activity.elapsed() >= KEEP_ALIVE_TIMEOUT
+                {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::debug!(%peer_addr, error = %e, "HLS connection error");
+                break;
+            }
+        }
+    }
+}
+
+/// Answer a request head. Returns the response, whether it is for a
+/// `HEAD`, and whether to keep the connection open; `None` for requests
+/// that are not HTTP.
+fn handle(
+    head: &str,
+    config: &HlsConfig,
+    mounts: &MountRegistry,
+) -> Option<(Response, bool, bool)> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let (method, target, version) = (
+        request_line.next()?,
+        request_line.next()?,
+        request_line.next()?,
+    );
+    if !version.starts_with("HTTP/") {
+        return None;
+    }
+    let connection = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("connection")
+            .then(|| value.trim().to_ascii_lowercase())
+    });
+    let keep_alive = match connection.as_deref() {
+        Some("close") => false,
+        Some("keep-alive") => true,
+        _ => version == "HTTP/1.1",
+    };
+    let response = match method {
+        "GET" | "HEAD" => route(target, config, mounts),
+        _ => Response::error(405),
+    };
+    Some((response, method == "HEAD", keep_alive))
+}
+
+/// Map `/<mount>/<file>?<query>` to a mount's HLS output.
+fn route(target: &str, config: &HlsConfig, mounts: &MountRegistry) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let Some((mount_path, name)) = path.rsplit_once('/') else {
+        return Response::error(404);
+    };
+    let (Some(mount), Some(resource)) =
+        (mounts.get(mount_path), Resource::parse(name, config.format))
+    else {
+        return Response::error(404);
+    };
+    let hls = match mount.hls_stream(config) {
+        Ok(hls) => hls,
+        Err(e) => {
+            tracing::debug!(mount = mount_path, error = %e, "mount cannot be served as HLS");
+            return Response::error(404);
+        }
+    };
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    };
+    let msn = param("_HLS_msn").and_then(|v| v.parse().ok());
+    let part = param("_HLS_part").and_then(|v| v.parse().ok());
+    if part.is_some() && msn.is_none() {
+        return Response::error(400);
+    }
+
+    let (content_type, cache) = match (resource, config.format) {
+        (Resource::Playlist, _) => ("application/vnd.apple.mpegurl", "no-cache"),
+        (Resource::Init(_), _) => ("video/mp4", "max-age=3600"),
+        (_, HlsFormat::Fmp4) => ("video/iso.segment", "max-age=3600"),
+        (_, HlsFormat::Ts) => ("video/mp2t", "max-age=3600"),
+    };
+    match hls.get(resource, msn, part) {
+        Ok(body) => Response {
+            status: 200,
+            content_type,
+            cache,
+            body,
+        },
+        Err(Unavailable::NotFound) => Response::error(404),
+        Err(Unavailable::BadRequest) => Response::error(400),
+        Err(Unavailable::Timeout) => Response::error(503),
+    }
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    response: &Response,
+    head_only: bool,
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: {}\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len(),
+        response.cache,
+        if keep_alive { "keep-alive" } else { "close" },
+    );
+    stream.write_all(head.as_bytes())?;
+    if !head_only {
+        stream.write_all(&response.body)?;
+    }
+    stream.flush()
+}
@@ -0,0 +1,131 @@
+//! HLS and Low-Latency HLS output of mounts over HTTP.
+//!
+//! With [`ServerConfig::hls`](crate::ServerConfig::hls) set, the server
+//! runs a small HTTP/1.1 server next to the RTSP listener that serves every
+//! mount as an HLS media playlist (RFC 8216) for browsers and other
+//! players that cannot speak RTSP: mount `/stream` is played from
+//! `http://<host>:<port>/stream/index.m3u8`.
+//!
+//! A mount is segmented from its first playlist request on, which waits
+//! for the first segment, until no client has asked for it for
+//! [`IDLE_TIMEOUT`]. Segmentation taps the frames the mount packetizes
+//! (from `send_frame*`, a send queue, or a seekable mount's player while
+//! it plays), the same way [recording](crate::record) does, so it follows
+//! the producer's timing exactly and costs RTSP viewers nothing.
+//!
+//! - **Formats** ([`HlsFormat`]): CMAF fragmented MP4 with H.264, H.265
+//!   and AAC tracks, or MPEG-TS with one H.264 and one AAC track. Other
+//!   tracks are left out.
+//! - **Segments** start at keyframes of the first video track, once the
+//!   current one holds [`segment_duration`](HlsConfig::segment_duration)
+//!   of media, so GOPs longer than that make longer segments. A change of
+//!   parameter sets starts a new segment after an `EXT-X-DISCONTINUITY`,
+//!   with a new `EXT-X-MAP` for fMP4.
+//! - **Playlists** roll: they list the last
+//!   [`playlist_length`](HlsConfig::playlist_length) complete segments.
+//!   Segments that left the playlist stay available for a little longer
+//!   for clients that are behind.
+//! - **Low latency** ([`part_duration`](HlsConfig::part_duration)): each
+//!   segment is also published as partial segments (`EXT-X-PART`) while it
+//!   is written, with an `EXT-X-PRELOAD-HINT` for the next part. Playlist
+//!   requests with `_HLS_msn`/`_HLS_part` and requests for the hinted part
+//!   block until it is available.
+//!
+//! Responses carry `Access-Control-Allow-Origin: *`, so players on other
+//! origins (hls.js) can fetch them.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rtsp::{Server, ServerConfig};
+//! use rtsp::hls::HlsConfig;
+//!
+//! let config = ServerConfig {
+//!     hls: Some(HlsConfig::new("0.0.0.0:8888").with_low_latency(Duration::from_millis(200))),
+//!     ..ServerConfig::default()
+//! };
+//! let mut server = Server::with_config("0.0.0.0:8554", config);
+//! server.start()?;
+//! // Browsers play http://<host>:8888/stream/index.m3u8
+//! # Ok::<(), rtsp::RtspError>(())
+//! ```
+
+use std::time::Duration;
+
+mod http;
+mod segmenter;
+mod stream;
+
+pub(crate) use http::HlsServer;
+pub(crate) use stream::HlsStream;
+
+/// How long a mount is segmented after its last HLS request.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Container of HLS segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HlsFormat {
+    /// CMAF fragmented MP4 (`.m4s` segments after an `init.mp4`): H.264,
+    /// H.265 and AAC.
+    #[default]
+    Fmp4,
+    /// MPEG-2 transport stream (`.ts` segments): H.264 and AAC only, for
+    /// older players.
+    Ts,
+}
+
+/// Configuration of the HLS HTTP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HlsConfig {
+    /// Address of the HTTP listener, `host:port` (port 0 picks a free
+    /// port, see [`Server::hls_addr`](crate::Server::hls_addr)).
+    pub bind_addr: String,
+    pub format: HlsFormat,
+    /// Target segment duration. Segments end at the first keyframe after
+    /// it.
+    pub segment_duration: Duration,
+    /// Complete segments listed in the playlist.
+    pub playlist_length: usize,
+    /// Partial segment duration for Low-Latency HLS; `None` serves
+    /// regular HLS.
+    pub part_duration: Option<Duration>,
+}
+
+impl HlsConfig {
+    /// fMP4 HLS with 2-second segments and 6-segment playlists on
+    /// `bind_addr`.
+    pub fn new(bind_addr: &str) -> Self {
+        Self {
+            bind_addr: bind_addr.to_string(),
+            format: HlsFormat::Fmp4,
+            segment_duration: Duration::from_secs(2),
+            playlist_length: 6,
+            part_duration: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: HlsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_segment_duration(mut self, duration: Duration) -> Self {
+        self.segment_duration = duration;
+        self
+    }
+
+    pub fn with_playlist_length(mut self, segments: usize) -> Self {
+        self.playlist_length = segments;
+        self
+    }
+
+    /// Publish partial segments of `part_duration` (Low-Latency HLS).
+    pub fn with_low_latency(mut self, part_duration: Duration) -> Self {
+        self.part_duration = Some(part_duration);
+        self
+    }
+
+    /// How long a blocking request waits: three target durations.
+    fn blocking_timeout(&self) -> Duration {
+        self.segment_duration * 3
+    }
+}
@@ -0,0 +1,820 @@
+//! Cutting a mount's frames into HLS segments and partial segments, and
+//! the media playlist that lists them.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+
+use super::{HlsConfig, HlsFormat};
+use crate::error::{Result, RtspError};
+use crate::media::mp2t::TsMuxer;
+use crate::mount::{TappedFrame, Track};
+use crate::playback::VideoCodec;
+use crate::record::fmp4::{self, SampleEntry, TrackHeader, TrackRun};
+use crate::record::track::{Codec, SampleTrack};
+
+/// Complete segments kept after they left the playlist, for clients that
+/// loaded an older playlist.
+const RETAINED_SEGMENTS: usize = 2;
+
+/// Partial segments are listed for the segments of the last three target
+/// durations (RFC 8216bis §4.4.4.9).
+const PART_WINDOW_TARGETS: u32 = 3;
+
+/// A file a playlist refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Resource {
+    Playlist,
+    /// fMP4 init segment, by id.
+    Init(u32),
+    /// Complete segment, by media sequence number.
+    Segment(u64),
+    /// Partial segment: media sequence number and index in its segment.
+    Part(u64, usize),
+}
+
+impl Resource {
+    /// Resolve a file name in a mount's directory.
+    pub(super) fn parse(name: &str, format: HlsFormat) -> Option<Self> {
+        if name == "index.m3u8" {
+            return Some(Self::Playlist);
+        }
+        if let Some(id) = name
+            .strip_prefix("init")
+            .and_then(|rest| rest.strip_suffix(".mp4"))
+        {
+            return (format == HlsFormat::Fmp4)
+                .then(|| id.parse().ok().map(Self::Init))
+                .flatten();
+        }
+        let stem = name.strip_suffix(extension(format))?.strip_suffix('.')?;
+        if let Some(sequence) = stem.strip_prefix("seg") {
+            return sequence.parse().ok().map(Self::Segment);
+        }
+        let (sequence, index) = stem.strip_prefix("part")?.split_once('.')?;
+        Some(Self::Part(sequence.parse().ok()?, index.parse().ok()?))
+    }
+}
+
+fn extension(format: HlsFormat) -> &'static str {
+    match format {
+        HlsFormat::Fmp4 => "m4s",
+        HlsFormat::Ts => "ts",
+    }
+}
+
+/// A partial segment: one `moof` + `mdat` fragment, or a run of TS
+/// packets.
+struct Part {
+    data: Bytes,
+    duration: Duration,
+    /// Starts with a keyframe of the lead track.
+    independent: bool,
+}
+
+struct Segment {
+    sequence: u64,
+    /// Id of the [`Init`] it was cut under.
+    init: u32,
+    /// Preceded by `EXT-X-DISCONTINUITY`.
+    discontinuity: bool,
+    /// Media time of its first lead frame since segmentation started.
+    start: Duration,
+    parts: Vec<Part>,
+    /// Set once the segment is complete.
+    duration: Option<Duration>,
+}
+
+impl Segment {
+    fn data(&self) -> Bytes {
+        let mut data = BytesMut::with_capacity(self.parts.iter().map(|p| p.data.len()).sum());
+        for part in &self.parts {
+            data.extend_from_slice(&part.data);
+        }
+        data.freeze()
+    }
+
+    fn end(&self) -> Duration {
+        self.start + self.parts.iter().map(|p| p.duration).sum::<Duration>()
+    }
+}
+
+/// The track configuration segments are cut under; a new one starts a
+/// discontinuity.
+struct Init {
+    id: u32,
+    /// fMP4 init segment; empty for TS.
+    data: Bytes,
+    /// `stsd` entry of each track; `None` for tracks left out.
+    entries: Vec<Option<SampleEntry>>,
+}
+
+enum Muxer {
+    /// Next `moof` sequence number.
+    Fmp4(u32),
+    Ts(TsMuxer),
+}
+
+/// A mount's HLS segmenter: the tracks' sample state, the segments in the
+/// playlist window and the one being written.
+pub(super) struct Segmenter {
+    format: HlsFormat,
+    segment_duration: Duration,
+    part_duration: Option<Duration>,
+    playlist_length: usize,
+    started: Instant,
+    tracks: Vec<SampleTrack>,
+    /// Track whose keyframes start segments: the first video track, or the
+    /// first track.
+    lead: usize,
+    muxer: Muxer,
+    inits: Vec<Init>,
+    /// Retained segments, the last one open once segmentation started.
+    segments: VecDeque<Segment>,
+    next_sequence: u64,
+    /// Discontinuities of segments no longer retained.
+    discontinuity_sequence: u64,
+    /// `EXT-X-TARGETDURATION`, in seconds.
+    target_duration: u64,
+}
+
+impl Segmenter {
+    pub(super) fn new(mount_path: &str, tracks: &[Arc<Track>], config: &HlsConfig) -> Result<Self> {
+        let mut tracks = SampleTrack::of_tracks(mount_path, tracks);
+        if config.format == HlsFormat::Ts {
+            let (mut video, mut audio) = (false, false);
+            tracks.retain(|track| {
+                let kept = match &track.codec {
+                    Codec::Video(VideoCodec::H264) => !std::mem::replace(&mut video, true),
+                    Codec::Aac(config) => {
+                        config.adts_header(0).is_some() && !std::mem::replace(&mut audio, true)
+                    }
+                    Codec::Video(VideoCodec::H265) => false,
+                };
+                if !kept {
+                    tracing::debug!(
+                        mount = mount_path,
+                        track = track.mount_track,
+                        "track left out of MPEG-TS segments"
+                    );
+                }
+                kept
+            });
+        }
+        if tracks.is_empty() {
+            return Err(RtspError::Hls(format!(
+                "{mount_path} has no track that can be served as {:?} HLS",
+                config.format
+            )));
+        }
+        let muxer = match config.format {
+            HlsFormat::Fmp4 => Muxer::Fmp4(1),
+            HlsFormat::Ts => Muxer::Ts(TsMuxer::new(
+                tracks.iter().any(SampleTrack::is_video),
+                tracks.iter().any(|t| !t.is_video()),
+            )),
+        };
+        Ok(Self {
+            format: config.format,
+            segment_duration: config.segment_duration,
+            part_duration: config.part_duration,
+            playlist_length: config.playlist_length.max(1),
+            started: Instant::now(),
+            lead: tracks.iter().position(SampleTrack::is_video).unwrap_or(0),
+            tracks,
+            muxer,
+            inits: Vec::new(),
+            segments: VecDeque::new(),
+            next_sequence: 0,
+            discontinuity_sequence: 0,
+            target_duration: config.segment_duration.as_secs_f64().ceil().max(1.0) as u64,
+        })
+    }
+
+    /// Segment a tapped frame. Returns whether a part or segment was
+    /// published.
+    pub(super) fn push(&mut self, frame: &TappedFrame) -> bool {
+        let Some(index) = self
+            .tracks
+            .iter()
+            .position(|t| t.mount_track == frame.track)
+        else {
+            return false;
+        };
+        let since_start = frame.arrival.saturating_duration_since(self.started);
+        let track = &mut self.tracks[index];
+        let Some((data, keyframe)) = track.sample_of(&frame.data) else {
+            return false;
+        };
+        let offset = track.ticks(frame.composition_offset);
+        let timestamp = frame.timestamp.wrapping_sub(offset as u32);
+        track.advance(timestamp, since_start);
+
+        let mut published = false;
+        if index == self.lead {
+            // The lead's previous sample ends where this frame starts.
+            let dts = track.dts;
+            let video = track.is_video();
+            track.release(Some(dts));
+            let next_span = track.pending_span() + track.time(u64::from(track.last_duration));
+            let part_full = self.part_duration.is_some_and(|part| next_span > part);
+            let time = track.time(dts);
+            if keyframe && self.on_lead_keyframe(time) {
+                published = true;
+            } else if part_full || (keyframe && video && self.part_duration.is_some()) {
+                published = self.flush_part();
+            }
+        }
+
+        let Some(init) = self.inits.last() else {
+            return published;
+        };
+        let track = &mut self.tracks[index];
+        if init.entries[index].is_none() || (!track.synced && !keyframe) {
+            return published;
+        }
+        track.synced = true;
+        track.hold(data, offset, keyframe);
+        published
+    }
+
+    /// Start the first segment at the first lead keyframe, and the next
+    /// one once the open segment is long enough or the lead's
+    /// configuration changed. Returns whether a segment was started.
+    fn on_lead_keyframe(&mut self, time: Duration) -> bool {
+        let Some(lead_entry) = self.tracks[self.lead].sample_entry() else {
+            tracing::debug!("waiting for parameter sets to start an HLS segment");
+            return false;
+        };
+        let new_init = self
+            .inits
+            .last()
+            .is_none_or(|init| init.entries[self.lead].as_ref() != Some(&lead_entry));
+        if let Some(open) = self.segments.back() {
+            if !new_init && time.saturating_sub(open.start) < self.segment_duration {
+                return false;
+            }
+            self.close_segment(time);
+        }
+        if new_init {
+            self.new_init();
+        }
+        self.segments.push_back(Segment {
+            sequence: self.next_sequence,
+            init: self.inits.last().map_or(0, |init| init.id),
+            discontinuity: new_init && self.next_sequence > 0,
+            start: time,
+            parts: Vec::new(),
+            duration: None,
+        });
+        self.next_sequence += 1;
+        self.trim();
+        true
+    }
+
+    fn new_init(&mut self) {
+        let entries: Vec<Option<SampleEntry>> =
+            self.tracks.iter().map(SampleTrack::sample_entry).collect();
+        let data = match self.format {
+            HlsFormat::Fmp4 => {
+                let headers: Vec<TrackHeader> = entries
+                    .iter()
+                    .zip(&self.tracks)
+                    .zip(1..)
+                    .filter_map(|((entry, track), id)| {
+                        Some(TrackHeader {
+                            id,
+                            timescale: track.clock_rate,
+                            entry: entry.clone()?,
+                        })
+                    })
+                    .collect();
+                Bytes::from(fmp4::init_segment(&headers))
+            }
+            HlsFormat::Ts => Bytes::new(),
+        };
+        for (entry, track) in entries.iter().zip(&mut self.tracks) {
+            if entry.is_none() {
+                tracing::warn!(
+                    track = track.mount_track,
+                    "no parameter sets yet, track left out of HLS"
+                );
+            }
+            track.synced = false;
+        }
+        let id = self.inits.last().map_or(0, |init| init.id + 1);
+        self.inits.push(Init { id, data, entries });
+    }
+
+    /// Complete the open segment at `end`.
+    fn close_segment(&mut self, end: Duration) {
+        self.flush_part();
+        if let Some(open) = self.segments.back_mut() {
+            let duration = end.saturating_sub(open.start);
+            open.duration = Some(duration);
+            // EXTINF rounded to the nearest second must not exceed it.
+            self.target_duration = self
+                .target_duration
+                .max(duration.as_secs_f64().round() as u64);
+        }
+    }
+
+    /// Drop the complete segments that fell out of the retention window,
+    /// and the init segments no longer referenced.
+    fn trim(&mut self) {
+        while self.segments.len() > self.playlist_length + RETAINED_SEGMENTS + 1 {
+            if let Some(removed) = self.segments.pop_front() {
+                self.discontinuity_sequence += u64::from(removed.discontinuity);
+            }
+        }
+        if let Some(oldest) = self.segments.front().map(|s| s.init) {
+            self.inits.retain(|init| init.id >= oldest);
+        }
+    }
+
+    /// Publish the pending samples of every track as a part of the open
+    /// segment. Held samples wait for their track's next frame.
+    fn flush_part(&mut self) -> bool {
+        let Some(open) = self.segments.back_mut() else {
+            return false;
+        };
+        if self.tracks.iter().all(|t| t.pending.is_empty()) {
+            return false;
+        }
+        let lead = &self.tracks[self.lead];
+        let duration = lead.pending_span();
+        let independent = lead.pending.first().is_some_and(|s| s.keyframe);
+        let data = match &mut self.muxer {
+            Muxer::Fmp4(sequence) => {
+                let runs: Vec<TrackRun<'_>> = self
+                    .tracks
+                    .iter()
+                    .zip(1..)
+                    .filter(|(track, _)| !track.pending.is_empty())
+                    .map(|(track, track_id)| TrackRun {
+                        track_id,
+                        decode_time: track.pending_dts,
+                        samples: &track.pending,
+                    })
+                    .collect();
+                let fragment = fmp4::fragment(*sequence, &runs);
+                *sequence += 1;
+                fragment
+            }
+            Muxer::Ts(muxer) => mux_ts(muxer, &self.tracks, self.lead),
+        };
+        open.parts.push(Part {
+            data: Bytes::from(data),
+            duration,
+            independent,
+        });
+        for track in &mut self.tracks {
+            track.pending.clear();
+        }
+        true
+    }
+
+    pub(super) fn init(&self, id: u32) -> Option<Bytes> {
+        self.inits
+            .iter()
+            .find(|init| init.id == id)
+            .map(|init| init.data.clone())
+    }
+
+    /// A complete segment.
+    pub(super) fn segment(&self, sequence: u64) -> Option<Bytes> {
+        self.segments
+            .iter()
+            .find(|s| s.sequence == sequence && s.duration.is_some())
+            .map(Segment::data)
+    }
+
+    pub(super) fn part(&self, sequence: u64, index: usize) -> Option<Bytes> {
+        self.segments
+            .iter()
+            .find(|s| s.sequence == sequence)?
+            .parts
+            .get(index)
+            .map(|part| part.data.clone())
+    }
+
+    /// Whether a part is the next one to be published, which clients may
+    /// request ahead of time (`EXT-X-PRELOAD-HINT`).
+    pub(super) fn is_next_part(&self, sequence: u64, index: usize) -> bool {
+        self.segments.back().is_some_and(|open| {
+            (open.sequence == sequence && index == open.parts.len())
+                || (open.sequence + 1 == sequence && index == 0)
+        })
+    }
+
+    /// Media sequence number of the segment being written.
+    pub(super) fn open_sequence(&self) -> Option<u64> {
+        self.segments.back().map(|open| open.sequence)
+    }
+
+    /// Whether the playlist has at least one complete segment.
+    pub(super) fn has_segments(&self) -> bool {
+        self.segments.iter().any(|s| s.duration.is_some())
+    }
+
+    /// Whether the playlist contains segment `msn` complete, or with
+    /// `part`, that part of it (`_HLS_msn` / `_HLS_part`). A part index
+    /// past the end of a complete segment means the next segment's first
+    /// part.
+    pub(super) fn contains(&self, msn: u64, part: Option<usize>) -> bool {
+        let Some(segment) = self.segments.iter().find(|s| s.sequence == msn) else {
+            return self.segments.back().is_some_and(|open| open.sequence > msn);
+        };
+        match part {
+            Some(part) if part < segment.parts.len() => true,
+            Some(_) if segment.duration.is_some() => self.contains(msn + 1, Some(0)),
+            _ => segment.duration.is_some(),
+        }
+    }
+
+    /// The media playlist.
+    pub(super) fn playlist(&self) -> String {
+        let ext = extension(self.format);
+        let complete = self
+            .segments
+            .iter()
+            .filter(|s| s.duration.is_some())
+            .count();
+        let skipped = complete.saturating_sub(self.playlist_length);
+        let listed: Vec<&Segment> = self
+            .segments
+            .iter()
+            .skip(skipped)
+            .filter(|s| s.duration.is_some() || self.part_duration.is_some())
+            .collect();
+        let discontinuity_sequence = self.discontinuity_sequence
+            + self
+                .segments
+                .iter()
+                .take(skipped)
+                .filter(|s| s.discontinuity)
+                .count() as u64;
+
+        let mut out = String::from("#EXTM3U\n");
+        let version = match self.format {
+            HlsFormat::Fmp4 => 7,
+            HlsFormat::Ts => 3,
+        };
+        let _ = writeln!(out, "#EXT-X-VERSION:{version}");
+        let _ = writeln!(out, "#EXT-X-TARGETDURATION:{}", self.target_duration);
+        if let Some(part) = self.part_duration {
+            let _ = writeln!(
+                out,
+                "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK={:.3}",
+                part.as_secs_f64() * 3.0
+            );
+            let _ = writeln!(out, "#EXT-X-PART-INF:PART-TARGET={:.3}", part.as_secs_f64());
+        }
+        let first = listed.first().map_or(self.next_sequence, |s| s.sequence);
+        let _ = writeln!(out, "#EXT-X-MEDIA-SEQUENCE:{first}");
+        if discontinuity_sequence > 0 {
+            let _ = writeln!(
+                out,
+                "#EXT-X-DISCONTINUITY-SEQUENCE:{discontinuity_sequence}"
+            );
+        }
+        out.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+
+        let edge = self.segments.back().map_or(Duration::ZERO, Segment::end);
+        let part_window = Duration::from_secs(self.target_duration) * PART_WINDOW_TARGETS;
+        let mut map = None;
+        for segment in listed {
+            if segment.discontinuity {
+                out.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+            if self.format == HlsFormat::Fmp4 && map != Some(segment.init) {
+                let _ = writeln!(out, "#EXT-X-MAP:URI=\"init{}.mp4\"", segment.init);
+                map = Some(segment.init);
+            }
+            if self.part_duration.is_some() && edge.saturating_sub(segment.end()) < part_window {
+                for (index, part) in segment.parts.iter().enumerate() {
+                    let _ = write!(
+                        out,
+                        "#EXT-X-PART:DURATION={:.3},URI=\"part{}.{index}.{ext}\"",
+                        part.duration.as_secs_f64(),
+                        segment.sequence
+                    );
+                    out.push_str(if part.independent {
+                        ",INDEPENDENT=YES\n"
+                    } else {
+                        "\n"
+                    });
+                }
+            }
+            if let Some(duration) = segment.duration {
+                let _ = writeln!(
+                    out,
+                    "#EXTINF:{:.3},\nseg{}.{ext}",
+                    duration.as_secs_f64(),
+                    segment.sequence
+                );
+            }
+        }
+        if let (Some(_), Some(open)) = (self.part_duration, self.segments.back()) {
+            let _ = writeln!(
+                out,
+                "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"part{}.{}.{ext}\"",
+                open.sequence,
+                open.parts.len()
+            );
+        }
+        out
+    }
+}
+
+/// Mux the pending samples of every track as TS packets, in decode order
+/// except that a part starting with a lead keyframe starts with it (and
+/// so with PAT and PMT). Keyframes get the track's parameter sets in
+/// front, AAC access units an ADTS header.
+fn mux_ts(muxer: &mut TsMuxer, tracks: &[SampleTrack], lead: usize) -> Vec<u8> {
+    let mut samples = Vec::new();
+    for (t, track) in tracks.iter().enumerate() {
+        let mut dts = track.pending_dts;
+        for (s, sample) in track.pending.iter().enumerate() {
+            samples.push((track.time(dts), t, s));
+            dts += u64::from(sample.duration);
+        }
+    }
+    let lead_keyframe = tracks[lead].pending.first().is_some_and(|s| s.keyframe);
+    samples.sort_by_key(|&(dts, t, s)| (!(lead_keyframe && t == lead && s == 0), dts));
+
+    let mut out = Vec::new();
+    for (dts, t, s) in samples {
+        let track = &tracks[t];
+        let sample = &track.pending[s];
+        match &track.codec {
+            Codec::Video(_) => {
+                let mut access_unit = Vec::with_capacity(sample.data.len() + 64);
+                if sample.keyframe {
+                    let mut sets: Vec<&(u8, Vec<u8>)> = track.parameter_sets.iter().collect();
+                    sets.sort_by_key(|(nal_type, _)| *nal_type);
+                    for (_, nal) in sets {
+                        access_unit.extend_from_slice(&[0, 0, 0, 1]);
+                        access_unit.extend_from_slice(nal);
+                    }
+                }
+                let mut rest = &sample.data[..];
+                while rest.len() >= 4 {
+                    let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+                    let nal = &rest[4..(4 + len).min(rest.len())];
+                    access_unit.extend_from_slice(&[0, 0, 0, 1]);
+                    access_unit.extend_from_slice(nal);
+                    rest = &rest[4 + nal.len()..];
+                }
+                let offset = u64::try_from(sample.composition_offset).unwrap_or(0);
+                let pts = dts + track.time(offset);
+                out.extend(muxer.mux_video(&access_unit, pts, Some(dts)));
+            }
+            Codec::Aac(config) => {
+                let Some(header) = config.adts_header(sample.data.len()) else {
+                    continue;
+                };
+                let mut frame = Vec::with_capacity(header.len() + sample.data.len());
+                frame.extend_from_slice(&header);
+                frame.extend_from_slice(&sample.data);
+                out.extend(muxer.mux_audio(&frame, dts));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::aac::{AacConfig, AacPacketizer};
+    use crate::media::h264::H264Packetizer;
+    use crate::mount::Mount;
+    use crate::record::fmp4::tests::walk;
+
+    const SPS: [u8; 12] = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10,
+    ];
+    const PPS: [u8; 4] = [0x68, 0xeb, 0xe3, 0xcb];
+
+    /// Frame `i` of a 10 fps stream with a keyframe every 10 frames, SPS
+    /// and PPS in front of the first.
+    fn video_frame(i: u32) -> TappedFrame {
+        let mut data = Vec::new();
+        if i == 0 {
+            for nal in [&SPS[..], &PPS[..]] {
+                data.extend_from_slice(&[0, 0, 0, 1]);
+                data.extend_from_slice(nal);
+            }
+        }
+        let nal_type = if i.is_multiple_of(10) { 0x65 } else { 0x41 };
+        data.extend_from_slice(&[0, 0, 0, 1, nal_type, 0x88, i as u8]);
+        TappedFrame {
+            track: 0,
+            data: Bytes::from(data),
+            timestamp: i * 9000,
+            composition_offset: Duration::ZERO,
+            arrival: Instant::now(),
+        }
+    }
+
+    fn audio_frame(i: u32) -> TappedFrame {
+        TappedFrame {
+            track: 1,
+            data: Bytes::from_static(&[0x21, 0x10, 0x04]),
+            timestamp: i * 1024,
+            composition_offset: Duration::ZERO,
+            arrival: Instant::now(),
+        }
+    }
+
+    fn segmenter(config: &HlsConfig) -> Segmenter {
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 1)));
+        let aac = AacConfig::new(2, 48000, 2).unwrap();
+        mount.add_track(Box::new(AacPacketizer::new(97, 2, aac)));
+        Segmenter::new("/cam", &mount.tracks(), config).unwrap()
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn resolves_file_names() {
+        let fmp4 = HlsFormat::Fmp4;
+        assert_eq!(
+            Resource::parse("index.m3u8", fmp4),
+            Some(Resource::Playlist)
+        );
+        assert_eq!(Resource::parse("init3.mp4", fmp4), Some(Resource::Init(3)));
+        assert_eq!(
+            Resource::parse("seg42.m4s", fmp4),
+            Some(Resource::Segment(42))
+        );
+        assert_eq!(
+            Resource::parse("part42.7.m4s", fmp4),
+            Some(Resource::Part(42, 7))
+        );
+        assert_eq!(Resource::parse("seg42.ts", fmp4), None);
+        assert_eq!(Resource::parse("init0.mp4", HlsFormat::Ts), None);
+        assert_eq!(
+            Resource::parse("seg1.ts", HlsFormat::Ts),
+            Some(Resource::Segment(1))
+        );
+    }
+
+    /// `(track_id, tfdt, sample count)` of each `traf` in a segment.
+    fn runs(segment: &[u8]) -> Vec<(u32, u64, u32)> {
+        walk(segment)
+            .iter()
+            .filter(|(path, _)| path == "moof/traf")
+            .map(|(_, body)| {
+                let traf = walk(body);
+                let tfdt = u64::from_be_bytes(traf[1].1[4..12].try_into().unwrap());
+                (u32_at(traf[0].1, 4), tfdt, u32_at(traf[2].1, 4))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cuts_segments_at_keyframes_and_rolls_the_playlist() {
+        let config = HlsConfig::new("127.0.0.1:0")
+            .with_segment_duration(Duration::from_secs(1))
+            .with_playlist_length(2);
+        let mut segmenter = segmenter(&config);
+        for i in 0..70 {
+            segmenter.push(&video_frame(i));
+            segmenter.push(&audio_frame(i * 2));
+            segmenter.push(&audio_frame(i * 2 + 1));
+        }
+        // Segments 0 to 5 are complete and 6 is open; 2 and 3 left the
+        // playlist but are still served.
+        assert!(segmenter.contains(5, None));
+        assert!(!segmenter.contains(6, None));
+        assert_eq!(segmenter.segment(1), None);
+        assert!(segmenter.segment(2).is_some());
+        assert_eq!(segmenter.segment(6), None);
+        assert_eq!(
+            segmenter.playlist(),
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-MEDIA-SEQUENCE:4\n\
+             #EXT-X-INDEPENDENT-SEGMENTS\n\
+             #EXT-X-MAP:URI=\"init0.mp4\"\n\
+             #EXTINF:1.000,\nseg4.m4s\n\
+             #EXTINF:1.000,\nseg5.m4s\n"
+        );
+
+        let init = segmenter.init(0).unwrap();
+        let traks = walk(&init)
+            .into_iter()
+            .filter(|(path, _)| path == "moov/trak")
+            .count();
+        assert_eq!(traks, 2);
+
+        // Ten frames per segment, decode times continuing across segments.
+        let (four, five) = (
+            runs(&segmenter.segment(4).unwrap()),
+            runs(&segmenter.segment(5).unwrap()),
+        );
+        assert_eq!((four[0].0, four[0].2), (1, 10));
+        assert_eq!(five[0].1 - four[0].1, 90000);
+        assert!(five.iter().any(|run| run.0 == 2), "audio");
+    }
+
+    #[test]
+    fn starts_a_discontinuity_when_parameter_sets_change() {
+        let config = HlsConfig::new("127.0.0.1:0").with_segment_duration(Duration::from_secs(1));
+        let mut segmenter = segmenter(&config);
+        for i in 0..=30 {
+            let mut frame = video_frame(i);
+            if i == 20 {
+                let mut data = vec![0, 0, 0, 1, 0x68, 0xee, 0x3c, 0x80];
+                data.extend_from_slice(&frame.data);
+                frame.data = Bytes::from(data);
+            }
+            segmenter.push(&frame);
+        }
+        let playlist = segmenter.playlist();
+        assert!(
+            playlist.ends_with(
+                "seg1.m4s\n\
+                 #EXT-X-DISCONTINUITY\n\
+                 #EXT-X-MAP:URI=\"init1.mp4\"\n\
+                 #EXTINF:1.000,\nseg2.m4s\n"
+            ),
+            "{playlist}"
+        );
+        assert_ne!(segmenter.init(0), segmenter.init(1));
+    }
+
+    #[test]
+    fn publishes_partial_segments() {
+        let config = HlsConfig::new("127.0.0.1:0")
+            .with_segment_duration(Duration::from_secs(1))
+            .with_low_latency(Duration::from_millis(300));
+        let mut segmenter = segmenter(&config);
+        for i in 0..15 {
+            segmenter.push(&video_frame(i));
+        }
+        // Segment 0 has parts of frames 0-2, 3-5, 6-8, 9; segment 1 is
+        // open with frames 10-12 published and 13 pending.
+        assert!(segmenter.contains(0, None));
+        assert!(segmenter.contains(1, Some(0)));
+        assert!(!segmenter.contains(1, Some(1)));
+        assert!(segmenter.contains(0, Some(9)), "past the end: next segment");
+        assert!(segmenter.is_next_part(1, 1));
+        assert!(!segmenter.is_next_part(1, 2));
+        assert_eq!(
+            segmenter.playlist(),
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=0.900\n\
+             #EXT-X-PART-INF:PART-TARGET=0.300\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n\
+             #EXT-X-INDEPENDENT-SEGMENTS\n\
+             #EXT-X-MAP:URI=\"init0.mp4\"\n\
+             #EXT-X-PART:DURATION=0.300,URI=\"part0.0.m4s\",INDEPENDENT=YES\n\
+             #EXT-X-PART:DURATION=0.300,URI=\"part0.1.m4s\"\n\
+             #EXT-X-PART:DURATION=0.300,URI=\"part0.2.m4s\"\n\
+             #EXT-X-PART:DURATION=0.100,URI=\"part0.3.m4s\"\n\
+             #EXTINF:1.000,\nseg0.m4s\n\
+             #EXT-X-PART:DURATION=0.300,URI=\"part1.0.m4s\",INDEPENDENT=YES\n\
+             #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"part1.1.m4s\"\n"
+        );
+        // The segment is its parts back to back.
+        let parts: Vec<u8> = (0..4).flat_map(|i| segmenter.part(0, i).unwrap()).collect();
+        assert_eq!(segmenter.segment(0).unwrap(), parts);
+    }
+
+    #[test]
+    fn muxes_ts_segments() {
+        let config = HlsConfig::new("127.0.0.1:0")
+            .with_format(HlsFormat::Ts)
+            .with_segment_duration(Duration::from_secs(1));
+        let mut segmenter = segmenter(&config);
+        for i in 0..25 {
+            segmenter.push(&video_frame(i));
+            segmenter.push(&audio_frame(i * 2));
+        }
+        assert!(segmenter.playlist().contains("#EXTINF:1.000,\nseg1.ts\n"));
+        assert!(!segmenter.playlist().contains("EXT-X-MAP"));
+        assert_eq!(segmenter.init(0).unwrap().len(), 0);
+
+        // Each segment starts with PAT and PMT, and its keyframe carries
+        // the SPS captured from the first frame.
+        let segment = segmenter.segment(1).unwrap();
+        assert_eq!(segment.len() % 188, 0);
+        assert_eq!(&segment[..3], &[0x47, 0x40, 0x00]);
+        assert_eq!(segment[188 + 1..188 + 3], [0x50, 0x00]);
+        let sps = segment.windows(SPS.len()).any(|w| w == SPS);
+        assert!(sps, "SPS repeated in segment 1");
+        let adts = segment.windows(2).any(|w| w == [0xff, 0xf1]);
+        assert!(adts, "AAC with ADTS headers");
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+use super::HlsConfig;
+use super::segmenter::{Resource, Segmenter};
+use crate::error::Result;
+use crate::mount::{TappedFrame, Track};
+
+/// Why an HLS request could not be answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Unavailable {
+    /// Not in the playlist (anymore).
+    NotFound,
+    /// A blocking request asked for a segment too far ahead.
+    BadRequest,
+    /// Nothing was published in time, or the stream stopped.
+    Timeout,
+}
+
+/// A mount's HLS output: its [`Segmenter`], fed by the mount's frame tap,
+/// and the HTTP requests waiting on it.
+pub(crate) struct HlsStream {
+    config: HlsConfig,
+    segmenter: Mutex<Segmenter>,
+    /// Signalled when a part or segment is published, or the stream stops.
+    published: Condvar,
+    last_request: Mutex<Instant>,
+    closed: AtomicBool,
+}
+
+impl HlsStream {
+    pub(crate) fn new(mount_path: &str, tracks: &[Arc<Track>], config: &HlsConfig) -> Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            segmenter: Mutex::new(Segmenter::new(mount_path, tracks, config)?),
+            published: Condvar::new(),
+            last_request: Mutex::new(Instant::now()),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    pub(crate) fn push(&self, frame: &TappedFrame) {
+        if self.segmenter.lock().push(frame) {
+            self.published.notify_all();
+        }
+    }
+
+    /// Stop serving: wake every waiting request.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _segmenter = self.segmenter.lock();
+        self.published.notify_all();
+    }
+
+    pub(crate) fn idle_for(&self) -> Duration {
+        self.last_request.lock().elapsed()
+    }
+
+    /// Answer a request for `resource`, with the playlist's `_HLS_msn` and
+    /// `_HLS_part` for blocking playlist reloads.
+    pub(super) fn get(
+        &self,
+        resource: Resource,
+        msn: Option<u64>,
+        part: Option<usize>,
+    ) -> std::result::Result<Bytes, Unavailable> {
+        *self.last_request.lock() = Instant::now();
+        match resource {
+            Resource::Playlist => {
+                if let Some(msn) = msn {
+                    let ahead = self
+                        .segmenter
+                        .lock()
+                        .open_sequence()
+                        .is_some_and(|open| msn > open + 1);
+                    if ahead {
+                        return Err(Unavailable::BadRequest);
+                    }
+                }
+                let segmenter = self.wait(|s| match msn {
+                    Some(msn) => s.contains(msn, part),
+                    None => s.has_segments(),
+                })?;
+                Ok(Bytes::from(segmenter.playlist()))
+            }
+            Resource::Init(id) => self.segmenter.lock().init(id).ok_or(Unavailable::NotFound),
+            Resource::Segment(sequence) => self
+                .segmenter
+                .lock()
+                .segment(sequence)
+                .ok_or(Unavailable::NotFound),
+            Resource::Part(sequence, index) => {
+                let segmenter = self.segmenter.lock();
+                if let Some(part) = segmenter.part(sequence, index) {
+                    return Ok(part);
+                }
+                if !segmenter.is_next_part(sequence, index) {
+                    return Err(Unavailable::NotFound);
+                }
+                drop(segmenter);
+                // A preload hint: answer once the part is published.
+                let segmenter = self.wait(|s| {
+                    s.part(sequence, index).is_some() || !s.is_next_part(sequence, index)
+                })?;
+                segmenter.part(sequence, index).ok_or(Unavailable::NotFound)
+            }
+        }
+    }
+
+    /// Wait until `ready` holds, for at most three target durations.
+    fn wait(
+        &self,
+        ready: impl Fn(&Segmenter) -> bool,
+    ) -> std::result::Result<MutexGuard<'_, Segmenter>, Unavailable> {
+        let deadline = Instant::now() + self.config.blocking_timeout();
+        let mut segmenter = self.segmenter.lock();
+        while !ready(&segmenter) {
+            if self.closed.load(Ordering::SeqCst)
+                || self
+                    .published
+                    .wait_until(&mut segmenter, deadline)
+                    .timed_out()
+            {
+                return Err(Unavailable::Timeout);
+            }
+        }
+        Ok(segmenter)
+    }
+}
@@ -1,7 +1,9 @@
 //! # rtsp — RTSP server library for live media streaming
 //!
-//! A Rust library for publishing live media streams (H.264, with H.265 and
-//! MJPEG planned) over the Real-Time Streaming Protocol (RTSP).
+//! A Rust library for publishing live media streams (H.264, H.265, VP8, VP9
+//! and AV1 video, AAC, Opus and G.711/L16 audio, with MJPEG planned) over the
+//! Real-Time Streaming Protocol (RTSP), for playing files on demand, for
+//! recording streams to MP4, and for serving them to browsers as HLS.
 //!
 //! ## Protocol references
 //!
@@ -11,6 +13,17 @@
 //! | [RFC 3550](https://tools.ietf.org/html/rfc3550) | RTP | Packet header format, SSRC generation, sequence/timestamp semantics |
 //! | [RFC 4566](https://tools.ietf.org/html/rfc4566) | SDP | Session description generation for DESCRIBE responses |
 //! | [RFC 6184](https://tools.ietf.org/html/rfc6184) | H.264 RTP payload | NAL unit packetization, FU-A fragmentation, SDP fmtp attributes |
+//! | [RFC 7798](https://tools.ietf.org/html/rfc7798) | H.265 RTP payload | NAL unit packetization, FU fragmentation, `sprop-vps/sps/pps` |
+//! | [RFC 3640](https://tools.ietf.org/html/rfc3640) | MPEG-4 elementary streams | AAC-hbr access units, `mpeg4-generic` SDP with the `AudioSpecificConfig` |
+//! | [RFC 7741](https://tools.ietf.org/html/rfc7741) / [RFC 9628](https://tools.ietf.org/html/rfc9628) | VP8/VP9 RTP payload | Payload descriptors with picture ID, key frame detection, VP9 scalability structure |
+//! | [RFC 2250](https://tools.ietf.org/html/rfc2250) | MPEG-2 TS over RTP | 7 TS packets per RTP packet, H.264/AAC muxing with PAT/PMT/PCR |
+//! | [ONVIF Streaming](https://www.onvif.org/specs/stream/ONVIF-Streaming-Spec.pdf) | Metadata track | `vnd.onvif.metadata/90000` `m=application` track aligned with the mount's video |
+//! | [RFC 6597](https://tools.ietf.org/html/rfc6597) | SMPTE ST 336 KLV over RTP | Universal-key/BER-length validation, unit fragmentation, `smpte336m` SDP |
+//! | [AV1 RTP](https://aomediacodec.github.io/av1-rtp-spec/) | AV1 RTP payload | OBU aggregation/fragmentation, SDP profile/level/tier from the sequence header |
+//! | [RFC 7587](https://tools.ietf.org/html/rfc7587) | Opus RTP payload | 48 kHz clock, TOC-derived timestamps, SDP fmtp attributes |
+//! | [RFC 3551](https://tools.ietf.org/html/rfc3551) | RTP A/V profile | Static payload types, G.711 PCMU/PCMA and L16 sample packetization |
+//! | [RFC 8216](https://tools.ietf.org/html/rfc8216) | HLS | fMP4/TS segments, rolling media playlists, LL-HLS partial segments and blocking reloads |
+//! | [RFC 4585](https://tools.ietf.org/html/rfc4585) / [RFC 5104](https://tools.ietf.org/html/rfc5104) | RTCP feedback | PLI/FIR keyframe requests forwarded to the mount's handler |
 //!
 //! ## Architecture
 //!
@@ -39,27 +52,58 @@
 //!
 //! // Push H.264 Annex B frames — the server packetizes and delivers via RTP.
 //! // server.send_frame(&h264_data, 3000).unwrap();
+//!
+//! // Or stamp each frame with its presentation time and let the server
+//! // derive RTP timestamps (no drift on dropped frames / variable fps).
+//! // server.send_frame_with_pts("/stream", &h264_data, pts).unwrap();
+//!
+//! // Extra tracks (audio, ONVIF metadata) join the mount's SDP as further
+//! // m= sections; metadata sent with a frame's PTS shares its RTP timestamp.
+//! // server.add_track("/stream", Box::new(OnvifMetadataPacketizer::with_random_ssrc(107)))?;
+//! // server.send_track_frame_with_pts("/stream", 1, frame.to_xml().as_bytes(), pts).unwrap();
 //! ```
 //!
 //! ## Crate layout
 //!
 //! - [`server`] — High-level [`Server`] orchestrator and [`ServerConfig`].
-//! - [`mount`] — [`Mount`] (stream endpoint) and [`MountRegistry`].
+//! - `async_server` — `AsyncServer`, the same server on a tokio runtime (`tokio` feature).
+//! - [`mount`] — [`Mount`] (stream endpoint with one or more [`Track`]s) and [`MountRegistry`].
+//! - [`playback`] — Seekable mounts: a [`MediaSource`] played on demand with `Range`, `Scale` and `Speed`, and [`MediaFile`] for MP4/MOV and Annex B files.
+//! - [`record`] — Recording mounts to segmented, fragmented MP4 files ([`RecordingConfig`]).
+//! - [`hls`] — HLS and Low-Latency HLS of every mount over a built-in HTTP server ([`HlsConfig`]).
 //! - [`protocol`] — RTSP request/response parsing, method handling, SDP generation.
+//! - [`send_queue`] — Per-mount frame queues ([`SendQueueConfig`], [`DropPolicy`]) that keep producers off the network path.
 //! - [`session`] — RTSP session state machine and transport negotiation.
-//! - [`transport`] — TCP listener for RTSP signaling, UDP sender for RTP delivery.
+//! - [`transport`] — TCP listener for RTSP signaling and interleaved RTP ([`OverflowPolicy`]), RTSP-over-HTTP tunnels, UDP sender and [`Pacing`] for RTP delivery.
 //! - [`media`] — [`Packetizer`] trait, RTP header builder, codec implementations.
 //! - [`error`] — [`RtspError`] enum and [`Result`] alias.
 
+#[cfg(feature = "tokio")]
+pub mod async_server;
 pub mod error;
+pub mod hls;
 pub mod media;
 pub mod mount;
+pub mod playback;
 pub mod protocol;
+pub mod record;
+pub mod send_queue;
 pub mod server;
 pub mod session;
 pub mod transport;
 
+#[cfg(feature = "tokio")]
+pub use async_server::AsyncServer;
 pub use error::{Result, RtspError};
+pub use hls::{HlsConfig, HlsFormat};
 pub use media::Packetizer;
-pub use mount::{DEFAULT_MOUNT_PATH, Mount, MountRegistry};
+pub use mount::{
+    DEFAULT_MOUNT_PATH, KeyframeRequest, KeyframeRequestPolicy, KeyframeRequestReason, Mount,
+    MountRegistry, Track,
+};
+pub use playback::{MediaFile, MediaSource, SourceFrame};
+pub use record::RecordingConfig;
+pub use send_queue::{DropPolicy, SendQueueConfig, SendStats};
 pub use server::{Server, ServerConfig, Viewer};
+pub use transport::interleaved::{OverflowPolicy, TcpViewerStats};
+pub use transport::pacer::Pacing;
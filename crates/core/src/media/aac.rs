@@ -0,0 +1,352 @@
+use super::bits::BitReader;
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, MediaType, Packetizer};
+
+/// Samples per AAC access unit (ISO/IEC 14496-3 §4.5.1.1; 960-sample
+/// frames are not supported).
+const SAMPLES_PER_FRAME: u32 = 1024;
+
+/// Sampling frequencies by `samplingFrequencyIndex` (ISO/IEC 14496-3
+/// §1.6.3.4).
+const SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Largest AU-size that fits the 13-bit `sizelength` of AAC-hbr.
+const MAX_AU_SIZE: usize = (1 << 13) - 1;
+
+/// An MPEG-4 `AudioSpecificConfig` (ISO/IEC 14496-3 §1.6.2.1), as found in
+/// an MP4 `esds` box or built from an ADTS header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AacConfig {
+    /// Audio object type (2 = AAC LC).
+    pub object_type: u8,
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// The encoded config, advertised as `config=` in the SDP.
+    pub bytes: Vec<u8>,
+}
+
+impl AacConfig {
+    /// Parse an `AudioSpecificConfig`. Returns `None` if it is truncated
+    /// or uses a reserved sampling frequency index.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut r = BitReader::new(bytes);
+        let mut object_type = r.bits(5)?;
+        if object_type == 31 {
+            object_type = 32 + r.bits(6)?;
+        }
+        let sample_rate = match r.bits(4)? {
+            15 => r.bits(24)?,
+            index => *SAMPLE_RATES.get(index as usize)?,
+        };
+        let channels = r.bits(4)? as u8;
+        Some(Self {
+            object_type: u8::try_from(object_type).ok()?,
+            sample_rate,
+            channels,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    /// The config of an AAC stream with the given object type, sample rate
+    /// and channel configuration. Returns `None` for object types that do
+    /// not fit in 5 bits.
+    pub fn new(object_type: u8, sample_rate: u32, channels: u8) -> Option<Self> {
+        if object_type == 0 || object_type >= 31 || channels > 15 {
+            return None;
+        }
+        let bits = match SAMPLE_RATES.iter().position(|r| *r == sample_rate) {
+            Some(index) => {
+                (u64::from(object_type) << 11) | ((index as u64) << 7) | (u64::from(channels) << 3)
+            }
+            // Explicit 24-bit frequency: 5 + 4 + 24 + 4 bits, padded to 40.
+            None => {
+                (u64::from(object_type) << 35)
+                    | (15 << 31)
+                    | (u64::from(sample_rate & 0xff_ffff) << 7)
+                    | (u64::from(channels) << 3)
+            }
+        };
+        let len = if bits >> 16 == 0 { 2 } else { 5 };
+        let bytes = bits.to_be_bytes()[8 - len..].to_vec();
+        Self::parse(&bytes)
+    }
+
+    /// ADTS header (ISO/IEC 14496-3 §1.A.2.2, without CRC) for an access
+    /// unit of `au_len` bytes. `None` if the config cannot be expressed in
+    /// ADTS (object types above 4, explicit sample rates, channel
+    /// configurations above 7) or the frame is too long.
+    pub(crate) fn adts_header(&self, au_len: usize) -> Option<[u8; 7]> {
+        let profile = self.object_type.checked_sub(1).filter(|p| *p < 4)?;
+        let index = SAMPLE_RATES.iter().position(|r| *r == self.sample_rate)? as u8;
+        let frame_len = au_len + 7;
+        if self.channels > 7 || frame_len >= 1 << 13 {
+            return None;
+        }
+        Some([
+            0xff,
+            0xf1,
+            (profile << 6) | (index << 2) | (self.channels >> 2),
+            ((self.channels & 0x03) << 6) | (frame_len >> 11) as u8,
+            (frame_len >> 3) as u8,
+            ((frame_len & 0x07) << 5) as u8 | 0x1f,
+            0xfc,
+        ])
+    }
+}
+
+/// AAC RTP packetizer, `mpeg4-generic` in AAC-hbr mode (RFC 3640 §3.3.6).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one raw AAC
+/// access unit (as stored in MP4 samples), or one ADTS frame whose header
+/// is stripped, and emits one RTP packet:
+///
+/// ```text
+/// +----------------------+------------------------+-----------+
+/// | AU-headers-length=16 | AU-size(13)|AU-index(3) | AU bytes  |
+/// +----------------------+------------------------+-----------+
+/// ```
+///
+/// An access unit larger than the MTU is fragmented over several packets,
+/// each repeating the AU header with the full AU size (§3.2.3.2); the
+/// marker bit is set on the last.
+///
+/// ## Timestamps
+///
+/// The RTP clock runs at the sample rate, and the timestamp advances by
+/// 1024 samples per access unit, so the `timestamp_increment` argument to
+/// [`packetize`](Packetizer::packetize) is ignored.
+///
+/// ## SDP attributes (RFC 3640 §4.1)
+///
+/// - `a=rtpmap:<pt> mpeg4-generic/<rate>/<channels>`
+/// - `a=fmtp:<pt> streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=<hex>`
+/// - `a=control:track1`
+#[derive(Debug)]
+pub struct AacPacketizer {
+    header: RtpHeader,
+    config: AacConfig,
+    mtu: usize,
+}
+
+impl AacPacketizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32, config: AacConfig) -> Self {
+        Self {
+            header: RtpHeader::new(pt, ssrc),
+            config,
+            mtu: DEFAULT_MTU,
+        }
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8, config: AacConfig) -> Self {
+        Self {
+            header: RtpHeader::with_random_ssrc(pt),
+            config,
+            mtu: DEFAULT_MTU,
+        }
+    }
+
+    pub fn config(&self) -> &AacConfig {
+        &self.config
+    }
+
+    /// The raw access unit inside an ADTS frame (ISO/IEC 14496-3
+    /// §1.A.2.2), or `data` itself if it does not start with an ADTS
+    /// header.
+    pub fn strip_adts(data: &[u8]) -> &[u8] {
+        if data.len() < 7 || data[0] != 0xff || data[1] & 0xf6 != 0xf0 {
+            return data;
+        }
+        let frame_len = (usize::from(data[3] & 0x03) << 11)
+            | (usize::from(data[4]) << 3)
+            | usize::from(data[5] >> 5);
+        let header_len = if data[1] & 0x01 == 0 { 9 } else { 7 };
+        if frame_len != data.len() || frame_len < header_len {
+            return data;
+        }
+        &data[header_len..]
+    }
+}
+
+impl Packetizer for AacPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let au = Self::strip_adts(encoded_data);
+        if au.is_empty() {
+            return Vec::new();
+        }
+        if au.len() > MAX_AU_SIZE {
+            tracing::warn!(
+                bytes = au.len(),
+                timestamp_increment,
+                "AAC access unit too large for AAC-hbr, dropped"
+            );
+            return Vec::new();
+        }
+
+        let au_header = ((au.len() as u16) << 3).to_be_bytes();
+        let max_fragment = self.mtu - 4;
+        let chunks: Vec<&[u8]> = au.chunks(max_fragment).collect();
+        let packets = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let hdr = self.header.write(i == chunks.len() - 1);
+                let mut packet = Vec::with_capacity(16 + chunk.len());
+                packet.extend_from_slice(&hdr);
+                packet.extend_from_slice(&[0x00, 0x10]);
+                packet.extend_from_slice(&au_header);
+                packet.extend_from_slice(chunk);
+                packet
+            })
+            .collect();
+
+        self.header.advance_timestamp(SAMPLES_PER_FRAME);
+
+        tracing::trace!(
+            bytes = au.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "AAC access unit packetized"
+        );
+        packets
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "mpeg4-generic"
+    }
+
+    /// The sample rate of the stream (RFC 3640 §4.1).
+    fn clock_rate(&self) -> u32 {
+        self.config.sample_rate
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn media_type(&self) -> MediaType {
+        MediaType::Audio
+    }
+
+    /// SDP attributes per RFC 3640 §4.1 for AAC-hbr.
+    fn sdp_attributes(&self) -> Vec<String> {
+        let pt = self.payload_type();
+        let config: String = self
+            .config
+            .bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}/{}",
+                pt,
+                self.codec_name(),
+                self.clock_rate(),
+                self.config.channels.max(1)
+            ),
+            format!(
+                "a=fmtp:{} streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config={}",
+                pt, config
+            ),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_audio_specific_config() {
+        // AAC LC, 44.1 kHz, stereo
+        let config = AacConfig::parse(&[0x12, 0x10]).unwrap();
+        assert_eq!(
+            (config.object_type, config.sample_rate, config.channels),
+            (2, 44100, 2)
+        );
+        assert_eq!(AacConfig::new(2, 44100, 2), Some(config));
+
+        let explicit = AacConfig::new(2, 44000, 1).unwrap();
+        assert_eq!(explicit.bytes.len(), 5);
+        assert_eq!(explicit.sample_rate, 44000);
+        assert!(AacConfig::parse(&[0x17]).is_none());
+    }
+
+    #[test]
+    fn packs_one_access_unit_per_packet() {
+        let mut p = AacPacketizer::new(97, 1, AacConfig::new(2, 48000, 2).unwrap());
+        let packets = p.packetize(&[0xAA; 300], 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0][1], 0x80 | 97, "marker set");
+        assert_eq!(&packets[0][12..16], &[0x00, 0x10, 0x09, 0x60]);
+        assert_eq!(packets[0].len(), 16 + 300);
+        assert_eq!(p.next_rtp_timestamp(), 1024);
+
+        let big = p.packetize(&[0xBB; 3000], 0);
+        assert_eq!(big.len(), 3);
+        assert!(
+            big.iter()
+                .all(|pk| pk[14..16] == ((3000u16 << 3).to_be_bytes()))
+        );
+        assert_eq!(big[0][1] & 0x80, 0);
+        assert_eq!(big[2][1] & 0x80, 0x80);
+    }
+
+    #[test]
+    fn strips_and_builds_adts_headers() {
+        let au = [0x21, 0x10, 0x04];
+        let len = 7 + au.len();
+        let mut adts = vec![
+            0xff,
+            0xf1,
+            0x50,
+            0x80,
+            (len >> 3) as u8,
+            ((len & 7) << 5) as u8 | 0x1f,
+            0xfc,
+        ];
+        let config = AacConfig::new(2, 44100, 2).unwrap();
+        assert_eq!(
+            config.adts_header(au.len()),
+            Some(adts[..].try_into().unwrap())
+        );
+        adts.extend_from_slice(&au);
+        assert_eq!(AacPacketizer::strip_adts(&adts), &au);
+        assert_eq!(AacPacketizer::strip_adts(&au), &au);
+    }
+
+    #[test]
+    fn sdp_advertises_mpeg4_generic() {
+        let p = AacPacketizer::new(97, 1, AacConfig::parse(&[0x11, 0x90]).unwrap());
+        assert_eq!(
+            p.sdp_attributes(),
+            [
+                "a=rtpmap:97 mpeg4-generic/48000/2",
+                "a=fmtp:97 streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=1190",
+                "a=control:track1",
+            ]
+        );
+    }
+}
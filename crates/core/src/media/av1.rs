@@ -0,0 +1,560 @@
+use super::bits::BitReader;
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, Packetizer};
+
+/// OBU types from AV1 spec §6.2.2 that the packetizer treats specially.
+const OBU_SEQUENCE_HEADER: u8 = 1;
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+const OBU_TILE_LIST: u8 = 8;
+const OBU_PADDING: u8 = 15;
+
+/// Aggregation header bits ("RTP Payload Format for AV1" §4.4).
+const AGG_Z: u8 = 0x80;
+const AGG_Y: u8 = 0x40;
+const AGG_N: u8 = 0x08;
+
+/// AV1 RTP packetizer (AOM "RTP Payload Format for AV1", v1.0.0).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one temporal unit
+/// in the low-overhead bitstream format (AV1 spec §5.2: a sequence of OBUs,
+/// normally each with `obu_has_size_field = 1`) and emits RTP packets whose
+/// payload is an aggregation header followed by OBU elements:
+///
+/// ```text
+///  0 1 2 3 4 5 6 7
+/// +-+-+-+-+-+-+-+-+
+/// |Z|Y| W |N|-|-|-|
+/// +-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - **Z**: the first OBU element continues a fragment from the previous packet.
+/// - **Y**: the last OBU element continues in the next packet.
+/// - **W**: number of OBU elements (1–3, the last one without a length
+///   field), or 0 when every element carries a LEB128 length.
+/// - **N**: first packet of a coded video sequence (the temporal unit
+///   carries a sequence header).
+///
+/// ## OBU handling (§5)
+///
+/// - `obu_has_size_field` is cleared and the size removed — the element
+///   length (or packet end) delimits OBUs on the wire.
+/// - Temporal delimiters, tile lists and padding OBUs are dropped.
+/// - OBUs larger than the remaining packet space are fragmented across
+///   packets using Z/Y.
+///
+/// ## SDP attributes (§7.2)
+///
+/// - `a=rtpmap:<pt> AV1/90000`
+/// - `a=fmtp:<pt> profile=<p>;level-idx=<l>;tier=<t>` once a sequence header
+///   has been seen (only `a=rtpmap` and `a=control` before that)
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the last packet of a temporal unit (§4.2).
+#[derive(Debug)]
+pub struct Av1Packetizer {
+    header: RtpHeader,
+    mtu: usize,
+    sequence_header: Option<SequenceHeaderInfo>,
+}
+
+/// Fields of an AV1 sequence header needed for SDP (AV1 spec §5.5.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceHeaderInfo {
+    /// `seq_profile` (0 = Main, 1 = High, 2 = Professional).
+    pub profile: u8,
+    /// `seq_level_idx[0]` of the first operating point.
+    pub level_idx: u8,
+    /// `seq_tier[0]` of the first operating point (0 = Main, 1 = High).
+    pub tier: u8,
+}
+
+/// One OBU from the low-overhead bitstream, split into header and payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Obu<'a> {
+    /// `obu_type` (AV1 spec §6.2.2).
+    pub obu_type: u8,
+    /// OBU header bytes (1, or 2 with the extension byte), as received.
+    pub header: &'a [u8],
+    /// OBU payload, excluding any size field.
+    pub payload: &'a [u8],
+}
+
+impl Av1Packetizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self {
+            header: RtpHeader::new(pt, ssrc),
+            mtu: DEFAULT_MTU,
+            sequence_header: None,
+        }
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self {
+            header: RtpHeader::with_random_ssrc(pt),
+            mtu: DEFAULT_MTU,
+            sequence_header: None,
+        }
+    }
+
+    /// Profile, level and tier from the most recent sequence header.
+    pub fn sequence_header(&self) -> Option<SequenceHeaderInfo> {
+        self.sequence_header
+    }
+
+    /// Split a low-overhead bitstream into OBUs (AV1 spec §5.3).
+    ///
+    /// An OBU without `obu_has_size_field` extends to the end of the data.
+    /// Parsing stops at the first truncated or malformed OBU.
+    pub fn parse_obus(data: &[u8]) -> Vec<Obu<'_>> {
+        let mut obus = Vec::new();
+        let mut offset = 0usize;
+
+        while offset < data.len() {
+            let first = data[offset];
+            let obu_type = (first >> 3) & 0x0f;
+            let has_extension = first & 0x04 != 0;
+            let has_size = first & 0x02 != 0;
+            let header_len = if has_extension { 2 } else { 1 };
+            if offset + header_len > data.len() {
+                tracing::warn!(offset, "truncated AV1 OBU header");
+                break;
+            }
+            let header = &data[offset..offset + header_len];
+            offset += header_len;
+
+            let payload_len = if has_size {
+                match read_leb128(&data[offset..]) {
+                    Some((value, len)) => {
+                        offset += len;
+                        value as usize
+                    }
+                    None => {
+                        tracing::warn!(offset, "invalid AV1 OBU size field");
+                        break;
+                    }
+                }
+            } else {
+                data.len() - offset
+            };
+            if offset + payload_len > data.len() {
+                tracing::warn!(offset, payload_len, "truncated AV1 OBU payload");
+                break;
+            }
+
+            obus.push(Obu {
+                obu_type,
+                header,
+                payload: &data[offset..offset + payload_len],
+            });
+            offset += payload_len;
+        }
+
+        obus
+    }
+
+    /// Build the RTP payload (aggregation header + elements) for one packet.
+    fn build_payload(flags: u8, elements: &[Vec<u8>]) -> Vec<u8> {
+        let w = if elements.len() <= 3 {
+            elements.len() as u8
+        } else {
+            0
+        };
+        let mut payload = vec![flags | (w << 4)];
+        for (i, element) in elements.iter().enumerate() {
+            let is_last = i == elements.len() - 1;
+            if w == 0 || !is_last {
+                write_leb128(element.len() as u64, &mut payload);
+            }
+            payload.extend_from_slice(element);
+        }
+        payload
+    }
+}
+
+impl Packetizer for Av1Packetizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let obus = Self::parse_obus(encoded_data);
+        let mut new_sequence = false;
+
+        // OBU elements as sent: header with obu_has_size_field cleared + payload.
+        let mut elements: Vec<Vec<u8>> = Vec::with_capacity(obus.len());
+        for obu in &obus {
+            match obu.obu_type {
+                OBU_TEMPORAL_DELIMITER | OBU_TILE_LIST | OBU_PADDING => continue,
+                OBU_SEQUENCE_HEADER => {
+                    new_sequence = true;
+                    match parse_sequence_header(obu.payload) {
+                        Some(info) => {
+                            if self.sequence_header != Some(info) {
+                                tracing::debug!(?info, "AV1 sequence header captured");
+                            }
+                            self.sequence_header = Some(info);
+                        }
+                        None => tracing::warn!("unparseable AV1 sequence header"),
+                    }
+                }
+                _ => {}
+            }
+            let mut element = Vec::with_capacity(obu.header.len() + obu.payload.len());
+            element.extend_from_slice(obu.header);
+            element[0] &= !0x02;
+            element.extend_from_slice(obu.payload);
+            elements.push(element);
+        }
+
+        // Greedily fill packets. Space is budgeted with a length field for
+        // every element, so whichever W the packet ends up using, it fits.
+        let max_payload = self.mtu - 1;
+        let mut packets_elements: Vec<(u8, Vec<Vec<u8>>)> = Vec::new();
+        let mut current: Vec<Vec<u8>> = Vec::new();
+        let mut current_size = 0usize;
+        let mut flags = if new_sequence { AGG_N } else { 0 };
+
+        for element in elements {
+            let mut rest: &[u8] = &element;
+            loop {
+                let avail = max_payload - current_size;
+                let needed = leb128_len(rest.len() as u64) + rest.len();
+                if needed <= avail {
+                    current.push(rest.to_vec());
+                    current_size += needed;
+                    break;
+                }
+                // Fragment if at least one payload byte fits, else start a new packet.
+                if avail > leb128_len(avail as u64) {
+                    let fragment_len = avail - leb128_len(avail as u64);
+                    current.push(rest[..fragment_len].to_vec());
+                    rest = &rest[fragment_len..];
+                    packets_elements.push((flags | AGG_Y, std::mem::take(&mut current)));
+                    flags = AGG_Z;
+                } else {
+                    packets_elements.push((flags, std::mem::take(&mut current)));
+                    flags = 0;
+                }
+                current_size = 0;
+            }
+        }
+        if !current.is_empty() {
+            packets_elements.push((flags, current));
+        }
+
+        let count = packets_elements.len();
+        let mut packets = Vec::with_capacity(count);
+        for (i, (flags, elements)) in packets_elements.iter().enumerate() {
+            let payload = Self::build_payload(*flags, elements);
+            let hdr = self.header.write(i == count - 1);
+            let mut packet = Vec::with_capacity(12 + payload.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(&payload);
+            packets.push(packet);
+        }
+
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            obu_count = obus.len(),
+            rtp_packets = packets.len(),
+            frame_bytes = encoded_data.len(),
+            new_sequence,
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "temporal unit packetized"
+        );
+
+        packets
+    }
+
+    /// Temporal units that start a coded video sequence carry a sequence
+    /// header (the same test as the aggregation header's N bit).
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        Self::parse_obus(encoded_data)
+            .iter()
+            .any(|obu| obu.obu_type == OBU_SEQUENCE_HEADER)
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "AV1"
+    }
+
+    /// 90 kHz clock rate (§7.1).
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    /// SDP attributes per §7.2. `profile`, `level-idx` and `tier` are only
+    /// known after the first sequence header has been packetized.
+    fn sdp_attributes(&self) -> Vec<String> {
+        let pt = self.payload_type();
+        let mut attrs = vec![format!(
+            "a=rtpmap:{} {}/{}",
+            pt,
+            self.codec_name(),
+            self.clock_rate()
+        )];
+        if let Some(info) = self.sequence_header {
+            attrs.push(format!(
+                "a=fmtp:{} profile={};level-idx={};tier={}",
+                pt, info.profile, info.level_idx, info.tier
+            ));
+        }
+        attrs.push("a=control:track1".to_string());
+        attrs
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+/// Decode an unsigned LEB128 value (AV1 spec §4.10.5).
+/// Returns the value and the number of bytes consumed.
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn write_leb128(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn leb128_len(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
+/// Parse the fields of `sequence_header_obu()` needed for SDP (AV1 spec §5.5.1).
+fn parse_sequence_header(payload: &[u8]) -> Option<SequenceHeaderInfo> {
+    let mut r = BitReader::new(payload);
+    let profile = r.bits(3)? as u8;
+    let _still_picture = r.bits(1)?;
+    let reduced_still_picture_header = r.bits(1)? == 1;
+
+    if reduced_still_picture_header {
+        let level_idx = r.bits(5)? as u8;
+        return Some(SequenceHeaderInfo {
+            profile,
+            level_idx,
+            tier: 0,
+        });
+    }
+
+    let timing_info_present = r.bits(1)? == 1;
+    if timing_info_present {
+        // timing_info(): num_units_in_display_tick, time_scale
+        r.bits(32)?;
+        r.bits(32)?;
+        if r.bits(1)? == 1 {
+            r.uvlc()?; // num_ticks_per_picture_minus_1
+        }
+        let decoder_model_info_present = r.bits(1)? == 1;
+        if decoder_model_info_present {
+            // decoder_model_info(): buffer_delay_length_minus_1,
+            // num_units_in_decoding_tick, buffer_removal_time_length_minus_1,
+            // frame_presentation_time_length_minus_1
+            r.bits(5)?;
+            r.bits(32)?;
+            r.bits(5)?;
+            r.bits(5)?;
+        }
+    }
+    let _initial_display_delay_present = r.bits(1)?;
+    let _operating_points_cnt_minus_1 = r.bits(5)?;
+
+    // Operating point 0 comes first; its level and tier precede any
+    // per-operating-point decoder model fields.
+    let _operating_point_idc = r.bits(12)?;
+    let level_idx = r.bits(5)? as u8;
+    let tier = if level_idx > 7 { r.bits(1)? as u8 } else { 0 };
+
+    Some(SequenceHeaderInfo {
+        profile,
+        level_idx,
+        tier,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an OBU with `obu_has_size_field = 1`.
+    fn obu(obu_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![(obu_type << 3) | 0x02];
+        write_leb128(payload.len() as u64, &mut out);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Pack a string of '0'/'1' into bytes, MSB first, zero-padded.
+    fn bits(s: &str) -> Vec<u8> {
+        let bits: Vec<u8> = s.bytes().filter(|b| *b != b' ').map(|b| b - b'0').collect();
+        bits.chunks(8)
+            .map(|c| {
+                c.iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (i, b)| acc | (b << (7 - i)))
+            })
+            .collect()
+    }
+
+    /// profile=0, no timing info, 1 operating point, level 8 (4.0), high tier.
+    fn sequence_header_payload() -> Vec<u8> {
+        bits("000 0 0 0 0 00000 000000000000 01000 1")
+    }
+
+    #[test]
+    fn leb128_roundtrip() {
+        for value in [0u64, 1, 127, 128, 300, 16383, 16384, 1 << 40] {
+            let mut buf = Vec::new();
+            write_leb128(value, &mut buf);
+            assert_eq!(buf.len(), leb128_len(value));
+            assert_eq!(read_leb128(&buf), Some((value, buf.len())));
+        }
+    }
+
+    #[test]
+    fn parses_obus_with_and_without_size() {
+        let mut data = obu(OBU_TEMPORAL_DELIMITER, &[]);
+        data.extend(obu(6, &[1, 2, 3]));
+        // Last OBU without size field runs to the end.
+        data.extend_from_slice(&[(6 << 3), 9, 9]);
+        let obus = Av1Packetizer::parse_obus(&data);
+        assert_eq!(obus.len(), 3);
+        assert_eq!(obus[0].obu_type, OBU_TEMPORAL_DELIMITER);
+        assert_eq!(obus[1].payload, &[1, 2, 3]);
+        assert_eq!(obus[2].payload, &[9, 9]);
+    }
+
+    #[test]
+    fn parses_sequence_header() {
+        assert_eq!(
+            parse_sequence_header(&sequence_header_payload()),
+            Some(SequenceHeaderInfo {
+                profile: 0,
+                level_idx: 8,
+                tier: 1
+            })
+        );
+        // Reduced still picture header: profile 1, level 5.
+        assert_eq!(
+            parse_sequence_header(&bits("001 1 1 00101")),
+            Some(SequenceHeaderInfo {
+                profile: 1,
+                level_idx: 5,
+                tier: 0
+            })
+        );
+    }
+
+    #[test]
+    fn small_temporal_unit_aggregated() {
+        let mut p = Av1Packetizer::new(96, 0x1234);
+        let mut tu = obu(OBU_TEMPORAL_DELIMITER, &[]);
+        tu.extend(obu(OBU_SEQUENCE_HEADER, &sequence_header_payload()));
+        tu.extend(obu(6, &[0xAA; 10]));
+        let packets = p.packetize(&tu, 3000);
+        assert_eq!(packets.len(), 1);
+
+        let pkt = &packets[0];
+        assert_eq!(pkt[1] & 0x80, 0x80, "marker on last packet of TU");
+        let agg = pkt[12];
+        assert_eq!(agg & (AGG_Z | AGG_Y), 0);
+        assert_eq!((agg >> 4) & 0x03, 2, "W=2: TD dropped, seq header + frame");
+        assert_eq!(agg & AGG_N, AGG_N, "sequence header starts a new CVS");
+
+        // First element: length-prefixed sequence header with size flag cleared.
+        let (len, n) = read_leb128(&pkt[13..]).unwrap();
+        assert_eq!(pkt[13 + n], OBU_SEQUENCE_HEADER << 3);
+        assert_eq!(len as usize, 1 + sequence_header_payload().len());
+        // Last element: no length field, runs to end of packet.
+        let last = &pkt[13 + n + len as usize..];
+        assert_eq!(last[0], 6 << 3);
+        assert_eq!(&last[1..], &[0xAA; 10]);
+
+        assert_eq!(
+            p.sdp_attributes()[1],
+            "a=fmtp:96 profile=0;level-idx=8;tier=1"
+        );
+    }
+
+    #[test]
+    fn large_obu_fragmented() {
+        let mut p = Av1Packetizer::new(96, 0x1234);
+        let tu = obu(6, &vec![0x55; DEFAULT_MTU * 2 + 100]);
+        let packets = p.packetize(&tu, 3000);
+        assert_eq!(packets.len(), 3);
+
+        let aggs: Vec<u8> = packets.iter().map(|pkt| pkt[12]).collect();
+        assert_eq!(aggs[0] & (AGG_Z | AGG_Y), AGG_Y);
+        assert_eq!(aggs[1] & (AGG_Z | AGG_Y), AGG_Z | AGG_Y);
+        assert_eq!(aggs[2] & (AGG_Z | AGG_Y), AGG_Z);
+        assert!(aggs.iter().all(|a| a & AGG_N == 0));
+        assert!(packets.iter().all(|pkt| pkt.len() <= 12 + DEFAULT_MTU));
+        assert_eq!(packets[0][1] & 0x80, 0);
+        assert_eq!(packets[2][1] & 0x80, 0x80);
+
+        // Reassemble: every packet has a single element (W=1, no length).
+        let mut obu_bytes = Vec::new();
+        for pkt in &packets {
+            assert_eq!((pkt[12] >> 4) & 0x03, 1);
+            obu_bytes.extend_from_slice(&pkt[13..]);
+        }
+        assert_eq!(obu_bytes[0], 6 << 3);
+        assert_eq!(obu_bytes.len(), 1 + DEFAULT_MTU * 2 + 100);
+    }
+
+    #[test]
+    fn many_small_obus_use_w0() {
+        let mut p = Av1Packetizer::new(96, 0x1234);
+        let tu: Vec<u8> = (0..5).flat_map(|_| obu(6, &[1, 2])).collect();
+        let packets = p.packetize(&tu, 3000);
+        assert_eq!(packets.len(), 1);
+        assert_eq!((packets[0][12] >> 4) & 0x03, 0);
+        // 5 × (length byte + 3-byte element)
+        assert_eq!(packets[0].len(), 12 + 1 + 5 * 4);
+    }
+
+    #[test]
+    fn sdp_without_sequence_header() {
+        let p = Av1Packetizer::new(98, 1);
+        assert_eq!(
+            p.sdp_attributes(),
+            vec!["a=rtpmap:98 AV1/90000", "a=control:track1"]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+/// MSB-first bit reader for codec header parsing (AV1 sequence headers,
+/// VP9 uncompressed headers, H.264/H.265 parameter sets).
+///
+/// Every read returns `None` once the data is exhausted, so parsers can
+/// propagate truncation with `?`.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Read `n` bits (at most 32) as an unsigned integer.
+    pub(crate) fn bits(&mut self, n: u32) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..n {
+            let byte = *self.data.get(self.pos / 8)?;
+            let bit = (byte >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Variable-length unsigned integer (AV1 spec §4.10.3).
+    pub(crate) fn uvlc(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0u32;
+        while self.bits(1)? == 0 {
+            leading_zeros += 1;
+            if leading_zeros >= 32 {
+                return Some(u32::MAX);
+            }
+        }
+        Some(self.bits(leading_zeros)? + ((1u32 << leading_zeros) - 1))
+    }
+
+    /// Unsigned Exp-Golomb code `ue(v)` (H.264 §9.1). Same encoding as
+    /// [`uvlc`](Self::uvlc), but longer codes are an error.
+    pub(crate) fn ue(&mut self) -> Option<u32> {
+        match self.uvlc()? {
+            u32::MAX => None,
+            value => Some(value),
+        }
+    }
+
+    /// Signed Exp-Golomb code `se(v)` (H.264 §9.1.1).
+    pub(crate) fn se(&mut self) -> Option<i32> {
+        let code = self.ue()?;
+        let magnitude = code.div_ceil(2) as i32;
+        Some(if code % 2 == 1 { magnitude } else { -magnitude })
+    }
+
+    /// Skip `n` bits.
+    pub(crate) fn skip(&mut self, n: usize) -> Option<()> {
+        if self.pos + n > self.data.len() * 8 {
+            return None;
+        }
+        self.pos += n;
+        Some(())
+    }
+}
+
+/// The RBSP of an H.264/H.265 NAL unit payload: `emulation_prevention_three_byte`s
+/// removed (H.264 §7.4.1).
+pub(crate) fn rbsp(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len());
+    let mut zeros = 0;
+    for &byte in payload {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        out.push(byte);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_across_byte_boundaries() {
+        let mut r = BitReader::new(&[0b1010_1100, 0b0101_0000]);
+        assert_eq!(r.bits(3), Some(0b101));
+        assert_eq!(r.bits(7), Some(0b0110001));
+        assert_eq!(r.bits(6), Some(0b010000));
+        assert_eq!(r.bits(1), None);
+    }
+
+    #[test]
+    fn reads_uvlc() {
+        // 1 → 0, 010 → 1, 00111 → 6
+        let mut r = BitReader::new(&[0b1010_0011, 0b1000_0000]);
+        assert_eq!(r.uvlc(), Some(0));
+        assert_eq!(r.uvlc(), Some(1));
+        assert_eq!(r.uvlc(), Some(6));
+    }
+
+    #[test]
+    fn reads_exp_golomb_and_strips_emulation_prevention() {
+        // ue: 1 → 0, 011 → 2; se: 010 → 1, 011 → -1, 00100 → 2
+        let mut r = BitReader::new(&[0b1011_0100, 0b1100_1000]);
+        assert_eq!(r.ue(), Some(0));
+        assert_eq!(r.ue(), Some(2));
+        assert_eq!(r.se(), Some(1));
+        assert_eq!(r.se(), Some(-1));
+        assert_eq!(r.se(), Some(2));
+        assert_eq!(r.skip(1), Some(()));
+        assert_eq!(r.skip(1), None);
+
+        assert_eq!(
+            rbsp(&[0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x03, 0x00, 0x03]),
+            [0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03]
+        );
+    }
+}
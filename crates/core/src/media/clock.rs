@@ -0,0 +1,204 @@
+use std::time::{Duration, SystemTime};
+
+use rand::RngExt;
+
+/// Maps presentation timestamps to a codec's RTP clock (RFC 3550 §5.1).
+///
+/// Callers hand in the PTS of each frame as a [`Duration`] on any time base
+/// (encoder PTS, GStreamer running time, capture time). The clock converts it
+/// to RTP ticks at the codec's clock rate and adds a random initial offset,
+/// as RFC 3550 §5.1 recommends, so the wire timestamp never depends on how
+/// long the producer has been running.
+///
+/// ## Reordered frames (B-frames)
+///
+/// The RTP timestamp of a video frame is its *presentation* time
+/// (RFC 6184 §5.1), so with B-frames the timestamps on the wire are not
+/// monotonic in packet order. That is expected. What must stay monotonic is
+/// the mapping to wall-clock time used by RTCP sender reports, which is why
+/// the reference is advanced by the *decode* timestamp: either the DTS the
+/// caller supplies, or — when only PTS is known — the highest PTS seen so far.
+///
+/// ```text
+/// decode order:  I(pts=0)  P(pts=3)  B(pts=1)  B(pts=2)
+/// RTP ts:        o+0       o+3·r     o+1·r     o+2·r      (o = random offset)
+/// reference:     0         3         3         3          (never moves back)
+/// ```
+#[derive(Debug)]
+pub struct RtpClock {
+    clock_rate: u32,
+    offset: u32,
+    reference: Option<ClockReference>,
+}
+
+/// Wall-clock anchor of an [`RtpClock`]: media time `media_time` was
+/// delivered at `wall_time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockReference {
+    /// Decode-order media time of the most recent frame.
+    pub media_time: Duration,
+    /// RTP timestamp corresponding to `media_time`.
+    pub rtp_timestamp: u32,
+    /// Wall-clock time at which that frame was packetized.
+    pub wall_time: SystemTime,
+}
+
+impl RtpClock {
+    /// Create a clock with a random initial RTP timestamp offset.
+    pub fn new(clock_rate: u32) -> Self {
+        Self::with_offset(clock_rate, rand::rng().random::<u32>())
+    }
+
+    /// Create a clock with an explicit initial offset (useful in tests).
+    pub fn with_offset(clock_rate: u32, offset: u32) -> Self {
+        Self {
+            clock_rate,
+            offset,
+            reference: None,
+        }
+    }
+
+    /// Codec clock rate in Hz.
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Initial RTP timestamp offset (the RTP timestamp of media time zero).
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Convert a media time to an RTP timestamp (offset applied, wrapping).
+    pub fn rtp_timestamp(&self, media_time: Duration) -> u32 {
+        self.offset.wrapping_add(self.ticks(media_time))
+    }
+
+    /// Duration in clock ticks, rounded to nearest and truncated to 32 bits.
+    fn ticks(&self, d: Duration) -> u32 {
+        ((d.as_nanos() * self.clock_rate as u128 + 500_000_000) / 1_000_000_000) as u32
+    }
+
+    /// Register a frame and return the RTP timestamp for its PTS.
+    ///
+    /// `dts` is the decode timestamp when the producer knows it; without it
+    /// the PTS is assumed to be in decode order except for reordered frames,
+    /// which never move the wall-clock reference backwards.
+    pub fn on_frame(&mut self, pts: Duration, dts: Option<Duration>) -> u32 {
+        self.on_frame_at(pts, dts, SystemTime::now())
+    }
+
+    fn on_frame_at(&mut self, pts: Duration, dts: Option<Duration>, now: SystemTime) -> u32 {
+        let decode_time = dts.unwrap_or(pts);
+        let advance = match &self.reference {
+            Some(r) => decode_time >= r.media_time,
+            None => true,
+        };
+        if advance {
+            self.reference = Some(ClockReference {
+                media_time: decode_time,
+                rtp_timestamp: self.rtp_timestamp(decode_time),
+                wall_time: now,
+            });
+        } else {
+            tracing::trace!(
+                ?pts,
+                ?dts,
+                reference = ?self.reference.map(|r| r.media_time),
+                "reordered frame, wall-clock reference unchanged"
+            );
+        }
+        self.rtp_timestamp(pts)
+    }
+
+    /// Current wall-clock anchor, if any frame has been registered.
+    pub fn reference(&self) -> Option<ClockReference> {
+        self.reference
+    }
+
+    /// RTP timestamp corresponding to a wall-clock instant, extrapolated from
+    /// the reference at the codec clock rate. This is the value an RTCP
+    /// sender report (RFC 3550 §6.4.1) pairs with its NTP timestamp.
+    pub fn rtp_timestamp_at(&self, wall_time: SystemTime) -> Option<u32> {
+        let reference = self.reference?;
+        Some(match wall_time.duration_since(reference.wall_time) {
+            Ok(ahead) => reference.rtp_timestamp.wrapping_add(self.ticks(ahead)),
+            Err(behind) => reference
+                .rtp_timestamp
+                .wrapping_sub(self.ticks(behind.duration())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_pts_with_offset() {
+        let clock = RtpClock::with_offset(90000, 1000);
+        assert_eq!(clock.rtp_timestamp(Duration::ZERO), 1000);
+        assert_eq!(clock.rtp_timestamp(Duration::from_millis(40)), 1000 + 3600);
+        assert_eq!(clock.rtp_timestamp(Duration::from_secs(1)), 1000 + 90000);
+    }
+
+    #[test]
+    fn wraps_around_u32() {
+        let clock = RtpClock::with_offset(90000, u32::MAX);
+        assert_eq!(clock.rtp_timestamp(Duration::from_secs(1)), 89999);
+    }
+
+    #[test]
+    fn random_offsets_differ() {
+        let a = RtpClock::new(90000);
+        let b = RtpClock::new(90000);
+        assert_ne!(
+            a.rtp_timestamp(Duration::ZERO),
+            b.rtp_timestamp(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn reordered_frames_keep_reference() {
+        let mut clock = RtpClock::with_offset(90000, 0);
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let frame = Duration::from_millis(40);
+
+        assert_eq!(clock.on_frame_at(Duration::ZERO, None, t0), 0);
+        assert_eq!(clock.on_frame_at(frame * 3, None, t0 + frame), 3 * 3600);
+        let b = clock.on_frame_at(frame, None, t0 + frame * 2);
+        assert_eq!(b, 3600, "B-frame keeps its presentation timestamp");
+
+        let reference = clock.reference().unwrap();
+        assert_eq!(reference.media_time, frame * 3);
+        assert_eq!(reference.wall_time, t0 + frame);
+    }
+
+    #[test]
+    fn dts_drives_reference() {
+        let mut clock = RtpClock::with_offset(90000, 0);
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let frame = Duration::from_millis(40);
+
+        clock.on_frame_at(frame * 3, Some(frame), t0);
+        let reference = clock.reference().unwrap();
+        assert_eq!(reference.media_time, frame);
+        assert_eq!(reference.rtp_timestamp, 3600);
+    }
+
+    #[test]
+    fn extrapolates_wall_clock() {
+        let mut clock = RtpClock::with_offset(90000, 0);
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        assert!(clock.rtp_timestamp_at(t0).is_none());
+
+        clock.on_frame_at(Duration::from_secs(2), None, t0);
+        assert_eq!(
+            clock.rtp_timestamp_at(t0 + Duration::from_millis(500)),
+            Some(180000 + 45000)
+        );
+        assert_eq!(
+            clock.rtp_timestamp_at(t0 - Duration::from_millis(500)),
+            Some(180000 - 45000)
+        );
+    }
+}
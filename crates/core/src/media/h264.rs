@@ -1,9 +1,12 @@
+use std::ops::Range;
+
 use base64::prelude::{BASE64_STANDARD, Engine as _};
+use bytes::Bytes;
 
-use super::Packetizer;
+use super::bits::{self, BitReader};
+use super::packet::RtpPacket;
 use super::rtp::RtpHeader;
-
-const DEFAULT_MTU: usize = 1400;
+use super::{DEFAULT_MTU, Packetizer};
 
 /// H.264 RTP packetizer (RFC 6184).
 ///
@@ -79,6 +82,15 @@ impl H264Packetizer {
         }
     }
 
+    /// Advertise `sps` and `pps` (NAL units without start codes) in the
+    /// SDP from the start, e.g. from a file's `avcC` box, instead of
+    /// waiting for them to appear in the bitstream.
+    pub fn with_parameter_sets(mut self, sps: Vec<u8>, pps: Vec<u8>) -> Self {
+        self.sps = Some(sps);
+        self.pps = Some(pps);
+        self
+    }
+
     /// Derive profile-level-id from SPS NAL (RFC 6184 §8.1): bytes 1–3 are profile_idc, constraint_set, level_idc.
     fn get_profile_level_id(&self) -> Result<String, String> {
         let sps = self.sps.as_deref().ok_or("SPS not set")?;
@@ -105,66 +117,76 @@ impl H264Packetizer {
     /// (RFC 6184 §5.8).
     fn packetize_nal(&mut self, nal_unit: &[u8], is_last_nal: bool) -> Vec<Vec<u8>> {
         let mut packets = Vec::new();
+        self.fragment_nal(nal_unit, is_last_nal, |header, range| {
+            let mut packet = Vec::with_capacity(header.len() + range.len());
+            packet.extend_from_slice(header);
+            packet.extend_from_slice(&nal_unit[range]);
+            packets.push(packet);
+        });
+        packets
+    }
 
+    /// Split a NAL unit into RTP packets without copying its payload.
+    ///
+    /// For each packet, `emit` receives the packet header (RTP header plus
+    /// the FU indicator/header for FU-A) and the range of `nal_unit` that
+    /// follows it. Both the `Vec` and the shared-buffer paths build on this.
+    fn fragment_nal(
+        &mut self,
+        nal_unit: &[u8],
+        is_last_nal: bool,
+        mut emit: impl FnMut(&[u8], Range<usize>),
+    ) {
         if nal_unit.is_empty() {
-            return packets;
+            return;
         }
 
         if nal_unit.len() <= self.mtu {
             // Single NAL Unit mode (RFC 6184 §5.6)
             let hdr = self.header.write(is_last_nal);
-            let mut packet = Vec::with_capacity(12 + nal_unit.len());
-            packet.extend_from_slice(&hdr);
-            packet.extend_from_slice(nal_unit);
-            packets.push(packet);
-        } else {
-            // FU-A fragmentation (RFC 6184 §5.8)
-            let nal_header = nal_unit[0];
-            let nal_type = nal_header & 0x1f;
-            let nri = nal_header & 0x60;
-
-            // FU indicator: NRI from original NAL, type = 28 (FU-A)
-            let fu_indicator = nri | 28;
-            let payload = &nal_unit[1..];
-
-            let max_fragment = self.mtu - 2; // 2 bytes for FU indicator + FU header
-            let mut offset = 0usize;
-            let mut first = true;
-
-            while offset < payload.len() {
-                let remaining = payload.len() - offset;
-                let last_fragment = remaining <= max_fragment;
-                let chunk_size = std::cmp::min(max_fragment, remaining);
-                let chunk = &payload[offset..offset + chunk_size];
-
-                // FU header: S=start, E=end, R=0, Type=original NAL type
-                let start_bit = if first { 0x80 } else { 0x00 };
-                let end_bit = if last_fragment { 0x40 } else { 0x00 };
-                let fu_header = start_bit | end_bit | nal_type;
-
-                let marker = is_last_nal && last_fragment;
-                let hdr = self.header.write(marker);
-
-                let mut packet = Vec::with_capacity(12 + 2 + chunk.len());
-                packet.extend_from_slice(&hdr);
-                packet.push(fu_indicator);
-                packet.push(fu_header);
-                packet.extend_from_slice(chunk);
-                packets.push(packet);
-
-                offset += chunk_size;
-                first = false;
-            }
+            emit(&hdr, 0..nal_unit.len());
+            return;
+        }
 
-            tracing::trace!(
-                nal_type,
-                nal_size = nal_unit.len(),
-                fragments = packets.len(),
-                "FU-A fragmented NAL unit"
-            );
+        // FU-A fragmentation (RFC 6184 §5.8)
+        let nal_header = nal_unit[0];
+        let nal_type = nal_header & 0x1f;
+        let nri = nal_header & 0x60;
+
+        // FU indicator: NRI from original NAL, type = 28 (FU-A)
+        let fu_indicator = nri | 28;
+
+        let max_fragment = self.mtu - 2; // 2 bytes for FU indicator + FU header
+        let mut offset = 1usize; // the NAL header is carried in the FU header
+        let mut fragments = 0usize;
+
+        while offset < nal_unit.len() {
+            let remaining = nal_unit.len() - offset;
+            let last_fragment = remaining <= max_fragment;
+            let chunk_size = std::cmp::min(max_fragment, remaining);
+
+            // FU header: S=start, E=end, R=0, Type=original NAL type
+            let start_bit = if fragments == 0 { 0x80 } else { 0x00 };
+            let end_bit = if last_fragment { 0x40 } else { 0x00 };
+            let fu_header = start_bit | end_bit | nal_type;
+
+            let marker = is_last_nal && last_fragment;
+            let mut hdr = [0u8; 14];
+            hdr[..12].copy_from_slice(&self.header.write(marker));
+            hdr[12] = fu_indicator;
+            hdr[13] = fu_header;
+            emit(&hdr, offset..offset + chunk_size);
+
+            offset += chunk_size;
+            fragments += 1;
         }
 
-        packets
+        tracing::trace!(
+            nal_type,
+            nal_size = nal_unit.len(),
+            fragments,
+            "FU-A fragmented NAL unit"
+        );
     }
 
     /// Extract NAL units from an H.264 Annex B bitstream.
@@ -177,18 +199,34 @@ impl H264Packetizer {
     /// between adjacent NALs are computed correctly when mixed 3-byte
     /// and 4-byte start codes appear.
     pub fn extract_nal_units(data: &[u8]) -> Vec<Vec<u8>> {
+        Self::nal_unit_ranges(data)
+            .into_iter()
+            .map(|range| data[range].to_vec())
+            .collect()
+    }
+
+    /// Byte ranges of the NAL units in an Annex B bitstream, without the
+    /// start codes. [`extract_nal_units`](Self::extract_nal_units) without
+    /// the copies.
+    pub fn nal_unit_ranges(data: &[u8]) -> Vec<Range<usize>> {
         let mut nal_units = Vec::new();
         let mut i = 0usize;
 
         // (nal_data_start_index, start_code_length)
         let mut start_entries: Vec<(usize, usize)> = Vec::new();
 
-        while i < data.len() {
-            if i + 3 < data.len() && data[i..i + 4] == [0, 0, 0, 1] {
-                start_entries.push((i + 4, 4));
-                i += 4;
-            } else if i + 2 < data.len() && data[i..i + 3] == [0, 0, 1] {
-                start_entries.push((i + 3, 3));
+        // Look for `00 00 01` and widen it to the 4-byte form when preceded
+        // by another zero. A third byte above 1 rules out a start code at
+        // this position and the next two, so the scan skips ahead by three.
+        while i + 2 < data.len() {
+            if data[i + 2] > 1 {
+                i += 3;
+            } else if data[i..i + 3] == [0, 0, 1] {
+                if i > 0 && data[i - 1] == 0 {
+                    start_entries.push((i + 3, 4));
+                } else {
+                    start_entries.push((i + 3, 3));
+                }
                 i += 3;
             } else {
                 i += 1;
@@ -204,56 +242,121 @@ impl H264Packetizer {
             };
 
             if start < end {
-                nal_units.push(data[start..end].to_vec());
+                nal_units.push(start..end);
             }
         }
 
         nal_units
     }
-}
 
-impl Packetizer for H264Packetizer {
-    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
-        let nal_units = Self::extract_nal_units(encoded_data);
-        let mut packets = Vec::new();
-
-        // Auto-capture SPS/PPS from first frame that contains them (e.g. first keyframe).
-        // Only set when not already provided by the user.
-        if self.sps.is_none() || self.pps.is_none() {
-            for nal in &nal_units {
-                if nal.is_empty() {
-                    continue;
-                }
-                let nal_type = nal[0] & 0x1f;
-                if nal_type == 7 && self.sps.is_none() {
-                    self.sps = Some(nal.clone());
-                    tracing::debug!("H.264 SPS captured from bitstream ({} bytes)", nal.len());
-                } else if nal_type == 8 && self.pps.is_none() {
-                    self.pps = Some(nal.clone());
-                    tracing::debug!("H.264 PPS captured from bitstream ({} bytes)", nal.len());
+    /// Whether an access unit contains an IDR slice (NAL type 5).
+    ///
+    /// Stops at the first slice NAL, so only the parameter sets and SEI in
+    /// front of it are scanned.
+    pub fn is_idr(data: &[u8]) -> bool {
+        let mut i = 0usize;
+        while i + 3 < data.len() {
+            if data[i..i + 3] == [0, 0, 1] {
+                match data[i + 3] & 0x1f {
+                    5 => return true,
+                    1..=4 => return false,
+                    _ => {}
                 }
+                i += 3;
+            } else {
+                i += 1;
             }
         }
+        false
+    }
 
-        for (i, nal) in nal_units.iter().enumerate() {
-            let is_last = i == nal_units.len() - 1;
-            packets.append(&mut self.packetize_nal(nal, is_last));
+    /// Auto-capture SPS/PPS from first frame that contains them (e.g. first keyframe).
+    /// Only set when not already provided by the user.
+    fn capture_parameter_sets(&mut self, data: &[u8], nal_units: &[Range<usize>]) {
+        if self.sps.is_some() && self.pps.is_some() {
+            return;
         }
+        for range in nal_units {
+            let nal = &data[range.clone()];
+            let nal_type = nal[0] & 0x1f;
+            if nal_type == 7 && self.sps.is_none() {
+                self.sps = Some(nal.to_vec());
+                tracing::debug!("H.264 SPS captured from bitstream ({} bytes)", nal.len());
+            } else if nal_type == 8 && self.pps.is_none() {
+                self.pps = Some(nal.to_vec());
+                tracing::debug!("H.264 PPS captured from bitstream ({} bytes)", nal.len());
+            }
+        }
+    }
 
+    fn finish_frame(
+        &mut self,
+        nal_count: usize,
+        rtp_packets: usize,
+        frame_bytes: usize,
+        timestamp_increment: u32,
+    ) {
         self.header.advance_timestamp(timestamp_increment);
 
         tracing::trace!(
-            nal_count = nal_units.len(),
-            rtp_packets = packets.len(),
-            frame_bytes = encoded_data.len(),
+            nal_count,
+            rtp_packets,
+            frame_bytes,
             seq = self.header.sequence(),
             ts = self.header.timestamp(),
             "frame packetized"
         );
+    }
+}
 
+impl Packetizer for H264Packetizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let nal_units = Self::nal_unit_ranges(encoded_data);
+        self.capture_parameter_sets(encoded_data, &nal_units);
+
+        let mut packets = Vec::new();
+        for (i, range) in nal_units.iter().enumerate() {
+            let is_last = i == nal_units.len() - 1;
+            packets.append(&mut self.packetize_nal(&encoded_data[range.clone()], is_last));
+        }
+
+        self.finish_frame(
+            nal_units.len(),
+            packets.len(),
+            encoded_data.len(),
+            timestamp_increment,
+        );
         packets
     }
 
+    /// Zero-copy packetization: every packet's payload is a slice of
+    /// `encoded_data`; only the RTP and FU headers are written.
+    fn packetize_bytes(&mut self, encoded_data: Bytes, timestamp_increment: u32) -> Vec<RtpPacket> {
+        let nal_units = Self::nal_unit_ranges(&encoded_data);
+        self.capture_parameter_sets(&encoded_data, &nal_units);
+
+        let mut packets = Vec::new();
+        for (i, range) in nal_units.iter().enumerate() {
+            let is_last = i == nal_units.len() - 1;
+            let nal = encoded_data.slice(range.clone());
+            self.fragment_nal(&nal, is_last, |header, payload| {
+                packets.push(RtpPacket::new(header, nal.slice(payload)));
+            });
+        }
+
+        self.finish_frame(
+            nal_units.len(),
+            packets.len(),
+            encoded_data.len(),
+            timestamp_increment,
+        );
+        packets
+    }
+
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        Self::is_idr(encoded_data)
+    }
+
     fn codec_name(&self) -> &'static str {
         "H264"
     }
@@ -301,9 +404,144 @@ impl Packetizer for H264Packetizer {
         self.header.sequence()
     }
 
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
     fn next_rtp_timestamp(&self) -> u32 {
         self.header.timestamp() as u32
     }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+/// Fields of an H.264 sequence parameter set needed to describe the stream
+/// in a container (ITU-T H.264 §7.3.2.1.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceParameterSet {
+    pub profile_idc: u8,
+    /// `constraint_set0_flag` .. `constraint_set5_flag` and the reserved bits.
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+    /// 0 = monochrome, 1 = 4:2:0, 2 = 4:2:2, 3 = 4:4:4.
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+    /// Frame size in luma samples, after cropping.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SequenceParameterSet {
+    /// Parse an SPS NAL unit (with its header, without start code).
+    /// Returns `None` if it is truncated or not an SPS.
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        if nal.len() < 4 || nal[0] & 0x1f != 7 {
+            return None;
+        }
+        let data = bits::rbsp(&nal[1..]);
+        let mut r = BitReader::new(&data);
+        let profile_idc = r.bits(8)? as u8;
+        let constraint_flags = r.bits(8)? as u8;
+        let level_idc = r.bits(8)? as u8;
+        let _seq_parameter_set_id = r.ue()?;
+
+        let (mut chroma_format_idc, mut bit_depth_luma, mut bit_depth_chroma) = (1, 8, 8);
+        let mut separate_colour_plane = false;
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            chroma_format_idc = r.ue()?;
+            if chroma_format_idc == 3 {
+                separate_colour_plane = r.bits(1)? == 1;
+            }
+            bit_depth_luma = 8 + r.ue()?;
+            bit_depth_chroma = 8 + r.ue()?;
+            let _qpprime_y_zero_transform_bypass_flag = r.bits(1)?;
+            if r.bits(1)? == 1 {
+                let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..lists {
+                    if r.bits(1)? == 1 {
+                        skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        let _log2_max_frame_num_minus4 = r.ue()?;
+        match r.ue()? {
+            0 => {
+                let _log2_max_pic_order_cnt_lsb_minus4 = r.ue()?;
+            }
+            1 => {
+                r.skip(1)?;
+                r.se()?;
+                r.se()?;
+                for _ in 0..r.ue()? {
+                    r.se()?;
+                }
+            }
+            _ => {}
+        }
+        let _max_num_ref_frames = r.ue()?;
+        r.skip(1)?;
+        let width_in_mbs = r.ue()? + 1;
+        let height_in_map_units = r.ue()? + 1;
+        let frame_mbs_only = r.bits(1)?;
+        if frame_mbs_only == 0 {
+            r.skip(1)?;
+        }
+        r.skip(1)?;
+
+        let mut width = width_in_mbs * 16;
+        let mut height = (2 - frame_mbs_only) * height_in_map_units * 16;
+        if r.bits(1)? == 1 {
+            let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+            // CropUnitX/CropUnitY (H.264 equations 7-19 to 7-22).
+            let (sub_width, sub_height) = match (chroma_format_idc, separate_colour_plane) {
+                (1, false) => (2, 2),
+                (2, false) => (2, 1),
+                _ => (1, 1),
+            };
+            let unit_x = if chroma_format_idc == 0 { 1 } else { sub_width };
+            let unit_y = if chroma_format_idc == 0 {
+                1
+            } else {
+                sub_height
+            } * (2 - frame_mbs_only);
+            width = width.checked_sub(unit_x * (left + right))?;
+            height = height.checked_sub(unit_y * (top + bottom))?;
+        }
+
+        Some(Self {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            chroma_format_idc: chroma_format_idc as u8,
+            bit_depth_luma: bit_depth_luma as u8,
+            bit_depth_chroma: bit_depth_chroma as u8,
+            width,
+            height,
+        })
+    }
+}
+
+/// Skip a `scaling_list()` (H.264 §7.3.2.1.1.1).
+fn skip_scaling_list(r: &mut BitReader<'_>, size: usize) -> Option<()> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
 }
 
 #[cfg(test)]
@@ -352,6 +590,19 @@ mod tests {
         assert_eq!(nals[1], vec![0x68, 0xCE]);
     }
 
+    #[test]
+    fn extract_start_codes_at_any_alignment() {
+        for pad in 0..4 {
+            let mut data = vec![0, 0, 0, 1, 0x65];
+            data.extend(std::iter::repeat_n(0xAA, pad));
+            data.extend_from_slice(&[0xFF, 0, 0, 1, 0x41, 0x9A]);
+            let nals = H264Packetizer::extract_nal_units(&data);
+            assert_eq!(nals.len(), 2, "pad {pad}");
+            assert_eq!(nals[1], vec![0x41, 0x9A]);
+            assert_eq!(nals[0].len(), 2 + pad);
+        }
+    }
+
     #[test]
     fn extract_empty_data() {
         assert!(H264Packetizer::extract_nal_units(&[]).is_empty());
@@ -407,11 +658,45 @@ mod tests {
         assert!(!packets.is_empty());
     }
 
+    #[test]
+    fn packetize_bytes_matches_packetize_without_copies() {
+        let mut nal = vec![0x65];
+        nal.extend((0..DEFAULT_MTU * 2).map(|i| i as u8));
+        let frame = [&[0u8, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e, 0, 0, 1][..], &nal].concat();
+
+        let mut copying = make_packetizer();
+        let mut shared = make_packetizer();
+        let expected = copying.packetize(&frame, 3000);
+        let frame = Bytes::from(frame);
+        let packets = shared.packetize_bytes(frame.clone(), 3000);
+
+        assert_eq!(packets.len(), expected.len());
+        for (packet, expected) in packets.iter().zip(&expected) {
+            assert_eq!(&packet.to_vec(), expected);
+            let payload = packet.payload().as_ptr() as usize;
+            let frame_start = frame.as_ptr() as usize;
+            assert!((frame_start..frame_start + frame.len()).contains(&payload));
+        }
+        assert_eq!(shared.next_rtp_timestamp(), copying.next_rtp_timestamp());
+    }
+
+    #[test]
+    fn detects_idr_access_units() {
+        let p = make_packetizer();
+        let idr = [
+            0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88,
+        ];
+        let non_idr = [0, 0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x41, 0x9a, 0, 0, 1, 0x65];
+        assert!(p.is_keyframe(&idr));
+        assert!(!p.is_keyframe(&non_idr), "stops at the first slice");
+        assert!(!p.is_keyframe(&[]));
+    }
+
     #[test]
     fn sdp_attributes_include_packetization_mode() {
         let p = make_packetizer();
         let attrs = p.sdp_attributes();
-        assert!(attrs.len() >= 1, "must include at least fmtp");
+        assert!(!attrs.is_empty(), "must include at least fmtp");
         assert!(
             attrs.iter().any(|a| a.contains("packetization-mode=1")),
             "must include packetization-mode=1"
@@ -455,4 +740,25 @@ mod tests {
             "SPS/PPS auto-captured, sprop-parameter-sets in SDP"
         );
     }
+
+    #[test]
+    fn parses_sps_dimensions() {
+        // x264, High profile, 1280x720
+        let sps = [
+            0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10, 0x00, 0x00,
+            0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xc0, 0xf1, 0x83, 0x19, 0x60,
+        ];
+        let info = SequenceParameterSet::parse(&sps).unwrap();
+        assert_eq!((info.profile_idc, info.level_idc), (100, 31));
+        assert_eq!((info.width, info.height), (1280, 720));
+        assert_eq!(info.chroma_format_idc, 1);
+
+        // 1920x1088 coded, cropped to 1080
+        let sps = [
+            0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0xc0, 0x44,
+        ];
+        let info = SequenceParameterSet::parse(&sps).unwrap();
+        assert_eq!((info.width, info.height), (1920, 1080));
+        assert!(SequenceParameterSet::parse(&[0x68, 0xce, 0x38, 0x80]).is_none());
+    }
 }
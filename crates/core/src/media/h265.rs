@@ -1,22 +1,500 @@
-//! H.265 (HEVC) RTP packetizer — RFC 7798.
-//!
-//! Key differences from H.264 (RFC 6184):
-//!
-//! - **2-byte NAL unit header** (vs 1-byte in H.264).
-//!   The NAL type is in bits 1..6 of the first byte.
-//!
-//! - **FU header format**: FU indicator (1 byte) + FU header (1 byte)
-//!   with a 6-bit NAL type field.
-//!
-//! - **SDP attributes** (RFC 7798 §7.1):
-//!   ```text
-//!   a=rtpmap:96 H265/90000
-//!   a=fmtp:96 sprop-vps=...; sprop-sps=...; sprop-pps=...
-//!   ```
-//!
-//! ## Implementation plan
-//!
-//! Will follow the same pattern as [`super::h264::H264Packetizer`]:
-//! - Compose an [`super::rtp::RtpHeader`] for generic header building.
-//! - Implement [`super::Packetizer`] trait.
-//! - Extract NAL units from Annex B (same start codes, different header parsing).
+use std::ops::Range;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use bytes::Bytes;
+
+use super::bits::{self, BitReader};
+use super::h264::H264Packetizer;
+use super::packet::RtpPacket;
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, Packetizer};
+
+/// NAL unit types of the parameter sets (ITU-T H.265 Table 7-1).
+const NAL_VPS: u8 = 32;
+const NAL_SPS: u8 = 33;
+const NAL_PPS: u8 = 34;
+
+/// Payload header type of a fragmentation unit (RFC 7798 §4.4.3).
+const NAL_FU: u8 = 49;
+
+/// H.265 (HEVC) RTP packetizer (RFC 7798).
+///
+/// Converts H.265 Annex B bitstreams into RTP packets, the same way
+/// [`H264Packetizer`] does for H.264. The differences come from the
+/// **2-byte NAL unit header**:
+///
+/// ```text
+/// +---------------+---------------+
+/// |F|   Type    |  LayerId  | TID |
+/// +---------------+---------------+
+/// ```
+///
+/// - **Single NAL Unit packets** (§4.4.1): NALs that fit within the MTU
+///   are sent as-is.
+///
+/// - **Fragmentation Units** (§4.4.3): larger NALs are split. Each
+///   fragment starts with a 2-byte payload header (the NAL header with
+///   Type = 49) and a 1-byte FU header, and carries the NAL without its
+///   own header:
+///
+///   ```text
+///   PayloadHdr:    [F|Type=49|LayerId|TID]  (2 bytes)
+///   FU header:     [S|E|FuType]             (1 byte)
+///   Fragment data: [...]                    (up to MTU - 3 bytes)
+///   ```
+///
+/// ## SDP attributes (RFC 7798 §7.1)
+///
+/// - `a=rtpmap:96 H265/90000`
+/// - `a=fmtp:96 sprop-vps=...;sprop-sps=...;sprop-pps=...` — once the
+///   parameter sets are known, either auto-captured from the first frame
+///   that carries them or given with
+///   [`with_parameter_sets`](Self::with_parameter_sets).
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the last RTP packet of an access unit (RFC 7798 §4.1).
+#[derive(Debug)]
+pub struct H265Packetizer {
+    header: RtpHeader,
+    mtu: usize,
+    vps: Option<Vec<u8>>,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+}
+
+impl H265Packetizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self::from_header(RtpHeader::new(pt, ssrc))
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self::from_header(RtpHeader::with_random_ssrc(pt))
+    }
+
+    fn from_header(header: RtpHeader) -> Self {
+        Self {
+            header,
+            mtu: DEFAULT_MTU,
+            vps: None,
+            sps: None,
+            pps: None,
+        }
+    }
+
+    /// Advertise `vps`, `sps` and `pps` (NAL units without start codes) in
+    /// the SDP from the start, e.g. from a file's `hvcC` box.
+    pub fn with_parameter_sets(mut self, vps: Vec<u8>, sps: Vec<u8>, pps: Vec<u8>) -> Self {
+        self.vps = Some(vps);
+        self.sps = Some(sps);
+        self.pps = Some(pps);
+        self
+    }
+
+    /// NAL unit type, from bits 1..6 of the first header byte.
+    pub fn nal_type(nal: &[u8]) -> Option<u8> {
+        nal.first().map(|b| (b >> 1) & 0x3f)
+    }
+
+    /// Whether an access unit contains an IRAP picture (IDR, CRA or BLA,
+    /// NAL types 16–23).
+    ///
+    /// Stops at the first slice NAL, so only the parameter sets and SEI in
+    /// front of it are scanned.
+    pub fn is_irap(data: &[u8]) -> bool {
+        for range in H264Packetizer::nal_unit_ranges(data) {
+            match Self::nal_type(&data[range]) {
+                Some(16..=23) => return true,
+                Some(0..=31) => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Split a NAL unit into RTP packets without copying its payload.
+    ///
+    /// For each packet, `emit` receives the packet header (RTP header plus
+    /// the payload and FU headers of a fragment) and the range of
+    /// `nal_unit` that follows it.
+    fn fragment_nal(
+        &mut self,
+        nal_unit: &[u8],
+        is_last_nal: bool,
+        mut emit: impl FnMut(&[u8], Range<usize>),
+    ) {
+        if nal_unit.len() < 2 {
+            return;
+        }
+
+        if nal_unit.len() <= self.mtu {
+            // Single NAL Unit packet (RFC 7798 §4.4.1)
+            let hdr = self.header.write(is_last_nal);
+            emit(&hdr, 0..nal_unit.len());
+            return;
+        }
+
+        // Fragmentation Units (RFC 7798 §4.4.3)
+        let nal_type = (nal_unit[0] >> 1) & 0x3f;
+        let payload_hdr = [(nal_unit[0] & 0x81) | (NAL_FU << 1), nal_unit[1]];
+
+        let max_fragment = self.mtu - 3; // payload header + FU header
+        let mut offset = 2usize; // the NAL header is carried in the FU headers
+        let mut fragments = 0usize;
+
+        while offset < nal_unit.len() {
+            let remaining = nal_unit.len() - offset;
+            let last_fragment = remaining <= max_fragment;
+            let chunk_size = std::cmp::min(max_fragment, remaining);
+
+            let start_bit = if fragments == 0 { 0x80 } else { 0x00 };
+            let end_bit = if last_fragment { 0x40 } else { 0x00 };
+
+            let mut hdr = [0u8; 15];
+            hdr[..12].copy_from_slice(&self.header.write(is_last_nal && last_fragment));
+            hdr[12..14].copy_from_slice(&payload_hdr);
+            hdr[14] = start_bit | end_bit | nal_type;
+            emit(&hdr, offset..offset + chunk_size);
+
+            offset += chunk_size;
+            fragments += 1;
+        }
+
+        tracing::trace!(
+            nal_type,
+            nal_size = nal_unit.len(),
+            fragments,
+            "H.265 NAL unit fragmented"
+        );
+    }
+
+    /// Auto-capture VPS/SPS/PPS from the first frame that contains them.
+    /// Only set when not already provided by the user.
+    fn capture_parameter_sets(&mut self, data: &[u8], nal_units: &[Range<usize>]) {
+        if self.vps.is_some() && self.sps.is_some() && self.pps.is_some() {
+            return;
+        }
+        for range in nal_units {
+            let nal = &data[range.clone()];
+            let slot = match Self::nal_type(nal) {
+                Some(NAL_VPS) => &mut self.vps,
+                Some(NAL_SPS) => &mut self.sps,
+                Some(NAL_PPS) => &mut self.pps,
+                _ => continue,
+            };
+            if slot.is_none() {
+                *slot = Some(nal.to_vec());
+                tracing::debug!(
+                    nal_type = Self::nal_type(nal),
+                    bytes = nal.len(),
+                    "H.265 parameter set captured from bitstream"
+                );
+            }
+        }
+    }
+
+    fn finish_frame(&mut self, nal_count: usize, rtp_packets: usize, timestamp_increment: u32) {
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            nal_count,
+            rtp_packets,
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "H.265 frame packetized"
+        );
+    }
+}
+
+impl Packetizer for H265Packetizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let nal_units = H264Packetizer::nal_unit_ranges(encoded_data);
+        self.capture_parameter_sets(encoded_data, &nal_units);
+
+        let mut packets = Vec::new();
+        for (i, range) in nal_units.iter().enumerate() {
+            let nal = &encoded_data[range.clone()];
+            self.fragment_nal(nal, i == nal_units.len() - 1, |header, payload| {
+                packets.push([header, &nal[payload]].concat());
+            });
+        }
+
+        self.finish_frame(nal_units.len(), packets.len(), timestamp_increment);
+        packets
+    }
+
+    /// Zero-copy packetization: every packet's payload is a slice of
+    /// `encoded_data`; only the RTP and FU headers are written.
+    fn packetize_bytes(&mut self, encoded_data: Bytes, timestamp_increment: u32) -> Vec<RtpPacket> {
+        let nal_units = H264Packetizer::nal_unit_ranges(&encoded_data);
+        self.capture_parameter_sets(&encoded_data, &nal_units);
+
+        let mut packets = Vec::new();
+        for (i, range) in nal_units.iter().enumerate() {
+            let nal = encoded_data.slice(range.clone());
+            self.fragment_nal(&nal, i == nal_units.len() - 1, |header, payload| {
+                packets.push(RtpPacket::new(header, nal.slice(payload)));
+            });
+        }
+
+        self.finish_frame(nal_units.len(), packets.len(), timestamp_increment);
+        packets
+    }
+
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        Self::is_irap(encoded_data)
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "H265"
+    }
+
+    /// 90 kHz clock rate per RFC 7798 §7.1.
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    /// SDP attributes per RFC 7798 §7.1; `a=fmtp` only once a parameter
+    /// set is known.
+    fn sdp_attributes(&self) -> Vec<String> {
+        let pt = self.payload_type();
+        let mut attrs = vec![format!(
+            "a=rtpmap:{} {}/{}",
+            pt,
+            self.codec_name(),
+            self.clock_rate()
+        )];
+        let sprops: Vec<String> = [
+            ("sprop-vps", &self.vps),
+            ("sprop-sps", &self.sps),
+            ("sprop-pps", &self.pps),
+        ]
+        .into_iter()
+        .filter_map(|(name, nal)| {
+            nal.as_ref()
+                .map(|nal| format!("{}={}", name, BASE64_STANDARD.encode(nal)))
+        })
+        .collect();
+        if !sprops.is_empty() {
+            attrs.push(format!("a=fmtp:{} {}", pt, sprops.join(";")));
+        }
+        attrs.push("a=control:track1".to_string());
+        attrs
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+/// Fields of an H.265 sequence parameter set needed to describe the stream
+/// in a container (ITU-T H.265 §7.3.2.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceParameterSet {
+    /// The general part of `profile_tier_level()` as coded: profile space,
+    /// tier and profile, 32 compatibility flags, 48 constraint flags and
+    /// `general_level_idc`, as copied into an `hvcC` box.
+    pub profile_tier_level: [u8; 12],
+    /// `sps_max_sub_layers_minus1 + 1`.
+    pub max_sub_layers: u8,
+    pub temporal_id_nesting: bool,
+    /// 0 = monochrome, 1 = 4:2:0, 2 = 4:2:2, 3 = 4:4:4.
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+    /// Picture size in luma samples, inside the conformance window.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SequenceParameterSet {
+    /// Parse an SPS NAL unit (with its header, without start code).
+    /// Returns `None` if it is truncated or not an SPS.
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        if H265Packetizer::nal_type(nal)? != NAL_SPS {
+            return None;
+        }
+        let data = bits::rbsp(nal.get(2..)?);
+        let mut r = BitReader::new(&data);
+        let _sps_video_parameter_set_id = r.bits(4)?;
+        let max_sub_layers_minus1 = r.bits(3)?;
+        let temporal_id_nesting = r.bits(1)? == 1;
+        let profile_tier_level: [u8; 12] = data.get(1..13)?.try_into().ok()?;
+        r.skip(96)?;
+
+        // Sub-layer profile and level presence, padded to 8 entries.
+        let mut sub_layers = Vec::new();
+        for _ in 0..max_sub_layers_minus1 {
+            sub_layers.push((r.bits(1)? == 1, r.bits(1)? == 1));
+        }
+        if max_sub_layers_minus1 > 0 {
+            r.skip(2 * (8 - max_sub_layers_minus1 as usize))?;
+        }
+        for (profile_present, level_present) in sub_layers {
+            if profile_present {
+                r.skip(88)?;
+            }
+            if level_present {
+                r.skip(8)?;
+            }
+        }
+
+        let _sps_seq_parameter_set_id = r.ue()?;
+        let chroma_format_idc = r.ue()?;
+        let separate_colour_plane = chroma_format_idc == 3 && r.bits(1)? == 1;
+        let mut width = r.ue()?;
+        let mut height = r.ue()?;
+        if r.bits(1)? == 1 {
+            let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+            // SubWidthC/SubHeightC (H.265 Table 6-1).
+            let (sub_width, sub_height) = match (chroma_format_idc, separate_colour_plane) {
+                (1, _) => (2, 2),
+                (2, _) => (2, 1),
+                _ => (1, 1),
+            };
+            width = width.checked_sub(sub_width * (left + right))?;
+            height = height.checked_sub(sub_height * (top + bottom))?;
+        }
+        let bit_depth_luma = 8 + r.ue()?;
+        let bit_depth_chroma = 8 + r.ue()?;
+
+        Some(Self {
+            profile_tier_level,
+            max_sub_layers: max_sub_layers_minus1 as u8 + 1,
+            temporal_id_nesting,
+            chroma_format_idc: chroma_format_idc as u8,
+            bit_depth_luma: bit_depth_luma as u8,
+            bit_depth_chroma: bit_depth_chroma as u8,
+            width,
+            height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VPS: [u8; 3] = [0x40, 0x01, 0x0c];
+    const SPS: [u8; 3] = [0x42, 0x01, 0x01];
+    const PPS: [u8; 3] = [0x44, 0x01, 0xc1];
+
+    fn keyframe(slice_len: usize) -> Vec<u8> {
+        let mut frame = Vec::new();
+        for nal in [&VPS[..], &SPS, &PPS] {
+            frame.extend_from_slice(&[0, 0, 0, 1]);
+            frame.extend_from_slice(nal);
+        }
+        // IDR_W_RADL (19)
+        frame.extend_from_slice(&[0, 0, 0, 1, 0x26, 0x01]);
+        frame.extend((0..slice_len).map(|i| i as u8));
+        frame
+    }
+
+    #[test]
+    fn small_nals_are_sent_whole() {
+        let mut p = H265Packetizer::new(96, 1);
+        let packets = p.packetize(&keyframe(10), 3000);
+        assert_eq!(packets.len(), 4);
+        assert_eq!(&packets[0][12..], &VPS);
+        assert_eq!(packets[0][1] & 0x80, 0, "marker only on the last NAL");
+        assert_eq!(packets[3][1] & 0x80, 0x80);
+    }
+
+    #[test]
+    fn large_nals_are_fragmented() {
+        let mut p = H265Packetizer::new(96, 1);
+        let frame = Bytes::from(keyframe(DEFAULT_MTU * 2));
+        let packets = p.packetize_bytes(frame, 3000);
+        let fragments = &packets[3..];
+        assert_eq!(fragments.len(), 3);
+
+        let first = fragments[0].to_vec();
+        assert_eq!((first[12] >> 1) & 0x3f, NAL_FU);
+        assert_eq!(first[13], 0x01, "LayerId/TID copied");
+        assert_eq!(first[14], 0x80 | 19, "start bit and original type");
+
+        let last = fragments[2].to_vec();
+        assert_eq!(last[14], 0x40 | 19);
+        assert_eq!(last[1] & 0x80, 0x80);
+
+        let payload: usize = fragments.iter().map(|p| p.payload().len()).sum();
+        assert_eq!(payload, DEFAULT_MTU * 2, "NAL header not repeated");
+    }
+
+    #[test]
+    fn detects_irap_access_units() {
+        assert!(H265Packetizer::is_irap(&keyframe(4)));
+        // TRAIL_R (1)
+        assert!(!H265Packetizer::is_irap(&[0, 0, 0, 1, 0x02, 0x01, 0xaa]));
+        assert!(!H265Packetizer::is_irap(&[]));
+    }
+
+    #[test]
+    fn sdp_carries_parameter_sets() {
+        let mut p = H265Packetizer::new(98, 1);
+        assert_eq!(
+            p.sdp_attributes(),
+            ["a=rtpmap:98 H265/90000", "a=control:track1"]
+        );
+
+        p.packetize(&keyframe(4), 3000);
+        let attrs = p.sdp_attributes();
+        assert_eq!(
+            attrs[1],
+            format!(
+                "a=fmtp:98 sprop-vps={};sprop-sps={};sprop-pps={}",
+                BASE64_STANDARD.encode(VPS),
+                BASE64_STANDARD.encode(SPS),
+                BASE64_STANDARD.encode(PPS)
+            )
+        );
+
+        let given = H265Packetizer::new(98, 1).with_parameter_sets(
+            VPS.to_vec(),
+            SPS.to_vec(),
+            PPS.to_vec(),
+        );
+        assert_eq!(given.sdp_attributes(), attrs);
+    }
+
+    #[test]
+    fn parses_sps_dimensions() {
+        // x265, Main profile, 1280x720
+        let sps = [
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2d, 0x16, 0x59, 0x59, 0xa4,
+        ];
+        let info = SequenceParameterSet::parse(&sps).unwrap();
+        assert_eq!((info.width, info.height), (1280, 720));
+        assert_eq!(
+            info.profile_tier_level,
+            [
+                0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5d
+            ]
+        );
+        assert_eq!((info.max_sub_layers, info.temporal_id_nesting), (1, true));
+        assert_eq!((info.chroma_format_idc, info.bit_depth_luma), (1, 8));
+        assert!(SequenceParameterSet::parse(&VPS).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, MediaType, Packetizer};
+
+/// Default RTP clock rate for KLV (RFC 6597 §6.1 examples; MISB ST 0604
+/// streams often use 90000 instead to share the video clock).
+pub const KLV_DEFAULT_CLOCK_RATE: u32 = 1000;
+
+/// SMPTE Universal Label prefix every KLV key starts with (SMPTE ST 298).
+const UL_PREFIX: [u8; 4] = [0x06, 0x0e, 0x2b, 0x34];
+
+const KEY_LEN: usize = 16;
+
+/// Why a KLV unit was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum KlvError {
+    /// The unit contained no KLV items.
+    #[error("empty KLV unit")]
+    Empty,
+    /// A key did not start with the SMPTE UL prefix `06 0E 2B 34`.
+    #[error("KLV item at offset {offset} has no SMPTE universal label key")]
+    InvalidKey { offset: usize },
+    /// A BER length used a reserved or over-long form.
+    #[error("KLV item at offset {offset} has an invalid BER length")]
+    InvalidLength { offset: usize },
+    /// An item's key, length or value runs past the end of the unit.
+    #[error("KLV item at offset {offset} is truncated")]
+    Truncated { offset: usize },
+}
+
+/// KLV metadata RTP packetizer (RFC 6597).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one KLV unit —
+/// all KLV items (e.g. a MISB ST 0601 UAS Datalink Local Set) that apply
+/// to one instant — and sends it with no payload header, fragmented at the
+/// MTU (§4.2). Units are validated first (§4.1): every item needs a 16-byte
+/// SMPTE universal label key and a well-formed BER length covering its
+/// value. Invalid units are dropped with a warning.
+///
+/// ```text
+/// +----------------------+---------------+---------------------+
+/// | Key (16 bytes, UL)   | BER length    | Value (length bytes)|  × items
+/// +----------------------+---------------+---------------------+
+///   06 0E 2B 34 ...        short: 0xxxxxxx
+///                          long:  1nnnnnnn + n length bytes
+/// ```
+///
+/// ## SDP attributes (§6)
+///
+/// - `m=application 0 RTP/AVP <pt>`
+/// - `a=rtpmap:<pt> smpte336m/<rate>` — 1000 Hz unless set with
+///   [`with_clock_rate`](Self::with_clock_rate)
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the last packet of a KLV unit (§4.2).
+#[derive(Debug)]
+pub struct KlvPacketizer {
+    header: RtpHeader,
+    mtu: usize,
+    clock_rate: u32,
+}
+
+impl KlvPacketizer {
+    /// Create with explicit payload type and SSRC, at 1000 Hz.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self {
+            header: RtpHeader::new(pt, ssrc),
+            mtu: DEFAULT_MTU,
+            clock_rate: KLV_DEFAULT_CLOCK_RATE,
+        }
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1), at 1000 Hz.
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self {
+            header: RtpHeader::with_random_ssrc(pt),
+            mtu: DEFAULT_MTU,
+            clock_rate: KLV_DEFAULT_CLOCK_RATE,
+        }
+    }
+
+    /// Use a different RTP clock rate. With 90000 the KLV track shares the
+    /// video clock, so [`Mount::add_track`](crate::mount::Mount::add_track)
+    /// aligns its timestamps with the video frames.
+    pub fn with_clock_rate(mut self, clock_rate: u32) -> Self {
+        self.clock_rate = clock_rate;
+        self
+    }
+
+    /// Validate a KLV unit and return the number of items it holds.
+    pub fn validate_unit(unit: &[u8]) -> Result<usize, KlvError> {
+        if unit.is_empty() {
+            return Err(KlvError::Empty);
+        }
+
+        let mut offset = 0;
+        let mut items = 0;
+        while offset < unit.len() {
+            if unit.len() - offset < KEY_LEN {
+                return Err(KlvError::Truncated { offset });
+            }
+            if unit[offset..offset + UL_PREFIX.len()] != UL_PREFIX {
+                return Err(KlvError::InvalidKey { offset });
+            }
+            let (value_len, len_bytes) = read_ber_length(&unit[offset + KEY_LEN..], offset)?;
+            let value_start = offset + KEY_LEN + len_bytes;
+            if unit.len() - value_start < value_len {
+                return Err(KlvError::Truncated { offset });
+            }
+            offset = value_start + value_len;
+            items += 1;
+        }
+        Ok(items)
+    }
+}
+
+/// Decode a BER length (SMPTE ST 336 §6.3). Returns the value length and
+/// the number of bytes the length field occupies.
+fn read_ber_length(data: &[u8], offset: usize) -> Result<(usize, usize), KlvError> {
+    let first = *data.first().ok_or(KlvError::Truncated { offset })?;
+    if first < 0x80 {
+        return Ok((first as usize, 1));
+    }
+
+    let count = (first & 0x7f) as usize;
+    // 0x80 (indefinite) and 0xFF (reserved) are not allowed; 8 bytes is
+    // already far beyond any RTP-carried unit.
+    if count == 0 || count > 8 {
+        return Err(KlvError::InvalidLength { offset });
+    }
+    let bytes = data.get(1..=count).ok_or(KlvError::Truncated { offset })?;
+    let len = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    let len = usize::try_from(len).map_err(|_| KlvError::InvalidLength { offset })?;
+    Ok((len, 1 + count))
+}
+
+impl Packetizer for KlvPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let items = match Self::validate_unit(encoded_data) {
+            Ok(items) => items,
+            Err(e) => {
+                tracing::warn!(error = %e, bytes = encoded_data.len(), "dropping invalid KLV unit");
+                return Vec::new();
+            }
+        };
+
+        let chunks: Vec<&[u8]> = encoded_data.chunks(self.mtu).collect();
+        let mut packets = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let hdr = self.header.write(i == chunks.len() - 1);
+            let mut packet = Vec::with_capacity(12 + chunk.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(chunk);
+            packets.push(packet);
+        }
+
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            items,
+            bytes = encoded_data.len(),
+            rtp_packets = packets.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "KLV unit packetized"
+        );
+
+        packets
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "smpte336m"
+    }
+
+    fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn media_type(&self) -> MediaType {
+        MediaType::Application
+    }
+
+    fn sdp_attributes(&self) -> Vec<String> {
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}",
+                self.payload_type(),
+                self.codec_name(),
+                self.clock_rate()
+            ),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MISB ST 0601 UAS Datalink Local Set key.
+    const ST0601_KEY: [u8; 16] = [
+        0x06, 0x0e, 0x2b, 0x34, 0x02, 0x0b, 0x01, 0x01, 0x0e, 0x01, 0x03, 0x01, 0x01, 0x00, 0x00,
+        0x00,
+    ];
+
+    fn klv_item(value_len: usize) -> Vec<u8> {
+        let mut item = ST0601_KEY.to_vec();
+        if value_len < 0x80 {
+            item.push(value_len as u8);
+        } else {
+            item.push(0x82);
+            item.extend_from_slice(&(value_len as u16).to_be_bytes());
+        }
+        item.resize(item.len() + value_len, 0x42);
+        item
+    }
+
+    #[test]
+    fn validates_short_and_long_lengths() {
+        assert_eq!(KlvPacketizer::validate_unit(&klv_item(10)), Ok(1));
+        assert_eq!(KlvPacketizer::validate_unit(&klv_item(300)), Ok(1));
+        let mut two = klv_item(5);
+        two.extend(klv_item(200));
+        assert_eq!(KlvPacketizer::validate_unit(&two), Ok(2));
+    }
+
+    #[test]
+    fn rejects_malformed_units() {
+        assert_eq!(KlvPacketizer::validate_unit(&[]), Err(KlvError::Empty));
+
+        let mut bad_key = klv_item(4);
+        bad_key[0] = 0x07;
+        assert_eq!(
+            KlvPacketizer::validate_unit(&bad_key),
+            Err(KlvError::InvalidKey { offset: 0 })
+        );
+
+        let mut indefinite = ST0601_KEY.to_vec();
+        indefinite.push(0x80);
+        assert_eq!(
+            KlvPacketizer::validate_unit(&indefinite),
+            Err(KlvError::InvalidLength { offset: 0 })
+        );
+
+        let mut short_value = klv_item(10);
+        short_value.pop();
+        assert_eq!(
+            KlvPacketizer::validate_unit(&short_value),
+            Err(KlvError::Truncated { offset: 0 })
+        );
+
+        let mut trailing = klv_item(3);
+        trailing.extend_from_slice(&[0x06, 0x0e]);
+        assert_eq!(
+            KlvPacketizer::validate_unit(&trailing),
+            Err(KlvError::Truncated { offset: 20 })
+        );
+    }
+
+    #[test]
+    fn fragments_with_marker_on_last() {
+        let mut p = KlvPacketizer::new(112, 0x1234);
+        let unit = klv_item(3000);
+        let packets = p.packetize(&unit, 40);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0][1] & 0x80, 0);
+        assert_eq!(packets[1][1] & 0x80, 0);
+        assert_eq!(packets[2][1] & 0x80, 0x80);
+        let payload: Vec<u8> = packets.iter().flat_map(|pkt| pkt[12..].to_vec()).collect();
+        assert_eq!(payload, unit);
+    }
+
+    #[test]
+    fn drops_invalid_unit() {
+        let mut p = KlvPacketizer::new(112, 0x1234);
+        let seq = p.next_sequence();
+        assert!(p.packetize(&[0xde, 0xad], 40).is_empty());
+        assert_eq!(p.next_sequence(), seq);
+    }
+
+    #[test]
+    fn sdp_clock_rate_configurable() {
+        let p = KlvPacketizer::new(112, 0x1234);
+        assert_eq!(p.sdp_attributes()[0], "a=rtpmap:112 smpte336m/1000");
+        assert_eq!(p.media_type(), MediaType::Application);
+
+        let p = KlvPacketizer::new(112, 0x1234).with_clock_rate(90000);
+        assert_eq!(p.clock_rate(), 90000);
+        assert_eq!(p.sdp_attributes()[0], "a=rtpmap:112 smpte336m/90000");
+    }
+}
@@ -14,18 +14,77 @@
 //! - **SSRC** (32-bit) — randomly chosen to identify the sender.
 //! - **Marker bit** — set on the last packet of an access unit (frame).
 //!
+//! Packets travel through the server as [`packet::RtpPacket`]s — an inline
+//! header plus a reference-counted payload slice — so a frame is
+//! packetized once and fanned out to every viewer without copies.
+//!
+//! [`clock::RtpClock`] converts presentation timestamps to RTP timestamps
+//! for producers that stamp frames with a PTS instead of an increment.
+//!
+//! Receiver feedback arrives over RTCP; [`rtcp`] extracts the keyframe
+//! requests (PLI/FIR) that are forwarded to the mount's
+//! [keyframe request handler](crate::mount::Mount::set_keyframe_request_handler).
+//!
 //! ## Supported codecs
 //!
 //! | Codec | Module | RFC | Status |
 //! |-------|--------|-----|--------|
 //! | H.264 | [`h264`] | [RFC 6184](https://tools.ietf.org/html/rfc6184) | Implemented |
-//! | H.265 | [`h265`] | [RFC 7798](https://tools.ietf.org/html/rfc7798) | Planned |
+//! | H.265 | [`h265`] | [RFC 7798](https://tools.ietf.org/html/rfc7798) | Implemented |
 //! | MJPEG | [`mjpeg`] | [RFC 2435](https://tools.ietf.org/html/rfc2435) | Planned |
+//! | AAC | [`aac`] | [RFC 3640](https://tools.ietf.org/html/rfc3640) | Implemented |
+//! | Opus | [`opus`] | [RFC 7587](https://tools.ietf.org/html/rfc7587) | Implemented |
+//! | G.711 PCMU/PCMA, L16 | [`pcm`] | [RFC 3551](https://tools.ietf.org/html/rfc3551) | Implemented |
+//! | VP8 | [`vp8`] | [RFC 7741](https://tools.ietf.org/html/rfc7741) | Implemented |
+//! | VP9 | [`vp9`] | [RFC 9628](https://tools.ietf.org/html/rfc9628) | Implemented |
+//! | MPEG-2 TS | [`mp2t`] | [RFC 2250](https://tools.ietf.org/html/rfc2250) | Implemented |
+//! | ONVIF metadata | [`onvif`] | ONVIF Streaming Spec §5.2.1.1 | Implemented |
+//! | KLV (SMPTE ST 336) | [`klv`] | [RFC 6597](https://tools.ietf.org/html/rfc6597) | Implemented |
+//! | AV1 | [`av1`] | [AOM AV1 RTP](https://aomediacodec.github.io/av1-rtp-spec/) | Implemented |
 
+pub mod aac;
+pub mod av1;
+mod bits;
+pub mod clock;
 pub mod h264;
 pub mod h265;
+pub mod klv;
 pub mod mjpeg;
+pub mod mp2t;
+pub mod onvif;
+pub mod opus;
+pub mod packet;
+pub mod pcm;
+pub mod rtcp;
 pub mod rtp;
+pub mod vp8;
+pub mod vp9;
+
+use bytes::Bytes;
+
+use packet::RtpPacket;
+
+/// Maximum RTP payload size used by packetizers when splitting frames.
+pub(crate) const DEFAULT_MTU: usize = 1400;
+
+/// SDP media type of a packetizer's stream (the `<media>` field of `m=`,
+/// RFC 4566 §5.14).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Video,
+    Audio,
+    Application,
+}
+
+impl std::fmt::Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Video => write!(f, "video"),
+            Self::Audio => write!(f, "audio"),
+            Self::Application => write!(f, "application"),
+        }
+    }
+}
 
 /// Codec-specific RTP packetizer.
 ///
@@ -50,8 +109,27 @@ pub trait Packetizer: Send {
     ///
     /// `timestamp_increment` advances the RTP timestamp after this frame,
     /// typically `clock_rate / fps` (e.g. 3000 for 30 fps at 90 kHz).
+    /// Producers that know each frame's presentation time should prefer
+    /// [`Mount::packetize_with_pts`](crate::mount::Mount::packetize_with_pts),
+    /// which sets the timestamp via
+    /// [`set_next_rtp_timestamp`](Self::set_next_rtp_timestamp) and passes 0.
     fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>>;
 
+    /// Packetize a shared frame buffer into [`RtpPacket`]s.
+    ///
+    /// This is the path [`Mount`](crate::mount::Mount) and
+    /// [`Server`](crate::Server) use: packets are built once and cloned
+    /// cheaply for every viewer. Packetizers that can carry payload as
+    /// slices of `encoded_data` (H.264) override it to avoid copying media
+    /// bytes; the default wraps the output of
+    /// [`packetize`](Self::packetize) without further copies.
+    fn packetize_bytes(&mut self, encoded_data: Bytes, timestamp_increment: u32) -> Vec<RtpPacket> {
+        self.packetize(&encoded_data, timestamp_increment)
+            .into_iter()
+            .map(RtpPacket::from)
+            .collect()
+    }
+
     /// Codec name for the SDP `a=rtpmap` attribute (e.g. `"H264"`, `"H265"`).
     fn codec_name(&self) -> &'static str;
 
@@ -65,6 +143,12 @@ pub trait Packetizer: Send {
     /// Dynamic types use 96–127. H.264 conventionally uses 96.
     fn payload_type(&self) -> u8;
 
+    /// SDP media type for the `m=` line. Defaults to video; audio and
+    /// metadata packetizers override it.
+    fn media_type(&self) -> MediaType {
+        MediaType::Video
+    }
+
     /// SDP media-level attribute lines for this codec.
     ///
     /// Returned strings include the `a=` prefix, e.g.:
@@ -73,9 +157,39 @@ pub trait Packetizer: Send {
     /// - `"a=control:track1"`
     fn sdp_attributes(&self) -> Vec<String>;
 
+    /// Whether decoding can start at this frame (IDR, key frame), so send
+    /// queues know which frames are safe to drop. Defaults to `true`:
+    /// audio and metadata frames stand alone.
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        let _ = encoded_data;
+        true
+    }
+
     /// Current RTP sequence number (for the `RTP-Info` header in PLAY responses).
     fn next_sequence(&self) -> u16;
 
+    /// SSRC written in this packetizer's RTP headers (for the RTCP BYE
+    /// sent on shutdown). Defaults to `None`: the track then takes it from
+    /// the first packet it emits.
+    fn ssrc(&self) -> Option<u32> {
+        None
+    }
+
     /// Current RTP timestamp as u32 (for the `RTP-Info` header in PLAY responses).
     fn next_rtp_timestamp(&self) -> u32;
+
+    /// Override the RTP timestamp written on the next packetized frame.
+    /// Returns whether the packetizer applied it.
+    ///
+    /// Used for PTS-driven ingest, where the timestamp comes from an
+    /// [`clock::RtpClock`] rather than accumulated increments, and to align
+    /// extra tracks with a mount's first track. PTS-driven sending
+    /// (`send_frame_with_pts` and friends) needs a packetizer that
+    /// overrides it; the default ignores the timestamp and returns `false`,
+    /// so frames keep the one reached through increments and the track
+    /// logs a warning.
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        let _ = timestamp;
+        false
+    }
 }
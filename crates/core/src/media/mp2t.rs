@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::Packetizer;
+use super::h264::H264Packetizer;
+use super::rtp::RtpHeader;
+
+/// Static RTP payload type for MPEG-2 transport streams (RFC 3551 §6).
+pub const PT_MP2T: u8 = 33;
+
+/// MPEG-2 TS packet size (ISO/IEC 13818-1 §2.4.3.2).
+pub const TS_PACKET_SIZE: usize = 188;
+
+/// TS packets per RTP packet: 7 × 188 = 1316 bytes fits a 1500-byte
+/// Ethernet MTU with IP/UDP/RTP headers.
+pub const TS_PACKETS_PER_RTP: usize = 7;
+
+const TS_SYNC_BYTE: u8 = 0x47;
+
+const PID_PAT: u16 = 0x0000;
+const PID_PMT: u16 = 0x1000;
+const PID_VIDEO: u16 = 0x0100;
+const PID_AUDIO: u16 = 0x0101;
+
+/// `stream_type` values (ISO/IEC 13818-1 Table 2-34).
+const STREAM_TYPE_H264: u8 = 0x1b;
+const STREAM_TYPE_AAC_ADTS: u8 = 0x0f;
+
+const STREAM_ID_VIDEO: u8 = 0xe0;
+const STREAM_ID_AUDIO: u8 = 0xc0;
+
+/// Offset added to PTS/DTS relative to the PCR, giving decoders 700 ms of
+/// buffer (90 kHz ticks).
+const MUX_DELAY: u64 = 63_000;
+
+/// Maximum spacing between PAT/PMT repetitions (100 ms in 90 kHz ticks).
+const PSI_INTERVAL: u64 = 9_000;
+
+/// PTS/DTS/PCR base values are 33 bits wide.
+const TIMESTAMP_MASK: u64 = (1 << 33) - 1;
+
+/// Access unit delimiter prepended to H.264 access units that lack one
+/// (ISO/IEC 13818-1 §2.14.1 requires it in transport streams).
+const H264_AUD: [u8; 6] = [0, 0, 0, 1, 0x09, 0xf0];
+
+/// What [`Mp2tPacketizer::packetize`](Packetizer::packetize) receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mp2tInput {
+    /// Already-muxed transport stream bytes (any multiple or fragment of
+    /// 188-byte packets).
+    Ts,
+    /// H.264 access units in Annex B format, muxed as a single video stream.
+    H264,
+    /// AAC frames with ADTS headers, muxed as a single audio stream.
+    Aac,
+}
+
+/// MPEG-2 transport stream RTP packetizer (RFC 2250).
+///
+/// Carries a transport stream as RTP payload type 33 (`MP2T/90000`), with
+/// [`TS_PACKETS_PER_RTP`] 188-byte TS packets per RTP packet (§2). The last
+/// RTP packet of a call may carry fewer.
+///
+/// The input is selected by [`Mp2tInput`]:
+///
+/// - [`Ts`](Mp2tInput::Ts): pre-muxed TS is regrouped as-is. Partial TS
+///   packets are held until the next call, and data that does not start
+///   with a sync byte is skipped until the next one.
+/// - [`H264`](Mp2tInput::H264) / [`Aac`](Mp2tInput::Aac): each frame is muxed
+///   by an internal [`TsMuxer`] with PAT/PMT and PCR. PES timestamps follow
+///   the RTP timestamps, so both
+///   [`send_frame`](crate::Server::send_frame) increments and
+///   [`send_frame_with_pts`](crate::Server::send_frame_with_pts) work.
+///
+/// To carry H.264 and AAC in one program, mux them with a [`TsMuxer`] and
+/// send the result to a [`Ts`](Mp2tInput::Ts) mount.
+///
+/// ## SDP attributes (RFC 3551 §6)
+///
+/// - `a=rtpmap:33 MP2T/90000`
+/// - `a=control:track1`
+///
+/// ## Timestamps and marker bit
+///
+/// All RTP packets produced by one call share its timestamp. The marker bit
+/// is never set; RFC 2250 §2.1 reserves it for timestamp discontinuities.
+#[derive(Debug)]
+pub struct Mp2tPacketizer {
+    header: RtpHeader,
+    input: Mp2tInput,
+    muxer: Option<TsMuxer>,
+    pending: Vec<u8>,
+    last_rtp_timestamp: Option<u32>,
+    media_ticks: u64,
+}
+
+impl Mp2tPacketizer {
+    /// Create with explicit SSRC; the payload type is always 33.
+    pub fn new(ssrc: u32, input: Mp2tInput) -> Self {
+        Self::from_header(RtpHeader::new(PT_MP2T, ssrc), input)
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(input: Mp2tInput) -> Self {
+        Self::from_header(RtpHeader::with_random_ssrc(PT_MP2T), input)
+    }
+
+    fn from_header(header: RtpHeader, input: Mp2tInput) -> Self {
+        let muxer = match input {
+            Mp2tInput::Ts => None,
+            Mp2tInput::H264 => Some(TsMuxer::new(true, false)),
+            Mp2tInput::Aac => Some(TsMuxer::new(false, true)),
+        };
+        Self {
+            header,
+            input,
+            muxer,
+            pending: Vec::new(),
+            last_rtp_timestamp: None,
+            media_ticks: 0,
+        }
+    }
+
+    /// Input format this packetizer was created for.
+    pub fn input(&self) -> Mp2tInput {
+        self.input
+    }
+
+    /// Media time of the next frame in 90 kHz ticks, extended from the
+    /// 32-bit RTP timestamp so the PES timestamps wrap at 33 bits instead.
+    fn next_media_ticks(&mut self) -> u64 {
+        let rtp_ts = self.header.timestamp() as u32;
+        if let Some(last) = self.last_rtp_timestamp {
+            let delta = rtp_ts.wrapping_sub(last) as i32 as i64;
+            self.media_ticks = self.media_ticks.wrapping_add_signed(delta);
+        }
+        self.last_rtp_timestamp = Some(rtp_ts);
+        self.media_ticks
+    }
+
+    /// Take the complete, sync-aligned TS packets from the pending buffer.
+    fn take_aligned(&mut self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.pending.len());
+        let mut offset = 0;
+        while self.pending.len() - offset >= TS_PACKET_SIZE {
+            if self.pending[offset] != TS_SYNC_BYTE {
+                let skip = self.pending[offset..]
+                    .iter()
+                    .position(|&b| b == TS_SYNC_BYTE)
+                    .unwrap_or(self.pending.len() - offset);
+                tracing::warn!(skipped = skip, "MPEG-TS input lost sync");
+                offset += skip;
+                continue;
+            }
+            out.extend_from_slice(&self.pending[offset..offset + TS_PACKET_SIZE]);
+            offset += TS_PACKET_SIZE;
+        }
+        self.pending.drain(..offset);
+        out
+    }
+}
+
+impl Packetizer for Mp2tPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let ts = match self.input {
+            Mp2tInput::Ts => {
+                self.pending.extend_from_slice(encoded_data);
+                self.take_aligned()
+            }
+            Mp2tInput::H264 | Mp2tInput::Aac => {
+                let ticks = self.next_media_ticks();
+                let muxer = self.muxer.as_mut().expect("muxer exists for ES input");
+                if self.input == Mp2tInput::H264 {
+                    muxer.mux_video_ticks(encoded_data, ticks, None)
+                } else {
+                    muxer.mux_audio_ticks(encoded_data, ticks)
+                }
+            }
+        };
+
+        let packets: Vec<Vec<u8>> = ts
+            .chunks(TS_PACKET_SIZE * TS_PACKETS_PER_RTP)
+            .map(|chunk| {
+                let hdr = self.header.write(false);
+                let mut packet = Vec::with_capacity(12 + chunk.len());
+                packet.extend_from_slice(&hdr);
+                packet.extend_from_slice(chunk);
+                packet
+            })
+            .collect();
+
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            input = ?self.input,
+            ts_packets = ts.len() / TS_PACKET_SIZE,
+            rtp_packets = packets.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "MPEG-TS packetized"
+        );
+
+        packets
+    }
+
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        match self.input {
+            Mp2tInput::H264 => H264Packetizer::is_idr(encoded_data),
+            Mp2tInput::Ts | Mp2tInput::Aac => true,
+        }
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "MP2T"
+    }
+
+    /// 90 kHz clock rate (RFC 2250 §2.1).
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn sdp_attributes(&self) -> Vec<String> {
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}",
+                self.payload_type(),
+                self.codec_name(),
+                self.clock_rate()
+            ),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+/// Minimal MPEG-2 transport stream multiplexer (ISO/IEC 13818-1) for one
+/// program with an H.264 video and/or AAC (ADTS) audio stream.
+///
+/// Each `mux_*` call wraps one frame in a PES packet and returns the
+/// resulting 188-byte TS packets. PAT and PMT are emitted before the first
+/// frame, before every H.264 IDR, and at least every 100 ms. The PCR rides
+/// on the video PID when there is one, else on the audio PID, and is sent
+/// with every PES on that PID.
+///
+/// | PID | Content |
+/// |-----|---------|
+/// | `0x0000` | PAT |
+/// | `0x1000` | PMT |
+/// | `0x0100` | H.264 (`stream_type` 0x1B) |
+/// | `0x0101` | AAC ADTS (`stream_type` 0x0F) |
+#[derive(Debug)]
+pub struct TsMuxer {
+    video: bool,
+    audio: bool,
+    continuity: HashMap<u16, u8>,
+    last_psi: Option<u64>,
+    last_pcr: Option<u64>,
+}
+
+impl TsMuxer {
+    /// Create a muxer for a program with the given elementary streams.
+    ///
+    /// # Panics
+    ///
+    /// Panics if neither stream is enabled.
+    pub fn new(video: bool, audio: bool) -> Self {
+        assert!(video || audio, "TsMuxer needs at least one stream");
+        Self {
+            video,
+            audio,
+            continuity: HashMap::new(),
+            last_psi: None,
+            last_pcr: None,
+        }
+    }
+
+    /// Mux one H.264 access unit (Annex B). `dts` is only needed when the
+    /// stream has B-frames.
+    pub fn mux_video(
+        &mut self,
+        access_unit: &[u8],
+        pts: Duration,
+        dts: Option<Duration>,
+    ) -> Vec<u8> {
+        self.mux_video_ticks(access_unit, to_ticks(pts), dts.map(to_ticks))
+    }
+
+    /// Mux one AAC frame with its ADTS header.
+    pub fn mux_audio(&mut self, adts_frame: &[u8], pts: Duration) -> Vec<u8> {
+        self.mux_audio_ticks(adts_frame, to_ticks(pts))
+    }
+
+    fn pcr_pid(&self) -> u16 {
+        if self.video { PID_VIDEO } else { PID_AUDIO }
+    }
+
+    fn mux_video_ticks(&mut self, access_unit: &[u8], pts: u64, dts: Option<u64>) -> Vec<u8> {
+        if !self.video {
+            tracing::warn!("video frame sent to a TsMuxer without a video stream");
+            return Vec::new();
+        }
+        let nals = H264Packetizer::extract_nal_units(access_unit);
+        let keyframe = nals.iter().any(|nal| nal[0] & 0x1f == 5);
+        let has_aud = nals.first().is_some_and(|nal| nal[0] & 0x1f == 9);
+
+        let mut es = Vec::with_capacity(access_unit.len() + H264_AUD.len());
+        if !has_aud {
+            es.extend_from_slice(&H264_AUD);
+        }
+        es.extend_from_slice(access_unit);
+
+        self.mux_frame(PID_VIDEO, STREAM_ID_VIDEO, &es, pts, dts, keyframe)
+    }
+
+    fn mux_audio_ticks(&mut self, adts_frame: &[u8], pts: u64) -> Vec<u8> {
+        if !self.audio {
+            tracing::warn!("audio frame sent to a TsMuxer without an audio stream");
+            return Vec::new();
+        }
+        self.mux_frame(PID_AUDIO, STREAM_ID_AUDIO, adts_frame, pts, None, false)
+    }
+
+    fn mux_frame(
+        &mut self,
+        pid: u16,
+        stream_id: u8,
+        es: &[u8],
+        pts: u64,
+        dts: Option<u64>,
+        keyframe: bool,
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        let decode_time = dts.unwrap_or(pts);
+
+        let psi_due = match self.last_psi {
+            None => true,
+            Some(last) => keyframe || decode_time.saturating_sub(last) >= PSI_INTERVAL,
+        };
+        if psi_due {
+            self.write_psi(&mut out);
+            self.last_psi = Some(decode_time);
+        }
+
+        // PCR never moves backwards, even for reordered frames without DTS.
+        let pcr = if pid == self.pcr_pid() {
+            let pcr = self
+                .last_pcr
+                .map_or(decode_time, |last| last.max(decode_time));
+            self.last_pcr = Some(pcr);
+            Some(pcr & TIMESTAMP_MASK)
+        } else {
+            None
+        };
+
+        let pes = pes_packet(stream_id, es, pts + MUX_DELAY, dts.map(|d| d + MUX_DELAY));
+        self.write_payload(&mut out, pid, &pes, pcr, keyframe);
+        out
+    }
+
+    fn next_continuity(&mut self, pid: u16) -> u8 {
+        let cc = self.continuity.entry(pid).or_insert(0x0f);
+        *cc = (*cc + 1) & 0x0f;
+        *cc
+    }
+
+    /// Write PAT and PMT, each in a single TS packet.
+    fn write_psi(&mut self, out: &mut Vec<u8>) {
+        // PAT: program 1 → PMT PID
+        let pat = psi_section(
+            0x00,
+            0x0001,
+            &[0x00, 0x01, 0xe0 | (PID_PMT >> 8) as u8, PID_PMT as u8],
+        );
+        self.write_section(out, PID_PAT, &pat);
+
+        let pcr_pid = self.pcr_pid();
+        let mut pmt_body = vec![0xe0 | (pcr_pid >> 8) as u8, pcr_pid as u8, 0xf0, 0x00];
+        let streams = [
+            (self.video, STREAM_TYPE_H264, PID_VIDEO),
+            (self.audio, STREAM_TYPE_AAC_ADTS, PID_AUDIO),
+        ];
+        for (_, stream_type, pid) in streams.iter().filter(|(enabled, _, _)| *enabled) {
+            pmt_body.extend_from_slice(&[
+                *stream_type,
+                0xe0 | (pid >> 8) as u8,
+                *pid as u8,
+                0xf0,
+                0x00,
+            ]);
+        }
+        let pmt = psi_section(0x02, 0x0001, &pmt_body);
+        self.write_section(out, PID_PMT, &pmt);
+    }
+
+    fn write_section(&mut self, out: &mut Vec<u8>, pid: u16, section: &[u8]) {
+        let cc = self.next_continuity(pid);
+        let start = out.len();
+        out.extend_from_slice(&[
+            TS_SYNC_BYTE,
+            0x40 | (pid >> 8) as u8,
+            pid as u8,
+            0x10 | cc,
+            0x00, // pointer_field
+        ]);
+        out.extend_from_slice(section);
+        out.resize(start + TS_PACKET_SIZE, 0xff);
+    }
+
+    /// Split a PES packet into TS packets, with the PCR and random access
+    /// indicator in the first packet's adaptation field and adaptation-field
+    /// stuffing in the last.
+    fn write_payload(
+        &mut self,
+        out: &mut Vec<u8>,
+        pid: u16,
+        pes: &[u8],
+        pcr: Option<u64>,
+        random_access: bool,
+    ) {
+        let mut rest = pes;
+        let mut first = true;
+        while !rest.is_empty() {
+            // Adaptation field contents after the length byte.
+            let mut af = Vec::new();
+            if first && (pcr.is_some() || random_access) {
+                let mut flags = 0u8;
+                if random_access {
+                    flags |= 0x40;
+                }
+                if pcr.is_some() {
+                    flags |= 0x10;
+                }
+                af.push(flags);
+                if let Some(pcr) = pcr {
+                    af.extend_from_slice(&encode_pcr(pcr));
+                }
+            }
+
+            let room = TS_PACKET_SIZE - 4 - if af.is_empty() { 0 } else { 1 + af.len() };
+            let take = rest.len().min(room);
+            let stuffing = room - take;
+            let has_af = !af.is_empty() || stuffing > 0;
+            if stuffing > 0 {
+                if af.is_empty() {
+                    // The length byte itself takes one of the stuffing bytes.
+                    if stuffing >= 2 {
+                        af.push(0x00);
+                        af.resize(stuffing - 1, 0xff);
+                    }
+                } else {
+                    af.resize(af.len() + stuffing, 0xff);
+                }
+            }
+
+            let cc = self.next_continuity(pid);
+            let pusi = if first { 0x40 } else { 0x00 };
+            let afc = if has_af { 0x30 } else { 0x10 };
+            out.extend_from_slice(&[TS_SYNC_BYTE, pusi | (pid >> 8) as u8, pid as u8, afc | cc]);
+            if has_af {
+                out.push(af.len() as u8);
+                out.extend_from_slice(&af);
+            }
+            out.extend_from_slice(&rest[..take]);
+
+            rest = &rest[take..];
+            first = false;
+        }
+    }
+}
+
+fn to_ticks(d: Duration) -> u64 {
+    ((d.as_nanos() * 90_000 + 500_000_000) / 1_000_000_000) as u64
+}
+
+/// Build a PES packet (ISO/IEC 13818-1 §2.4.3.6) with PTS and optional DTS.
+fn pes_packet(stream_id: u8, es: &[u8], pts: u64, dts: Option<u64>) -> Vec<u8> {
+    let header_data_len = if dts.is_some() { 10 } else { 5 };
+    let body_len = 3 + header_data_len + es.len();
+    // Unbounded length (0) is only allowed for video streams.
+    let packet_len = if body_len <= u16::MAX as usize {
+        body_len as u16
+    } else {
+        0
+    };
+
+    let mut pes = Vec::with_capacity(6 + body_len);
+    pes.extend_from_slice(&[0x00, 0x00, 0x01, stream_id]);
+    pes.extend_from_slice(&packet_len.to_be_bytes());
+    pes.push(0x80); // '10' marker, no scrambling/priority/alignment flags
+    match dts {
+        Some(dts) => {
+            pes.extend_from_slice(&[0xc0, header_data_len as u8]);
+            pes.extend_from_slice(&encode_timestamp(0x3, pts));
+            pes.extend_from_slice(&encode_timestamp(0x1, dts));
+        }
+        None => {
+            pes.extend_from_slice(&[0x80, header_data_len as u8]);
+            pes.extend_from_slice(&encode_timestamp(0x2, pts));
+        }
+    }
+    pes.extend_from_slice(es);
+    pes
+}
+
+/// Encode a 33-bit PTS/DTS with its 4-bit prefix and marker bits.
+fn encode_timestamp(prefix: u8, ts: u64) -> [u8; 5] {
+    let ts = ts & TIMESTAMP_MASK;
+    [
+        (prefix << 4) | (((ts >> 30) as u8 & 0x07) << 1) | 1,
+        (ts >> 22) as u8,
+        (((ts >> 15) as u8 & 0x7f) << 1) | 1,
+        (ts >> 7) as u8,
+        ((ts as u8 & 0x7f) << 1) | 1,
+    ]
+}
+
+/// Encode a PCR with the given 90 kHz base and zero extension.
+fn encode_pcr(base: u64) -> [u8; 6] {
+    [
+        (base >> 25) as u8,
+        (base >> 17) as u8,
+        (base >> 9) as u8,
+        (base >> 1) as u8,
+        ((base as u8 & 0x01) << 7) | 0x7e,
+        0x00,
+    ]
+}
+
+/// Build a long-form PSI section with version 0 and a trailing CRC-32.
+fn psi_section(table_id: u8, id: u16, body: &[u8]) -> Vec<u8> {
+    // id + version/current_next + section_number + last_section_number + body + CRC
+    let section_length = 5 + body.len() + 4;
+    let mut section = Vec::with_capacity(3 + section_length);
+    section.push(table_id);
+    section.push(0xb0 | (section_length >> 8) as u8);
+    section.push(section_length as u8);
+    section.extend_from_slice(&id.to_be_bytes());
+    section.extend_from_slice(&[0xc1, 0x00, 0x00]);
+    section.extend_from_slice(body);
+    let crc = crc32_mpeg2(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+/// CRC-32/MPEG-2 (ISO/IEC 13818-1 Annex A): polynomial 0x04C11DB7,
+/// initial value 0xFFFFFFFF, no reflection, no final XOR.
+fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts_packet(pid: u16, fill: u8) -> Vec<u8> {
+        let mut packet = vec![TS_SYNC_BYTE, (pid >> 8) as u8, pid as u8, 0x10];
+        packet.resize(TS_PACKET_SIZE, fill);
+        packet
+    }
+
+    fn pid(ts: &[u8]) -> u16 {
+        (((ts[1] & 0x1f) as u16) << 8) | ts[2] as u16
+    }
+
+    #[test]
+    fn psi_crc_validates() {
+        let pat = psi_section(0x00, 1, &[0x00, 0x01, 0xf0, 0x00]);
+        // Running the CRC over the section including its CRC yields zero.
+        assert_eq!(crc32_mpeg2(&pat), 0);
+        // Same PAT as FFmpeg writes for program 1 on PMT PID 0x1000.
+        assert_eq!(&pat[pat.len() - 4..], &[0x2a, 0xb1, 0x04, 0xb2]);
+    }
+
+    #[test]
+    fn encodes_pts() {
+        assert_eq!(encode_timestamp(0x2, 0), [0x21, 0x00, 0x01, 0x00, 0x01]);
+        assert_eq!(
+            encode_timestamp(0x2, TIMESTAMP_MASK),
+            [0x2f, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn groups_premuxed_ts_seven_per_packet() {
+        let mut p = Mp2tPacketizer::new(0x1234, Mp2tInput::Ts);
+        let ts: Vec<u8> = (0..10).flat_map(|i| ts_packet(0x100, i)).collect();
+        let packets = p.packetize(&ts, 3000);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].len(), 12 + 7 * TS_PACKET_SIZE);
+        assert_eq!(packets[1].len(), 12 + 3 * TS_PACKET_SIZE);
+        assert_eq!(packets[0][1], PT_MP2T, "static PT 33, no marker");
+        assert_eq!(&packets[0][4..8], &packets[1][4..8], "shared timestamp");
+    }
+
+    #[test]
+    fn premuxed_ts_buffers_partial_and_resyncs() {
+        let mut p = Mp2tPacketizer::new(0x1234, Mp2tInput::Ts);
+        let packet = ts_packet(0x100, 0xAA);
+
+        // Garbage, one and a half TS packets.
+        let mut data = vec![0x00, 0x01, 0x02];
+        data.extend_from_slice(&packet);
+        data.extend_from_slice(&packet[..100]);
+        let packets = p.packetize(&data, 3000);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][12..], &packet[..]);
+
+        let packets = p.packetize(&packet[100..], 3000);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][12..], &packet[..]);
+    }
+
+    #[test]
+    fn muxes_h264_with_psi_and_pcr() {
+        let mut p = Mp2tPacketizer::new(0x1234, Mp2tInput::H264);
+        let mut idr = vec![0, 0, 0, 1, 0x65];
+        idr.resize(2000, 0x11);
+        let packets = p.packetize(&idr, 3000);
+
+        let ts: Vec<u8> = packets.iter().flat_map(|pkt| pkt[12..].to_vec()).collect();
+        assert_eq!(ts.len() % TS_PACKET_SIZE, 0);
+        let ts_packets: Vec<&[u8]> = ts.chunks(TS_PACKET_SIZE).collect();
+        assert!(ts_packets.iter().all(|t| t[0] == TS_SYNC_BYTE));
+
+        assert_eq!(pid(ts_packets[0]), PID_PAT);
+        assert_eq!(pid(ts_packets[1]), PID_PMT);
+        // PMT lists H.264 on the video PID.
+        assert_eq!(ts_packets[1][17], STREAM_TYPE_H264);
+
+        let video = ts_packets[2];
+        assert_eq!(pid(video), PID_VIDEO);
+        assert_eq!(video[1] & 0x40, 0x40, "payload unit start");
+        assert_eq!(video[3] & 0x30, 0x30, "adaptation field present");
+        assert_eq!(video[5] & 0x50, 0x50, "PCR and random access flags");
+        // PES header follows the 8-byte adaptation field.
+        assert_eq!(&video[12..16], &[0x00, 0x00, 0x01, STREAM_ID_VIDEO]);
+        // AUD inserted before the IDR.
+        assert_eq!(&video[26..32], &H264_AUD);
+
+        // Continuity counter increments on the video PID.
+        let video_cc: Vec<u8> = ts_packets
+            .iter()
+            .filter(|t| pid(t) == PID_VIDEO)
+            .map(|t| t[3] & 0x0f)
+            .collect();
+        assert!(video_cc.windows(2).all(|w| w[1] == (w[0] + 1) & 0x0f));
+
+        // Next non-IDR frame within 100 ms: no PSI repeated.
+        let packets = p.packetize(&[0, 0, 0, 1, 0x41, 0x22], 3000);
+        assert_eq!(pid(&packets[0][12..]), PID_VIDEO);
+    }
+
+    #[test]
+    fn muxes_aac_on_audio_pid() {
+        let mut muxer = TsMuxer::new(false, true);
+        let adts = [0xff, 0xf1, 0x50, 0x80, 0x02, 0x1f, 0xfc, 0xde, 0xad];
+        let ts = muxer.mux_audio(&adts, Duration::from_millis(21));
+        assert_eq!(ts.len(), 3 * TS_PACKET_SIZE);
+
+        let pmt = &ts[TS_PACKET_SIZE..2 * TS_PACKET_SIZE];
+        assert_eq!(pmt[17], STREAM_TYPE_AAC_ADTS);
+        let audio = &ts[2 * TS_PACKET_SIZE..];
+        assert_eq!(pid(audio), PID_AUDIO);
+        assert!(
+            audio.ends_with(&adts),
+            "stuffing goes in the adaptation field"
+        );
+        let pes = &audio[5 + audio[4] as usize..];
+        assert_eq!(&pes[..4], &[0x00, 0x00, 0x01, STREAM_ID_AUDIO]);
+        assert_eq!(
+            u16::from_be_bytes([pes[4], pes[5]]) as usize,
+            pes.len() - 6,
+            "bounded PES length for audio"
+        );
+    }
+
+    #[test]
+    fn sdp_attributes() {
+        let p = Mp2tPacketizer::new(0x1234, Mp2tInput::Ts);
+        assert_eq!(p.payload_type(), 33);
+        assert_eq!(
+            p.sdp_attributes(),
+            vec!["a=rtpmap:33 MP2T/90000", "a=control:track1"]
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, MediaType, Packetizer};
+
+const NS_SCHEMA: &str = "http://www.onvif.org/ver10/schema";
+const NS_WSNT: &str = "http://docs.oasis-open.org/wsn/b-2";
+const NS_TOPICS: &str = "http://www.onvif.org/ver10/topics";
+const TOPIC_DIALECT: &str = "http://www.onvif.org/ver10/tev/topicExpression/ConcreteSet";
+
+/// ONVIF metadata RTP packetizer (ONVIF Streaming Specification §5.2.1.1).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one complete XML
+/// document — a `tt:MetadataStream`, either produced by the caller or by
+/// [`MetadataFrame::to_xml`] — and sends it as plain XML in the RTP payload,
+/// split at the MTU with no payload header.
+///
+/// Metadata usually rides as a second track next to the video of the same
+/// mount (see [`Mount::add_track`](crate::mount::Mount::add_track)); at the
+/// shared 90 kHz clock, metadata sent with a frame's PTS carries that
+/// frame's RTP timestamp, which is how ONVIF clients match the two.
+///
+/// ## SDP attributes
+///
+/// - `m=application 0 RTP/AVP <pt>`
+/// - `a=rtpmap:<pt> vnd.onvif.metadata/90000`
+/// - `a=control:track1` (rewritten per track by the SDP generator)
+///
+/// ## Marker bit
+///
+/// Set on the packet that closes the XML document.
+#[derive(Debug)]
+pub struct OnvifMetadataPacketizer {
+    header: RtpHeader,
+    mtu: usize,
+}
+
+impl OnvifMetadataPacketizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self {
+            header: RtpHeader::new(pt, ssrc),
+            mtu: DEFAULT_MTU,
+        }
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self {
+            header: RtpHeader::with_random_ssrc(pt),
+            mtu: DEFAULT_MTU,
+        }
+    }
+}
+
+impl Packetizer for OnvifMetadataPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        if encoded_data.is_empty() {
+            return Vec::new();
+        }
+
+        let chunks: Vec<&[u8]> = encoded_data.chunks(self.mtu).collect();
+        let mut packets = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let hdr = self.header.write(i == chunks.len() - 1);
+            let mut packet = Vec::with_capacity(12 + chunk.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(chunk);
+            packets.push(packet);
+        }
+
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            bytes = encoded_data.len(),
+            rtp_packets = packets.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "ONVIF metadata packetized"
+        );
+
+        packets
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "vnd.onvif.metadata"
+    }
+
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn media_type(&self) -> MediaType {
+        MediaType::Application
+    }
+
+    fn sdp_attributes(&self) -> Vec<String> {
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}",
+                self.payload_type(),
+                self.codec_name(),
+                self.clock_rate()
+            ),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+/// Metadata for one video frame: detected objects and/or events,
+/// serialized as an ONVIF `tt:MetadataStream` document.
+#[derive(Debug, Clone)]
+pub struct MetadataFrame {
+    /// Capture time of the frame the metadata describes (`UtcTime`).
+    pub utc_time: SystemTime,
+    /// Objects detected in the frame (`tt:VideoAnalytics/tt:Frame/tt:Object`).
+    pub objects: Vec<MetadataObject>,
+    /// Events raised for the frame (`tt:Event/wsnt:NotificationMessage`).
+    pub events: Vec<MetadataEvent>,
+}
+
+/// A detected object (`tt:Object`).
+#[derive(Debug, Clone)]
+pub struct MetadataObject {
+    /// Tracking ID, stable across frames for the same object.
+    pub object_id: u32,
+    /// Bounding box in ONVIF normalized coordinates (-1..1, y up).
+    pub bounding_box: BoundingBox,
+    /// Classification, if any (`tt:Class/tt:Type`).
+    pub class: Option<ObjectClass>,
+}
+
+/// Rectangle in ONVIF normalized coordinates (`tt:BoundingBox`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// Object classification (`tt:Type`), e.g. `Human` or `Vehicle`.
+#[derive(Debug, Clone)]
+pub struct ObjectClass {
+    pub class_type: String,
+    /// Confidence in 0..1.
+    pub likelihood: f32,
+}
+
+/// An ONVIF event notification (`wsnt:NotificationMessage`).
+#[derive(Debug, Clone)]
+pub struct MetadataEvent {
+    /// Topic in the `tns1:` namespace, e.g. `tns1:RuleEngine/LineDetector/Crossed`.
+    pub topic: String,
+    /// Property operation for property events; `None` for plain events.
+    pub property_operation: Option<PropertyOperation>,
+    /// `tt:Source` simple items (name, value).
+    pub source: Vec<(String, String)>,
+    /// `tt:Data` simple items (name, value).
+    pub data: Vec<(String, String)>,
+}
+
+/// `PropertyOperation` attribute of an event message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyOperation {
+    Initialized,
+    Changed,
+    Deleted,
+}
+
+impl PropertyOperation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Initialized => "Initialized",
+            Self::Changed => "Changed",
+            Self::Deleted => "Deleted",
+        }
+    }
+}
+
+impl MetadataFrame {
+    /// Frame metadata with no objects or events.
+    pub fn new(utc_time: SystemTime) -> Self {
+        Self {
+            utc_time,
+            objects: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Serialize as a `tt:MetadataStream` XML document.
+    pub fn to_xml(&self) -> String {
+        let time = format_utc(self.utc_time);
+        let mut xml = String::new();
+        xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = write!(
+            xml,
+            r#"<tt:MetadataStream xmlns:tt="{NS_SCHEMA}" xmlns:wsnt="{NS_WSNT}" xmlns:tns1="{NS_TOPICS}">"#
+        );
+
+        if !self.objects.is_empty() {
+            let _ = write!(xml, r#"<tt:VideoAnalytics><tt:Frame UtcTime="{time}">"#);
+            for object in &self.objects {
+                let b = object.bounding_box;
+                let _ = write!(
+                    xml,
+                    r#"<tt:Object ObjectId="{}"><tt:Appearance><tt:Shape><tt:BoundingBox left="{}" top="{}" right="{}" bottom="{}"/><tt:CenterOfGravity x="{}" y="{}"/></tt:Shape>"#,
+                    object.object_id,
+                    b.left,
+                    b.top,
+                    b.right,
+                    b.bottom,
+                    (b.left + b.right) / 2.0,
+                    (b.top + b.bottom) / 2.0
+                );
+                if let Some(class) = &object.class {
+                    let _ = write!(
+                        xml,
+                        r#"<tt:Class><tt:Type Likelihood="{}">{}</tt:Type></tt:Class>"#,
+                        class.likelihood,
+                        escape(&class.class_type)
+                    );
+                }
+                xml.push_str("</tt:Appearance></tt:Object>");
+            }
+            xml.push_str("</tt:Frame></tt:VideoAnalytics>");
+        }
+
+        for event in &self.events {
+            let _ = write!(
+                xml,
+                r#"<tt:Event><wsnt:NotificationMessage><wsnt:Topic Dialect="{TOPIC_DIALECT}">{}</wsnt:Topic><wsnt:Message><tt:Message UtcTime="{time}""#,
+                escape(&event.topic)
+            );
+            if let Some(op) = event.property_operation {
+                let _ = write!(xml, r#" PropertyOperation="{}""#, op.as_str());
+            }
+            xml.push('>');
+            write_simple_items(&mut xml, "tt:Source", &event.source);
+            write_simple_items(&mut xml, "tt:Data", &event.data);
+            xml.push_str("</tt:Message></wsnt:Message></wsnt:NotificationMessage></tt:Event>");
+        }
+
+        xml.push_str("</tt:MetadataStream>");
+        xml
+    }
+}
+
+fn write_simple_items(xml: &mut String, element: &str, items: &[(String, String)]) {
+    let _ = write!(xml, "<{element}>");
+    for (name, value) in items {
+        let _ = write!(
+            xml,
+            r#"<tt:SimpleItem Name="{}" Value="{}"/>"#,
+            escape(name),
+            escape(value)
+        );
+    }
+    let _ = write!(xml, "</{element}>");
+}
+
+/// Escape text for use in XML content and attribute values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Format as `xs:dateTime` in UTC with millisecond precision,
+/// e.g. `2024-05-06T07:08:09.123Z`.
+fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (H. Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_utc_time() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        // 2024-02-29T12:34:56.789Z (leap day)
+        let t = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(format_utc(t), "2024-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn serializes_objects() {
+        let mut frame = MetadataFrame::new(UNIX_EPOCH);
+        frame.objects.push(MetadataObject {
+            object_id: 7,
+            bounding_box: BoundingBox {
+                left: -0.5,
+                top: 0.5,
+                right: 0.5,
+                bottom: -0.5,
+            },
+            class: Some(ObjectClass {
+                class_type: "Human".to_string(),
+                likelihood: 0.9,
+            }),
+        });
+        let xml = frame.to_xml();
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains(r#"<tt:Frame UtcTime="1970-01-01T00:00:00.000Z">"#));
+        assert!(xml.contains(r#"<tt:Object ObjectId="7">"#));
+        assert!(
+            xml.contains(r#"<tt:BoundingBox left="-0.5" top="0.5" right="0.5" bottom="-0.5"/>"#)
+        );
+        assert!(xml.contains(r#"<tt:CenterOfGravity x="0" y="0"/>"#));
+        assert!(xml.contains(r#"<tt:Type Likelihood="0.9">Human</tt:Type>"#));
+        assert!(xml.ends_with("</tt:MetadataStream>"));
+        assert!(!xml.contains("tt:Event"));
+    }
+
+    #[test]
+    fn serializes_events_escaped() {
+        let mut frame = MetadataFrame::new(UNIX_EPOCH);
+        frame.events.push(MetadataEvent {
+            topic: "tns1:RuleEngine/LineDetector/Crossed".to_string(),
+            property_operation: Some(PropertyOperation::Changed),
+            source: vec![("Rule".to_string(), "Gate <A> & \"B\"".to_string())],
+            data: vec![("ObjectId".to_string(), "7".to_string())],
+        });
+        let xml = frame.to_xml();
+        assert!(xml.contains(">tns1:RuleEngine/LineDetector/Crossed</wsnt:Topic>"));
+        assert!(xml.contains(r#"PropertyOperation="Changed""#));
+        assert!(xml.contains(r#"Value="Gate &lt;A&gt; &amp; &quot;B&quot;""#));
+        assert!(xml.contains(r#"<tt:Data><tt:SimpleItem Name="ObjectId" Value="7"/></tt:Data>"#));
+        assert!(!xml.contains("tt:VideoAnalytics"));
+    }
+
+    #[test]
+    fn packetizes_with_marker_on_last() {
+        let mut p = OnvifMetadataPacketizer::new(107, 0x1234);
+        let xml = vec![b'x'; DEFAULT_MTU + 10];
+        let packets = p.packetize(&xml, 3000);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][1], 107);
+        assert_eq!(packets[1][1], 0x80 | 107);
+        assert_eq!(packets[1].len(), 12 + 10);
+        assert!(p.packetize(&[], 3000).is_empty());
+    }
+
+    #[test]
+    fn sdp_attributes() {
+        let p = OnvifMetadataPacketizer::new(107, 0x1234);
+        assert_eq!(p.media_type(), MediaType::Application);
+        assert_eq!(
+            p.sdp_attributes()[0],
+            "a=rtpmap:107 vnd.onvif.metadata/90000"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use super::rtp::RtpHeader;
+use super::{MediaType, Packetizer};
+
+/// Opus RTP clock rate — always 48 kHz, whatever the encoder's input rate
+/// (RFC 7587 §4.1).
+const OPUS_CLOCK_RATE: u32 = 48000;
+
+/// Opus RTP packetizer (RFC 7587).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one Opus packet
+/// (as produced by `opus_encode`) and emits one RTP packet carrying it
+/// unchanged — Opus packets are never fragmented or aggregated (§4.2).
+///
+/// ## Timestamps
+///
+/// The RTP clock always runs at 48 kHz (§4.1), even when the encoder was
+/// opened at 8/12/16/24 kHz. The timestamp advance is derived from the
+/// packet's TOC byte (RFC 6716 §3.1) — frame duration × frame count — so the
+/// `timestamp_increment` argument is only used for packets whose TOC cannot
+/// be parsed.
+///
+/// ```text
+///  0 1 2 3 4 5 6 7
+/// +-+-+-+-+-+-+-+-+
+/// | config  |s| c |    config → mode/bandwidth/frame size, c → frame count
+/// +-+-+-+-+-+-+-+-+
+/// ```
+///
+/// ## SDP attributes (RFC 7587 §7)
+///
+/// - `a=rtpmap:<pt> opus/48000/2` — always two channels, even for mono.
+/// - `a=fmtp:<pt> sprop-stereo=<0|1>;useinbandfec=<0|1>[;maxaveragebitrate=<bps>]`
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the first packet of a talkspurt (RFC 3551 §4.1); since DTX is not
+/// tracked, that is the first packet sent.
+#[derive(Debug)]
+pub struct OpusPacketizer {
+    header: RtpHeader,
+    stereo: bool,
+    inband_fec: bool,
+    max_average_bitrate: Option<u32>,
+    talkspurt_start: bool,
+}
+
+impl OpusPacketizer {
+    /// Create with explicit payload type and SSRC (mono, no in-band FEC).
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self::from_header(RtpHeader::new(pt, ssrc))
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self::from_header(RtpHeader::with_random_ssrc(pt))
+    }
+
+    fn from_header(header: RtpHeader) -> Self {
+        Self {
+            header,
+            stereo: false,
+            inband_fec: false,
+            max_average_bitrate: None,
+            talkspurt_start: true,
+        }
+    }
+
+    /// Advertise a stereo stream (`sprop-stereo=1`).
+    pub fn with_stereo(mut self, stereo: bool) -> Self {
+        self.stereo = stereo;
+        self
+    }
+
+    /// Advertise that packets carry in-band FEC (`useinbandfec=1`).
+    pub fn with_inband_fec(mut self, inband_fec: bool) -> Self {
+        self.inband_fec = inband_fec;
+        self
+    }
+
+    /// Advertise the encoder's average bitrate in bits per second
+    /// (`maxaveragebitrate`, RFC 7587 §6.1).
+    pub fn with_max_average_bitrate(mut self, bps: u32) -> Self {
+        self.max_average_bitrate = Some(bps);
+        self
+    }
+
+    /// Duration of an Opus packet in 48 kHz samples, from its TOC byte
+    /// (RFC 6716 §3.1). Returns `None` for empty or malformed packets.
+    pub fn packet_duration(packet: &[u8]) -> Option<u32> {
+        let toc = *packet.first()?;
+        let config = toc >> 3;
+
+        // Frame size per configuration, in 48 kHz samples.
+        let frame_samples = match config {
+            // SILK-only (NB/MB/WB): 10, 20, 40, 60 ms
+            0..=11 => [480, 960, 1920, 2880][(config % 4) as usize],
+            // Hybrid (SWB/FB): 10, 20 ms
+            12..=15 => [480, 960][(config % 2) as usize],
+            // CELT-only (NB/WB/SWB/FB): 2.5, 5, 10, 20 ms
+            _ => [120, 240, 480, 960][(config % 4) as usize],
+        };
+
+        let frames = match toc & 0x03 {
+            0 => 1,
+            1 | 2 => 2,
+            _ => (*packet.get(1)? & 0x3f) as u32,
+        };
+
+        let duration = frame_samples * frames;
+        // A packet may not exceed 120 ms (RFC 6716 §3.2.5).
+        if frames == 0 || duration > 5760 {
+            return None;
+        }
+        Some(duration)
+    }
+}
+
+impl Packetizer for OpusPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        if encoded_data.is_empty() {
+            return Vec::new();
+        }
+
+        let duration = Self::packet_duration(encoded_data).unwrap_or_else(|| {
+            tracing::warn!(
+                bytes = encoded_data.len(),
+                timestamp_increment,
+                "unparseable Opus TOC, using caller's timestamp increment"
+            );
+            timestamp_increment
+        });
+
+        let hdr = self.header.write(self.talkspurt_start);
+        self.talkspurt_start = false;
+
+        let mut packet = Vec::with_capacity(12 + encoded_data.len());
+        packet.extend_from_slice(&hdr);
+        packet.extend_from_slice(encoded_data);
+
+        self.header.advance_timestamp(duration);
+
+        tracing::trace!(
+            bytes = encoded_data.len(),
+            samples = duration,
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "Opus packet packetized"
+        );
+
+        vec![packet]
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "opus"
+    }
+
+    /// 48 kHz regardless of the encoder's input rate (RFC 7587 §4.1).
+    fn clock_rate(&self) -> u32 {
+        OPUS_CLOCK_RATE
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn media_type(&self) -> MediaType {
+        MediaType::Audio
+    }
+
+    /// SDP attributes per RFC 7587 §7.
+    ///
+    /// The channel count in `a=rtpmap` is always 2; whether the stream
+    /// actually carries stereo is signalled by `sprop-stereo`.
+    fn sdp_attributes(&self) -> Vec<String> {
+        let pt = self.payload_type();
+        let mut fmtp = format!(
+            "a=fmtp:{} sprop-stereo={};useinbandfec={}",
+            pt, self.stereo as u8, self.inband_fec as u8
+        );
+        if let Some(bps) = self.max_average_bitrate {
+            fmtp.push_str(&format!(";maxaveragebitrate={}", bps));
+        }
+
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}/2",
+                pt,
+                self.codec_name(),
+                self.clock_rate()
+            ),
+            fmtp,
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtp_timestamp(packet: &[u8]) -> u32 {
+        u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]])
+    }
+
+    #[test]
+    fn toc_durations() {
+        // config 1: SILK NB 20 ms, one frame
+        assert_eq!(OpusPacketizer::packet_duration(&[1 << 3]), Some(960));
+        // config 3: SILK NB 60 ms
+        assert_eq!(OpusPacketizer::packet_duration(&[3 << 3]), Some(2880));
+        // config 13: Hybrid SWB 20 ms
+        assert_eq!(OpusPacketizer::packet_duration(&[13 << 3]), Some(960));
+        // config 16: CELT NB 2.5 ms
+        assert_eq!(OpusPacketizer::packet_duration(&[16 << 3]), Some(120));
+        // config 31: CELT FB 20 ms, c=1 → two frames
+        assert_eq!(OpusPacketizer::packet_duration(&[31 << 3 | 1]), Some(1920));
+        // c=3: frame count in the second byte (3 × 10 ms)
+        assert_eq!(
+            OpusPacketizer::packet_duration(&[30 << 3 | 3, 3]),
+            Some(1440)
+        );
+    }
+
+    #[test]
+    fn toc_rejects_malformed() {
+        assert_eq!(OpusPacketizer::packet_duration(&[]), None);
+        // c=3 without frame count byte
+        assert_eq!(OpusPacketizer::packet_duration(&[31 << 3 | 3]), None);
+        // 0 frames
+        assert_eq!(OpusPacketizer::packet_duration(&[31 << 3 | 3, 0]), None);
+        // 7 × 20 ms = 140 ms > 120 ms limit
+        assert_eq!(OpusPacketizer::packet_duration(&[31 << 3 | 3, 7]), None);
+    }
+
+    #[test]
+    fn one_rtp_packet_per_opus_packet() {
+        let mut p = OpusPacketizer::new(111, 0x1234);
+        let opus = [31 << 3, 0xAA, 0xBB];
+        let packets = p.packetize(&opus, 0);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][12..], &opus);
+        assert_eq!(packets[0][1] & 0x7f, 111);
+    }
+
+    #[test]
+    fn timestamp_advances_from_toc() {
+        let mut p = OpusPacketizer::new(111, 0x1234);
+        let first = p.packetize(&[31 << 3, 0], 12345);
+        let second = p.packetize(&[16 << 3, 0], 12345);
+        let third = p.packetize(&[0, 0], 12345);
+        assert_eq!(rtp_timestamp(&second[0]) - rtp_timestamp(&first[0]), 960);
+        assert_eq!(rtp_timestamp(&third[0]) - rtp_timestamp(&second[0]), 120);
+    }
+
+    #[test]
+    fn marker_on_first_packet_only() {
+        let mut p = OpusPacketizer::new(111, 0x1234);
+        let first = p.packetize(&[31 << 3], 0);
+        let second = p.packetize(&[31 << 3], 0);
+        assert_eq!(first[0][1] & 0x80, 0x80);
+        assert_eq!(second[0][1] & 0x80, 0);
+    }
+
+    #[test]
+    fn sdp_attributes() {
+        let p = OpusPacketizer::new(111, 0x1234)
+            .with_stereo(true)
+            .with_inband_fec(true)
+            .with_max_average_bitrate(32000);
+        let attrs = p.sdp_attributes();
+        assert_eq!(attrs[0], "a=rtpmap:111 opus/48000/2");
+        assert_eq!(
+            attrs[1],
+            "a=fmtp:111 sprop-stereo=1;useinbandfec=1;maxaveragebitrate=32000"
+        );
+        assert_eq!(p.clock_rate(), 48000);
+        assert_eq!(p.media_type(), MediaType::Audio);
+    }
+
+    #[test]
+    fn sdp_defaults_mono_without_fec() {
+        let attrs = OpusPacketizer::new(111, 0x1234).sdp_attributes();
+        assert_eq!(attrs[1], "a=fmtp:111 sprop-stereo=0;useinbandfec=0");
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::io::IoSlice;
+
+use bytes::Bytes;
+
+/// Largest header a packet can carry inline: the 12-byte RTP fixed header
+/// plus the biggest payload header a packetizer writes before sliced data
+/// (e.g. the VP9 descriptor with its scalability structure).
+pub const MAX_INLINE_HEADER: usize = 32;
+
+/// An RTP packet as a scatter/gather pair: a small inline header and a
+/// shared payload slice.
+///
+/// The header holds the RTP fixed header (RFC 3550 §5.1) followed by any
+/// codec payload header (FU indicator/header, VP8 descriptor, ...). The
+/// payload is a reference-counted view into the frame that was
+/// packetized, so building the packet copies no media bytes and cloning it
+/// for another viewer only bumps a reference count.
+///
+/// ```text
+/// header (inline, ≤ 32 bytes)     payload (Bytes, shared with the frame)
+/// +-----------+----------------+  +--------------------------------+
+/// | RTP (12)  | codec header   |  | slice of the encoded frame     |
+/// +-----------+----------------+  +--------------------------------+
+/// ```
+///
+/// Packetizers that build whole packets in a `Vec<u8>` convert with
+/// [`From<Vec<u8>>`], which moves the buffer into the payload and leaves
+/// the inline header empty.
+#[derive(Clone)]
+pub struct RtpPacket {
+    header: [u8; MAX_INLINE_HEADER],
+    header_len: u8,
+    payload: Bytes,
+}
+
+impl RtpPacket {
+    /// Build a packet from a header and a payload slice.
+    ///
+    /// # Panics
+    ///
+    /// If `header` is longer than [`MAX_INLINE_HEADER`].
+    pub fn new(header: &[u8], payload: Bytes) -> Self {
+        assert!(
+            header.len() <= MAX_INLINE_HEADER,
+            "RTP packet header of {} bytes exceeds inline capacity",
+            header.len()
+        );
+        let mut inline = [0u8; MAX_INLINE_HEADER];
+        inline[..header.len()].copy_from_slice(header);
+        Self {
+            header: inline,
+            header_len: header.len() as u8,
+            payload,
+        }
+    }
+
+    /// Inline header bytes (RTP fixed header plus codec payload header).
+    pub fn header(&self) -> &[u8] {
+        &self.header[..self.header_len as usize]
+    }
+
+    /// Shared payload bytes following the header.
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Total packet length on the wire.
+    pub fn len(&self) -> usize {
+        self.header_len as usize + self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// RTP timestamp from the fixed header (bytes 4–7), or `None` if the
+    /// packet is shorter than an RTP header.
+    pub fn timestamp(&self) -> Option<u32> {
+        self.fixed_header_u32(4)
+    }
+
+    /// SSRC from the fixed header (bytes 8–11), or `None` if the packet is
+    /// shorter than an RTP header.
+    pub fn ssrc(&self) -> Option<u32> {
+        self.fixed_header_u32(8)
+    }
+
+    fn fixed_header_u32(&self, offset: usize) -> Option<u32> {
+        if self.len() < 12 {
+            return None;
+        }
+        let header = self.header();
+        let byte = |i: usize| match header.get(i) {
+            Some(&b) => b,
+            None => self.payload[i - header.len()],
+        };
+        Some(u32::from_be_bytes([
+            byte(offset),
+            byte(offset + 1),
+            byte(offset + 2),
+            byte(offset + 3),
+        ]))
+    }
+
+    /// The packet as I/O slices for vectored sends (`sendmsg`).
+    pub fn io_slices(&self) -> [IoSlice<'_>; 2] {
+        [IoSlice::new(self.header()), IoSlice::new(&self.payload)]
+    }
+
+    /// Append the contiguous packet to `buf`.
+    pub fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.reserve(self.len());
+        buf.extend_from_slice(self.header());
+        buf.extend_from_slice(&self.payload);
+    }
+
+    /// Copy into a contiguous buffer.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.len());
+        self.write_to(&mut buf);
+        buf
+    }
+}
+
+impl From<Vec<u8>> for RtpPacket {
+    fn from(packet: Vec<u8>) -> Self {
+        Self {
+            header: [0u8; MAX_INLINE_HEADER],
+            header_len: 0,
+            payload: Bytes::from(packet),
+        }
+    }
+}
+
+impl PartialEq for RtpPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.to_vec() == other.to_vec()
+    }
+}
+
+impl Eq for RtpPacket {}
+
+impl fmt::Debug for RtpPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RtpPacket")
+            .field("header", &self.header())
+            .field("payload_len", &self.payload.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_shares_frame_buffer() {
+        let frame = Bytes::from(vec![7u8; 100]);
+        let packet = RtpPacket::new(&[0x80, 96], frame.slice(10..60));
+        assert_eq!(packet.len(), 52);
+        assert_eq!(packet.payload().as_ptr(), frame[10..].as_ptr());
+
+        let copy = packet.clone();
+        assert_eq!(copy.payload().as_ptr(), packet.payload().as_ptr());
+    }
+
+    #[test]
+    fn contiguous_forms_match() {
+        let packet = RtpPacket::new(&[1, 2, 3], Bytes::from_static(&[4, 5]));
+        assert_eq!(packet.to_vec(), vec![1, 2, 3, 4, 5]);
+        let slices = packet.io_slices();
+        assert_eq!(&*slices[0], &[1, 2, 3]);
+        assert_eq!(&*slices[1], &[4, 5]);
+        assert_eq!(packet, RtpPacket::from(vec![1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn timestamp_reads_across_header_and_payload() {
+        let rtp = [0x80, 96, 0, 1, 0x12, 0x34, 0x56, 0x78, 0, 0, 0, 1];
+        let split = RtpPacket::new(&rtp[..6], Bytes::copy_from_slice(&rtp[6..]));
+        assert_eq!(split.timestamp(), Some(0x1234_5678));
+        assert_eq!(RtpPacket::from(rtp.to_vec()).timestamp(), Some(0x1234_5678));
+        assert_eq!(RtpPacket::from(vec![0x80]).timestamp(), None);
+        assert_eq!(split.ssrc(), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds inline capacity")]
+    fn oversized_header_panics() {
+        RtpPacket::new(&[0u8; MAX_INLINE_HEADER + 1], Bytes::new());
+    }
+}
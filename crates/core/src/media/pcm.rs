@@ -0,0 +1,372 @@
+use std::time::Duration;
+
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, MediaType, Packetizer};
+
+/// Static payload type for PCMU (RFC 3551 §6, Table 4).
+pub const PT_PCMU: u8 = 0;
+/// Static payload type for PCMA (RFC 3551 §6, Table 4).
+pub const PT_PCMA: u8 = 8;
+/// Static payload type for L16 44.1 kHz stereo (RFC 3551 §6).
+pub const PT_L16_STEREO: u8 = 10;
+/// Static payload type for L16 44.1 kHz mono (RFC 3551 §6).
+pub const PT_L16_MONO: u8 = 11;
+
+/// Default packet duration (RFC 3551 §4.5 recommends 20 ms).
+const DEFAULT_PACKET_DURATION: Duration = Duration::from_millis(20);
+
+/// Sample encoding carried by a [`PcmPacketizer`] (RFC 3551 §4.5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmEncoding {
+    /// G.711 µ-law, 8-bit samples (§4.5.14).
+    Pcmu,
+    /// G.711 A-law, 8-bit samples (§4.5.14).
+    Pcma,
+    /// Uncompressed 16-bit signed samples, network byte order (§4.5.11).
+    L16,
+}
+
+impl PcmEncoding {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::Pcmu | Self::Pcma => 1,
+            Self::L16 => 2,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pcmu => "PCMU",
+            Self::Pcma => "PCMA",
+            Self::L16 => "L16",
+        }
+    }
+}
+
+/// RTP packetizer for sample-based audio: G.711 PCMU/PCMA and L16 (RFC 3551).
+///
+/// Takes raw sample buffers of any length and cuts them into packets of a
+/// fixed duration (20 ms by default). Samples that don't fill a whole packet
+/// are kept and prepended to the next call's buffer, so packet boundaries
+/// are independent of how the producer chunks its audio.
+///
+/// ## Timestamps
+///
+/// The RTP timestamp advances by the number of samples (per channel) in
+/// each packet (RFC 3551 §4.5), so the `timestamp_increment` argument to
+/// [`packetize`](Packetizer::packetize) is ignored.
+///
+/// ## SDP attributes
+///
+/// Static payload types (PCMU = 0, PCMA = 8, L16 = 10/11) need no `a=fmtp`;
+/// `a=rtpmap` is still emitted since many clients expect it:
+///
+/// - `a=rtpmap:0 PCMU/8000`
+/// - `a=ptime:20`
+/// - `a=control:track1`
+///
+/// ## L16 byte order
+///
+/// L16 samples go on the wire big-endian. Most capture APIs produce
+/// little-endian samples; use
+/// [`with_little_endian_input`](Self::with_little_endian_input) to have the
+/// packetizer swap them.
+#[derive(Debug)]
+pub struct PcmPacketizer {
+    header: RtpHeader,
+    encoding: PcmEncoding,
+    sample_rate: u32,
+    channels: u8,
+    samples_per_packet: usize,
+    little_endian_input: bool,
+    /// Samples carried over from the previous call (already in wire order).
+    pending: Vec<u8>,
+    talkspurt_start: bool,
+}
+
+impl PcmPacketizer {
+    /// G.711 µ-law, 8 kHz mono, static payload type 0.
+    pub fn pcmu(ssrc: u32) -> Self {
+        Self::new(RtpHeader::new(PT_PCMU, ssrc), PcmEncoding::Pcmu, 8000, 1)
+    }
+
+    /// G.711 A-law, 8 kHz mono, static payload type 8.
+    pub fn pcma(ssrc: u32) -> Self {
+        Self::new(RtpHeader::new(PT_PCMA, ssrc), PcmEncoding::Pcma, 8000, 1)
+    }
+
+    /// L16 with an explicit payload type, sample rate and channel count.
+    ///
+    /// Use [`PT_L16_STEREO`]/[`PT_L16_MONO`] for 44.1 kHz streams, or a
+    /// dynamic type (96–127) for any other rate. `sample_rate` and
+    /// `channels` are raised to at least 1.
+    pub fn l16(pt: u8, ssrc: u32, sample_rate: u32, channels: u8) -> Self {
+        Self::new(
+            RtpHeader::new(pt, ssrc),
+            PcmEncoding::L16,
+            sample_rate,
+            channels,
+        )
+    }
+
+    /// Same as the constructors above, with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8, encoding: PcmEncoding, sample_rate: u32, channels: u8) -> Self {
+        Self::new(
+            RtpHeader::with_random_ssrc(pt),
+            encoding,
+            sample_rate,
+            channels,
+        )
+    }
+
+    fn new(header: RtpHeader, encoding: PcmEncoding, sample_rate: u32, channels: u8) -> Self {
+        let mut packetizer = Self {
+            header,
+            encoding,
+            sample_rate: sample_rate.max(1),
+            channels: channels.max(1),
+            samples_per_packet: 0,
+            little_endian_input: false,
+            pending: Vec::new(),
+            talkspurt_start: true,
+        };
+        packetizer.set_packet_duration(DEFAULT_PACKET_DURATION);
+        packetizer
+    }
+
+    /// Set the audio duration carried by each RTP packet (default 20 ms).
+    ///
+    /// Clamped so a packet never exceeds the MTU (e.g. 44.1 kHz stereo L16
+    /// cannot fit 20 ms in one packet).
+    pub fn with_packet_duration(mut self, duration: Duration) -> Self {
+        self.set_packet_duration(duration);
+        self
+    }
+
+    /// Treat L16 input as little-endian and byte-swap it to network order.
+    /// Has no effect on G.711.
+    pub fn with_little_endian_input(mut self, little_endian: bool) -> Self {
+        self.little_endian_input = little_endian;
+        self
+    }
+
+    fn set_packet_duration(&mut self, duration: Duration) {
+        let requested = (duration.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        let max_for_mtu = DEFAULT_MTU / self.frame_bytes();
+        self.samples_per_packet = requested.clamp(1, max_for_mtu.max(1));
+    }
+
+    /// Bytes per sample frame (one sample for every channel).
+    fn frame_bytes(&self) -> usize {
+        self.encoding.bytes_per_sample() * self.channels as usize
+    }
+
+    /// Packet duration in milliseconds, for `a=ptime`.
+    fn ptime_ms(&self) -> u64 {
+        (self.samples_per_packet as u64 * 1000).div_ceil(self.sample_rate as u64)
+    }
+}
+
+impl Packetizer for PcmPacketizer {
+    fn packetize(&mut self, encoded_data: &[u8], _timestamp_increment: u32) -> Vec<Vec<u8>> {
+        let start = self.pending.len();
+        self.pending.extend_from_slice(encoded_data);
+        if self.encoding == PcmEncoding::L16 && self.little_endian_input {
+            // Swap only whole samples; an odd trailing byte waits for its pair.
+            // Everything before `aligned_start` was swapped by earlier calls.
+            let aligned_start = start - start % 2;
+            for sample in self.pending[aligned_start..].chunks_exact_mut(2) {
+                sample.swap(0, 1);
+            }
+        }
+
+        let packet_bytes = self.samples_per_packet * self.frame_bytes();
+        let mut packets = Vec::new();
+        let mut consumed = 0;
+        while self.pending.len() - consumed >= packet_bytes {
+            let payload = &self.pending[consumed..consumed + packet_bytes];
+            let hdr = self.header.write(self.talkspurt_start);
+            self.talkspurt_start = false;
+
+            let mut packet = Vec::with_capacity(12 + payload.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(payload);
+            packets.push(packet);
+
+            self.header
+                .advance_timestamp(self.samples_per_packet as u32);
+            consumed += packet_bytes;
+        }
+        self.pending.drain(..consumed);
+
+        tracing::trace!(
+            codec = self.encoding.name(),
+            input_bytes = encoded_data.len(),
+            rtp_packets = packets.len(),
+            pending_bytes = self.pending.len(),
+            ts = self.header.timestamp(),
+            "audio samples packetized"
+        );
+
+        packets
+    }
+
+    fn codec_name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    fn clock_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn media_type(&self) -> MediaType {
+        MediaType::Audio
+    }
+
+    /// `a=rtpmap` (channel count only when not mono), `a=ptime` and
+    /// `a=control`. No `a=fmtp` — none of these encodings take parameters.
+    fn sdp_attributes(&self) -> Vec<String> {
+        let mut rtpmap = format!(
+            "a=rtpmap:{} {}/{}",
+            self.payload_type(),
+            self.codec_name(),
+            self.clock_rate()
+        );
+        if self.channels > 1 {
+            rtpmap.push_str(&format!("/{}", self.channels));
+        }
+
+        vec![
+            rtpmap,
+            format!("a=ptime:{}", self.ptime_ms()),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    /// `timestamp` applies to the first sample of the next buffer; samples
+    /// still pending from earlier calls are placed just before it.
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        let pending_samples = (self.pending.len() / self.frame_bytes()) as u32;
+        self.header
+            .set_timestamp(timestamp.wrapping_sub(pending_samples));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtp_timestamp(packet: &[u8]) -> u32 {
+        u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]])
+    }
+
+    #[test]
+    fn clamps_zero_sample_rate() {
+        let p = PcmPacketizer::l16(96, 1, 0, 0);
+        assert_eq!(p.clock_rate(), 1);
+        assert!(
+            p.sdp_attributes()
+                .contains(&"a=rtpmap:96 L16/1".to_string())
+        );
+    }
+
+    #[test]
+    fn pcmu_chunks_into_20ms_packets() {
+        let mut p = PcmPacketizer::pcmu(0x1234);
+        let packets = p.packetize(&[0xFF; 400], 0);
+        assert_eq!(packets.len(), 2, "400 samples = two 160-sample packets");
+        assert!(packets.iter().all(|pkt| pkt.len() == 12 + 160));
+        assert_eq!(packets[0][1] & 0x7f, PT_PCMU);
+        assert_eq!(rtp_timestamp(&packets[1]) - rtp_timestamp(&packets[0]), 160);
+    }
+
+    #[test]
+    fn remainder_carries_over() {
+        let mut p = PcmPacketizer::pcma(0x1234);
+        let first = p.packetize(&[1; 100], 0);
+        assert!(first.is_empty());
+        let second = p.packetize(&[2; 100], 0);
+        assert_eq!(second.len(), 1);
+        assert_eq!(&second[0][12..112], &[1; 100]);
+        assert_eq!(&second[0][112..172], &[2; 60]);
+        assert_eq!(second[0][1] & 0x80, 0x80, "marker on first packet");
+
+        let third = p.packetize(&[3; 120], 0);
+        assert_eq!(third.len(), 1);
+        assert_eq!(third[0][1] & 0x80, 0);
+        assert_eq!(rtp_timestamp(&third[0]) - rtp_timestamp(&second[0]), 160);
+    }
+
+    #[test]
+    fn configurable_packet_duration() {
+        let mut p = PcmPacketizer::pcmu(0x1234).with_packet_duration(Duration::from_millis(10));
+        let packets = p.packetize(&[0; 160], 0);
+        assert_eq!(packets.len(), 2);
+        assert!(p.sdp_attributes().contains(&"a=ptime:10".to_string()));
+    }
+
+    #[test]
+    fn l16_timestamps_count_sample_frames() {
+        let mut p = PcmPacketizer::l16(96, 0x1234, 16000, 2);
+        // 20 ms at 16 kHz stereo = 320 frames × 4 bytes.
+        let packets = p.packetize(&vec![0; 320 * 4 * 2], 0);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].len(), 12 + 1280);
+        assert_eq!(rtp_timestamp(&packets[1]) - rtp_timestamp(&packets[0]), 320);
+    }
+
+    #[test]
+    fn l16_packet_clamped_to_mtu() {
+        let mut p = PcmPacketizer::l16(PT_L16_STEREO, 0x1234, 44100, 2);
+        let packets = p.packetize(&vec![0; 44100 * 4 / 50], 0);
+        assert!(packets.iter().all(|pkt| pkt.len() <= 12 + DEFAULT_MTU));
+    }
+
+    #[test]
+    fn l16_little_endian_swapped_across_calls() {
+        let mut p = PcmPacketizer::l16(96, 0x1234, 8000, 1)
+            .with_little_endian_input(true)
+            .with_packet_duration(Duration::from_nanos(250_000));
+        // 2 samples per packet; split the second sample across calls.
+        assert!(p.packetize(&[0x01, 0x02, 0x03], 0).is_empty());
+        let packets = p.packetize(&[0x04], 0);
+        assert_eq!(&packets[0][12..], &[0x02, 0x01, 0x04, 0x03]);
+    }
+
+    #[test]
+    fn set_timestamp_accounts_for_pending_samples() {
+        let mut p = PcmPacketizer::pcmu(0x1234);
+        p.packetize(&[0; 60], 0);
+        p.set_next_rtp_timestamp(1000);
+        let packets = p.packetize(&[0; 100], 0);
+        assert_eq!(rtp_timestamp(&packets[0]), 940);
+    }
+
+    #[test]
+    fn sdp_static_payload_types_have_no_fmtp() {
+        let attrs = PcmPacketizer::pcmu(1).sdp_attributes();
+        assert_eq!(attrs[0], "a=rtpmap:0 PCMU/8000");
+        assert!(attrs.iter().all(|a| !a.starts_with("a=fmtp")));
+        assert_eq!(
+            PcmPacketizer::l16(PT_L16_STEREO, 1, 44100, 2).sdp_attributes()[0],
+            "a=rtpmap:10 L16/44100/2"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! RTCP parsing for receiver feedback (RFC 3550 §6, RFC 4585, RFC 5104).
+//!
+//! Viewers send RTCP to the server's advertised RTCP port. Most of it
+//! (receiver reports, SDES) is informational; the messages the server acts
+//! on are the payload-specific feedback requests for a fresh keyframe:
+//!
+//! ```text
+//!  0                   1                   2                   3
+//!  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |V=2|P|   FMT   |   PT=206      |          length               |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |                  SSRC of packet sender                        |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |                  SSRC of media source                         |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! :            Feedback Control Information (FCI)                 :
+//! ```
+//!
+//! - **PLI** (FMT=1, RFC 4585 §6.3.1): no FCI; the decoder lost reference data.
+//! - **FIR** (FMT=4, RFC 5104 §4.3.1): one FCI entry per target SSRC, each
+//!   carrying `SSRC (32) | Seq nr. (8) | Reserved (24)`.
+//!
+//! Compound packets (RFC 3550 §6.1) are walked packet by packet; anything
+//! malformed stops the walk and whatever was parsed so far is returned.
+//!
+//! The one packet the server sends is a **BYE** (RFC 3550 §6.6) when it
+//! shuts down, so viewers stop waiting for media; see [`build_bye`].
+
+/// RTCP payload type for receiver reports (RFC 3550 §6.4.2).
+pub const PT_RR: u8 = 201;
+
+/// RTCP payload type for BYE (RFC 3550 §6.6).
+pub const PT_BYE: u8 = 203;
+
+/// RTCP payload type for payload-specific feedback messages (RFC 4585 §6.1).
+pub const PT_PSFB: u8 = 206;
+
+/// PSFB format: Picture Loss Indication (RFC 4585 §6.3.1).
+const FMT_PLI: u8 = 1;
+
+/// PSFB format: Full Intra Request (RFC 5104 §4.3.1).
+const FMT_FIR: u8 = 4;
+
+/// A keyframe-related feedback message extracted from an RTCP packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpFeedback {
+    /// Picture Loss Indication (RFC 4585 §6.3.1).
+    PictureLoss { sender_ssrc: u32, media_ssrc: u32 },
+    /// Full Intra Request (RFC 5104 §4.3.1). `media_ssrc` is taken from the
+    /// FCI entry, since the common header field is unused for FIR.
+    FullIntraRequest {
+        sender_ssrc: u32,
+        media_ssrc: u32,
+        seq: u8,
+    },
+}
+
+/// Extract PLI and FIR messages from a (possibly compound) RTCP packet.
+///
+/// Other RTCP packet types (SR, RR, SDES, BYE, NACK, ...) are skipped.
+pub fn parse_feedback(buf: &[u8]) -> Vec<RtcpFeedback> {
+    let mut feedback = Vec::new();
+    let mut offset = 0usize;
+
+    while offset + 4 <= buf.len() {
+        let first = buf[offset];
+        if first >> 6 != 2 {
+            tracing::trace!(offset, "RTCP packet with invalid version, stopping");
+            break;
+        }
+        let fmt = first & 0x1f;
+        let pt = buf[offset + 1];
+        let words = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+        let end = offset + (words + 1) * 4;
+        if end > buf.len() {
+            tracing::trace!(offset, words, "truncated RTCP packet, stopping");
+            break;
+        }
+
+        let body = &buf[offset + 4..end];
+        if pt == PT_PSFB && body.len() >= 8 {
+            let sender_ssrc = read_u32(&body[0..4]);
+            match fmt {
+                FMT_PLI => feedback.push(RtcpFeedback::PictureLoss {
+                    sender_ssrc,
+                    media_ssrc: read_u32(&body[4..8]),
+                }),
+                FMT_FIR => {
+                    for entry in body[8..].chunks_exact(8) {
+                        feedback.push(RtcpFeedback::FullIntraRequest {
+                            sender_ssrc,
+                            media_ssrc: read_u32(&entry[0..4]),
+                            seq: entry[4],
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        offset = end;
+    }
+
+    feedback
+}
+
+/// Build a compound RTCP packet announcing that the source `ssrc` is
+/// leaving: an empty receiver report, which must lead every compound
+/// packet (RFC 3550 §6.1), followed by a BYE carrying `reason`.
+///
+/// `reason` is truncated to 255 bytes.
+pub fn build_bye(ssrc: u32, reason: &str) -> Vec<u8> {
+    let reason = &reason.as_bytes()[..reason.len().min(255)];
+
+    let mut p = vec![0x80, PT_RR, 0, 1];
+    p.extend_from_slice(&ssrc.to_be_bytes());
+
+    let bye_start = p.len();
+    p.extend_from_slice(&[0x81, PT_BYE, 0, 0]);
+    p.extend_from_slice(&ssrc.to_be_bytes());
+    if !reason.is_empty() {
+        p.push(reason.len() as u8);
+        p.extend_from_slice(reason);
+        p.resize(p.len().next_multiple_of(4), 0);
+    }
+    let words = ((p.len() - bye_start) / 4 - 1) as u16;
+    p[bye_start + 2..bye_start + 4].copy_from_slice(&words.to_be_bytes());
+    p
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pli(sender: u32, media: u32) -> Vec<u8> {
+        let mut p = vec![0x80 | FMT_PLI, PT_PSFB, 0, 2];
+        p.extend_from_slice(&sender.to_be_bytes());
+        p.extend_from_slice(&media.to_be_bytes());
+        p
+    }
+
+    fn receiver_report(sender: u32) -> Vec<u8> {
+        // RR with zero report blocks: header + sender SSRC.
+        let mut p = vec![0x80, 201, 0, 1];
+        p.extend_from_slice(&sender.to_be_bytes());
+        p
+    }
+
+    #[test]
+    fn parses_pli() {
+        let fb = parse_feedback(&pli(0x1111, 0x2222));
+        assert_eq!(
+            fb,
+            vec![RtcpFeedback::PictureLoss {
+                sender_ssrc: 0x1111,
+                media_ssrc: 0x2222
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_fir_entries() {
+        let mut p = vec![0x80 | FMT_FIR, PT_PSFB, 0, 4];
+        p.extend_from_slice(&0x1111u32.to_be_bytes());
+        p.extend_from_slice(&0u32.to_be_bytes());
+        p.extend_from_slice(&0xAABBCCDDu32.to_be_bytes());
+        p.extend_from_slice(&[7, 0, 0, 0]);
+        let fb = parse_feedback(&p);
+        assert_eq!(
+            fb,
+            vec![RtcpFeedback::FullIntraRequest {
+                sender_ssrc: 0x1111,
+                media_ssrc: 0xAABBCCDD,
+                seq: 7
+            }]
+        );
+    }
+
+    #[test]
+    fn walks_compound_packet() {
+        let mut p = receiver_report(0x1111);
+        p.extend(pli(0x1111, 0x2222));
+        let fb = parse_feedback(&p);
+        assert_eq!(fb.len(), 1);
+    }
+
+    #[test]
+    fn bye_follows_receiver_report() {
+        let p = build_bye(0xAABBCCDD, "server shutdown");
+        assert_eq!(p.len() % 4, 0);
+        assert_eq!(&p[..8], &[0x80, PT_RR, 0, 1, 0xAA, 0xBB, 0xCC, 0xDD]);
+        assert_eq!(&p[8..12], &[0x81, PT_BYE, 0, 5]);
+        assert_eq!(read_u32(&p[12..16]), 0xAABBCCDD);
+        assert_eq!(p[16], 15);
+        assert_eq!(&p[17..32], b"server shutdown");
+        assert!(parse_feedback(&p).is_empty());
+
+        assert_eq!(build_bye(1, "").len(), 16);
+    }
+
+    #[test]
+    fn ignores_reports_and_garbage() {
+        assert!(parse_feedback(&receiver_report(1)).is_empty());
+        assert!(parse_feedback(&[0xFF, 0xFF]).is_empty());
+        // Length field claims more data than present.
+        assert!(parse_feedback(&[0x81, PT_PSFB, 0, 9, 0, 0, 0, 1]).is_empty());
+    }
+}
@@ -86,6 +86,14 @@ impl RtpHeader {
         header
     }
 
+    /// Set the RTP timestamp written by subsequent [`write`](Self::write) calls.
+    ///
+    /// Used when the timestamp is derived from a presentation time
+    /// (see [`RtpClock`](super::clock::RtpClock)) instead of increments.
+    pub fn set_timestamp(&mut self, timestamp: u32) {
+        self.timestamp = timestamp as u64;
+    }
+
     /// Advance the RTP timestamp by the given increment.
     ///
     /// For video at 90 kHz clock rate, the increment per frame is
@@ -164,6 +172,17 @@ mod tests {
         assert_eq!(h.timestamp(), 6000);
     }
 
+    #[test]
+    fn set_timestamp_written() {
+        let mut h = make_header();
+        h.set_timestamp(0xDEADBEEF);
+        let buf = h.write(false);
+        assert_eq!(
+            u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            0xDEADBEEF
+        );
+    }
+
     #[test]
     fn random_ssrc_differs() {
         let h1 = RtpHeader::with_random_ssrc(96);
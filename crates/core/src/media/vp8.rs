@@ -0,0 +1,244 @@
+use rand::RngExt;
+
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, Packetizer};
+
+/// Size of the payload descriptor written by [`Vp8Packetizer`]: required
+/// byte, extension byte and a 15-bit picture ID.
+const DESCRIPTOR_LEN: usize = 4;
+
+/// VP8 RTP packetizer (RFC 7741).
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one encoded VP8
+/// frame and splits it into RTP packets, each prefixed with a payload
+/// descriptor (§4.2):
+///
+/// ```text
+///       0 1 2 3 4 5 6 7
+///      +-+-+-+-+-+-+-+-+
+///      |X|R|N|S|R| PID |   X=1, S on the first packet, PID=0
+///      +-+-+-+-+-+-+-+-+
+///   X: |I|L|T|K| RSV   |   I=1
+///      +-+-+-+-+-+-+-+-+
+///   I: |M| PictureID   |   M=1: 15-bit picture ID
+///      +-+-+-+-+-+-+-+-+
+///      |   PictureID   |
+///      +-+-+-+-+-+-+-+-+
+/// ```
+///
+/// The frame is treated as a single partition: packets are cut at the MTU
+/// rather than at partition boundaries, which §4.4 permits. The picture ID
+/// starts at a random value and increments per frame, wrapping at 15 bits.
+///
+/// ## Keyframes
+///
+/// [`is_keyframe`](Self::is_keyframe) reads the frame tag (RFC 6386 §9.1):
+/// the P bit is clear and the key frame start code `9d 01 2a` follows.
+///
+/// ## SDP attributes (§6.1)
+///
+/// - `a=rtpmap:<pt> VP8/90000`
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the last packet of a frame (§4.1).
+#[derive(Debug)]
+pub struct Vp8Packetizer {
+    header: RtpHeader,
+    mtu: usize,
+    picture_id: u16,
+}
+
+impl Vp8Packetizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self::from_header(RtpHeader::new(pt, ssrc))
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self::from_header(RtpHeader::with_random_ssrc(pt))
+    }
+
+    fn from_header(header: RtpHeader) -> Self {
+        Self {
+            header,
+            mtu: DEFAULT_MTU,
+            picture_id: rand::rng().random::<u16>() & 0x7fff,
+        }
+    }
+
+    /// Picture ID that will be sent with the next frame.
+    pub fn picture_id(&self) -> u16 {
+        self.picture_id
+    }
+
+    /// Whether an encoded VP8 frame is a key frame (RFC 6386 §9.1).
+    pub fn is_keyframe(frame: &[u8]) -> bool {
+        frame.len() >= 10 && frame[0] & 0x01 == 0 && frame[3..6] == [0x9d, 0x01, 0x2a]
+    }
+}
+
+impl Packetizer for Vp8Packetizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        if encoded_data.is_empty() {
+            return Vec::new();
+        }
+
+        let keyframe = Self::is_keyframe(encoded_data);
+        let chunks: Vec<&[u8]> = encoded_data.chunks(self.mtu - DESCRIPTOR_LEN).collect();
+        let mut packets = Vec::with_capacity(chunks.len());
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let start = if i == 0 { 0x10 } else { 0 };
+            let hdr = self.header.write(i == chunks.len() - 1);
+            let mut packet = Vec::with_capacity(12 + DESCRIPTOR_LEN + chunk.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(&[
+                0x80 | start,
+                0x80,
+                0x80 | (self.picture_id >> 8) as u8,
+                self.picture_id as u8,
+            ]);
+            packet.extend_from_slice(chunk);
+            packets.push(packet);
+        }
+
+        self.picture_id = (self.picture_id + 1) & 0x7fff;
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            keyframe,
+            rtp_packets = packets.len(),
+            frame_bytes = encoded_data.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "VP8 frame packetized"
+        );
+
+        packets
+    }
+
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        Vp8Packetizer::is_keyframe(encoded_data)
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "VP8"
+    }
+
+    /// 90 kHz clock rate (RFC 7741 §6.1).
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn sdp_attributes(&self) -> Vec<String> {
+        vec![
+            format!(
+                "a=rtpmap:{} {}/{}",
+                self.payload_type(),
+                self.codec_name(),
+                self.clock_rate()
+            ),
+            "a=control:track1".to_string(),
+        ]
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal key frame: frame tag with P=0, start code, 320x240.
+    fn keyframe(len: usize) -> Vec<u8> {
+        let mut frame = vec![0x10, 0x02, 0x00, 0x9d, 0x01, 0x2a, 0x40, 0x01, 0xf0, 0x00];
+        frame.resize(len, 0xAB);
+        frame
+    }
+
+    fn picture_id(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[14] & 0x7f, packet[15]])
+    }
+
+    #[test]
+    fn detects_keyframes() {
+        assert!(Vp8Packetizer::is_keyframe(&keyframe(10)));
+        let mut inter = keyframe(10);
+        inter[0] |= 0x01;
+        assert!(!Vp8Packetizer::is_keyframe(&inter));
+        assert!(!Vp8Packetizer::is_keyframe(&[0x10, 0x02]));
+    }
+
+    #[test]
+    fn small_frame_single_packet() {
+        let mut p = Vp8Packetizer::new(96, 0x1234);
+        let frame = keyframe(100);
+        let packets = p.packetize(&frame, 3000);
+        assert_eq!(packets.len(), 1);
+        let pkt = &packets[0];
+        assert_eq!(pkt[1] & 0x80, 0x80, "marker on last packet");
+        assert_eq!(pkt[12], 0x90, "X and S set, PID 0");
+        assert_eq!(pkt[13], 0x80, "I set");
+        assert_eq!(pkt[14] & 0x80, 0x80, "15-bit picture ID");
+        assert_eq!(&pkt[16..], &frame[..]);
+    }
+
+    #[test]
+    fn large_frame_fragmented() {
+        let mut p = Vp8Packetizer::new(96, 0x1234);
+        let frame = keyframe(3000);
+        let packets = p.packetize(&frame, 3000);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0][12] & 0x10, 0x10);
+        assert_eq!(packets[1][12] & 0x10, 0);
+        assert_eq!(packets[0][1] & 0x80, 0);
+        assert_eq!(packets[2][1] & 0x80, 0x80);
+        assert!(packets.iter().all(|pkt| pkt.len() <= 12 + DEFAULT_MTU));
+        let id = picture_id(&packets[0]);
+        assert!(packets.iter().all(|pkt| picture_id(pkt) == id));
+
+        let payload: Vec<u8> = packets.iter().flat_map(|pkt| pkt[16..].to_vec()).collect();
+        assert_eq!(payload, frame);
+    }
+
+    #[test]
+    fn picture_id_increments_and_wraps() {
+        let mut p = Vp8Packetizer::new(96, 0x1234);
+        p.picture_id = 0x7fff;
+        let first = p.packetize(&keyframe(10), 3000);
+        let second = p.packetize(&keyframe(10), 3000);
+        assert_eq!(picture_id(&first[0]), 0x7fff);
+        assert_eq!(picture_id(&second[0]), 0);
+    }
+
+    #[test]
+    fn sdp_attributes() {
+        let p = Vp8Packetizer::new(97, 0x1234);
+        assert_eq!(
+            p.sdp_attributes(),
+            vec!["a=rtpmap:97 VP8/90000", "a=control:track1"]
+        );
+    }
+}
@@ -0,0 +1,404 @@
+use rand::RngExt;
+
+use super::bits::BitReader;
+use super::rtp::RtpHeader;
+use super::{DEFAULT_MTU, Packetizer};
+
+/// Payload descriptor flags (RFC 9628 §4.2).
+const DESC_I: u8 = 0x80;
+const DESC_P: u8 = 0x40;
+const DESC_B: u8 = 0x08;
+const DESC_E: u8 = 0x04;
+const DESC_V: u8 = 0x02;
+
+/// Required byte plus a 15-bit picture ID.
+const DESCRIPTOR_LEN: usize = 3;
+
+/// Scalability structure with one spatial layer and its resolution.
+const SS_LEN: usize = 5;
+
+/// VP9 RTP packetizer (RFC 9628), non-flexible mode without layering.
+///
+/// Each call to [`packetize`](Packetizer::packetize) takes one encoded VP9
+/// frame (or superframe, which is sent whole) and splits it into RTP
+/// packets, each prefixed with a payload descriptor (§4.2):
+///
+/// ```text
+///       0 1 2 3 4 5 6 7
+///      +-+-+-+-+-+-+-+-+
+///      |I|P|L|F|B|E|V|Z|   I=1, P on inter frames, B/E on first/last packet
+///      +-+-+-+-+-+-+-+-+
+///   I: |M| PICTURE ID  |   M=1: 15-bit picture ID
+///      +-+-+-+-+-+-+-+-+
+///      | EXTENDED PID  |
+///      +-+-+-+-+-+-+-+-+
+///   V: | SS            |   first packet of a key frame: one spatial layer
+///      +-+-+-+-+-+-+-+-+   with its width and height (§4.2.1)
+/// ```
+///
+/// The picture ID starts at a random value and increments per frame,
+/// wrapping at 15 bits.
+///
+/// ## Keyframes
+///
+/// [`parse_frame_header`](Self::parse_frame_header) reads the uncompressed
+/// header (VP9 bitstream spec §6.2) for the profile, frame type and, on key
+/// frames, the resolution advertised in the scalability structure.
+///
+/// ## SDP attributes (§6)
+///
+/// - `a=rtpmap:<pt> VP9/90000`
+/// - `a=fmtp:<pt> profile-id=<p>` once a key frame has been seen
+/// - `a=control:track1`
+///
+/// ## Marker bit
+///
+/// Set on the last packet of a picture (§4.1).
+#[derive(Debug)]
+pub struct Vp9Packetizer {
+    header: RtpHeader,
+    mtu: usize,
+    picture_id: u16,
+    profile: Option<u8>,
+}
+
+/// Fields of a VP9 uncompressed frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vp9FrameHeader {
+    /// Bitstream profile (0–3).
+    pub profile: u8,
+    /// `frame_type == KEY_FRAME`.
+    pub keyframe: bool,
+    /// Frame size, present on key frames only.
+    pub size: Option<(u16, u16)>,
+}
+
+impl Vp9Packetizer {
+    /// Create with explicit payload type and SSRC.
+    pub fn new(pt: u8, ssrc: u32) -> Self {
+        Self::from_header(RtpHeader::new(pt, ssrc))
+    }
+
+    /// Create with a random SSRC (RFC 3550 §8.1).
+    pub fn with_random_ssrc(pt: u8) -> Self {
+        Self::from_header(RtpHeader::with_random_ssrc(pt))
+    }
+
+    fn from_header(header: RtpHeader) -> Self {
+        Self {
+            header,
+            mtu: DEFAULT_MTU,
+            picture_id: rand::rng().random::<u16>() & 0x7fff,
+            profile: None,
+        }
+    }
+
+    /// Picture ID that will be sent with the next frame.
+    pub fn picture_id(&self) -> u16 {
+        self.picture_id
+    }
+
+    /// Parse the start of a VP9 uncompressed header (VP9 spec §6.2).
+    ///
+    /// Returns `None` if the frame marker or key frame sync code is wrong or
+    /// the data is truncated.
+    pub fn parse_frame_header(frame: &[u8]) -> Option<Vp9FrameHeader> {
+        let mut r = BitReader::new(frame);
+        if r.bits(2)? != 2 {
+            return None;
+        }
+        let profile_low = r.bits(1)?;
+        let profile = ((r.bits(1)? << 1) | profile_low) as u8;
+        if profile == 3 {
+            r.bits(1)?; // reserved_zero
+        }
+
+        let show_existing_frame = r.bits(1)? == 1;
+        if show_existing_frame {
+            return Some(Vp9FrameHeader {
+                profile,
+                keyframe: false,
+                size: None,
+            });
+        }
+
+        let keyframe = r.bits(1)? == 0;
+        r.bits(1)?; // show_frame
+        r.bits(1)?; // error_resilient_mode
+        if !keyframe {
+            return Some(Vp9FrameHeader {
+                profile,
+                keyframe,
+                size: None,
+            });
+        }
+
+        if r.bits(24)? != 0x49_83_42 {
+            return None;
+        }
+        // color_config()
+        if profile >= 2 {
+            r.bits(1)?; // ten_or_twelve_bit
+        }
+        let color_space = r.bits(3)?;
+        if color_space != 7 {
+            r.bits(1)?; // color_range
+            if profile == 1 || profile == 3 {
+                r.bits(3)?; // subsampling_x, subsampling_y, reserved_zero
+            }
+        } else if profile == 1 || profile == 3 {
+            r.bits(1)?; // reserved_zero
+        }
+        // frame_size(): 65536 cannot be signalled in the scalability
+        // structure, so treat it as malformed.
+        let width = (r.bits(16)? as u16).checked_add(1)?;
+        let height = (r.bits(16)? as u16).checked_add(1)?;
+
+        Some(Vp9FrameHeader {
+            profile,
+            keyframe,
+            size: Some((width, height)),
+        })
+    }
+}
+
+impl Packetizer for Vp9Packetizer {
+    fn packetize(&mut self, encoded_data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
+        if encoded_data.is_empty() {
+            return Vec::new();
+        }
+
+        let frame_header = Self::parse_frame_header(encoded_data);
+        if frame_header.is_none() {
+            tracing::warn!(
+                frame_bytes = encoded_data.len(),
+                "unparseable VP9 frame header, sending as inter frame"
+            );
+        }
+        let keyframe = frame_header.is_some_and(|h| h.keyframe);
+        if let Some(h) = frame_header.filter(|h| h.keyframe) {
+            if self.profile != Some(h.profile) {
+                tracing::debug!(profile = h.profile, size = ?h.size, "VP9 key frame profile captured");
+            }
+            self.profile = Some(h.profile);
+        }
+
+        // Scalability structure: N_S=0 (one layer), Y=1, G=0, then WIDTH/HEIGHT.
+        let ss = frame_header
+            .and_then(|h| h.size)
+            .filter(|_| keyframe)
+            .map(|(w, h)| {
+                let [w0, w1] = w.to_be_bytes();
+                let [h0, h1] = h.to_be_bytes();
+                [0x10, w0, w1, h0, h1]
+            });
+
+        let mut packets = Vec::new();
+        let mut rest = encoded_data;
+        while !rest.is_empty() {
+            let first = packets.is_empty();
+            let with_ss = first && ss.is_some();
+            let room = self.mtu - DESCRIPTOR_LEN - if with_ss { SS_LEN } else { 0 };
+            let (chunk, tail) = rest.split_at(rest.len().min(room));
+            rest = tail;
+            let last = rest.is_empty();
+
+            let mut flags = DESC_I;
+            if !keyframe {
+                flags |= DESC_P;
+            }
+            if first {
+                flags |= DESC_B;
+            }
+            if last {
+                flags |= DESC_E;
+            }
+            if with_ss {
+                flags |= DESC_V;
+            }
+
+            let hdr = self.header.write(last);
+            let mut packet = Vec::with_capacity(12 + DESCRIPTOR_LEN + SS_LEN + chunk.len());
+            packet.extend_from_slice(&hdr);
+            packet.extend_from_slice(&[
+                flags,
+                0x80 | (self.picture_id >> 8) as u8,
+                self.picture_id as u8,
+            ]);
+            if let Some(ss) = ss.filter(|_| with_ss) {
+                packet.extend_from_slice(&ss);
+            }
+            packet.extend_from_slice(chunk);
+            packets.push(packet);
+        }
+
+        self.picture_id = (self.picture_id + 1) & 0x7fff;
+        self.header.advance_timestamp(timestamp_increment);
+
+        tracing::trace!(
+            keyframe,
+            rtp_packets = packets.len(),
+            frame_bytes = encoded_data.len(),
+            seq = self.header.sequence(),
+            ts = self.header.timestamp(),
+            "VP9 frame packetized"
+        );
+
+        packets
+    }
+
+    fn is_keyframe(&self, encoded_data: &[u8]) -> bool {
+        Self::parse_frame_header(encoded_data).is_some_and(|h| h.keyframe)
+    }
+
+    fn codec_name(&self) -> &'static str {
+        "VP9"
+    }
+
+    /// 90 kHz clock rate (RFC 9628 §6).
+    fn clock_rate(&self) -> u32 {
+        90000
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.header.pt
+    }
+
+    fn sdp_attributes(&self) -> Vec<String> {
+        let pt = self.payload_type();
+        let mut attrs = vec![format!(
+            "a=rtpmap:{} {}/{}",
+            pt,
+            self.codec_name(),
+            self.clock_rate()
+        )];
+        if let Some(profile) = self.profile {
+            attrs.push(format!("a=fmtp:{} profile-id={}", pt, profile));
+        }
+        attrs.push("a=control:track1".to_string());
+        attrs
+    }
+
+    fn next_sequence(&self) -> u16 {
+        self.header.sequence()
+    }
+
+    fn ssrc(&self) -> Option<u32> {
+        Some(self.header.ssrc)
+    }
+
+    fn next_rtp_timestamp(&self) -> u32 {
+        self.header.timestamp() as u32
+    }
+
+    fn set_next_rtp_timestamp(&mut self, timestamp: u32) -> bool {
+        self.header.set_timestamp(timestamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Profile 0 key frame header, BT.601, 640x480, padded to `len`.
+    fn keyframe(len: usize) -> Vec<u8> {
+        // frame_marker=10 profile=00 show_existing=0 frame_type=0 show=1 err=0
+        let mut frame = vec![0b1000_0010, 0x49, 0x83, 0x42];
+        // color_space=001 color_range=0, then width-1=639, height-1=479
+        // packed MSB-first from bit 0 of byte 4
+        let bits: u64 = (0b0010u64 << 32) | (639u64 << 16) | 479;
+        let packed = bits << (64 - 36);
+        frame.extend_from_slice(&packed.to_be_bytes()[..5]);
+        frame.resize(len, 0xAB);
+        frame
+    }
+
+    /// Profile 0 inter frame header.
+    fn inter_frame(len: usize) -> Vec<u8> {
+        let mut frame = vec![0b1000_0110];
+        frame.resize(len, 0xCD);
+        frame
+    }
+
+    fn picture_id(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[13] & 0x7f, packet[14]])
+    }
+
+    #[test]
+    fn parses_keyframe_header() {
+        assert_eq!(
+            Vp9Packetizer::parse_frame_header(&keyframe(20)),
+            Some(Vp9FrameHeader {
+                profile: 0,
+                keyframe: true,
+                size: Some((640, 480)),
+            })
+        );
+        assert_eq!(
+            Vp9Packetizer::parse_frame_header(&inter_frame(4)),
+            Some(Vp9FrameHeader {
+                profile: 0,
+                keyframe: false,
+                size: None,
+            })
+        );
+        // Wrong frame marker
+        assert_eq!(Vp9Packetizer::parse_frame_header(&[0x00, 0x00]), None);
+        // width-1 = 0xFFFF: too wide for the scalability structure
+        let mut wide = keyframe(20);
+        wide[4] |= 0x0F;
+        wide[5] = 0xFF;
+        wide[6] |= 0xF0;
+        assert_eq!(Vp9Packetizer::parse_frame_header(&wide), None);
+    }
+
+    #[test]
+    fn keyframe_carries_scalability_structure() {
+        let mut p = Vp9Packetizer::new(98, 0x1234);
+        let frame = keyframe(100);
+        let packets = p.packetize(&frame, 3000);
+        assert_eq!(packets.len(), 1);
+        let pkt = &packets[0];
+        assert_eq!(pkt[12], DESC_I | DESC_B | DESC_E | DESC_V);
+        assert_eq!(&pkt[15..20], &[0x10, 0x02, 0x80, 0x01, 0xe0]);
+        assert_eq!(&pkt[20..], &frame[..]);
+        assert_eq!(pkt[1] & 0x80, 0x80);
+    }
+
+    #[test]
+    fn inter_frame_fragmented() {
+        let mut p = Vp9Packetizer::new(98, 0x1234);
+        let frame = inter_frame(3000);
+        let packets = p.packetize(&frame, 3000);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0][12], DESC_I | DESC_P | DESC_B);
+        assert_eq!(packets[1][12], DESC_I | DESC_P);
+        assert_eq!(packets[2][12], DESC_I | DESC_P | DESC_E);
+        assert_eq!(packets[0][1] & 0x80, 0);
+        assert_eq!(packets[2][1] & 0x80, 0x80);
+        assert!(packets.iter().all(|pkt| pkt.len() <= 12 + DEFAULT_MTU));
+
+        let payload: Vec<u8> = packets.iter().flat_map(|pkt| pkt[15..].to_vec()).collect();
+        assert_eq!(payload, frame);
+    }
+
+    #[test]
+    fn picture_id_increments() {
+        let mut p = Vp9Packetizer::new(98, 0x1234);
+        let first = p.packetize(&keyframe(20), 3000);
+        let second = p.packetize(&inter_frame(20), 3000);
+        assert_eq!(picture_id(&second[0]), (picture_id(&first[0]) + 1) & 0x7fff);
+    }
+
+    #[test]
+    fn sdp_profile_after_keyframe() {
+        let mut p = Vp9Packetizer::new(98, 0x1234);
+        assert_eq!(
+            p.sdp_attributes(),
+            vec!["a=rtpmap:98 VP9/90000", "a=control:track1"]
+        );
+        p.packetize(&keyframe(20), 3000);
+        assert_eq!(p.sdp_attributes()[1], "a=fmtp:98 profile-id=0");
+    }
+}
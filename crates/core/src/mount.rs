@@ -1,38 +1,207 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
+use bytes::Bytes;
 use parking_lot::{Mutex, RwLock};
 
-use crate::media::Packetizer;
+use crate::error::{Result, RtspError};
+use crate::hls::{HlsConfig, HlsStream};
+use crate::media::clock::{ClockReference, RtpClock};
+use crate::media::packet::RtpPacket;
+use crate::media::rtcp::{self, RtcpFeedback};
+use crate::media::{MediaType, Packetizer};
+use crate::playback::{MediaSource, Playback};
+use crate::record::{Recorder, RecordingConfig};
+use crate::send_queue::{SendCounters, SendQueueConfig, SendStats};
+use crate::transport::pacer::Pacing;
 
 pub const DEFAULT_MOUNT_PATH: &str = "/stream";
 
-/// A named stream endpoint (e.g. `/stream`, `/camera1`).
+/// Why a keyframe was requested from the encoder feeding a mount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyframeRequestReason {
+    /// A viewer sent an RTCP Picture Loss Indication (RFC 4585 §6.3.1).
+    PictureLoss,
+    /// A viewer sent an RTCP Full Intra Request (RFC 5104 §4.3.1).
+    FullIntraRequest,
+    /// A session transitioned into the Playing state and needs a decoder
+    /// refresh point to start rendering.
+    NewViewer,
+}
+
+impl fmt::Display for KeyframeRequestReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PictureLoss => write!(f, "pli"),
+            Self::FullIntraRequest => write!(f, "fir"),
+            Self::NewViewer => write!(f, "new-viewer"),
+        }
+    }
+}
+
+/// A keyframe request delivered to a mount's handler.
+#[derive(Debug, Clone)]
+pub struct KeyframeRequest {
+    /// Path of the mount the request targets.
+    pub mount_path: String,
+    /// What triggered the request.
+    pub reason: KeyframeRequestReason,
+    /// Session that triggered the request, when it could be attributed.
+    pub session_id: Option<String>,
+}
+
+/// Callback invoked when a mount's encoder should produce a keyframe.
 ///
-/// Owns a packetizer for its codec and tracks which sessions are subscribed.
-/// In the future, a mount may contain multiple tracks (video + audio).
-pub struct Mount {
-    path: String,
+/// Called from server threads (RTCP receiver, connection handlers), so it
+/// must be cheap and must not block — typically it just flags the encoder.
+pub type KeyframeRequestHandler = Arc<dyn Fn(&KeyframeRequest) + Send + Sync>;
+
+/// Rate limiting and trigger configuration for keyframe requests.
+#[derive(Debug, Clone)]
+pub struct KeyframeRequestPolicy {
+    /// Minimum time between two forwarded requests. Requests arriving sooner
+    /// are dropped, so a burst of PLIs from many viewers costs one keyframe.
+    pub min_interval: Duration,
+    /// Also request a keyframe when a session starts (or resumes) playing.
+    pub on_new_viewer: bool,
+}
+
+impl Default for KeyframeRequestPolicy {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(1),
+            on_new_viewer: true,
+        }
+    }
+}
+
+/// Handler, policy and rate-limit state for a mount's keyframe requests.
+#[derive(Default)]
+struct KeyframeRequests {
+    handler: Option<KeyframeRequestHandler>,
+    policy: KeyframeRequestPolicy,
+    last_forwarded: Option<Instant>,
+}
+
+/// One media stream within a mount (video, audio or metadata).
+///
+/// Each track owns a packetizer and an RTP clock, appears as its own `m=`
+/// section in the SDP, and is set up by clients through its control URL
+/// (`<mount>/track1`, `<mount>/track2`, ...).
+pub struct Track {
+    index: usize,
     packetizer: Mutex<Box<dyn Packetizer>>,
-    session_ids: RwLock<Vec<String>>,
+    /// PTS → RTP timestamp mapping for [`packetize_with_pts`](Self::packetize_with_pts).
+    clock: Mutex<RtpClock>,
+    /// SSRC of the first packet the track emitted, for packetizers that
+    /// don't report theirs.
+    ssrc: OnceLock<u32>,
+    /// Set once the packetizer was found to ignore PTS-derived timestamps,
+    /// which is logged once.
+    ignores_timestamps: AtomicBool,
 }
 
-impl Mount {
-    pub fn new(path: &str, packetizer: Box<dyn Packetizer>) -> Self {
+impl Track {
+    fn new(index: usize, packetizer: Box<dyn Packetizer>, clock: RtpClock) -> Self {
         Self {
-            path: path.to_string(),
+            index,
             packetizer: Mutex::new(packetizer),
-            session_ids: RwLock::new(Vec::new()),
+            clock: Mutex::new(clock),
+            ssrc: OnceLock::new(),
+            ignores_timestamps: AtomicBool::new(false),
         }
     }
 
-    pub fn path(&self) -> &str {
-        &self.path
+    /// Zero-based position of this track in the mount.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// SDP `a=control` value (`track1` for the first track).
+    pub fn control(&self) -> String {
+        format!("track{}", self.index + 1)
+    }
+
+    /// Packetize raw encoded data into RTP packets using this track's codec.
+    ///
+    /// Packets reference `data` rather than copying it where the codec
+    /// allows (see [`Packetizer::packetize_bytes`]); clone them freely to
+    /// send to several viewers.
+    pub fn packetize(&self, data: Bytes, timestamp_increment: u32) -> Vec<RtpPacket> {
+        let packets = self
+            .packetizer
+            .lock()
+            .packetize_bytes(data, timestamp_increment);
+        self.note_ssrc(&packets);
+        packets
+    }
+
+    /// Packetize a frame stamped with its presentation time.
+    ///
+    /// The PTS is mapped to the codec clock by the track's [`RtpClock`]
+    /// (random initial offset, wrapping), so dropped frames and variable
+    /// frame rates don't accumulate drift. Pass `dts` when frames arrive in
+    /// decode order with B-frame reordering; it keeps the wall-clock
+    /// reference monotonic. PTS and DTS must share one time base.
+    pub fn packetize_with_pts(
+        &self,
+        data: Bytes,
+        pts: Duration,
+        dts: Option<Duration>,
+    ) -> Vec<RtpPacket> {
+        let mut packetizer = self.packetizer.lock();
+        let rtp_timestamp = self.clock.lock().on_frame(pts, dts);
+        if !packetizer.set_next_rtp_timestamp(rtp_timestamp)
+            && !self.ignores_timestamps.swap(true, Ordering::Relaxed)
+        {
+            tracing::warn!(
+                track = %self.control(),
+                codec = packetizer.codec_name(),
+                "packetizer does not support setting RTP timestamps, \
+                 frames sent with a PTS keep increment-based timestamps"
+            );
+        }
+        let packets = packetizer.packetize_bytes(data, 0);
+        self.note_ssrc(&packets);
+        packets
+    }
+
+    fn note_ssrc(&self, packets: &[RtpPacket]) {
+        if self.ssrc.get().is_none()
+            && let Some(ssrc) = packets.first().and_then(RtpPacket::ssrc)
+        {
+            let _ = self.ssrc.set(ssrc);
+        }
+    }
+
+    /// Wall-clock anchor of the PTS-driven RTP clock (for RTCP sender reports).
+    ///
+    /// `None` until a frame has been sent with
+    /// [`packetize_with_pts`](Self::packetize_with_pts).
+    pub fn clock_reference(&self) -> Option<ClockReference> {
+        self.clock.lock().reference()
+    }
+
+    /// RTP timestamp [`packetize_with_pts`](Self::packetize_with_pts)
+    /// gives a frame with this PTS.
+    pub fn rtp_timestamp_for_pts(&self, pts: Duration) -> u32 {
+        self.clock.lock().rtp_timestamp(pts)
     }
 
-    /// Packetize raw encoded data into RTP packets using this mount's codec.
-    pub fn packetize(&self, data: &[u8], timestamp_increment: u32) -> Vec<Vec<u8>> {
-        self.packetizer.lock().packetize(data, timestamp_increment)
+    /// RTP timestamp corresponding to a wall-clock instant, extrapolated from
+    /// the [`clock_reference`](Self::clock_reference).
+    pub fn rtp_timestamp_at(&self, wall_time: SystemTime) -> Option<u32> {
+        self.clock.lock().rtp_timestamp_at(wall_time)
+    }
+
+    /// Whether decoding can start at this frame (see
+    /// [`Packetizer::is_keyframe`]).
+    pub fn is_keyframe(&self, data: &[u8]) -> bool {
+        self.packetizer.lock().is_keyframe(data)
     }
 
     /// RTP payload type from the underlying packetizer.
@@ -40,6 +209,16 @@ impl Mount {
         self.packetizer.lock().payload_type()
     }
 
+    /// Codec name from the underlying packetizer (`H264`, `opus`, ...).
+    pub fn codec_name(&self) -> &'static str {
+        self.packetizer.lock().codec_name()
+    }
+
+    /// SDP media type (`video`, `audio`, ...) from the underlying packetizer.
+    pub fn media_type(&self) -> MediaType {
+        self.packetizer.lock().media_type()
+    }
+
     /// SDP media-level attributes (delegated to packetizer).
     pub fn sdp_attributes(&self) -> Vec<String> {
         self.packetizer.lock().sdp_attributes()
@@ -55,10 +234,174 @@ impl Mount {
         self.packetizer.lock().next_sequence()
     }
 
+    /// SSRC of this track's RTP stream: the packetizer's, or else the one
+    /// of the first packet the track emitted; `None` until then.
+    pub fn ssrc(&self) -> Option<u32> {
+        self.packetizer
+            .lock()
+            .ssrc()
+            .or_else(|| self.ssrc.get().copied())
+    }
+
     /// Next RTP timestamp (for RTP-Info header).
     pub fn next_rtp_timestamp(&self) -> u32 {
         self.packetizer.lock().next_rtp_timestamp()
     }
+}
+
+/// A packetized frame handed to a mount's recording and HLS output.
+#[derive(Clone)]
+pub(crate) struct TappedFrame {
+    /// Mount track index.
+    pub track: usize,
+    pub data: Bytes,
+    /// RTP timestamp of the frame.
+    pub timestamp: u32,
+    /// PTS minus DTS, for frames sent in decode order with B-frames.
+    pub composition_offset: Duration,
+    pub arrival: Instant,
+}
+
+/// A named stream endpoint (e.g. `/stream`, `/camera1`).
+///
+/// Holds one or more [`Track`]s and tracks which sessions are subscribed.
+/// The first track is created with the mount; further tracks (audio,
+/// metadata) are added with [`add_track`](Self::add_track). The
+/// packetizing and RTP-state methods on `Mount` itself act on the first
+/// track.
+pub struct Mount {
+    path: String,
+    tracks: RwLock<Vec<Arc<Track>>>,
+    session_ids: RwLock<Vec<String>>,
+    keyframe_requests: Mutex<KeyframeRequests>,
+    pacing: RwLock<Option<Pacing>>,
+    send_queue: RwLock<Option<SendQueueConfig>>,
+    send_counters: SendCounters,
+    playback: RwLock<Option<Arc<Playback>>>,
+    recorder: RwLock<Option<Recorder>>,
+    hls: RwLock<Option<Arc<HlsStream>>>,
+}
+
+impl Mount {
+    pub fn new(path: &str, packetizer: Box<dyn Packetizer>) -> Self {
+        let clock = RtpClock::new(packetizer.clock_rate());
+        Self {
+            path: path.to_string(),
+            tracks: RwLock::new(vec![Arc::new(Track::new(0, packetizer, clock))]),
+            session_ids: RwLock::new(Vec::new()),
+            keyframe_requests: Mutex::new(KeyframeRequests::default()),
+            pacing: RwLock::new(None),
+            send_queue: RwLock::new(None),
+            send_counters: SendCounters::default(),
+            playback: RwLock::new(None),
+            recorder: RwLock::new(None),
+            hls: RwLock::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Add a track (e.g. audio or metadata) to this mount.
+    ///
+    /// A track with the same clock rate as the first track is aligned with
+    /// it: both PTS clocks share one RTP offset, and the new packetizer
+    /// starts at the first track's next timestamp. Frames sent to the two
+    /// tracks with the same PTS (or the same increments from the start)
+    /// therefore carry the same RTP timestamp, which is what ONVIF clients
+    /// use to match metadata to video frames.
+    pub fn add_track(&self, mut packetizer: Box<dyn Packetizer>) -> Arc<Track> {
+        let mut tracks = self.tracks.write();
+        let first = &tracks[0];
+        let clock_rate = packetizer.clock_rate();
+        let clock = if clock_rate == first.clock_rate() {
+            let _ = packetizer.set_next_rtp_timestamp(first.next_rtp_timestamp());
+            RtpClock::with_offset(clock_rate, first.clock.lock().offset())
+        } else {
+            RtpClock::new(clock_rate)
+        };
+
+        let track = Arc::new(Track::new(tracks.len(), packetizer, clock));
+        tracks.push(track.clone());
+        tracing::info!(
+            mount = %self.path,
+            control = %track.control(),
+            media = %track.media_type(),
+            "track added"
+        );
+        track
+    }
+
+    /// Track at a zero-based index.
+    pub fn track(&self, index: usize) -> Option<Arc<Track>> {
+        self.tracks.read().get(index).cloned()
+    }
+
+    /// All tracks, in SDP order.
+    pub fn tracks(&self) -> Vec<Arc<Track>> {
+        self.tracks.read().clone()
+    }
+
+    fn first_track(&self) -> Arc<Track> {
+        self.tracks.read()[0].clone()
+    }
+
+    /// Packetize raw encoded data into RTP packets using the first track's codec.
+    pub fn packetize(&self, data: Bytes, timestamp_increment: u32) -> Vec<RtpPacket> {
+        self.first_track().packetize(data, timestamp_increment)
+    }
+
+    /// Packetize a frame for the first track, stamped with its presentation
+    /// time (see [`Track::packetize_with_pts`]).
+    pub fn packetize_with_pts(
+        &self,
+        data: Bytes,
+        pts: Duration,
+        dts: Option<Duration>,
+    ) -> Vec<RtpPacket> {
+        self.first_track().packetize_with_pts(data, pts, dts)
+    }
+
+    /// Wall-clock anchor of the first track's PTS-driven RTP clock.
+    pub fn clock_reference(&self) -> Option<ClockReference> {
+        self.first_track().clock_reference()
+    }
+
+    /// First track's RTP timestamp corresponding to a wall-clock instant.
+    pub fn rtp_timestamp_at(&self, wall_time: SystemTime) -> Option<u32> {
+        self.first_track().rtp_timestamp_at(wall_time)
+    }
+
+    /// RTP payload type of the first track.
+    pub fn payload_type(&self) -> u8 {
+        self.first_track().payload_type()
+    }
+
+    /// SDP media type of the first track.
+    pub fn media_type(&self) -> MediaType {
+        self.first_track().media_type()
+    }
+
+    /// SDP media-level attributes of the first track.
+    pub fn sdp_attributes(&self) -> Vec<String> {
+        self.first_track().sdp_attributes()
+    }
+
+    /// Clock rate of the first track in Hz.
+    pub fn clock_rate(&self) -> u32 {
+        self.first_track().clock_rate()
+    }
+
+    /// Next RTP sequence number of the first track.
+    pub fn next_sequence(&self) -> u16 {
+        self.first_track().next_sequence()
+    }
+
+    /// Next RTP timestamp of the first track.
+    pub fn next_rtp_timestamp(&self) -> u32 {
+        self.first_track().next_rtp_timestamp()
+    }
 
     /// Subscribe a session to this mount (called during SETUP).
     pub fn subscribe(&self, session_id: &str) {
@@ -82,6 +425,209 @@ impl Mount {
     pub fn subscribed_session_ids(&self) -> Vec<String> {
         self.session_ids.read().clone()
     }
+
+    /// Install the callback that asks this mount's encoder for a keyframe.
+    ///
+    /// Invoked for RTCP PLI/FIR from viewers and, depending on the
+    /// [`KeyframeRequestPolicy`], when a session starts playing.
+    pub fn set_keyframe_request_handler<F>(&self, handler: F)
+    where
+        F: Fn(&KeyframeRequest) + Send + Sync + 'static,
+    {
+        self.keyframe_requests.lock().handler = Some(Arc::new(handler));
+    }
+
+    /// Remove the keyframe request callback.
+    pub fn clear_keyframe_request_handler(&self) {
+        self.keyframe_requests.lock().handler = None;
+    }
+
+    /// Replace the rate limiting / trigger policy for keyframe requests.
+    pub fn set_keyframe_request_policy(&self, policy: KeyframeRequestPolicy) {
+        self.keyframe_requests.lock().policy = policy;
+    }
+
+    /// Current keyframe request policy.
+    pub fn keyframe_request_policy(&self) -> KeyframeRequestPolicy {
+        self.keyframe_requests.lock().policy.clone()
+    }
+
+    /// Spread this mount's packets over time instead of sending each frame
+    /// in one burst (`None`, the default). Applies to all tracks; see
+    /// [`Pacing`].
+    pub fn set_pacing(&self, pacing: Option<Pacing>) {
+        *self.pacing.write() = pacing;
+    }
+
+    /// Current pacing policy.
+    pub fn pacing(&self) -> Option<Pacing> {
+        *self.pacing.read()
+    }
+
+    /// Send this mount's frames from a dedicated thread through a bounded
+    /// queue, so [`Server::send_frame_to`](crate::Server::send_frame_to)
+    /// and friends return without touching the network. `None` (the
+    /// default) sends on the caller's thread.
+    pub fn set_send_queue(&self, config: Option<SendQueueConfig>) {
+        *self.send_queue.write() = config;
+    }
+
+    /// Current send queue configuration.
+    pub fn send_queue(&self) -> Option<SendQueueConfig> {
+        *self.send_queue.read()
+    }
+
+    /// Frames sent, dropped by the send queue, and currently queued.
+    pub fn send_stats(&self) -> SendStats {
+        self.send_counters.snapshot()
+    }
+
+    pub(crate) fn send_counters(&self) -> &SendCounters {
+        &self.send_counters
+    }
+
+    /// Make this a seekable mount playing `source` (see [`crate::playback`]).
+    ///
+    /// Must be called before the server starts, which starts the mount's
+    /// player thread. Frames come from the source; don't also send frames
+    /// to the mount.
+    pub fn set_source(&self, source: Box<dyn MediaSource>) {
+        *self.playback.write() = Some(Arc::new(Playback::new(source)));
+    }
+
+    /// Playhead of a seekable mount; `None` for live mounts.
+    pub fn playback(&self) -> Option<Arc<Playback>> {
+        self.playback.read().clone()
+    }
+
+    /// Start writing the mount's H.264, H.265 and AAC tracks to fragmented
+    /// MP4 files (see [`crate::record`]). Tracks added afterwards are not
+    /// recorded.
+    ///
+    /// Fails with [`RtspError::AlreadyRecording`] if a recording is
+    /// running, and with [`RtspError::Recording`] if no track can be
+    /// recorded.
+    pub fn start_recording(&self, config: RecordingConfig) -> Result<()> {
+        let mut recorder = self.recorder.write();
+        if recorder.is_some() {
+            return Err(RtspError::AlreadyRecording(self.path.clone()));
+        }
+        *recorder = Some(Recorder::start(&self.path, &self.tracks(), config)?);
+        Ok(())
+    }
+
+    /// Stop recording, once the frames already tapped are written. Returns
+    /// the segment files written, or the error that stopped the recording
+    /// early.
+    pub fn stop_recording(&self) -> Result<Vec<PathBuf>> {
+        let recorder = self.recorder.write().take();
+        recorder
+            .ok_or_else(|| RtspError::NotRecording(self.path.clone()))?
+            .finish()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.read().is_some()
+    }
+
+    /// The mount's HLS output, started on the first request (see
+    /// [`crate::hls`]).
+    pub(crate) fn hls_stream(&self, config: &HlsConfig) -> Result<Arc<HlsStream>> {
+        if let Some(hls) = self.hls.read().as_ref() {
+            return Ok(hls.clone());
+        }
+        let mut slot = self.hls.write();
+        if let Some(hls) = slot.as_ref() {
+            return Ok(hls.clone());
+        }
+        let hls = Arc::new(HlsStream::new(&self.path, &self.tracks(), config)?);
+        tracing::info!(mount = %self.path, "HLS segmenting started");
+        *slot = Some(hls.clone());
+        Ok(hls)
+    }
+
+    pub(crate) fn hls(&self) -> Option<Arc<HlsStream>> {
+        self.hls.read().clone()
+    }
+
+    /// Stop the HLS output, waking requests waiting on it.
+    pub(crate) fn stop_hls(&self) {
+        if let Some(hls) = self.hls.write().take() {
+            hls.close();
+        }
+    }
+
+    /// Whether packetized frames are tapped, by a recording or HLS.
+    pub(crate) fn is_tapped(&self) -> bool {
+        self.recorder.read().is_some() || self.hls.read().is_some()
+    }
+
+    /// Hand a packetized frame to the recording and the HLS output, if
+    /// running. Its RTP timestamp is read from `packets`.
+    pub(crate) fn tap(
+        &self,
+        track: &Track,
+        data: Bytes,
+        packets: &[RtpPacket],
+        composition_offset: Duration,
+    ) {
+        let Some(timestamp) = packets.first().and_then(RtpPacket::timestamp) else {
+            return;
+        };
+        let frame = TappedFrame {
+            track: track.index(),
+            data,
+            timestamp,
+            composition_offset,
+            arrival: Instant::now(),
+        };
+        if let Some(hls) = self.hls() {
+            hls.push(&frame);
+        }
+        if let Some(recorder) = self.recorder.read().as_ref() {
+            recorder.push(track, frame);
+        }
+    }
+
+    /// Forward a keyframe request to the handler, subject to the policy.
+    ///
+    /// Returns `true` if the handler was invoked. Requests are dropped when
+    /// no handler is installed, when `reason` is
+    /// [`NewViewer`](KeyframeRequestReason::NewViewer) and the policy disables
+    /// it, or when the previous request was forwarded less than
+    /// `min_interval` ago.
+    pub fn request_keyframe(
+        &self,
+        reason: KeyframeRequestReason,
+        session_id: Option<&str>,
+    ) -> bool {
+        let handler = {
+            let mut state = self.keyframe_requests.lock();
+            let Some(handler) = state.handler.clone() else {
+                return false;
+            };
+            if reason == KeyframeRequestReason::NewViewer && !state.policy.on_new_viewer {
+                return false;
+            }
+            let now = Instant::now();
+            if let Some(last) = state.last_forwarded
+                && now.duration_since(last) < state.policy.min_interval
+            {
+                tracing::trace!(mount = %self.path, %reason, "keyframe request rate limited");
+                return false;
+            }
+            state.last_forwarded = Some(now);
+            handler
+        };
+
+        tracing::debug!(mount = %self.path, %reason, session_id, "keyframe requested");
+        handler(&KeyframeRequest {
+            mount_path: self.path.clone(),
+            reason,
+            session_id: session_id.map(str::to_string),
+        });
+        true
+    }
 }
 
 /// Registry of named mount points, keyed by path.
@@ -144,6 +690,34 @@ impl MountRegistry {
         })
     }
 
+    /// All registered mounts, in no particular order.
+    pub fn all(&self) -> Vec<Arc<Mount>> {
+        self.mounts.read().values().cloned().collect()
+    }
+
+    /// Find the mount a session is subscribed to.
+    pub fn find_by_session(&self, session_id: &str) -> Option<Arc<Mount>> {
+        self.mounts
+            .read()
+            .values()
+            .find(|m| m.session_ids.read().iter().any(|id| id == session_id))
+            .cloned()
+    }
+
+    /// Forward PLI/FIR feedback in an RTCP packet from a session to the
+    /// mount it watches.
+    pub fn forward_rtcp_feedback(&self, session_id: &str, packet: &[u8]) {
+        for feedback in rtcp::parse_feedback(packet) {
+            let reason = match feedback {
+                RtcpFeedback::PictureLoss { .. } => KeyframeRequestReason::PictureLoss,
+                RtcpFeedback::FullIntraRequest { .. } => KeyframeRequestReason::FullIntraRequest,
+            };
+            if let Some(mount) = self.find_by_session(session_id) {
+                mount.request_keyframe(reason, Some(session_id));
+            }
+        }
+    }
+
     /// Unsubscribe a session from all mounts (used during disconnect cleanup).
     pub fn unsubscribe_all(&self, session_id: &str) {
         let mounts = self.mounts.read();
@@ -151,6 +725,13 @@ impl MountRegistry {
             mount.unsubscribe(session_id);
         }
     }
+
+    /// Unsubscribe every session from every mount (used on server shutdown).
+    pub fn clear_subscriptions(&self) {
+        for mount in self.mounts.read().values() {
+            mount.session_ids.write().clear();
+        }
+    }
 }
 
 impl Default for MountRegistry {
@@ -189,6 +770,29 @@ pub fn extract_mount_path(uri: &str) -> &str {
     }
 }
 
+/// Extract the zero-based track index from an RTSP URI's control suffix.
+///
+/// `rtsp://host:8554/stream/track2` → `Some(1)`
+/// `rtsp://host:8554/stream`        → `None` (aggregate URL)
+pub fn extract_track_index(uri: &str) -> Option<usize> {
+    let (_, suffix) = uri.rsplit_once("/track")?;
+    match suffix.parse::<usize>() {
+        Ok(n) if n >= 1 => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// Control URL of a track, given any URL of its mount.
+///
+/// `(rtsp://host:8554/stream/track1, 1)` → `rtsp://host:8554/stream/track2`
+pub fn track_uri(uri: &str, index: usize) -> String {
+    let base = match uri.rfind("/track") {
+        Some(pos) if extract_track_index(uri).is_some() => &uri[..pos],
+        _ => uri.trim_end_matches('/'),
+    };
+    format!("{}/track{}", base, index + 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -235,6 +839,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extract_track_indices() {
+        assert_eq!(extract_track_index("rtsp://h:8554/stream/track1"), Some(0));
+        assert_eq!(extract_track_index("rtsp://h:8554/stream/track2"), Some(1));
+        assert_eq!(extract_track_index("rtsp://h:8554/stream"), None);
+        assert_eq!(extract_track_index("rtsp://h:8554/stream/track0"), None);
+        assert_eq!(
+            track_uri("rtsp://h:8554/stream/track1", 1),
+            "rtsp://h:8554/stream/track2"
+        );
+        assert_eq!(
+            track_uri("rtsp://h:8554/stream/", 0),
+            "rtsp://h:8554/stream/track1"
+        );
+    }
+
+    /// A packetizer implementing only the required trait methods, like
+    /// one written outside this crate.
+    struct Minimal;
+
+    impl Packetizer for Minimal {
+        fn packetize(&mut self, data: &[u8], _: u32) -> Vec<Vec<u8>> {
+            let header = [0x80, 96, 0, 1, 0, 0, 0, 0, 0xAB, 0xCD, 0, 1];
+            vec![[&header[..], data].concat()]
+        }
+        fn codec_name(&self) -> &'static str {
+            "minimal"
+        }
+        fn clock_rate(&self) -> u32 {
+            90000
+        }
+        fn payload_type(&self) -> u8 {
+            96
+        }
+        fn sdp_attributes(&self) -> Vec<String> {
+            Vec::new()
+        }
+        fn next_sequence(&self) -> u16 {
+            1
+        }
+        fn next_rtp_timestamp(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn track_learns_ssrc_from_its_first_packet() {
+        let mount = Mount::new("/ext", Box::new(Minimal));
+        let track = mount.first_track();
+        assert_eq!(track.ssrc(), None);
+        // PTS-driven sending still works; the timestamp is left as is.
+        let packets = track.packetize_with_pts(Bytes::from_static(&[1]), Duration::ZERO, None);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(track.ssrc(), Some(0xABCD_0001));
+        assert!(track.ignores_timestamps.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn added_track_is_timestamp_aligned() {
+        let mount = Mount::new(
+            "/cam",
+            Box::new(crate::media::h264::H264Packetizer::new(96, 0x1234)),
+        );
+        let metadata = mount.add_track(Box::new(crate::media::h264::H264Packetizer::new(
+            97, 0x5678,
+        )));
+        assert_eq!(metadata.index(), 1);
+        assert_eq!(metadata.control(), "track2");
+        assert_eq!(mount.tracks().len(), 2);
+        assert_eq!(metadata.next_rtp_timestamp(), mount.next_rtp_timestamp());
+
+        let frame = Bytes::from_static(&[0, 0, 0, 1, 0x65, 0xAA]);
+        let rtp_ts = |p: &RtpPacket| u32::from_be_bytes(p.header()[4..8].try_into().unwrap());
+        let pts = Duration::from_millis(1234);
+        let video = mount.packetize_with_pts(frame.clone(), pts, None);
+        let meta = metadata.packetize_with_pts(frame, pts, None);
+        assert_eq!(rtp_ts(&video[0]), rtp_ts(&meta[0]));
+    }
+
     #[test]
     fn subscribe_unsubscribe() {
         let mount = Mount::new(
@@ -348,4 +1031,93 @@ mod tests {
                 .is_empty()
         );
     }
+
+    #[test]
+    fn packetize_with_pts_sets_rtp_timestamp() {
+        let mount = Mount::new(
+            "/test",
+            Box::new(crate::media::h264::H264Packetizer::new(96, 0x1234)),
+        );
+        let frame = Bytes::from_static(&[0, 0, 0, 1, 0x65, 0xAA, 0xBB]);
+        let rtp_ts = |p: &RtpPacket| u32::from_be_bytes(p.header()[4..8].try_into().unwrap());
+
+        let first = mount.packetize_with_pts(frame.clone(), Duration::from_millis(1000), None);
+        // A dropped frame (skipped 40 ms) must not shift later timestamps.
+        let second = mount.packetize_with_pts(frame.clone(), Duration::from_millis(1080), None);
+        assert_eq!(
+            rtp_ts(&second[0]).wrapping_sub(rtp_ts(&first[0])),
+            7200,
+            "80 ms at 90 kHz"
+        );
+
+        // B-frame: presented between the two, sent after them.
+        let b = mount.packetize_with_pts(
+            frame,
+            Duration::from_millis(1040),
+            Some(Duration::from_millis(1080)),
+        );
+        assert_eq!(rtp_ts(&b[0]).wrapping_sub(rtp_ts(&first[0])), 3600);
+        assert_eq!(
+            mount.clock_reference().unwrap().media_time,
+            Duration::from_millis(1080)
+        );
+    }
+
+    fn keyframe_mount(policy: KeyframeRequestPolicy) -> (Mount, Arc<Mutex<Vec<KeyframeRequest>>>) {
+        let mount = Mount::new(
+            "/cam",
+            Box::new(crate::media::h264::H264Packetizer::new(96, 0x1234)),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        mount.set_keyframe_request_handler(move |req| sink.lock().push(req.clone()));
+        mount.set_keyframe_request_policy(policy);
+        (mount, seen)
+    }
+
+    #[test]
+    fn keyframe_request_without_handler_is_dropped() {
+        let mount = Mount::new(
+            "/cam",
+            Box::new(crate::media::h264::H264Packetizer::new(96, 0x1234)),
+        );
+        assert!(!mount.request_keyframe(KeyframeRequestReason::PictureLoss, None));
+    }
+
+    #[test]
+    fn keyframe_requests_are_rate_limited() {
+        let (mount, seen) = keyframe_mount(KeyframeRequestPolicy {
+            min_interval: Duration::from_secs(60),
+            on_new_viewer: true,
+        });
+        assert!(mount.request_keyframe(KeyframeRequestReason::PictureLoss, Some("s1")));
+        assert!(!mount.request_keyframe(KeyframeRequestReason::FullIntraRequest, Some("s2")));
+
+        let seen = seen.lock();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].mount_path, "/cam");
+        assert_eq!(seen[0].reason, KeyframeRequestReason::PictureLoss);
+        assert_eq!(seen[0].session_id.as_deref(), Some("s1"));
+    }
+
+    #[test]
+    fn keyframe_request_zero_interval_forwards_all() {
+        let (mount, seen) = keyframe_mount(KeyframeRequestPolicy {
+            min_interval: Duration::ZERO,
+            on_new_viewer: true,
+        });
+        mount.request_keyframe(KeyframeRequestReason::PictureLoss, None);
+        mount.request_keyframe(KeyframeRequestReason::PictureLoss, None);
+        assert_eq!(seen.lock().len(), 2);
+    }
+
+    #[test]
+    fn new_viewer_requests_respect_policy() {
+        let (mount, seen) = keyframe_mount(KeyframeRequestPolicy {
+            min_interval: Duration::ZERO,
+            on_new_viewer: false,
+        });
+        assert!(!mount.request_keyframe(KeyframeRequestReason::NewViewer, Some("s1")));
+        assert!(seen.lock().is_empty());
+    }
 }
@@ -0,0 +1,304 @@
+//! Raw H.264/H.265 Annex B streams as a [`MediaSource`].
+//!
+//! The stream is read into memory and split into access units (ITU-T
+//! H.264 §7.4.1.2.3, H.265 §7.4.2.4.4): a new access unit starts at an
+//! access unit delimiter, at a parameter set or SEI after a slice, or at a
+//! slice that is the first of its picture (`first_mb_in_slice == 0`,
+//! `first_slice_segment_in_pic_flag`). Frames are spaced by a fixed frame
+//! rate since the stream has no timing of its own.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use super::file::{TrackCodec, VideoCodec};
+use super::{MediaSource, SourceFrame};
+use crate::error::{Result, RtspError};
+use crate::media::h264::H264Packetizer;
+
+/// Frame rate assumed for Annex B streams.
+pub const DEFAULT_FRAME_RATE: f64 = 30.0;
+
+#[derive(Debug, Clone)]
+struct AccessUnit {
+    range: Range<usize>,
+    keyframe: bool,
+}
+
+/// An in-memory H.264 or H.265 Annex B stream, played at a fixed frame
+/// rate.
+pub struct AnnexBSource {
+    data: Bytes,
+    track: TrackCodec,
+    units: Vec<AccessUnit>,
+    frame_duration: Duration,
+    next: usize,
+}
+
+impl AnnexBSource {
+    /// Index the access units of `data`. Fails with
+    /// [`RtspError::InvalidMediaFile`] if it holds no slices.
+    pub fn new(data: impl Into<Bytes>, codec: VideoCodec) -> Result<Self> {
+        let data = data.into();
+        let (units, parameter_sets) = split_access_units(&data, codec);
+        if units.is_empty() {
+            return Err(RtspError::InvalidMediaFile(format!(
+                "no {codec:?} frames in Annex B stream"
+            )));
+        }
+        tracing::debug!(
+            ?codec,
+            frames = units.len(),
+            keyframes = units.iter().filter(|u| u.keyframe).count(),
+            "Annex B stream indexed"
+        );
+        Ok(Self {
+            data,
+            track: TrackCodec::Video {
+                codec,
+                parameter_sets,
+            },
+            units,
+            frame_duration: Duration::from_secs_f64(1.0 / DEFAULT_FRAME_RATE),
+            next: 0,
+        })
+    }
+
+    /// Play at `fps` frames per second (default 30). Non-positive rates
+    /// are ignored.
+    pub fn with_frame_rate(mut self, fps: f64) -> Self {
+        if fps.is_finite() && fps > 0.0 {
+            self.frame_duration = Duration::from_secs_f64(1.0 / fps);
+        }
+        self
+    }
+
+    /// Guess the codec of a stream from its first NAL unit: H.265 if it
+    /// reads as a VPS, SPS, PPS, AUD or SEI with an H.265 header, H.264
+    /// otherwise.
+    pub fn sniff_codec(data: &[u8]) -> VideoCodec {
+        let first = H264Packetizer::nal_unit_ranges(data)
+            .into_iter()
+            .next()
+            .map(|range| &data[range]);
+        match first {
+            Some(nal)
+                if nal.len() > 2
+                    && nal[1] == 0x01
+                    && matches!(VideoCodec::H265.nal_type(nal), 32..=35 | 39) =>
+            {
+                VideoCodec::H265
+            }
+            _ => VideoCodec::H264,
+        }
+    }
+
+    /// The stream's codec and the first parameter sets found in it.
+    pub fn codec(&self) -> TrackCodec {
+        self.track.clone()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.units.len()
+    }
+
+    fn pts(&self, index: usize) -> Duration {
+        self.frame_duration * index as u32
+    }
+}
+
+impl MediaSource for AnnexBSource {
+    fn duration(&self) -> Option<Duration> {
+        Some(self.pts(self.units.len()))
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<Duration> {
+        let index = (position.as_nanos() / self.frame_duration.as_nanos().max(1)) as usize;
+        let index = index.min(self.units.len() - 1);
+        self.next = self.units[..=index]
+            .iter()
+            .rposition(|unit| unit.keyframe)
+            .unwrap_or(0);
+        Ok(self.pts(self.next))
+    }
+
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let Some(unit) = self.units.get(self.next) else {
+            return Ok(None);
+        };
+        let mut data = self.data.slice(unit.range.clone());
+        if unit.keyframe {
+            data = self.track.with_parameter_sets(data);
+        }
+        let frame = SourceFrame {
+            track: 0,
+            data,
+            pts: self.pts(self.next),
+            dts: None,
+            keyframe: unit.keyframe,
+        };
+        self.next += 1;
+        Ok(Some(frame))
+    }
+}
+
+/// Access units of an Annex B stream (with their start codes), and the
+/// first parameter set of each type.
+fn split_access_units(data: &[u8], codec: VideoCodec) -> (Vec<AccessUnit>, Vec<Vec<u8>>) {
+    let mut units = Vec::new();
+    let mut parameter_sets: Vec<Vec<u8>> = Vec::new();
+    let mut start = None;
+    let mut has_vcl = false;
+    let mut keyframe = false;
+
+    for range in H264Packetizer::nal_unit_ranges(data) {
+        let nal = &data[range.clone()];
+        let nal_type = codec.nal_type(nal);
+        let is_vcl = codec.is_vcl(nal_type);
+
+        // Include the start code, and the leading zero of a 4-byte one.
+        let mut nal_start = range.start - 3;
+        if nal_start > 0 && data[nal_start - 1] == 0 {
+            nal_start -= 1;
+        }
+        let first_slice = match codec {
+            VideoCodec::H264 => nal.get(1).is_some_and(|b| b & 0x80 != 0),
+            VideoCodec::H265 => nal.get(2).is_some_and(|b| b & 0x80 != 0),
+        };
+        let begins_unit = match codec {
+            VideoCodec::H264 => matches!(nal_type, 6..=9 | 14..=18),
+            VideoCodec::H265 => matches!(nal_type, 32..=35 | 39 | 41..=44 | 48..=55),
+        } || (is_vcl && first_slice);
+
+        if has_vcl && begins_unit {
+            if let Some(start) = start {
+                units.push(AccessUnit {
+                    range: start..nal_start,
+                    keyframe,
+                });
+            }
+            start = None;
+            has_vcl = false;
+            keyframe = false;
+        }
+        start.get_or_insert(nal_start);
+        has_vcl |= is_vcl;
+        keyframe |= codec.is_keyframe(nal_type);
+
+        if codec.is_parameter_set(nal_type)
+            && !parameter_sets
+                .iter()
+                .any(|set| codec.nal_type(set) == nal_type)
+        {
+            parameter_sets.push(nal.to_vec());
+        }
+    }
+    if let (Some(start), true) = (start, has_vcl) {
+        units.push(AccessUnit {
+            range: start..data.len(),
+            keyframe,
+        });
+    }
+    (units, parameter_sets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+        nals.iter()
+            .flat_map(|nal| [&[0u8, 0, 0, 1][..], nal].concat())
+            .collect()
+    }
+
+    /// SPS, PPS, then `gop` frames per GOP (IDR + P slices), two GOPs, the
+    /// second IDR without parameter sets in front.
+    fn h264_stream(gop: usize) -> Vec<u8> {
+        let mut nals: Vec<&[u8]> = vec![&[0x67, 0x42, 0x00, 0x1e], &[0x68, 0xce]];
+        for _ in 0..2 {
+            nals.push(&[0x65, 0x88, 0x80]);
+            // A second slice of the same picture (first_mb_in_slice != 0).
+            nals.push(&[0x65, 0x40, 0x80]);
+            nals.extend(std::iter::repeat_n(&[0x41u8, 0x9a][..], gop - 1));
+        }
+        annex_b(&nals)
+    }
+
+    #[test]
+    fn splits_h264_access_units() {
+        let data = h264_stream(3);
+        let mut source = AnnexBSource::new(data, VideoCodec::H264)
+            .unwrap()
+            .with_frame_rate(10.0);
+        assert_eq!(source.frame_count(), 6);
+        assert_eq!(source.duration(), Some(Duration::from_millis(600)));
+
+        let first = source.next_frame().unwrap().unwrap();
+        assert!(first.keyframe);
+        assert_eq!(
+            &first.data[..],
+            annex_b(&[
+                &[0x67, 0x42, 0x00, 0x1e],
+                &[0x68, 0xce],
+                &[0x65, 0x88, 0x80],
+                &[0x65, 0x40, 0x80]
+            ])
+        );
+        let second = source.next_frame().unwrap().unwrap();
+        assert!(!second.keyframe);
+        assert_eq!(second.pts, Duration::from_millis(100));
+        assert_eq!(&second.data[..], annex_b(&[&[0x41, 0x9a]]));
+    }
+
+    #[test]
+    fn seeks_to_the_previous_keyframe() {
+        let mut source = AnnexBSource::new(h264_stream(3), VideoCodec::H264)
+            .unwrap()
+            .with_frame_rate(10.0);
+        assert_eq!(
+            source.seek(Duration::from_millis(550)).unwrap(),
+            Duration::from_millis(300)
+        );
+        let frame = source.next_frame().unwrap().unwrap();
+        assert!(frame.keyframe);
+        assert!(
+            frame.data.starts_with(&[0, 0, 0, 1, 0x67]),
+            "parameter sets prepended"
+        );
+        assert_eq!(
+            source.seek(Duration::from_secs(60)).unwrap(),
+            Duration::from_millis(300)
+        );
+        assert_eq!(source.seek(Duration::ZERO).unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn splits_h265_access_units() {
+        let data = annex_b(&[
+            &[0x40, 0x01, 0x0c],
+            &[0x42, 0x01, 0x01],
+            &[0x44, 0x01, 0xc1],
+            &[0x26, 0x01, 0x80],
+            &[0x02, 0x01, 0x80],
+            &[0x02, 0x01, 0x80],
+        ]);
+        assert_eq!(AnnexBSource::sniff_codec(&data), VideoCodec::H265);
+        let source = AnnexBSource::new(data, VideoCodec::H265).unwrap();
+        assert_eq!(source.frame_count(), 3);
+        let TrackCodec::Video { parameter_sets, .. } = source.codec() else {
+            panic!("video track");
+        };
+        assert_eq!(parameter_sets.len(), 3);
+        assert_eq!(AnnexBSource::sniff_codec(&h264_stream(2)), VideoCodec::H264);
+    }
+
+    #[test]
+    fn rejects_streams_without_slices() {
+        assert!(matches!(
+            AnnexBSource::new(annex_b(&[&[0x67, 0x42]]), VideoCodec::H264),
+            Err(RtspError::InvalidMediaFile(_))
+        ));
+    }
+}
@@ -0,0 +1,335 @@
+//! Media files as seekable mount sources.
+//!
+//! [`MediaFile::open`] recognises two kinds of file:
+//!
+//! - **MP4/MOV** (ISO/IEC 14496-12 / QuickTime), with `avc1`/`avc3`,
+//!   `hvc1`/`hev1` and `mp4a` tracks, read by [`Mp4Source`]. Samples keep
+//!   their timing, including B-frame composition offsets.
+//! - **Raw H.264/H.265 Annex B** elementary streams (`.h264`, `.264`,
+//!   `.h265`, `.hevc`, ...), read by [`AnnexBSource`]. They carry no
+//!   timing, so frames are spaced by a fixed frame rate.
+//!
+//! Each track of the file gets a packetizer, advertised in the SDP with
+//! the codec configuration from the file; video tracks come first.
+//!
+//! ```no_run
+//! use rtsp::Server;
+//! use rtsp::playback::MediaFile;
+//!
+//! let mut server = Server::new("0.0.0.0:8554");
+//! let clip = MediaFile::open("clip.mp4")?.with_looping(true);
+//! server.add_file_mount("/clip", clip);
+//! server.start()?;
+//! # Ok::<(), rtsp::RtspError>(())
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+
+use super::MediaSource;
+use super::annexb::AnnexBSource;
+use super::mp4::Mp4Source;
+use crate::error::{Result, RtspError};
+use crate::media::Packetizer;
+use crate::media::aac::{AacConfig, AacPacketizer};
+use crate::media::h264::H264Packetizer;
+use crate::media::h265::H265Packetizer;
+use crate::mount::{Mount, MountRegistry};
+
+/// First dynamic payload type given to a file's tracks, in track order.
+const FIRST_PAYLOAD_TYPE: u8 = 96;
+
+/// Video codec of an Annex B stream or MP4 track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    H265,
+}
+
+impl VideoCodec {
+    /// NAL unit type of a NAL (without start code).
+    pub(crate) fn nal_type(self, nal: &[u8]) -> u8 {
+        match (self, nal.first()) {
+            (_, None) => 0,
+            (Self::H264, Some(b)) => b & 0x1f,
+            (Self::H265, Some(b)) => (b >> 1) & 0x3f,
+        }
+    }
+
+    pub(crate) fn is_vcl(self, nal_type: u8) -> bool {
+        match self {
+            Self::H264 => (1..=5).contains(&nal_type),
+            Self::H265 => nal_type <= 31,
+        }
+    }
+
+    /// IDR (H.264) or IRAP (H.265) slice.
+    pub(crate) fn is_keyframe(self, nal_type: u8) -> bool {
+        match self {
+            Self::H264 => nal_type == 5,
+            Self::H265 => (16..=23).contains(&nal_type),
+        }
+    }
+
+    /// VPS, SPS or PPS.
+    pub(crate) fn is_parameter_set(self, nal_type: u8) -> bool {
+        match self {
+            Self::H264 => nal_type == 7 || nal_type == 8,
+            Self::H265 => (32..=34).contains(&nal_type),
+        }
+    }
+}
+
+/// Codec and configuration of one track of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackCodec {
+    /// H.264 or H.265 video, with the parameter sets (VPS, SPS, PPS as
+    /// applicable, without start codes) found in the file.
+    Video {
+        codec: VideoCodec,
+        parameter_sets: Vec<Vec<u8>>,
+    },
+    /// AAC audio.
+    Aac(AacConfig),
+}
+
+impl TrackCodec {
+    /// A packetizer for this track with a random SSRC, advertising the
+    /// file's codec configuration in the SDP.
+    pub fn packetizer(&self, pt: u8) -> Box<dyn Packetizer> {
+        match self {
+            Self::Video {
+                codec,
+                parameter_sets,
+            } => {
+                let find = |nal_type: u8| {
+                    parameter_sets
+                        .iter()
+                        .find(|nal| codec.nal_type(nal) == nal_type)
+                        .cloned()
+                };
+                match codec {
+                    VideoCodec::H264 => {
+                        let packetizer = H264Packetizer::with_random_ssrc(pt);
+                        match (find(7), find(8)) {
+                            (Some(sps), Some(pps)) => {
+                                Box::new(packetizer.with_parameter_sets(sps, pps))
+                            }
+                            _ => Box::new(packetizer),
+                        }
+                    }
+                    VideoCodec::H265 => {
+                        let packetizer = H265Packetizer::with_random_ssrc(pt);
+                        match (find(32), find(33), find(34)) {
+                            (Some(vps), Some(sps), Some(pps)) => {
+                                Box::new(packetizer.with_parameter_sets(vps, sps, pps))
+                            }
+                            _ => Box::new(packetizer),
+                        }
+                    }
+                }
+            }
+            Self::Aac(config) => Box::new(AacPacketizer::with_random_ssrc(pt, config.clone())),
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(self, Self::Video { .. })
+    }
+
+    /// `frame` (an Annex B access unit) with the track's parameter sets in
+    /// front, unless it carries its own. Lets a viewer start decoding at
+    /// any keyframe the playhead seeks to.
+    pub(crate) fn with_parameter_sets(&self, frame: Bytes) -> Bytes {
+        let Self::Video {
+            codec,
+            parameter_sets,
+        } = self
+        else {
+            return frame;
+        };
+        let has_own = H264Packetizer::nal_unit_ranges(&frame)
+            .into_iter()
+            .any(|range| codec.is_parameter_set(codec.nal_type(&frame[range])));
+        if has_own || parameter_sets.is_empty() {
+            return frame;
+        }
+        let mut out = BytesMut::with_capacity(
+            frame.len() + parameter_sets.iter().map(|p| p.len() + 4).sum::<usize>(),
+        );
+        for nal in parameter_sets {
+            out.extend_from_slice(&[0, 0, 0, 1]);
+            out.extend_from_slice(nal);
+        }
+        out.extend_from_slice(&frame);
+        out.freeze()
+    }
+}
+
+/// A media file opened for playback: its source and the codecs of its
+/// tracks. Register it with
+/// [`Server::add_file_mount`](crate::Server::add_file_mount).
+pub struct MediaFile {
+    source: FileSource,
+    looping: bool,
+}
+
+enum FileSource {
+    AnnexB(AnnexBSource),
+    Mp4(Mp4Source),
+}
+
+impl MediaFile {
+    /// Open an MP4/MOV file or an H.264/H.265 Annex B stream.
+    ///
+    /// MP4 is recognised by its box structure. Annex B streams are H.265
+    /// if the extension says so (`.h265`, `.265`, `.hevc`) or the stream
+    /// starts with an H.265 parameter set, and H.264 otherwise. Fails with
+    /// [`RtspError::InvalidMediaFile`] for other files, and for MP4 files
+    /// without a supported track.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut head = [0u8; 8];
+        let read = File::open(path)?.read(&mut head)?;
+        let source = if read == 8 && Mp4Source::is_mp4(&head) {
+            FileSource::Mp4(Mp4Source::open(path)?)
+        } else if head[..read].starts_with(&[0, 0, 1]) || head[..read].starts_with(&[0, 0, 0, 1]) {
+            let data = Bytes::from(std::fs::read(path)?);
+            let by_extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase);
+            let codec = match by_extension.as_deref() {
+                Some("h265" | "265" | "hevc") => VideoCodec::H265,
+                Some("h264" | "264" | "avc") => VideoCodec::H264,
+                _ => AnnexBSource::sniff_codec(&data),
+            };
+            FileSource::AnnexB(AnnexBSource::new(data, codec)?)
+        } else {
+            return Err(RtspError::InvalidMediaFile(format!(
+                "{}: neither MP4 nor an Annex B stream",
+                path.display()
+            )));
+        };
+        tracing::debug!(path = %path.display(), "media file opened");
+        Ok(Self {
+            source,
+            looping: false,
+        })
+    }
+
+    /// Start over at the end of the file (default: stop).
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Frame rate of an Annex B stream (default 30). Ignored for MP4 files,
+    /// whose samples carry their own timing.
+    pub fn with_frame_rate(mut self, fps: f64) -> Self {
+        if let FileSource::AnnexB(source) = self.source {
+            self.source = FileSource::AnnexB(source.with_frame_rate(fps));
+        }
+        self
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        match &self.source {
+            FileSource::AnnexB(source) => source.duration(),
+            FileSource::Mp4(source) => source.duration(),
+        }
+    }
+
+    /// Codecs of the file's tracks, in mount track order.
+    pub fn tracks(&self) -> Vec<TrackCodec> {
+        match &self.source {
+            FileSource::AnnexB(source) => vec![source.codec()],
+            FileSource::Mp4(source) => source.tracks(),
+        }
+    }
+
+    /// One packetizer per track, with payload types from 96 up, and the
+    /// source feeding them.
+    pub fn into_parts(self) -> (Vec<Box<dyn Packetizer>>, Box<dyn MediaSource>) {
+        let packetizers = self
+            .tracks()
+            .iter()
+            .zip(FIRST_PAYLOAD_TYPE..)
+            .map(|(codec, pt)| codec.packetizer(pt))
+            .collect();
+        let source: Box<dyn MediaSource> = match self.source {
+            FileSource::AnnexB(source) => Box::new(source),
+            FileSource::Mp4(source) => Box::new(source),
+        };
+        (packetizers, source)
+    }
+
+    /// Register a seekable mount at `path` with one track per file track.
+    pub(crate) fn mount(self, mounts: &MountRegistry, path: &str) -> Arc<Mount> {
+        let looping = self.looping;
+        let (packetizers, source) = self.into_parts();
+        let mut packetizers = packetizers.into_iter();
+        let first = packetizers
+            .next()
+            .expect("a media file has at least one track");
+        let mount = mounts.add(path, first);
+        for packetizer in packetizers {
+            mount.add_track(packetizer);
+        }
+        mount.set_source(source);
+        if let Some(playback) = mount.playback() {
+            playback.set_looping(looping);
+        }
+        mount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepends_parameter_sets_to_frames_without_them() {
+        let codec = TrackCodec::Video {
+            codec: VideoCodec::H264,
+            parameter_sets: vec![vec![0x67, 0x42], vec![0x68, 0xce]],
+        };
+        let idr = Bytes::from_static(&[0, 0, 0, 1, 0x65, 0x88]);
+        assert_eq!(
+            &codec.with_parameter_sets(idr)[..],
+            [
+                0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0x88
+            ]
+        );
+        let with_own = Bytes::from_static(&[0, 0, 0, 1, 0x67, 0x4d, 0, 0, 0, 1, 0x65, 0x88]);
+        assert_eq!(codec.with_parameter_sets(with_own.clone()), with_own);
+    }
+
+    #[test]
+    fn packetizers_advertise_the_file_configuration() {
+        let codec = TrackCodec::Video {
+            codec: VideoCodec::H264,
+            parameter_sets: vec![vec![0x67, 0x42, 0x00, 0x1e], vec![0x68, 0xce]],
+        };
+        let attrs = codec.packetizer(96).sdp_attributes();
+        assert!(attrs[1].contains("profile-level-id=42001e"), "{attrs:?}");
+    }
+
+    #[test]
+    fn rejects_unknown_files() {
+        let path = std::env::temp_dir().join(format!("rtsp-rs-not-media-{}", std::process::id()));
+        std::fs::write(&path, b"hello world").unwrap();
+        let result = MediaFile::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RtspError::InvalidMediaFile(_))));
+    }
+}
@@ -0,0 +1,675 @@
+//! On-demand playback of seekable mounts (RFC 2326 §10.5, §12.29,
+//! §12.34, §12.35).
+//!
+//! A live mount sends whatever its producer hands to `send_frame*`. A
+//! seekable mount instead pulls frames from a [`MediaSource`] (a recording,
+//! a file) on a player thread, paced to real time, and lets clients steer
+//! it with PLAY:
+//!
+//! - `Range: npt=30-` seeks to 30 s; `npt=30-60` stops at 60 s.
+//! - `Scale: 4` fast-forwards and `Scale: -2` rewinds, sending only the
+//!   keyframes of the first track. The RTP timeline advances at the
+//!   delivery pace, so players render the keyframes as they arrive.
+//! - `Speed: 2` delivers twice as fast without changing the timeline
+//!   (e.g. to fill a client buffer).
+//!
+//! PAUSE stops the playhead where it is; a PLAY without `Range` resumes
+//! from there. The playhead also stops by itself when no viewer is
+//! playing, so an unwatched mount does not read its source. A
+//! [looping](Playback::set_looping) playhead starts over when it reaches
+//! the end, and the RTP timeline carries on across the loop.
+//!
+//! [`MediaFile`] reads H.264/H.265 Annex B and MP4/MOV files into a source
+//! with matching packetizers, for
+//! [`Server::add_file_mount`](crate::Server::add_file_mount).
+//!
+//! A seekable mount has a single playhead, shared by its viewers: a PLAY
+//! with `Range` from one viewer seeks all of them. Give each viewer its
+//! own mount for independent playback.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+use crate::error::{Result, RtspError};
+use crate::mount::Track;
+use crate::protocol::range::{Range, RangeTime};
+use crate::send_queue::FrameTiming;
+
+pub mod annexb;
+pub mod file;
+pub mod mp4;
+
+pub use file::{MediaFile, TrackCodec, VideoCodec};
+
+/// Longest the player thread sleeps before checking for shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One encoded frame read from a [`MediaSource`].
+#[derive(Debug, Clone)]
+pub struct SourceFrame {
+    /// Zero-based index of the mount track the frame belongs to.
+    pub track: usize,
+    pub data: Bytes,
+    /// Presentation time, as an offset from the start of the presentation.
+    pub pts: Duration,
+    /// Decode time, for sources with B-frames whose frames come in decode
+    /// order. Frames are sent at their decode time.
+    pub dts: Option<Duration>,
+    /// Whether decoding can start at this frame.
+    pub keyframe: bool,
+}
+
+impl SourceFrame {
+    fn decode_time(&self) -> Duration {
+        self.dts.unwrap_or(self.pts)
+    }
+}
+
+/// Frames of a seekable presentation, read in decode order.
+///
+/// Implemented by recordings and file readers; installed on a mount with
+/// [`Mount::set_source`](crate::Mount::set_source) or
+/// [`Server::add_seekable_mount`](crate::Server::add_seekable_mount).
+pub trait MediaSource: Send {
+    /// Length of the presentation, advertised in the SDP as
+    /// `a=range:npt=0-<duration>`. `None` if unknown.
+    fn duration(&self) -> Option<Duration>;
+
+    /// Wall-clock time of the start of the presentation, which lets
+    /// clients seek with `Range: clock=...`. `None` (the default) rejects
+    /// such ranges.
+    fn start_time(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Reposition so that [`next_frame`](Self::next_frame) continues from
+    /// the last keyframe of the first track at or before `position`.
+    /// Returns the position actually reached.
+    fn seek(&mut self, position: Duration) -> Result<Duration>;
+
+    /// The next frame, or `None` at the end of the presentation.
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>>;
+}
+
+/// Where a PLAY started, for the response's `Range` and `RTP-Info`.
+#[derive(Debug, Clone)]
+pub struct PlayStart {
+    /// Position playback (re)started from.
+    pub start: Duration,
+    /// Where it will stop: the requested end, else the source's duration.
+    pub end: Option<Duration>,
+    /// `(next sequence number, RTP timestamp of the first frame)` of each
+    /// track, by track index.
+    pub rtp_info: Vec<(u16, u32)>,
+}
+
+/// The playhead of a seekable mount: its [`MediaSource`], position and
+/// PLAY parameters. Driven by a player thread the server starts for each
+/// seekable mount.
+pub struct Playback {
+    state: Mutex<PlaybackState>,
+    wake: Condvar,
+}
+
+struct PlaybackState {
+    source: Box<dyn MediaSource>,
+    playing: bool,
+    /// The source is exhausted or past the range end.
+    ended: bool,
+    looping: bool,
+    /// Looped back to the start and not sent a frame since.
+    looped: bool,
+    scale: f64,
+    speed: f64,
+    /// Position the current PLAY started from.
+    range_start: Duration,
+    range_end: Option<Duration>,
+    /// Output time (the RTP timeline) and wall-clock instant of
+    /// `range_start`.
+    stream_base: Duration,
+    wall_base: Instant,
+    /// Output time and send instant of the last frame sent.
+    last_sent: Option<(Duration, Instant)>,
+    /// Position of the last frame sent, or of the last seek.
+    position: Duration,
+    /// Frame read ahead but not yet due.
+    pending: Option<SourceFrame>,
+    /// While rewinding, position of the last keyframe sent.
+    reverse_from: Option<Duration>,
+}
+
+impl Playback {
+    pub fn new(source: Box<dyn MediaSource>) -> Self {
+        Self {
+            state: Mutex::new(PlaybackState {
+                source,
+                playing: false,
+                ended: false,
+                looping: false,
+                looped: false,
+                scale: 1.0,
+                speed: 1.0,
+                range_start: Duration::ZERO,
+                range_end: None,
+                stream_base: Duration::ZERO,
+                wall_base: Instant::now(),
+                last_sent: None,
+                position: Duration::ZERO,
+                pending: None,
+                reverse_from: None,
+            }),
+            wake: Condvar::new(),
+        }
+    }
+
+    /// Length of the presentation, if the source knows it.
+    pub fn duration(&self) -> Option<Duration> {
+        self.state.lock().source.duration()
+    }
+
+    /// Position of the last frame sent (or of the last seek).
+    pub fn position(&self) -> Duration {
+        self.state.lock().position
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.lock().playing
+    }
+
+    /// Start over from the beginning at the end of the presentation,
+    /// instead of stopping. Applies to forward playback without a `Range`
+    /// end.
+    pub fn set_looping(&self, looping: bool) {
+        self.state.lock().looping = looping;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.state.lock().looping
+    }
+
+    /// Start or resume playback.
+    ///
+    /// With a `range` start, seeks there; otherwise resumes from the
+    /// current position, or from the beginning once the presentation has
+    /// ended. `scale` and `speed` must be non-zero and `speed` positive.
+    /// Fails with [`RtspError::InvalidRange`] for a range outside the
+    /// presentation.
+    pub(crate) fn play(
+        &self,
+        range: Option<&Range>,
+        scale: f64,
+        speed: f64,
+        tracks: &[Arc<Track>],
+    ) -> Result<PlayStart> {
+        let mut state = self.state.lock();
+        let start = match range.and_then(|r| r.start) {
+            Some(time) => Some(state.resolve(time)?),
+            None => None,
+        };
+        let end = match range.and_then(|r| r.end) {
+            Some(time) => Some(state.resolve(time)?),
+            None => None,
+        };
+        let duration = state.source.duration();
+        let reversed = scale < 0.0;
+        if let Some(start) = start {
+            if duration.is_some_and(|d| start > d) {
+                return Err(RtspError::InvalidRange(format!(
+                    "start {start:?} beyond duration {duration:?}"
+                )));
+            }
+            if let Some(end) = end
+                && (if reversed { end > start } else { end < start })
+            {
+                return Err(RtspError::InvalidRange(format!(
+                    "end {end:?} before start {start:?}"
+                )));
+            }
+        }
+
+        let already_playing = state.playing;
+        let direction_changed = (state.scale < 0.0) != reversed;
+        let resume_from = state.pending.as_ref().map_or(state.position, |f| f.pts);
+        match start {
+            Some(start) => state.seek(start)?,
+            None if state.ended => state.seek(Duration::ZERO)?,
+            // Keyframes-only and full-rate reading start from different
+            // places; realign on the current position.
+            None if direction_changed || (scale != state.scale && already_playing) => {
+                state.seek(resume_from)?
+            }
+            None => state.range_start = resume_from,
+        }
+        state.range_end = end;
+        state.scale = scale;
+        state.speed = speed;
+        state.stream_base = match state.last_sent {
+            Some((output, sent_at)) => output.saturating_add(sent_at.elapsed()),
+            None => Duration::ZERO,
+        };
+        state.wall_base = Instant::now();
+        state.playing = true;
+
+        let rtp_info = tracks
+            .iter()
+            .map(|track| {
+                (
+                    track.next_sequence(),
+                    track.rtp_timestamp_for_pts(state.stream_base),
+                )
+            })
+            .collect();
+        tracing::debug!(
+            start = ?state.range_start,
+            end = ?state.range_end,
+            scale,
+            speed,
+            "playback started"
+        );
+        self.wake.notify_all();
+        Ok(PlayStart {
+            start: state.range_start,
+            end: state.range_end.or(duration.filter(|_| !reversed)),
+            rtp_info,
+        })
+    }
+
+    /// Stop the playhead, keeping its position.
+    pub(crate) fn pause(&self) {
+        let mut state = self.state.lock();
+        if state.playing {
+            state.playing = false;
+            tracing::debug!(position = ?state.position, "playback paused");
+        }
+    }
+
+    /// Player loop: read frames from the source and hand them to `deliver`
+    /// when they are due, until `keep_running` returns `false`. Pauses when
+    /// `has_viewers` returns `false`.
+    pub(crate) fn run(
+        &self,
+        keep_running: impl Fn() -> bool,
+        has_viewers: impl Fn() -> bool,
+        mut deliver: impl FnMut(usize, Bytes, FrameTiming),
+    ) {
+        let mut state = self.state.lock();
+        while keep_running() {
+            if !state.playing {
+                self.wake.wait_for(&mut state, POLL_INTERVAL);
+                continue;
+            }
+            if !has_viewers() {
+                state.playing = false;
+                tracing::debug!(position = ?state.position, "no viewers, playback paused");
+                continue;
+            }
+
+            let frame = match state.pending.take() {
+                Some(frame) => frame,
+                None => match state.read() {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => {
+                        if !state.restart() {
+                            state.end();
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "media source failed, playback stopped");
+                        state.end();
+                        continue;
+                    }
+                },
+            };
+            if state.past_end(&frame) {
+                state.end();
+                continue;
+            }
+
+            let output = state.output_time(frame.pts);
+            let delay = div_rate(
+                state
+                    .output_time(frame.decode_time())
+                    .saturating_sub(state.stream_base),
+                state.speed,
+            );
+            let now = Instant::now();
+            let wait = state
+                .wall_base
+                .checked_add(delay)
+                .map_or(POLL_INTERVAL, |due| due.saturating_duration_since(now));
+            if !wait.is_zero() {
+                state.pending = Some(frame);
+                self.wake.wait_for(&mut state, wait.min(POLL_INTERVAL));
+                continue;
+            }
+
+            state.position = frame.pts;
+            state.last_sent = Some((output, now));
+            state.looped = false;
+            let timing = FrameTiming::Pts {
+                pts: output,
+                dts: frame.dts.map(|_| state.output_time(frame.decode_time())),
+            };
+            // Delivery does network I/O and may wait on a pacer or send
+            // queue; PLAY and PAUSE must not wait behind it.
+            MutexGuard::unlocked(&mut state, || deliver(frame.track, frame.data, timing));
+        }
+    }
+}
+
+impl PlaybackState {
+    /// A range time as a position in the presentation.
+    fn resolve(&self, time: RangeTime) -> Result<Duration> {
+        match time {
+            RangeTime::Npt(position) => Ok(position),
+            RangeTime::Now => Ok(self.position),
+            RangeTime::Clock(at) => {
+                let start = self.source.start_time().ok_or_else(|| {
+                    RtspError::InvalidRange("clock range on a source without start time".into())
+                })?;
+                at.duration_since(start)
+                    .map_err(|_| RtspError::InvalidRange("clock range before start".into()))
+            }
+        }
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<()> {
+        let reached = self.source.seek(position)?;
+        self.range_start = reached;
+        self.position = reached;
+        self.pending = None;
+        self.reverse_from = None;
+        self.ended = false;
+        self.looped = false;
+        Ok(())
+    }
+
+    /// Loop back to the start, continuing the output timeline where the
+    /// last frame sent ends. Returns `false` if playback should end
+    /// instead: not looping, rewinding, stopping at a `Range` end, or
+    /// nothing was read since the last loop.
+    fn restart(&mut self) -> bool {
+        if !self.looping || self.scale < 0.0 || self.range_end.is_some() || self.looped {
+            return false;
+        }
+        let Some((output, sent_at)) = self.last_sent else {
+            return false;
+        };
+        // The last frame lasts until the end of the presentation.
+        let gap = div_rate(
+            self.source
+                .duration()
+                .map_or(Duration::ZERO, |d| d.saturating_sub(self.position)),
+            self.scale,
+        );
+        let Some(wall_base) = sent_at.checked_add(div_rate(gap, self.speed)) else {
+            return false;
+        };
+        if let Err(e) = self.seek(Duration::ZERO) {
+            tracing::warn!(error = %e, "media source failed to loop");
+            return false;
+        }
+        self.stream_base = output.saturating_add(gap);
+        self.wall_base = wall_base;
+        self.looped = true;
+        tracing::debug!("playback looped");
+        true
+    }
+
+    /// Keyframes only in trick modes (fast-forward and rewind), every frame
+    /// otherwise.
+    fn read(&mut self) -> Result<Option<SourceFrame>> {
+        if self.scale < 0.0 {
+            if let Some(last) = self.reverse_from {
+                if last.is_zero() {
+                    return Ok(None);
+                }
+                let reached = self.source.seek(last - Duration::from_nanos(1))?;
+                if reached >= last {
+                    return Ok(None);
+                }
+            }
+        } else if self.scale <= 1.0 {
+            return self.source.next_frame();
+        }
+
+        while let Some(frame) = self.source.next_frame()? {
+            if frame.track == 0 && frame.keyframe {
+                if self.scale < 0.0 {
+                    self.reverse_from = Some(frame.pts);
+                }
+                return Ok(Some(frame));
+            }
+        }
+        Ok(None)
+    }
+
+    fn past_end(&self, frame: &SourceFrame) -> bool {
+        match self.range_end {
+            Some(end) if self.scale < 0.0 => frame.pts < end,
+            Some(end) => frame.pts > end,
+            None => false,
+        }
+    }
+
+    fn end(&mut self) {
+        self.playing = false;
+        self.ended = true;
+        tracing::debug!(position = ?self.position, "playback reached the end");
+    }
+
+    /// Output time of a position: media time elapsed since `range_start`,
+    /// divided by the scale, after `stream_base`.
+    fn output_time(&self, position: Duration) -> Duration {
+        let elapsed = if self.scale < 0.0 {
+            self.range_start.saturating_sub(position)
+        } else {
+            position.saturating_sub(self.range_start)
+        };
+        self.stream_base
+            .saturating_add(div_rate(elapsed, self.scale.abs()))
+    }
+}
+
+/// `duration` divided by a positive Scale or Speed `rate`, saturating
+/// where the quotient overflows (rates too close to zero) instead of
+/// panicking like [`Duration::div_f64`].
+fn div_rate(duration: Duration, rate: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() / rate).unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::media::h264::H264Packetizer;
+    use crate::mount::Mount;
+
+    /// 10 s of 10 fps frames, a keyframe every second.
+    struct Clip {
+        next: u64,
+    }
+
+    impl MediaSource for Clip {
+        fn duration(&self) -> Option<Duration> {
+            Some(Duration::from_secs(10))
+        }
+
+        fn seek(&mut self, position: Duration) -> Result<Duration> {
+            self.next = position.as_secs() * 10;
+            Ok(Duration::from_millis(self.next * 100))
+        }
+
+        fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+            if self.next >= 100 {
+                return Ok(None);
+            }
+            let frame = SourceFrame {
+                track: 0,
+                data: Bytes::from(vec![0x65, self.next as u8]),
+                pts: Duration::from_millis(self.next * 100),
+                dts: None,
+                keyframe: self.next.is_multiple_of(10),
+            };
+            self.next += 1;
+            Ok(Some(frame))
+        }
+    }
+
+    fn npt(start: u64, end: Option<u64>) -> Range {
+        Range {
+            start: Some(RangeTime::Npt(Duration::from_secs(start))),
+            end: end.map(|e| RangeTime::Npt(Duration::from_secs(e))),
+        }
+    }
+
+    /// Frames the player sends, by number, with a speed high enough that
+    /// pacing takes no time.
+    fn played(playback: &Playback, range: Option<Range>, scale: f64) -> Vec<u8> {
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        playback
+            .play(range.as_ref(), scale, 10_000.0, &mount.tracks())
+            .unwrap();
+        let mut sent = Vec::new();
+        let deadline = Instant::now() + Duration::from_millis(300);
+        playback.run(
+            || Instant::now() < deadline,
+            || true,
+            |_, data, _| sent.push(data[1]),
+        );
+        sent
+    }
+
+    #[test]
+    fn plays_a_range() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        let sent = played(&playback, Some(npt(3, Some(5))), 1.0);
+        assert_eq!(sent, (30..=50).collect::<Vec<u8>>());
+        assert_eq!(playback.position(), Duration::from_secs(5));
+        assert!(!playback.is_playing());
+
+        // Once ended, PLAY without a range starts over.
+        assert_eq!(played(&playback, None, 1.0).len(), 100);
+    }
+
+    #[test]
+    fn trick_modes_send_keyframes_only() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        assert_eq!(
+            played(&playback, Some(npt(2, None)), 4.0),
+            [20, 30, 40, 50, 60, 70, 80, 90]
+        );
+        assert_eq!(
+            played(&playback, Some(npt(5, Some(2))), -2.0),
+            [50, 40, 30, 20]
+        );
+    }
+
+    #[test]
+    fn extreme_rates_stall_instead_of_panicking() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        playback
+            .play(None, 1e-300, 1e-300, &mount.tracks())
+            .unwrap();
+        let mut sent = Vec::new();
+        let deadline = Instant::now() + Duration::from_millis(200);
+        playback.run(
+            || Instant::now() < deadline,
+            || true,
+            |_, data, _| sent.push(data[1]),
+        );
+        // The second frame is due further away than time can express.
+        assert_eq!(sent, [0]);
+        assert!(playback.is_playing());
+    }
+
+    #[test]
+    fn delivers_without_holding_the_state_lock() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        playback
+            .play(Some(&npt(0, Some(1))), 1.0, 10_000.0, &mount.tracks())
+            .unwrap();
+        let mut positions = Vec::new();
+        let deadline = Instant::now() + Duration::from_millis(300);
+        playback.run(
+            || Instant::now() < deadline,
+            || true,
+            // Would deadlock if the state were still locked.
+            |_, _, _| positions.push(playback.position()),
+        );
+        assert_eq!(positions.len(), 11);
+    }
+
+    #[test]
+    fn rejects_ranges_outside_the_presentation() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        for range in [npt(11, None), npt(5, Some(2))] {
+            assert!(matches!(
+                playback.play(Some(&range), 1.0, 1.0, &mount.tracks()),
+                Err(RtspError::InvalidRange(_))
+            ));
+        }
+        let clock = Range {
+            start: Some(RangeTime::Clock(SystemTime::now())),
+            end: None,
+        };
+        assert!(
+            playback
+                .play(Some(&clock), 1.0, 1.0, &mount.tracks())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn looping_continues_the_timeline() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        playback.set_looping(true);
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        playback
+            .play(Some(&npt(9, None)), 1.0, 10_000.0, &mount.tracks())
+            .unwrap();
+        let sent = RefCell::new(Vec::new());
+        playback.run(
+            || sent.borrow().len() < 12,
+            || true,
+            |_, data, timing| {
+                let FrameTiming::Pts { pts, .. } = timing else {
+                    panic!("seekable mounts send PTS timing");
+                };
+                sent.borrow_mut().push((data[1], pts));
+            },
+        );
+        let sent = sent.into_inner();
+        let numbers: Vec<u8> = sent.iter().map(|(n, _)| *n).collect();
+        assert_eq!(numbers[9..], [99, 0, 1]);
+        assert!(
+            sent.windows(2)
+                .all(|w| w[1].1 - w[0].1 == Duration::from_millis(100))
+        );
+        assert!(playback.is_playing());
+    }
+
+    #[test]
+    fn pause_keeps_the_position() {
+        let playback = Playback::new(Box::new(Clip { next: 0 }));
+        let mount = Mount::new("/vod", Box::new(H264Packetizer::new(96, 1)));
+        let start = playback
+            .play(Some(&npt(4, None)), 1.0, 1.0, &mount.tracks())
+            .unwrap();
+        assert_eq!(start.start, Duration::from_secs(4));
+        assert_eq!(start.end, Some(Duration::from_secs(10)));
+        playback.pause();
+
+        let resumed = playback.play(None, 1.0, 1.0, &mount.tracks()).unwrap();
+        assert_eq!(resumed.start, Duration::from_secs(4));
+        assert_eq!(resumed.rtp_info.len(), 1);
+    }
+}
@@ -0,0 +1,901 @@
+//! MP4/MOV files as a [`MediaSource`] (ISO/IEC 14496-12 and -15,
+//! QuickTime File Format).
+//!
+//! The `moov` box is read into memory and each supported track's sample
+//! table (`stts`, `ctts`, `stss`, `stsc`, `stsz`, `stco`/`co64`) is
+//! expanded into a list of samples; sample data is read from the file as
+//! it is played. Supported sample entries:
+//!
+//! | Entry | Codec | Configuration |
+//! |-------|-------|---------------|
+//! | `avc1`, `avc3` | H.264 | `avcC` (SPS/PPS, NAL length size) |
+//! | `hvc1`, `hev1` | H.265 | `hvcC` (VPS/SPS/PPS, NAL length size) |
+//! | `mp4a` | AAC | `esds` `AudioSpecificConfig` |
+//!
+//! Video samples are converted from length-prefixed NAL units to Annex B.
+//! Presentation times include the composition offsets (`ctts`), shifted by
+//! the first edit list entry so the first frame is shown at 0. Other
+//! tracks are skipped. Fragmented MP4 (`moof`) is not supported.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use super::file::{TrackCodec, VideoCodec};
+use super::{MediaSource, SourceFrame};
+use crate::error::{Result, RtspError};
+use crate::media::aac::AacConfig;
+
+/// Box types that can start an MP4/MOV file.
+const TOP_LEVEL_BOXES: [&[u8; 4]; 7] = [
+    b"ftyp", b"moov", b"mdat", b"free", b"skip", b"wide", b"pnot",
+];
+
+/// Bytes of a `VisualSampleEntry` before its child boxes.
+const VISUAL_SAMPLE_ENTRY_LEN: usize = 78;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    offset: u64,
+    size: u32,
+    /// Decode time in track timescale units.
+    dts: u64,
+    /// Composition offset (`ctts`), in track timescale units.
+    cts_offset: i64,
+    sync: bool,
+}
+
+struct Mp4Track {
+    codec: TrackCodec,
+    /// Size of the NAL length prefix of video samples.
+    length_size: usize,
+    timescale: u32,
+    samples: Vec<Sample>,
+    /// Decode time at which the track ends.
+    end: u64,
+    /// Media time shown at presentation time 0 (first `elst` entry).
+    edit_offset: u64,
+    has_ctts: bool,
+    next: usize,
+}
+
+impl Mp4Track {
+    fn time(&self, ticks: i64) -> Duration {
+        let ticks = ticks.max(0) as u128;
+        Duration::from_nanos((ticks * 1_000_000_000 / u128::from(self.timescale.max(1))) as u64)
+    }
+
+    fn pts(&self, index: usize) -> Duration {
+        let sample = &self.samples[index];
+        self.time(sample.dts as i64 + sample.cts_offset - self.edit_offset as i64)
+    }
+
+    fn dts(&self, index: usize) -> Duration {
+        self.time(self.samples[index].dts as i64 - self.edit_offset as i64)
+    }
+}
+
+/// The H.264, H.265 and AAC tracks of an MP4/MOV file.
+pub struct Mp4Source {
+    file: File,
+    tracks: Vec<Mp4Track>,
+    duration: Duration,
+}
+
+impl Mp4Source {
+    /// Whether the first 8 bytes of a file are an MP4/MOV box header.
+    pub fn is_mp4(head: &[u8]) -> bool {
+        head.len() >= 8 && TOP_LEVEL_BOXES.iter().any(|kind| head[4..8] == kind[..])
+    }
+
+    /// Read the sample tables of `path`. Fails with
+    /// [`RtspError::InvalidMediaFile`] if the file has no `moov` box, is
+    /// fragmented, or has no supported track.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let moov = read_moov(&mut file, file_len)?;
+
+        let mut tracks: Vec<Mp4Track> = boxes(&moov)
+            .filter(|(kind, _)| kind == b"trak")
+            .filter_map(|(_, trak)| parse_track(trak, file_len))
+            .collect();
+        if tracks.is_empty() {
+            return Err(RtspError::InvalidMediaFile(
+                "no H.264, H.265 or AAC track with samples".into(),
+            ));
+        }
+        // Video first: seeks land on the first track's keyframes.
+        tracks.sort_by_key(|track| !track.codec.is_video());
+        let duration = tracks
+            .iter()
+            .map(|track| track.time(track.end as i64))
+            .max()
+            .unwrap_or_default();
+        tracing::debug!(tracks = tracks.len(), ?duration, "MP4 sample tables read");
+        Ok(Self {
+            file,
+            tracks,
+            duration,
+        })
+    }
+
+    /// Codecs of the supported tracks, video first.
+    pub fn tracks(&self) -> Vec<TrackCodec> {
+        self.tracks.iter().map(|t| t.codec.clone()).collect()
+    }
+
+    fn read_sample(&mut self, track: usize, index: usize) -> Result<Bytes> {
+        let sample = self.tracks[track].samples[index];
+        let mut data = vec![0u8; sample.size as usize];
+        self.file.seek(SeekFrom::Start(sample.offset))?;
+        self.file.read_exact(&mut data)?;
+
+        let track = &self.tracks[track];
+        if !track.codec.is_video() {
+            return Ok(Bytes::from(data));
+        }
+        let frame = Bytes::from(to_annex_b(&data, track.length_size).ok_or_else(|| {
+            RtspError::InvalidMediaFile(format!("malformed video sample {index}"))
+        })?);
+        Ok(if sample.sync {
+            track.codec.with_parameter_sets(frame)
+        } else {
+            frame
+        })
+    }
+}
+
+impl MediaSource for Mp4Source {
+    fn duration(&self) -> Option<Duration> {
+        Some(self.duration)
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<Duration> {
+        let lead = &mut self.tracks[0];
+        let index = (0..lead.samples.len())
+            .rev()
+            .find(|&i| lead.samples[i].sync && lead.pts(i) <= position)
+            .or_else(|| lead.samples.iter().position(|s| s.sync))
+            .unwrap_or(0);
+        lead.next = index;
+        let reached = lead.pts(index);
+        let lead_dts = lead.dts(index);
+
+        for track in &mut self.tracks[1..] {
+            track.next = (0..track.samples.len())
+                .find(|&i| track.dts(i) >= lead_dts)
+                .unwrap_or(track.samples.len());
+        }
+        Ok(reached)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let mut next: Option<(usize, Duration)> = None;
+        for (i, track) in self.tracks.iter().enumerate() {
+            if track.next < track.samples.len() {
+                let dts = track.dts(track.next);
+                if next.is_none_or(|(_, earliest)| dts < earliest) {
+                    next = Some((i, dts));
+                }
+            }
+        }
+        let Some((index, dts)) = next else {
+            return Ok(None);
+        };
+
+        let sample = self.tracks[index].next;
+        let data = self.read_sample(index, sample)?;
+        let track = &mut self.tracks[index];
+        track.next += 1;
+        Ok(Some(SourceFrame {
+            track: index,
+            data,
+            pts: track.pts(sample),
+            dts: track.has_ctts.then_some(dts),
+            keyframe: track.samples[sample].sync,
+        }))
+    }
+}
+
+/// Read the `moov` box of a file of `len` bytes, walking the top-level
+/// boxes.
+fn read_moov(file: &mut File, len: u64) -> Result<Vec<u8>> {
+    let mut pos = 0u64;
+    let mut moov = None;
+    while pos + 8 <= len {
+        let mut header = [0u8; 16];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header[..8])?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (len - pos, 8),
+            1 => {
+                file.read_exact(&mut header[8..])?;
+                (u64::from_be_bytes(header[8..].try_into().unwrap()), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        if size < header_len || pos.checked_add(size).is_none_or(|end| end > len) {
+            if &kind == b"moov" {
+                return Err(RtspError::InvalidMediaFile(format!(
+                    "moov box of {size} bytes runs past the end of the file"
+                )));
+            }
+            break;
+        }
+        match &kind {
+            b"moov" => {
+                let mut data = vec![0u8; (size - header_len) as usize];
+                file.read_exact(&mut data)?;
+                moov = Some(data);
+            }
+            b"moof" => {
+                return Err(RtspError::InvalidMediaFile(
+                    "fragmented MP4 is not supported".into(),
+                ));
+            }
+            _ => {}
+        }
+        pos += size;
+    }
+    moov.ok_or_else(|| RtspError::InvalidMediaFile("no moov box".into()))
+}
+
+/// Child boxes of a box payload, as `(type, payload)`.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let mut r = Reader::new(data);
+        let size = r.u32()? as usize;
+        let kind: [u8; 4] = r.bytes(4)?.try_into().ok()?;
+        let (size, header_len) = match size {
+            0 => (data.len(), 8),
+            1 => (usize::try_from(r.u64()?).ok()?, 16),
+            size => (size, 8),
+        };
+        if size < header_len || size > data.len() {
+            return None;
+        }
+        let payload = &data[header_len..size];
+        data = &data[size..];
+        Some((kind, payload))
+    })
+}
+
+/// Payload of the box at `path` below `data`.
+fn child<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    let (first, rest) = path.split_first()?;
+    let (_, payload) = boxes(data).find(|(kind, _)| kind == *first)?;
+    if rest.is_empty() {
+        Some(payload)
+    } else {
+        child(payload, rest)
+    }
+}
+
+fn parse_track(trak: &[u8], file_len: u64) -> Option<Mp4Track> {
+    let mdia = child(trak, &[b"mdia"])?;
+    let stbl = child(mdia, &[b"minf", b"stbl"])?;
+    let timescale = parse_mdhd(child(mdia, &[b"mdhd"])?)?;
+    let (kind, entry) = boxes(child(stbl, &[b"stsd"])?.get(8..)?).next()?;
+
+    let (codec, length_size) = match &kind {
+        b"avc1" | b"avc3" | b"hvc1" | b"hev1" => {
+            let children = entry.get(VISUAL_SAMPLE_ENTRY_LEN..)?;
+            if kind.starts_with(b"avc") {
+                parse_avcc(child(children, &[b"avcC"])?)?
+            } else {
+                parse_hvcc(child(children, &[b"hvcC"])?)?
+            }
+        }
+        b"mp4a" => (TrackCodec::Aac(parse_mp4a(entry)?), 0),
+        _ => {
+            tracing::debug!(
+                entry = %String::from_utf8_lossy(&kind),
+                "unsupported MP4 track skipped"
+            );
+            return None;
+        }
+    };
+
+    let ctts = child(stbl, &[b"ctts"]);
+    let (samples, end) = parse_sample_table(stbl, ctts, file_len)?;
+    if samples.is_empty() {
+        return None;
+    }
+    let edit_offset = child(trak, &[b"edts", b"elst"])
+        .and_then(parse_elst)
+        .unwrap_or(0);
+    Some(Mp4Track {
+        codec,
+        length_size,
+        timescale,
+        samples,
+        end,
+        edit_offset,
+        has_ctts: ctts.is_some(),
+        next: 0,
+    })
+}
+
+fn parse_mdhd(mdhd: &[u8]) -> Option<u32> {
+    let mut r = Reader::new(mdhd);
+    let version = r.u8()?;
+    r.skip(3)?;
+    r.skip(if version == 1 { 16 } else { 8 })?;
+    r.u32().filter(|t| *t > 0)
+}
+
+/// Media time of the first non-empty edit.
+fn parse_elst(elst: &[u8]) -> Option<u64> {
+    let mut r = Reader::new(elst);
+    let version = r.u8()?;
+    r.skip(3)?;
+    for _ in 0..r.u32()? {
+        let media_time = if version == 1 {
+            r.skip(8)?;
+            r.u64()? as i64
+        } else {
+            r.skip(4)?;
+            i64::from(r.u32()? as i32)
+        };
+        r.skip(4)?;
+        if media_time >= 0 {
+            return Some(media_time as u64);
+        }
+    }
+    None
+}
+
+fn parse_avcc(avcc: &[u8]) -> Option<(TrackCodec, usize)> {
+    let mut r = Reader::new(avcc);
+    r.skip(4)?;
+    let length_size = usize::from(r.u8()? & 0x03) + 1;
+    let mut parameter_sets = Vec::new();
+    for _ in 0..(r.u8()? & 0x1f) {
+        let len = r.u16()?;
+        parameter_sets.push(r.bytes(len.into())?.to_vec());
+    }
+    for _ in 0..r.u8()? {
+        let len = r.u16()?;
+        parameter_sets.push(r.bytes(len.into())?.to_vec());
+    }
+    let codec = TrackCodec::Video {
+        codec: VideoCodec::H264,
+        parameter_sets,
+    };
+    Some((codec, length_size))
+}
+
+fn parse_hvcc(hvcc: &[u8]) -> Option<(TrackCodec, usize)> {
+    let mut r = Reader::new(hvcc);
+    r.skip(21)?;
+    let length_size = usize::from(r.u8()? & 0x03) + 1;
+    let mut parameter_sets = Vec::new();
+    for _ in 0..r.u8()? {
+        let nal_type = r.u8()? & 0x3f;
+        for _ in 0..r.u16()? {
+            let len = r.u16()?;
+            let nal = r.bytes(len.into())?;
+            if VideoCodec::H265.is_parameter_set(nal_type) {
+                parameter_sets.push(nal.to_vec());
+            }
+        }
+    }
+    let codec = TrackCodec::Video {
+        codec: VideoCodec::H265,
+        parameter_sets,
+    };
+    Some((codec, length_size))
+}
+
+/// The `AudioSpecificConfig` of an `mp4a` sample entry, from its `esds`
+/// (also inside a QuickTime `wave` box), or derived from the entry's
+/// channel count and sample rate when there is none.
+fn parse_mp4a(entry: &[u8]) -> Option<AacConfig> {
+    let mut r = Reader::new(entry);
+    r.skip(8)?;
+    let version = r.u16()?;
+    r.skip(6)?;
+    let channels = r.u16()?;
+    r.skip(6)?;
+    let sample_rate = r.u32()? >> 16;
+    let children_at = 28
+        + match version {
+            1 => 16,
+            2 => 36,
+            _ => 0,
+        };
+    let children = entry.get(children_at..)?;
+    let esds = child(children, &[b"esds"]).or_else(|| child(children, &[b"wave", b"esds"]));
+    match esds.and_then(|esds| decoder_specific_info(esds.get(4..)?)) {
+        Some(config) => AacConfig::parse(config),
+        None => AacConfig::new(2, sample_rate, u8::try_from(channels).ok()?),
+    }
+}
+
+/// The DecoderSpecificInfo (tag 5) inside the ES_Descriptor (tag 3) and
+/// DecoderConfigDescriptor (tag 4) of an `esds` box (ISO/IEC 14496-1
+/// §7.2.6).
+fn decoder_specific_info(mut data: &[u8]) -> Option<&[u8]> {
+    while !data.is_empty() {
+        let mut r = Reader::new(data);
+        let tag = r.u8()?;
+        let mut len = 0usize;
+        for _ in 0..4 {
+            let b = r.u8()?;
+            len = (len << 7) | usize::from(b & 0x7f);
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        let body = r.bytes(len)?;
+        data = &data[r.pos..];
+        match tag {
+            5 => return Some(body),
+            3 => {
+                let mut es = Reader::new(body);
+                es.skip(2)?;
+                let flags = es.u8()?;
+                if flags & 0x80 != 0 {
+                    es.skip(2)?;
+                }
+                if flags & 0x40 != 0 {
+                    let url_len = es.u8()?;
+                    es.skip(url_len.into())?;
+                }
+                if flags & 0x20 != 0 {
+                    es.skip(2)?;
+                }
+                return decoder_specific_info(&body[es.pos..]);
+            }
+            4 => return decoder_specific_info(body.get(13..)?),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Samples of a track with their file offsets, decode times and flags,
+/// and the decode time at which the track ends. `file_len` bounds the
+/// samples the tables can describe.
+fn parse_sample_table(
+    stbl: &[u8],
+    ctts: Option<&[u8]>,
+    file_len: u64,
+) -> Option<(Vec<Sample>, u64)> {
+    let sizes = parse_stsz(child(stbl, &[b"stsz"])?, file_len)?;
+    let chunk_offsets = match child(stbl, &[b"stco"]) {
+        Some(stco) => full_box_entries(stco, |r| r.u32().map(u64::from))?,
+        None => full_box_entries(child(stbl, &[b"co64"])?, |r| r.u64())?,
+    };
+    let stsc = full_box_entries(child(stbl, &[b"stsc"])?, |r| {
+        let first_chunk = r.u32()?;
+        let samples = r.u32()?;
+        r.skip(4)?;
+        Some((first_chunk, samples))
+    })?;
+    let stts = full_box_entries(child(stbl, &[b"stts"])?, |r| Some((r.u32()?, r.u32()?)))?;
+    let ctts = match ctts {
+        Some(ctts) => full_box_entries(ctts, |r| Some((r.u32()?, r.u32()? as i32)))?,
+        None => Vec::new(),
+    };
+    let sync = match child(stbl, &[b"stss"]) {
+        Some(stss) => Some(full_box_entries(stss, |r| r.u32())?),
+        None => None,
+    };
+
+    // File offsets: consecutive samples of a chunk are contiguous.
+    let mut offsets = Vec::with_capacity(sizes.len());
+    for (chunk, &chunk_offset) in chunk_offsets.iter().enumerate() {
+        let chunk_number = chunk as u32 + 1;
+        let per_chunk = stsc
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= chunk_number)
+            .map_or(0, |(_, samples)| *samples);
+        let mut offset = chunk_offset;
+        for _ in 0..per_chunk {
+            let Some(size) = sizes.get(offsets.len()) else {
+                break;
+            };
+            // Samples must lie within the file, or reading one would
+            // allocate whatever size the table claims.
+            let end = offset
+                .checked_add(u64::from(*size))
+                .filter(|end| *end <= file_len)?;
+            offsets.push(offset);
+            offset = end;
+        }
+    }
+
+    let deltas = stts
+        .iter()
+        .flat_map(|&(count, delta)| std::iter::repeat_n(delta, count as usize));
+    let mut cts_offsets = ctts
+        .iter()
+        .flat_map(|&(count, offset)| std::iter::repeat_n(offset, count as usize));
+    let mut samples = Vec::with_capacity(offsets.len());
+    let mut dts = 0u64;
+    for (i, (offset, delta)) in offsets.iter().zip(deltas).enumerate() {
+        samples.push(Sample {
+            offset: *offset,
+            size: sizes[i],
+            dts,
+            cts_offset: i64::from(cts_offsets.next().unwrap_or(0)),
+            sync: sync
+                .as_ref()
+                .is_none_or(|sync| sync.binary_search(&(i as u32 + 1)).is_ok()),
+        });
+        dts += u64::from(delta);
+    }
+    Some((samples, dts))
+}
+
+/// Sample sizes. `None` if the table is truncated or, for samples of a
+/// uniform size (which have no table), if they would not fit in a file of
+/// `file_len` bytes.
+fn parse_stsz(stsz: &[u8], file_len: u64) -> Option<Vec<u32>> {
+    let mut r = Reader::new(stsz);
+    r.skip(4)?;
+    let size = r.u32()?;
+    let count = r.u32()?;
+    if size != 0 {
+        if u64::from(size) * u64::from(count) > file_len {
+            return None;
+        }
+        return Some(vec![size; count as usize]);
+    }
+    // A corrupt count must not reserve more than the data can hold.
+    let mut sizes = Vec::with_capacity((count as usize).min(stsz.len() / 4));
+    for _ in 0..count {
+        sizes.push(r.u32()?);
+    }
+    Some(sizes)
+}
+
+/// Entries of a full box whose payload is a 32-bit count and a table.
+fn full_box_entries<T>(
+    data: &[u8],
+    mut entry: impl FnMut(&mut Reader<'_>) -> Option<T>,
+) -> Option<Vec<T>> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let count = r.u32()?;
+    // A corrupt count must not reserve more than the data can hold.
+    let mut entries = Vec::with_capacity((count as usize).min(data.len() / 4));
+    for _ in 0..count {
+        entries.push(entry(&mut r)?);
+    }
+    Some(entries)
+}
+
+/// Length-prefixed NAL units to Annex B. Returns `None` if a length runs
+/// past the end of the sample.
+fn to_annex_b(sample: &[u8], length_size: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(sample.len() + 16);
+    let mut r = Reader::new(sample);
+    while r.pos < sample.len() {
+        let len = r
+            .bytes(length_size)?
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | usize::from(*b));
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(r.bytes(len)?);
+    }
+    Some(out)
+}
+
+/// Big-endian cursor over box payloads.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const SPS: [u8; 4] = [0x67, 0x42, 0x00, 0x1e];
+    const PPS: [u8; 2] = [0x68, 0xce];
+
+    fn mp4_box(kind: &[u8; 4], parts: &[&[u8]]) -> Vec<u8> {
+        let payload = parts.concat();
+        [
+            &(payload.len() as u32 + 8).to_be_bytes()[..],
+            kind,
+            &payload,
+        ]
+        .concat()
+    }
+
+    /// A box with version 0 and no flags.
+    fn full_box(kind: &[u8; 4], parts: &[&[u8]]) -> Vec<u8> {
+        mp4_box(kind, &[&[0u8; 4][..], &parts.concat()])
+    }
+
+    fn table(entries: &[&[u32]]) -> Vec<u8> {
+        let mut out = (entries.len() as u32).to_be_bytes().to_vec();
+        for entry in entries {
+            for value in *entry {
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        out
+    }
+
+    fn trak(
+        timescale: u32,
+        sample_entry: Vec<u8>,
+        samples: &[Vec<u8>],
+        first_offset: u32,
+        delta: u32,
+        extra: &[Vec<u8>],
+        edit: Option<u32>,
+    ) -> Vec<u8> {
+        // sample_size 0, then one size per sample.
+        let stsz: Vec<u32> = [0, samples.len() as u32]
+            .into_iter()
+            .chain(samples.iter().map(|s| s.len() as u32))
+            .collect();
+        let mut stbl = vec![
+            full_box(b"stsd", &[&1u32.to_be_bytes(), &sample_entry]),
+            full_box(b"stts", &[&table(&[&[samples.len() as u32, delta]])]),
+            full_box(b"stsc", &[&table(&[&[1, samples.len() as u32, 1]])]),
+            full_box(b"stsz", &[&table(&[&stsz])[4..]]),
+            full_box(b"stco", &[&table(&[&[first_offset]])]),
+        ];
+        stbl.extend(extra.iter().cloned());
+        let mdhd = full_box(b"mdhd", &[&[0u8; 8], &timescale.to_be_bytes(), &[0u8; 8]]);
+        let minf = mp4_box(b"minf", &[&mp4_box(b"stbl", &[&stbl.concat()])]);
+        let mdia = mp4_box(b"mdia", &[&mdhd, &minf]);
+        let edts = edit.map(|media_time| {
+            mp4_box(
+                b"edts",
+                &[&full_box(
+                    b"elst",
+                    &[&table(&[&[0, media_time, 0x0001_0000]])],
+                )],
+            )
+        });
+        mp4_box(b"trak", &[&edts.unwrap_or_default(), &mdia])
+    }
+
+    /// An MP4 file with four 10 fps H.264 frames (keyframes 0 and 2, one
+    /// frame of composition delay undone by an edit list) and five AAC
+    /// frames at 48 kHz. Sample `n` of each track ends with byte `n`.
+    pub(crate) fn test_mp4() -> Vec<u8> {
+        let video: Vec<Vec<u8>> = (0..4u8)
+            .map(|n| match n % 2 {
+                0 => vec![0, 0, 0, 3, 0x65, 0x88, n],
+                _ => vec![0, 0, 0, 2, 0x41, n],
+            })
+            .collect();
+        let audio: Vec<Vec<u8>> = (0..5u8).map(|n| vec![0x21, n]).collect();
+
+        let ftyp = mp4_box(b"ftyp", &[b"isom", &[0, 0, 2, 0], b"isomavc1"]);
+        let mdat = mp4_box(b"mdat", &[&video.concat(), &audio.concat()]);
+        let video_offset = ftyp.len() as u32 + 8;
+        let audio_offset = video_offset + video.concat().len() as u32;
+
+        let avcc = mp4_box(
+            b"avcC",
+            &[
+                &[1, 0x42, 0x00, 0x1e, 0xff, 0xe1, 0, 4],
+                &SPS,
+                &[1, 0, 2],
+                &PPS,
+            ],
+        );
+        let avc1 = mp4_box(b"avc1", &[&[0u8; VISUAL_SAMPLE_ENTRY_LEN], &avcc]);
+        let ctts = full_box(b"ctts", &[&table(&[&[4, 9000]])]);
+        let stss = full_box(b"stss", &[&table(&[&[1], &[3]])]);
+        let video_trak = trak(
+            90_000,
+            avc1,
+            &video,
+            video_offset,
+            9000,
+            &[ctts, stss],
+            Some(9000),
+        );
+
+        let esds = full_box(
+            b"esds",
+            &[
+                &[3, 22, 0, 1, 0, 4, 17, 0x40, 0x15],
+                &[0u8; 11],
+                &[5, 2, 0x11, 0x90],
+            ],
+        );
+        let mut mp4a_fields = [0u8; 28];
+        mp4a_fields[17] = 2; // channels
+        mp4a_fields[24..26].copy_from_slice(&48000u16.to_be_bytes());
+        let mp4a = mp4_box(b"mp4a", &[&mp4a_fields, &esds]);
+        let audio_trak = trak(48_000, mp4a, &audio, audio_offset, 1024, &[], None);
+
+        let moov = mp4_box(b"moov", &[&audio_trak, &video_trak]);
+        [ftyp, mdat, moov].concat()
+    }
+
+    pub(crate) fn write_test_mp4(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rtsp-rs-{}-{}.mp4", name, std::process::id()));
+        std::fs::write(&path, test_mp4()).unwrap();
+        path
+    }
+
+    fn frames(source: &mut Mp4Source) -> Vec<SourceFrame> {
+        std::iter::from_fn(|| source.next_frame().unwrap()).collect()
+    }
+
+    #[test]
+    fn reads_tracks_and_codec_configuration() {
+        let path = write_test_mp4("tracks");
+        let source = Mp4Source::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            source.tracks(),
+            [
+                TrackCodec::Video {
+                    codec: VideoCodec::H264,
+                    parameter_sets: vec![SPS.to_vec(), PPS.to_vec()],
+                },
+                TrackCodec::Aac(AacConfig::new(2, 48000, 2).unwrap()),
+            ]
+        );
+        assert_eq!(source.duration(), Some(Duration::from_millis(400)));
+    }
+
+    #[test]
+    fn interleaves_samples_by_decode_time() {
+        let path = write_test_mp4("interleave");
+        let mut source = Mp4Source::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let frames = frames(&mut source);
+        let order: Vec<(usize, u8)> = frames
+            .iter()
+            .map(|f| (f.track, *f.data.last().unwrap()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (0, 0),
+                (0, 1),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (1, 3),
+                (1, 4),
+                (0, 2),
+                (0, 3)
+            ]
+        );
+
+        let video: Vec<&SourceFrame> = frames.iter().filter(|f| f.track == 0).collect();
+        assert_eq!(video[1].pts, Duration::from_millis(100));
+        assert_eq!(video[3].dts, Some(Duration::from_millis(200)));
+        assert_eq!(
+            &video[1].data[..],
+            [0, 0, 0, 1, 0x41, 1],
+            "converted to Annex B"
+        );
+        assert_eq!(frames[2].data[..], [0x21, 0]);
+        assert_eq!(frames[2].pts, Duration::ZERO);
+    }
+
+    #[test]
+    fn seeks_every_track_to_the_previous_keyframe() {
+        let path = write_test_mp4("seek");
+        let mut source = Mp4Source::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            source.seek(Duration::from_millis(250)).unwrap(),
+            Duration::from_millis(200)
+        );
+        let frames = frames(&mut source);
+        assert_eq!(frames.len(), 2, "audio before the keyframe skipped");
+        assert!(frames[0].keyframe);
+        assert_eq!(
+            &frames[0].data[..],
+            [
+                &[0, 0, 0, 1][..],
+                &SPS,
+                &[0, 0, 0, 1],
+                &PPS,
+                &[0, 0, 0, 1, 0x65, 0x88, 2]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn sample_counts_are_bounded_by_the_data() {
+        // Version and flags, sample size, sample count: no table.
+        let stsz =
+            |size: u32, count: u32| [[0u8; 4], size.to_be_bytes(), count.to_be_bytes()].concat();
+        assert_eq!(parse_stsz(&stsz(4, 10), 100), Some(vec![4; 10]));
+        assert_eq!(parse_stsz(&stsz(4, u32::MAX), 100), None);
+        assert_eq!(parse_stsz(&stsz(0, u32::MAX), 100), None);
+    }
+
+    #[test]
+    fn rejects_samples_past_the_end_of_the_file() {
+        let file = test_mp4();
+        let moov = boxes(&file).find(|(kind, _)| kind == b"moov").unwrap().1;
+        let (_, trak) = boxes(moov).find(|(kind, _)| kind == b"trak").unwrap();
+        assert!(parse_track(trak, file.len() as u64).is_some());
+        assert!(parse_track(trak, 60).is_none());
+    }
+
+    #[test]
+    fn rejects_boxes_past_the_end_of_the_file() {
+        let ftyp = mp4_box(b"ftyp", &[b"isom"]);
+        let mut oversized_moov = mp4_box(b"moov", &[&[0u8; 8]]);
+        oversized_moov[..4].copy_from_slice(&1000u32.to_be_bytes());
+        // A 64-bit size that overflows the position of the box.
+        let huge_free = [&1u32.to_be_bytes()[..], b"free", &u64::MAX.to_be_bytes()].concat();
+        for (name, file) in [
+            ("oversized-moov", [ftyp.clone(), oversized_moov].concat()),
+            ("huge-box", [ftyp.clone(), huge_free].concat()),
+        ] {
+            let path =
+                std::env::temp_dir().join(format!("rtsp-rs-{name}-{}.mp4", std::process::id()));
+            std::fs::write(&path, file).unwrap();
+            let result = Mp4Source::open(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(
+                matches!(result, Err(RtspError::InvalidMediaFile(_))),
+                "{name}: {:?}",
+                result.map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_files_without_moov() {
+        let path = std::env::temp_dir().join(format!("rtsp-rs-no-moov-{}.mp4", std::process::id()));
+        std::fs::write(&path, mp4_box(b"ftyp", &[b"isom"])).unwrap();
+        let result = Mp4Source::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RtspError::InvalidMediaFile(_))));
+    }
+}
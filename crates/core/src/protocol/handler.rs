@@ -1,24 +1,39 @@
-use crate::mount::MountRegistry;
+use crate::error::RtspError;
+use crate::mount::{self, KeyframeRequestReason, Mount, MountRegistry};
+use crate::protocol::range::{self, Range};
 use crate::protocol::request::RtspRequest;
 use crate::protocol::response::RtspResponse;
 use crate::protocol::sdp;
+use crate::protocol::url::RtspUrl;
 use crate::server::ServerConfig;
 use crate::session::transport::TransportHeader;
-use crate::session::{SessionManager, SessionState, Transport};
+use crate::session::{Session, SessionManager, SessionState, Transport};
+use crate::transport::interleaved::InterleavedSink;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 /// Handles RTSP method requests for a single TCP connection.
 ///
 /// Tracks which sessions were created on this connection so they
-/// can be cleaned up when the connection drops.
+/// can be cleaned up when the connection drops, and so that other
+/// connections cannot control them (see [`ServerConfig::shared_sessions`]).
+///
+/// Session methods are checked against the session's state
+/// ([`SessionState::allows`]) and answered with 455 when not valid in it.
 pub struct MethodHandler {
     session_manager: SessionManager,
     mounts: MountRegistry,
     client_addr: SocketAddr,
+    /// Local address of the connection, whose port is the one the server
+    /// actually listens on.
+    local_addr: Option<SocketAddr>,
     config: Arc<ServerConfig>,
     /// Session IDs created during this connection, for cleanup on disconnect.
     session_ids: Vec<String>,
+    /// The connection's outbound side, if it can carry interleaved RTP.
+    interleaved: Option<Arc<InterleavedSink>>,
+    /// Next free interleaved channel pair on this connection.
+    next_channel: u8,
 }
 
 impl MethodHandler {
@@ -32,11 +47,28 @@ impl MethodHandler {
             session_manager,
             mounts,
             client_addr,
+            local_addr: None,
             config,
             session_ids: Vec::new(),
+            interleaved: None,
+            next_channel: 0,
         }
     }
 
+    /// Accept `RTP/AVP/TCP` transports, delivering their media through
+    /// `sink` (RFC 2326 §10.12).
+    pub(crate) fn with_interleaved_sink(mut self, sink: Arc<InterleavedSink>) -> Self {
+        self.interleaved = Some(sink);
+        self
+    }
+
+    /// Address the connection was accepted on, so URLs in responses carry
+    /// the effective listening port (e.g. after binding port 0).
+    pub(crate) fn with_local_addr(mut self, local_addr: SocketAddr) -> Self {
+        self.local_addr = Some(local_addr);
+        self
+    }
+
     /// Returns session IDs owned by this connection (for cleanup on disconnect).
     pub fn session_ids(&self) -> &[String] {
         &self.session_ids
@@ -68,27 +100,50 @@ impl MethodHandler {
         )
     }
 
-    /// Parses host from an RTSP URI (e.g. rtsp://host:8554/path -> host). Falls back to client IP if invalid.
+    /// Host for the SDP origin and connection lines: the public host, else
+    /// the host of the request URI (`rtsp://[::1]:8554/x` -> `::1`), else
+    /// the client's IP.
     fn host_from_uri_or_client(&self, uri: &str) -> String {
         if let Some(host) = &self.config.public_host {
-            return host.clone();
+            return host
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+                .unwrap_or(host)
+                .to_string();
         }
+        RtspUrl::parse(uri)
+            .map(|url| url.host().to_string())
+            .unwrap_or_else(|| self.client_addr.ip().to_string())
+    }
 
-        if let Some(after_scheme) = uri
-            .strip_prefix("rtsp://")
-            .or_else(|| uri.strip_prefix("rtsps://"))
-        {
-            let host = after_scheme
-                .split('/')
-                .next()
-                .and_then(|host_port| host_port.split(':').next())
-                .unwrap_or("")
-                .trim();
-            if !host.is_empty() {
-                return host.to_string();
-            }
-        }
-        self.client_addr.ip().to_string()
+    /// RTSP port clients should use: [`ServerConfig::public_port`], else
+    /// the port of the request URI (the one the client reached, possibly
+    /// through a port forward), else the port the connection was accepted
+    /// on.
+    fn effective_port(&self, url: &RtspUrl) -> Option<u16> {
+        self.config
+            .public_port
+            .or(url.port())
+            .or(self.local_addr.map(|addr| addr.port()))
+    }
+
+    /// Absolute URL of `mount` as clients should address it, for
+    /// `Content-Base`, `a=control` and `RTP-Info`.
+    ///
+    /// Keeps the scheme and host of the request URI, except that
+    /// [`ServerConfig::public_host`] replaces the host; the port is the
+    /// [effective port](Self::effective_port).
+    fn mount_url(&self, request_uri: &str, mount: &Mount) -> RtspUrl {
+        let url = RtspUrl::parse(request_uri).unwrap_or_else(|| {
+            let host = self.local_addr.unwrap_or(self.client_addr).ip();
+            RtspUrl::new(&host.to_string(), None, "/")
+        });
+        let port = self.effective_port(&url);
+        let url = match &self.config.public_host {
+            Some(host) => url.with_host(host),
+            None => url,
+        };
+        url.with_port(port).with_path(mount.path())
     }
 
     fn handle_describe(&self, cseq: &str, uri: &str) -> RtspResponse {
@@ -103,6 +158,7 @@ impl MethodHandler {
         };
 
         let host = self.host_from_uri_or_client(uri);
+        let url = self.mount_url(uri, &mount);
         let sdp = sdp::generate_sdp(
             &mount,
             &host,
@@ -110,12 +166,19 @@ impl MethodHandler {
             &self.config.sdp_session_version,
             &self.config.sdp_username,
             &self.config.sdp_session_name,
+            Some(&url),
         );
 
+        // A trailing slash, so relative controls resolve under the mount
+        // (RFC 2326 §C.1.1).
+        let mut content_base = url.to_string();
+        if !content_base.ends_with('/') {
+            content_base.push('/');
+        }
         RtspResponse::ok()
             .add_header("CSeq", cseq)
             .add_header("Content-Type", "application/sdp")
-            .add_header("Content-Base", uri)
+            .add_header("Content-Base", &content_base)
             .with_body(sdp)
     }
 
@@ -128,6 +191,13 @@ impl MethodHandler {
             }
         };
 
+        // `<mount>/trackN` selects a track; the aggregate URL means the first.
+        let track_index = mount::extract_track_index(&request.uri).unwrap_or(0);
+        if mount.track(track_index).is_none() {
+            tracing::warn!(uri = %request.uri, track_index, "SETUP for unknown track");
+            return RtspResponse::not_found().add_header("CSeq", cseq);
+        }
+
         let transport_header = match request.get_header("Transport") {
             Some(t) => t,
             None => {
@@ -136,17 +206,6 @@ impl MethodHandler {
             }
         };
 
-        // Only RTP/AVP (UDP) is implemented. TCP interleaved (RTP/AVP/TCP;interleaved=0-1) is not (RFC 2326 §10.12).
-        if transport_header.contains("RTP/AVP/TCP") || transport_header.contains("interleaved=") {
-            tracing::warn!(%cseq, transport = %transport_header, "client requested TCP transport (not implemented)");
-            return RtspResponse::new(461, "Unsupported Transport")
-                .add_header("CSeq", cseq)
-                .add_header(
-                    "Unsupported",
-                    "RTP/AVP/TCP (interleaved) not supported; use RTP/AVP (UDP), e.g. ffplay -rtsp_transport udp <url>",
-                );
-        }
-
         let client_transport = match TransportHeader::parse(transport_header) {
             Some(t) => t,
             None => {
@@ -155,47 +214,135 @@ impl MethodHandler {
             }
         };
 
-        let (server_rtp_port, server_rtcp_port) = match self.session_manager.allocate_server_ports()
-        {
-            Ok(ports) => ports,
-            Err(e) => {
-                tracing::error!(error = %e, "failed to allocate server ports");
-                return RtspResponse::new(500, "Internal Server Error").add_header("CSeq", cseq);
+        // A SETUP carrying a Session header adds a track to that session, or
+        // changes the transport of one already set up (RFC 2326 §10.4);
+        // without one, a new session is created.
+        let existing = match self.extract_session_id(request) {
+            Some(id) => match self.owned_session(&id) {
+                Some(session) => {
+                    let same_mount = self
+                        .mounts
+                        .find_by_session(&id)
+                        .is_some_and(|m| m.path() == mount.path());
+                    if !same_mount {
+                        tracing::warn!(session_id = %id, mount = %mount.path(), "SETUP adds a track from another mount");
+                        return RtspResponse::aggregate_operation_not_allowed()
+                            .add_header("CSeq", cseq);
+                    }
+                    let previous = session.track_transport(track_index);
+                    if previous.is_none() && session.is_playing() {
+                        tracing::warn!(session_id = %id, track_index, "SETUP adds a track to a playing session");
+                        return Self::method_not_valid(cseq, &session.get_state());
+                    }
+                    Some((session, previous))
+                }
+                None => {
+                    tracing::warn!(session_id = %id, "SETUP for unknown session");
+                    return RtspResponse::session_not_found().add_header("CSeq", cseq);
+                }
+            },
+            None => None,
+        };
+        let previous = existing
+            .as_ref()
+            .and_then(|(_, previous)| previous.as_ref());
+
+        let interleaved = match (&self.interleaved, client_transport.tcp) {
+            (_, false) => None,
+            (Some(sink), true) => {
+                // A re-SETUP keeps the track's channels unless the client
+                // asks for others.
+                let channels = client_transport
+                    .interleaved
+                    .or(previous.and_then(|t| t.interleaved))
+                    .unwrap_or((self.next_channel, self.next_channel.wrapping_add(1)));
+                self.next_channel = self.next_channel.max(channels.1.saturating_add(1));
+                Some((sink.clone(), channels))
+            }
+            (None, true) => {
+                tracing::warn!(%cseq, transport = %transport_header, "client requested TCP transport on a connection without interleaving");
+                return RtspResponse::new(461, "Unsupported Transport")
+                    .add_header("CSeq", cseq)
+                    .add_header(
+                        "Unsupported",
+                        "RTP/AVP/TCP (interleaved) not supported; use RTP/AVP (UDP), e.g. ffplay -rtsp_transport udp <url>",
+                    );
+            }
+        };
+
+        let (server_rtp_port, server_rtcp_port) = if interleaved.is_some() {
+            (0, 0)
+        } else {
+            match self.session_manager.allocate_server_ports() {
+                Ok(ports) => ports,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to allocate server ports");
+                    return RtspResponse::new(500, "Internal Server Error")
+                        .add_header("CSeq", cseq);
+                }
             }
         };
 
-        let session = self.session_manager.create_session(&request.uri);
+        let reconfigured = previous.is_some();
+        let session = match existing {
+            Some((session, _)) => session,
+            None => {
+                let session = match self.session_manager.create_session(&request.uri) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to create session");
+                        return RtspResponse::new(500, "Internal Server Error")
+                            .add_header("CSeq", cseq);
+                    }
+                };
+                mount.subscribe(&session.id);
+                self.session_ids.push(session.id.clone());
+                session
+            }
+        };
         let session_id = session.id.clone();
-        let client_rtp_addr =
-            SocketAddr::new(self.client_addr.ip(), client_transport.client_rtp_port);
+        let client_rtp_addr = match interleaved {
+            Some(_) => self.client_addr,
+            None => SocketAddr::new(self.client_addr.ip(), client_transport.client_rtp_port),
+        };
 
-        session.set_transport(Transport {
-            client_rtp_port: client_transport.client_rtp_port,
-            client_rtcp_port: client_transport.client_rtcp_port,
-            server_rtp_port,
-            server_rtcp_port,
-            client_addr: client_rtp_addr,
+        let channels = interleaved.map(|(sink, channels)| {
+            session.set_interleaved_sink(sink);
+            channels
         });
-
-        mount.subscribe(&session_id);
-        self.session_ids.push(session_id.clone());
+        session.set_track_transport(
+            track_index,
+            Transport {
+                client_rtp_port: client_transport.client_rtp_port,
+                client_rtcp_port: client_transport.client_rtcp_port,
+                server_rtp_port,
+                server_rtcp_port,
+                client_addr: client_rtp_addr,
+                interleaved: channels,
+            },
+        );
 
         tracing::info!(
             session_id,
+            track_index,
+            reconfigured,
             mount = %mount.path(),
             uri = %request.uri,
             client_rtp = %client_rtp_addr,
             server_rtp_port,
-            "session created via SETUP"
+            "track set up via SETUP"
         );
 
-        let transport_response = format!(
-            "RTP/AVP;unicast;client_port={}-{};server_port={}-{}",
-            client_transport.client_rtp_port,
-            client_transport.client_rtcp_port,
-            server_rtp_port,
-            server_rtcp_port
-        );
+        let transport_response = match channels {
+            Some((rtp, rtcp)) => format!("RTP/AVP/TCP;unicast;interleaved={rtp}-{rtcp}"),
+            None => format!(
+                "RTP/AVP;unicast;client_port={}-{};server_port={}-{}",
+                client_transport.client_rtp_port,
+                client_transport.client_rtcp_port,
+                server_rtp_port,
+                server_rtcp_port
+            ),
+        };
 
         RtspResponse::ok()
             .add_header("CSeq", cseq)
@@ -204,74 +351,153 @@ impl MethodHandler {
     }
 
     fn handle_play(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "PLAY missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
-            }
+        let session = match self.controlled_session(cseq, request) {
+            Ok(session) => session,
+            Err(response) => return response,
         };
+        let session_id = session.id.clone();
 
-        match self.session_manager.get_session(&session_id) {
-            Some(session) => {
-                session.set_state(SessionState::Playing);
-                tracing::info!(session_id, "session started playing");
+        // A session whose SETUP never completed has nothing to play.
+        if session.transports().is_empty() {
+            tracing::warn!(session_id, "PLAY before any track was set up");
+            return Self::method_not_valid(cseq, &session.get_state());
+        }
 
-                let mut resp = RtspResponse::ok()
-                    .add_header("CSeq", cseq)
-                    .add_header("Session", &session.session_header_value())
-                    .add_header("Range", "npt=0.000-");
-
-                if let Some(mount) = self.mounts.resolve_from_uri(&session.uri) {
-                    let rtp_info = format!(
-                        "url={};seq={};rtptime={}",
-                        session.uri,
-                        mount.next_sequence(),
-                        mount.next_rtp_timestamp()
-                    );
-                    resp = resp.add_header("RTP-Info", &rtp_info);
+        let range = match request.get_header("Range") {
+            Some(value) => match Range::parse(value) {
+                Some(range) => Some(range),
+                None => {
+                    tracing::warn!(session_id, range = value, "PLAY with unparseable Range");
+                    return RtspResponse::invalid_range().add_header("CSeq", cseq);
                 }
+            },
+            None => None,
+        };
+        let (Some(scale), Some(speed)) = (
+            parse_rate(request.get_header("Scale")),
+            parse_rate(request.get_header("Speed")),
+        ) else {
+            tracing::warn!(session_id, "PLAY with invalid Scale or Speed");
+            return RtspResponse::bad_request().add_header("CSeq", cseq);
+        };
+        if speed.is_some_and(|speed| speed < 0.0) {
+            tracing::warn!(session_id, ?speed, "PLAY with negative Speed");
+            return RtspResponse::bad_request().add_header("CSeq", cseq);
+        }
 
-                resp
-            }
-            None => {
-                tracing::warn!(session_id, "PLAY for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
+        let previous_state = session.get_state();
+        let was_playing = previous_state == SessionState::Playing;
+        // Playing before the playhead moves, so the viewer gets the first
+        // frame of the new position.
+        session.set_state(SessionState::Playing);
+
+        let mount = self.mounts.resolve_from_uri(&session.uri);
+        let playback = mount.as_ref().and_then(|mount| mount.playback());
+        let start = match (&mount, &playback) {
+            (Some(mount), Some(playback)) => match playback.play(
+                range.as_ref(),
+                scale.unwrap_or(1.0),
+                speed.unwrap_or(1.0),
+                &mount.tracks(),
+            ) {
+                Ok(start) => Some(start),
+                Err(e) => {
+                    session.set_state(previous_state);
+                    tracing::warn!(session_id, error = %e, "PLAY could not start playback");
+                    let resp = match e {
+                        RtspError::InvalidRange(_) => RtspResponse::invalid_range(),
+                        _ => RtspResponse::new(500, "Internal Server Error"),
+                    };
+                    return resp.add_header("CSeq", cseq);
+                }
+            },
+            _ => None,
+        };
+        tracing::info!(session_id, "session started playing");
+
+        // Live mounts play from "now" at normal rate whatever was asked.
+        let range = match &start {
+            Some(start) => range::format_npt(start.start, start.end),
+            None => "npt=0.000-".to_string(),
+        };
+        let mut resp = RtspResponse::ok()
+            .add_header("CSeq", cseq)
+            .add_header("Session", &session.session_header_value())
+            .add_header("Range", &range);
+        if let Some(scale) = scale {
+            let scale = if start.is_some() { scale } else { 1.0 };
+            resp = resp.add_header("Scale", &scale.to_string());
+        }
+        if let Some(speed) = speed {
+            let speed = if start.is_some() { speed } else { 1.0 };
+            resp = resp.add_header("Speed", &speed.to_string());
+        }
+
+        if let Some(mount) = mount {
+            // One entry per set-up track (RFC 2326 §12.33).
+            let aggregate = mount::extract_track_index(&session.uri).is_none();
+            let base = self.mount_url(&request.uri, &mount);
+            let rtp_info: Vec<String> = session
+                .transports()
+                .into_iter()
+                .filter_map(|(index, _)| mount.track(index))
+                .map(|track| {
+                    let url = if aggregate && track.index() == 0 {
+                        base.clone()
+                    } else {
+                        base.join(&track.control())
+                    };
+                    let (seq, rtptime) = start
+                        .as_ref()
+                        .and_then(|start| start.rtp_info.get(track.index()).copied())
+                        .unwrap_or_else(|| (track.next_sequence(), track.next_rtp_timestamp()));
+                    format!("url={url};seq={seq};rtptime={rtptime}")
+                })
+                .collect();
+            resp = resp.add_header("RTP-Info", &rtp_info.join(","));
+
+            if !was_playing {
+                mount.request_keyframe(KeyframeRequestReason::NewViewer, Some(&session_id));
             }
         }
+
+        resp
     }
 
     fn handle_pause(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "PAUSE missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
-            }
+        let session = match self.controlled_session(cseq, request) {
+            Ok(session) => session,
+            Err(response) => return response,
         };
 
-        match self.session_manager.get_session(&session_id) {
-            Some(session) => {
-                session.set_state(SessionState::Paused);
-                tracing::info!(session_id, "session paused");
-                RtspResponse::ok()
-                    .add_header("CSeq", cseq)
-                    .add_header("Session", &session.session_header_value())
-            }
-            None => {
-                tracing::warn!(session_id, "PAUSE for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
+        session.set_state(SessionState::Paused);
+        tracing::info!(session_id = %session.id, "session paused");
+        let mut resp = RtspResponse::ok()
+            .add_header("CSeq", cseq)
+            .add_header("Session", &session.session_header_value());
+
+        // A seekable mount's playhead stops with its last viewer, keeping
+        // its position for the next PLAY.
+        if let Some(mount) = self.mounts.resolve_from_uri(&session.uri)
+            && let Some(playback) = mount.playback()
+        {
+            let others_playing = mount.subscribed_session_ids().iter().any(|id| {
+                self.session_manager
+                    .get_session(id)
+                    .is_some_and(|s| s.is_playing())
+            });
+            if !others_playing {
+                playback.pause();
             }
+            resp = resp.add_header("Range", &range::format_npt(playback.position(), None));
         }
+        resp
     }
 
     fn handle_teardown(&mut self, cseq: &str, request: &RtspRequest) -> RtspResponse {
-        let session_id = match self.extract_session_id(request) {
-            Some(id) => id,
-            None => {
-                tracing::warn!(%cseq, "TEARDOWN missing Session header");
-                return RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq);
-            }
+        let session_id = match self.controlled_session(cseq, request) {
+            Ok(session) => session.id.clone(),
+            Err(response) => return response,
         };
 
         match self.session_manager.remove_session(&session_id) {
@@ -283,7 +509,7 @@ impl MethodHandler {
             }
             None => {
                 tracing::warn!(session_id, "TEARDOWN for unknown session");
-                RtspResponse::new(454, "Session Not Found").add_header("CSeq", cseq)
+                RtspResponse::session_not_found().add_header("CSeq", cseq)
             }
         }
     }
@@ -295,7 +521,7 @@ impl MethodHandler {
         let mut resp = RtspResponse::ok().add_header("CSeq", cseq);
 
         if let Some(id) = self.extract_session_id(request)
-            && self.session_manager.get_session(&id).is_some()
+            && self.owned_session(&id).is_some()
         {
             resp = resp.add_header("Session", &id);
         }
@@ -303,6 +529,66 @@ impl MethodHandler {
         resp
     }
 
+    /// Look up a session this connection may control: one it created, or
+    /// any session with [`ServerConfig::shared_sessions`].
+    fn owned_session(&self, id: &str) -> Option<Arc<Session>> {
+        let session = self.session_manager.get_session(id)?;
+        if self.config.shared_sessions || self.session_ids.iter().any(|own| own == id) {
+            Some(session)
+        } else {
+            tracing::warn!(session_id = %id, client = %self.client_addr, "session belongs to another connection");
+            None
+        }
+    }
+
+    /// The session a PLAY, PAUSE or TEARDOWN controls, or the response
+    /// refusing it: 454 if the session is unknown or not this connection's,
+    /// 459 if the request URI names another mount, 455 if the method is not
+    /// valid in the session's state (RFC 2326 §A.2).
+    fn controlled_session(
+        &self,
+        cseq: &str,
+        request: &RtspRequest,
+    ) -> Result<Arc<Session>, RtspResponse> {
+        let method = request.method.as_str();
+        let Some(id) = self.extract_session_id(request) else {
+            tracing::warn!(%cseq, method, "missing Session header");
+            return Err(RtspResponse::session_not_found().add_header("CSeq", cseq));
+        };
+        let Some(session) = self.owned_session(&id) else {
+            tracing::warn!(session_id = %id, method, "request for unknown session");
+            return Err(RtspResponse::session_not_found().add_header("CSeq", cseq));
+        };
+
+        // Only a URI naming another registered mount is refused; unknown
+        // paths keep resolving leniently, as for DESCRIBE.
+        let path = mount::extract_mount_path(&request.uri);
+        let path = path
+            .strip_suffix('/')
+            .filter(|p| !p.is_empty())
+            .unwrap_or(path);
+        if let (Some(named), Some(own)) = (self.mounts.get(path), self.mounts.find_by_session(&id))
+            && named.path() != own.path()
+        {
+            tracing::warn!(session_id = %id, method, uri = %request.uri, mount = %own.path(), "request URI names another mount");
+            return Err(RtspResponse::aggregate_operation_not_allowed().add_header("CSeq", cseq));
+        }
+
+        let state = session.get_state();
+        if !state.allows(method) {
+            tracing::warn!(session_id = %id, method, ?state, "method not valid in session state");
+            return Err(Self::method_not_valid(cseq, &state));
+        }
+        Ok(session)
+    }
+
+    /// 455 response listing the methods `state` accepts.
+    fn method_not_valid(cseq: &str, state: &SessionState) -> RtspResponse {
+        RtspResponse::method_not_valid_in_state()
+            .add_header("CSeq", cseq)
+            .add_header("Allow", &state.allowed_methods().join(", "))
+    }
+
     /// Extract session ID from the Session header.
     /// Handles timeout suffix: "SESSIONID;timeout=60" -> "SESSIONID"
     fn extract_session_id(&self, request: &RtspRequest) -> Option<String> {
@@ -311,3 +597,23 @@ impl MethodHandler {
             .map(|s| s.split(';').next().unwrap_or(s).trim().to_string())
     }
 }
+
+/// Largest `Scale` or `Speed` magnitude accepted in PLAY; the smallest is
+/// its inverse. Slower or faster rates are of no use to a player and push
+/// the playhead's timing arithmetic out of range.
+const MAX_RATE: f64 = 64.0;
+
+/// Parse an optional `Scale` or `Speed` value (RFC 2326 §12.34, §12.35).
+/// `None` if present but not a number whose magnitude lies between
+/// `1 / MAX_RATE` and [`MAX_RATE`].
+fn parse_rate(value: Option<&str>) -> Option<Option<f64>> {
+    match value {
+        None => Some(None),
+        Some(value) => value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|rate| (1.0 / MAX_RATE..=MAX_RATE).contains(&rate.abs()))
+            .map(Some),
+    }
+}
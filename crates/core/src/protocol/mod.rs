@@ -1,7 +1,9 @@
 //! RTSP protocol implementation (RFC 2326).
 //!
 //! This module handles the text-based RTSP signaling protocol — parsing
-//! requests, building responses, routing methods, and generating SDP.
+//! requests, building responses, routing methods, generating SDP,
+//! parsing PLAY ranges ([`range`]), and composing the absolute URLs
+//! advertised to clients ([`url`]).
 //!
 //! ## RTSP message format (RFC 2326 §4)
 //!
@@ -32,10 +34,13 @@
 //! | GET_PARAMETER | §10.8 | Keepalive / parameter query |
 
 pub mod handler;
+pub mod range;
 pub mod request;
 pub mod response;
 pub mod sdp;
+pub mod url;
 
 pub use handler::MethodHandler;
 pub use request::RtspRequest;
 pub use response::RtspResponse;
+pub use url::RtspUrl;
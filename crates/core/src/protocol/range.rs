@@ -0,0 +1,271 @@
+//! `Range` header parsing and formatting (RFC 2326 §3.5–3.7, §12.29).
+//!
+//! ```text
+//! Range: npt=12.5-60
+//! Range: npt=now-
+//! Range: smpte=0:10:00-0:10:33:05.01
+//! Range: smpte-30-drop=0:10:00:02-
+//! Range: clock=19961108T143720.25Z-
+//! ```
+//!
+//! NPT and SMPTE times are relative to the start of the presentation and
+//! are converted to a [`Duration`]; `clock` times are absolute UTC and need
+//! the presentation's start time to be turned into a position.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One end of a [`Range`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeTime {
+    /// Offset from the start of the presentation (NPT or SMPTE).
+    Npt(Duration),
+    /// `npt=now`: the live point.
+    Now,
+    /// Absolute UTC time (`clock=`).
+    Clock(SystemTime),
+}
+
+/// A parsed `Range` header. Either end may be open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Option<RangeTime>,
+    pub end: Option<RangeTime>,
+}
+
+impl Range {
+    /// Parse a `Range` header value. A `;time=` parameter, which asks for
+    /// the range to take effect later, is ignored. Returns `None` for
+    /// unknown units and malformed times.
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.split(';').next()?.trim();
+        let (unit, times) = spec.split_once('=')?;
+        let (start, end) = times.split_once('-')?;
+        let parse_time = |time: &str| -> Option<Option<RangeTime>> {
+            let time = time.trim();
+            if time.is_empty() {
+                return Some(None);
+            }
+            let parsed = match unit.trim().to_ascii_lowercase().as_str() {
+                "npt" => parse_npt(time)?,
+                "smpte" => RangeTime::Npt(parse_smpte(time, 30, false)?),
+                "smpte-25" => RangeTime::Npt(parse_smpte(time, 25, false)?),
+                "smpte-30-drop" => RangeTime::Npt(parse_smpte(time, 30, true)?),
+                "clock" => RangeTime::Clock(parse_clock(time)?),
+                _ => return None,
+            };
+            Some(Some(parsed))
+        };
+        let range = Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        };
+        (range.start.is_some() || range.end.is_some()).then_some(range)
+    }
+}
+
+/// Format an NPT range for a response, e.g. `npt=12.000-60.000`.
+pub fn format_npt(start: Duration, end: Option<Duration>) -> String {
+    match end {
+        Some(end) => format!("npt={:.3}-{:.3}", start.as_secs_f64(), end.as_secs_f64()),
+        None => format!("npt={:.3}-", start.as_secs_f64()),
+    }
+}
+
+/// `now`, seconds (`123.45`) or `hh:mm:ss[.fraction]`.
+fn parse_npt(time: &str) -> Option<RangeTime> {
+    if time.eq_ignore_ascii_case("now") {
+        return Some(RangeTime::Now);
+    }
+    let seconds = match time.split(':').collect::<Vec<_>>()[..] {
+        [seconds] => seconds.parse::<f64>().ok()?,
+        [hours, minutes, seconds] => {
+            let minutes = minutes.parse::<u8>().ok().filter(|m| *m < 60)?;
+            let seconds = seconds.parse::<f64>().ok().filter(|s| *s < 60.0)?;
+            hours.parse::<u64>().ok()? as f64 * 3600.0 + f64::from(minutes) * 60.0 + seconds
+        }
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .map(RangeTime::Npt)
+}
+
+/// `hh:mm:ss[:frames][.subframes]` at `fps` frames per second. Drop-frame
+/// timecode counts 30 fps labels on a 29.97 fps clock, skipping frame
+/// labels 0 and 1 of every minute not divisible by ten.
+fn parse_smpte(time: &str, fps: u64, drop_frame: bool) -> Option<Duration> {
+    let (time, subframes) = match time.split_once('.') {
+        Some((time, sub)) => (time, sub.parse::<u64>().ok().filter(|s| *s < 100)?),
+        None => (time, 0),
+    };
+    let fields: Vec<u64> = time
+        .split(':')
+        .map(|f| f.parse().ok())
+        .collect::<Option<_>>()?;
+    let (hours, minutes, seconds, frames) = match fields[..] {
+        [h, m, s] => (h, m, s, 0),
+        [h, m, s, f] => (h, m, s, f),
+        _ => return None,
+    };
+    if minutes >= 60 || seconds >= 60 || frames >= fps {
+        return None;
+    }
+
+    let total_minutes = hours.checked_mul(60)?.checked_add(minutes)?;
+    let mut frame_number = total_minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(fps)?
+        .checked_add(frames)?;
+    if drop_frame {
+        frame_number = frame_number.checked_sub(2 * (total_minutes - total_minutes / 10))?;
+    }
+    // Nanoseconds per frame: 1/fps, or 1001/30000 s for drop-frame.
+    let (num, den) = if drop_frame { (1001, 30_000) } else { (1, fps) };
+    let nanos = (u128::from(frame_number) * 100 + u128::from(subframes)) * num * 1_000_000_000
+        / (u128::from(den) * 100);
+    Some(Duration::from_nanos(u64::try_from(nanos).ok()?))
+}
+
+/// ISO 8601 basic format UTC, `YYYYMMDDThhmmss[.fraction]Z`.
+fn parse_clock(time: &str) -> Option<SystemTime> {
+    let time = time.strip_suffix('Z')?;
+    let (date, clock) = time.split_once('T')?;
+    let (clock, fraction) = match clock.split_once('.') {
+        Some((clock, fraction))
+            if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            let fraction = format!("0.{fraction}").parse::<f64>().ok()?;
+            (clock, Duration::try_from_secs_f64(fraction).ok()?)
+        }
+        Some(_) => return None,
+        None => (clock, Duration::ZERO),
+    };
+    if date.len() != 8
+        || clock.len() != 6
+        || !(date.bytes().chain(clock.bytes())).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let num = |s: &str| s.parse::<u64>().ok();
+    let (year, month, day) = (num(&date[..4])?, num(&date[4..6])?, num(&date[6..])?);
+    let (hour, minute, second) = (num(&clock[..2])?, num(&clock[2..4])?, num(&clock[4..])?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day)?;
+    let seconds = days
+        .checked_mul(86_400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?;
+    UNIX_EPOCH
+        .checked_add(Duration::from_secs(seconds))?
+        .checked_add(fraction)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (H. Hinnant's
+/// `days_from_civil`). `None` for dates before 1970.
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    if year < 1970 {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npt(seconds: f64) -> Option<RangeTime> {
+        Some(RangeTime::Npt(Duration::from_secs_f64(seconds)))
+    }
+
+    #[test]
+    fn parses_npt_ranges() {
+        let range = Range::parse("npt=12.5-60").unwrap();
+        assert_eq!(range.start, npt(12.5));
+        assert_eq!(range.end, npt(60.0));
+
+        let range = Range::parse("npt=1:02:03.5-;time=19970123T143720Z").unwrap();
+        assert_eq!(range.start, npt(3723.5));
+        assert_eq!(range.end, None);
+
+        assert_eq!(
+            Range::parse("npt=now-").unwrap().start,
+            Some(RangeTime::Now)
+        );
+        assert_eq!(Range::parse("npt=-20").unwrap().end, npt(20.0));
+        assert!(Range::parse("npt=-").is_none());
+        assert!(Range::parse("npt=abc-").is_none());
+        assert!(Range::parse("frames=1-2").is_none());
+    }
+
+    #[test]
+    fn parses_smpte_ranges() {
+        let range = Range::parse("smpte=0:10:00-0:10:33:15").unwrap();
+        assert_eq!(range.start, npt(600.0));
+        assert_eq!(range.end, npt(633.5));
+
+        let range = Range::parse("smpte-25=0:00:01:05.50-").unwrap();
+        assert_eq!(
+            range.start,
+            Some(RangeTime::Npt(Duration::from_millis(1220)))
+        );
+
+        // Minute one of drop-frame timecode starts at label 02, frame 1800.
+        let range = Range::parse("smpte-30-drop=0:01:00:02-").unwrap();
+        assert_eq!(
+            range.start,
+            Some(RangeTime::Npt(Duration::from_nanos(
+                1800 * 1001 * 1_000_000 / 30
+            )))
+        );
+        assert!(Range::parse("smpte=0:00:00:30-").is_none());
+    }
+
+    #[test]
+    fn parses_clock_ranges() {
+        let range = Range::parse("clock=19961108T143720.25Z-").unwrap();
+        let expected = UNIX_EPOCH + Duration::from_secs(847_463_840) + Duration::from_millis(250);
+        assert_eq!(range.start, Some(RangeTime::Clock(expected)));
+        assert!(Range::parse("clock=19961308T143720Z-").is_none());
+        assert!(Range::parse("clock=19961108T143720-").is_none());
+    }
+
+    #[test]
+    fn rejects_times_out_of_range() {
+        for header in [
+            "clock=19961108T143720.5e400Z-",
+            "clock=19961108T143720.Z-",
+            "clock=00000101T000000Z-",
+            "clock=19691231T235959Z-",
+            "smpte=999999999999999999:00:00-",
+            "smpte-30-drop=307445734561825860:00:00-",
+        ] {
+            assert!(Range::parse(header).is_none(), "{header}");
+        }
+    }
+
+    #[test]
+    fn formats_npt() {
+        assert_eq!(
+            format_npt(Duration::from_millis(12_500), None),
+            "npt=12.500-"
+        );
+        assert_eq!(
+            format_npt(Duration::ZERO, Some(Duration::from_secs(60))),
+            "npt=0.000-60.000"
+        );
+    }
+}
@@ -51,6 +51,30 @@ impl RtspResponse {
         Self::new(400, "Bad Request")
     }
 
+    /// 454 Session Not Found — unknown session ID, or one this connection
+    /// may not control (RFC 2326 §11.3.7).
+    pub fn session_not_found() -> Self {
+        Self::new(454, "Session Not Found")
+    }
+
+    /// 455 Method Not Valid in This State (RFC 2326 §11.3.8). Add an
+    /// `Allow` header listing the methods that are.
+    pub fn method_not_valid_in_state() -> Self {
+        Self::new(455, "Method Not Valid in This State")
+    }
+
+    /// 457 Invalid Range — the `Range` is malformed or outside the
+    /// presentation (RFC 2326 §11.3.14).
+    pub fn invalid_range() -> Self {
+        Self::new(457, "Invalid Range")
+    }
+
+    /// 459 Aggregate Operation Not Allowed — the request URI names a
+    /// presentation other than the session's (RFC 2326 §11.3.12).
+    pub fn aggregate_operation_not_allowed() -> Self {
+        Self::new(459, "Aggregate Operation Not Allowed")
+    }
+
     pub fn add_header(mut self, name: &str, value: &str) -> Self {
         self.headers.push((name.to_string(), value.to_string()));
         self
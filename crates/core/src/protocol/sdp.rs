@@ -4,13 +4,14 @@
 //!
 //! ```text
 //! v=0                                          ← protocol version
-//! o=<user> <sess-id> <sess-ver> IN IP4 <addr>  ← origin
+//! o=<user> <sess-id> <sess-ver> IN IP4 <addr>  ← origin (IP6 for IPv6 addresses)
 //! s=<session-name>                              ← session name
 //! c=IN IP4 <addr>                               ← connection address
 //! t=0 0                                         ← timing (live stream)
 //! a=tool:rtsp-rs                                ← server software (§6)
 //! a=sendonly                                    ← direction (§6)
-//! m=video 0 RTP/AVP 96                          ← media description
+//! a=range:npt=0-60.000                          ← length (seekable mounts only, RFC 2326 §C.1.5)
+//! m=video 0 RTP/AVP 96                          ← media description (type from packetizer)
 //! a=rtpmap:96 H264/90000                        ← codec/clock rate
 //! a=fmtp:96 packetization-mode=1[;profile-level-id=...][;sprop-parameter-sets=...]  ← H.264 params (RFC 6184 §8.1)
 //! a=control:track1                              ← track control URL
@@ -19,13 +20,21 @@
 //! For H.264, when SPS/PPS have been auto-captured from the first keyframe, the fmtp line
 //! also includes `profile-level-id` and `sprop-parameter-sets`. All session/origin fields
 //! come from [`ServerConfig`](crate::ServerConfig); nothing is hardcoded.
+//!
+//! A mount with several [tracks](crate::mount::Track) gets one `m=` section per track,
+//! each with its own `a=control:trackN`. Given the mount's URL, controls are absolute
+//! (`a=control:rtsp://host:8554/stream/track1`) so clients need not resolve them
+//! against `Content-Base`.
 
 use crate::mount::Mount;
+use crate::protocol::url::RtspUrl;
 
 /// Generate an SDP session description for the given mount.
 ///
-/// When multi-track (audio+video) support is added, this will iterate
-/// over the mount's tracks to produce multiple `m=` lines.
+/// Emits one media section per track. The packetizer's own `a=control`
+/// attribute is replaced by the track's control (`track1`, `track2`, ...),
+/// so packetizers don't need to know their position in the mount. With
+/// `base_url` (the mount's URL) the controls are absolute URLs.
 pub fn generate_sdp(
     mount: &Mount,
     ip: &str,
@@ -33,21 +42,44 @@ pub fn generate_sdp(
     session_version: &str,
     username: &str,
     session_name: &str,
+    base_url: Option<&RtspUrl>,
 ) -> String {
     let mut sdp: Vec<String> = Vec::new();
+    // Host names go under IP4 too (RFC 4566 §5.2); only IPv6 literals
+    // contain colons.
+    let addr_type = if ip.contains(':') { "IP6" } else { "IP4" };
 
     sdp.push("v=0".to_string());
     sdp.push(format!(
-        "o={} {} {} IN IP4 {}",
-        username, session_id, session_version, ip
+        "o={} {} {} IN {} {}",
+        username, session_id, session_version, addr_type, ip
     ));
     sdp.push(format!("s={}", session_name));
-    sdp.push(format!("c=IN IP4 {}", ip));
+    sdp.push(format!("c=IN {} {}", addr_type, ip));
     sdp.push("t=0 0".to_string());
     sdp.push("a=tool:rtsp-rs".to_string());
     sdp.push("a=sendonly".to_string());
-    sdp.push(format!("m=video 0 RTP/AVP {}", mount.payload_type()));
-    sdp.extend_from_slice(&mount.sdp_attributes()[0..]);
+    if let Some(duration) = mount.playback().and_then(|p| p.duration()) {
+        sdp.push(format!("a=range:npt=0-{:.3}", duration.as_secs_f64()));
+    }
+    for track in mount.tracks() {
+        sdp.push(format!(
+            "m={} 0 RTP/AVP {}",
+            track.media_type(),
+            track.payload_type()
+        ));
+        sdp.extend(
+            track
+                .sdp_attributes()
+                .into_iter()
+                .filter(|attr| !attr.starts_with("a=control:")),
+        );
+        let control = match base_url {
+            Some(base) => base.join(&track.control()).to_string(),
+            None => track.control(),
+        };
+        sdp.push(format!("a=control:{}", control));
+    }
 
     tracing::debug!("SDP: {}", sdp.join("\r\n"));
 
@@ -58,6 +90,10 @@ pub fn generate_sdp(
 mod tests {
     use super::*;
     use crate::media::h264::H264Packetizer;
+    use crate::media::mp2t::{Mp2tInput, Mp2tPacketizer};
+    use crate::media::onvif::OnvifMetadataPacketizer;
+    use crate::media::opus::OpusPacketizer;
+    use crate::media::pcm::PcmPacketizer;
 
     #[test]
     fn generates_h264_sdp() {
@@ -69,6 +105,7 @@ mod tests {
             "1",
             "server",
             "Test Session",
+            None,
         );
         assert!(sdp.contains("v=0\r\n"));
         assert!(sdp.contains("o=server 1234567890 1 IN IP4 192.168.1.100\r\n"));
@@ -112,6 +149,68 @@ mod tests {
         assert!(sdp.ends_with("\r\n"), "SDP must end with CRLF");
     }
 
+    #[test]
+    fn generates_opus_audio_sdp() {
+        let mount = Mount::new(
+            "/intercom",
+            Box::new(OpusPacketizer::new(111, 0x12345678).with_inband_fec(true)),
+        );
+        let sdp = generate_sdp(&mount, "10.0.0.1", "1", "1", "-", "Intercom", None);
+        assert!(sdp.contains("m=audio 0 RTP/AVP 111\r\n"));
+        assert!(!sdp.contains("m=video"));
+        assert!(sdp.contains("a=rtpmap:111 opus/48000/2\r\n"));
+        assert!(sdp.contains("a=fmtp:111 sprop-stereo=0;useinbandfec=1\r\n"));
+    }
+
+    #[test]
+    fn generates_pcmu_static_payload_sdp() {
+        let mount = Mount::new("/talkback", Box::new(PcmPacketizer::pcmu(0x12345678)));
+        let sdp = generate_sdp(&mount, "10.0.0.1", "1", "1", "-", "Talkback", None);
+        assert!(sdp.contains("m=audio 0 RTP/AVP 0\r\n"));
+        assert!(sdp.contains("a=rtpmap:0 PCMU/8000\r\n"));
+        assert!(
+            !sdp.contains("a=fmtp"),
+            "static payload types carry no fmtp"
+        );
+    }
+
+    #[test]
+    fn generates_one_media_section_per_track() {
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 0x12345678)));
+        mount.add_track(Box::new(OnvifMetadataPacketizer::new(107, 0x1234)));
+        let sdp = generate_sdp(&mount, "10.0.0.1", "1", "1", "-", "Camera", None);
+
+        let video = sdp.find("m=video 0 RTP/AVP 96\r\n").expect("video section");
+        let metadata = sdp
+            .find("m=application 0 RTP/AVP 107\r\n")
+            .expect("metadata section");
+        assert!(video < metadata);
+        assert!(sdp.contains("a=rtpmap:107 vnd.onvif.metadata/90000\r\n"));
+        assert_eq!(sdp.matches("a=control:track1\r\n").count(), 1);
+        assert!(sdp[metadata..].contains("a=control:track2\r\n"));
+    }
+
+    #[test]
+    fn absolute_controls_with_base_url() {
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 0x12345678)));
+        mount.add_track(Box::new(OnvifMetadataPacketizer::new(107, 0x1234)));
+        let base = RtspUrl::new("203.0.113.7", Some(18554), "/cam");
+        let sdp = generate_sdp(&mount, "203.0.113.7", "1", "1", "-", "Camera", Some(&base));
+        assert!(sdp.contains("a=control:rtsp://203.0.113.7:18554/cam/track1\r\n"));
+        assert!(sdp.contains("a=control:rtsp://203.0.113.7:18554/cam/track2\r\n"));
+    }
+
+    #[test]
+    fn generates_mp2t_sdp() {
+        let mount = Mount::new(
+            "/iptv",
+            Box::new(Mp2tPacketizer::new(0x12345678, Mp2tInput::Ts)),
+        );
+        let sdp = generate_sdp(&mount, "10.0.0.1", "1", "1", "-", "IPTV", None);
+        assert!(sdp.contains("m=video 0 RTP/AVP 33\r\n"));
+        assert!(sdp.contains("a=rtpmap:33 MP2T/90000\r\n"));
+    }
+
     #[test]
     fn generates_h264_sdp_with_sps_pps() {
         // After the mount packetizes a frame containing SPS/PPS, full SDP includes fmtp params.
@@ -126,7 +225,7 @@ mod tests {
             &[0, 0, 0, 1, 0x65, 0x88, 0x00][..],
         ]
         .concat();
-        mount.packetize(&frame, 3000);
+        mount.packetize(frame.into(), 3000);
         let sdp = generate_sdp(
             &mount,
             "192.168.1.100",
@@ -134,6 +233,7 @@ mod tests {
             "1",
             "server",
             "Test Session",
+            None,
         );
         assert!(
             sdp.contains("profile-level-id="),
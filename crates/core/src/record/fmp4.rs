@@ -0,0 +1,529 @@
+//! Fragmented MP4 writing (ISO/IEC 14496-12 §8.8, ISO/IEC 14496-15).
+//!
+//! A file is an init segment (`ftyp` + `moov` with empty sample tables and
+//! an `mvex`) followed by `moof` + `mdat` fragments. Nothing is rewritten
+//! once written, so a file cut short by a crash stays playable up to its
+//! last complete fragment.
+
+use bytes::Bytes;
+
+use crate::media::aac::AacConfig;
+use crate::media::{h264, h265};
+
+/// Codec configuration of a recorded track: the sample entry in `stsd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SampleEntry {
+    /// `avc1` with an `avcC` built from the SPS and PPS.
+    Avc {
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+        info: h264::SequenceParameterSet,
+    },
+    /// `hvc1` with an `hvcC` built from the VPS, SPS and PPS.
+    Hevc {
+        vps: Vec<u8>,
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+        info: h265::SequenceParameterSet,
+    },
+    /// `mp4a` with an `esds` carrying the `AudioSpecificConfig`.
+    Aac(AacConfig),
+}
+
+impl SampleEntry {
+    fn is_video(&self) -> bool {
+        !matches!(self, Self::Aac(_))
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Avc { info, .. } => (info.width, info.height),
+            Self::Hevc { info, .. } => (info.width, info.height),
+            Self::Aac(_) => (0, 0),
+        }
+    }
+}
+
+/// One track of the init segment.
+#[derive(Debug, Clone)]
+pub(crate) struct TrackHeader {
+    /// `track_ID`, from 1.
+    pub id: u32,
+    /// Media timescale: the track's RTP clock rate.
+    pub timescale: u32,
+    pub entry: SampleEntry,
+}
+
+/// One sample of a fragment. `data` is in MP4 form: length-prefixed NAL
+/// units for video, a raw access unit for AAC.
+#[derive(Debug, Clone)]
+pub(crate) struct Sample {
+    pub data: Bytes,
+    pub duration: u32,
+    /// Presentation minus decode time, in timescale units.
+    pub composition_offset: i32,
+    pub keyframe: bool,
+}
+
+/// The samples of one track in a fragment.
+pub(crate) struct TrackRun<'a> {
+    pub track_id: u32,
+    /// Decode time of the first sample (`tfdt`).
+    pub decode_time: u64,
+    pub samples: &'a [Sample],
+}
+
+/// `sample_flags` (§8.8.3.1) of sync samples: depends on no other sample.
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// `sample_flags` of other samples: depends on others, not a sync sample.
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// `ftyp` and `moov` for `tracks`.
+pub(crate) fn init_segment(tracks: &[TrackHeader]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_box(&mut out, b"ftyp", |b| {
+        b.extend_from_slice(b"iso6");
+        b.extend_from_slice(&0u32.to_be_bytes());
+        for brand in [b"iso6", b"cmfc", b"mp41"] {
+            b.extend_from_slice(brand);
+        }
+    });
+    write_box(&mut out, b"moov", |b| {
+        write_full_box(b, b"mvhd", 0, 0, |b| {
+            b.extend_from_slice(&[0; 8]); // creation and modification time
+            b.extend_from_slice(&1000u32.to_be_bytes());
+            b.extend_from_slice(&0u32.to_be_bytes()); // duration: in fragments
+            b.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+            b.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+            b.extend_from_slice(&[0; 10]);
+            write_matrix(b);
+            b.extend_from_slice(&[0; 24]); // pre_defined
+            let next_track_id = tracks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+            b.extend_from_slice(&next_track_id.to_be_bytes());
+        });
+        for track in tracks {
+            write_trak(b, track);
+        }
+        write_box(b, b"mvex", |b| {
+            for track in tracks {
+                write_full_box(b, b"trex", 0, 0, |b| {
+                    b.extend_from_slice(&track.id.to_be_bytes());
+                    b.extend_from_slice(&1u32.to_be_bytes()); // sample description index
+                    b.extend_from_slice(&[0; 12]); // default duration, size, flags
+                });
+            }
+        });
+    });
+    out
+}
+
+/// `moof` and `mdat` for fragment number `sequence` (from 1).
+pub(crate) fn fragment(sequence: u32, runs: &[TrackRun<'_>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    // Positions of each trun's data_offset, patched once the moof size is known.
+    let mut data_offsets = Vec::new();
+    write_box(&mut out, b"moof", |b| {
+        write_full_box(b, b"mfhd", 0, 0, |b| {
+            b.extend_from_slice(&sequence.to_be_bytes());
+        });
+        for run in runs {
+            write_box(b, b"traf", |b| {
+                // default-base-is-moof
+                write_full_box(b, b"tfhd", 0, 0x02_0000, |b| {
+                    b.extend_from_slice(&run.track_id.to_be_bytes());
+                });
+                write_full_box(b, b"tfdt", 1, 0, |b| {
+                    b.extend_from_slice(&run.decode_time.to_be_bytes());
+                });
+                let with_offsets = run.samples.iter().any(|s| s.composition_offset != 0);
+                // data-offset, sample duration, size and flags, composition offsets
+                let flags = 0x01 | 0x100 | 0x200 | 0x400 | if with_offsets { 0x800 } else { 0 };
+                write_full_box(b, b"trun", 1, flags, |b| {
+                    b.extend_from_slice(&(run.samples.len() as u32).to_be_bytes());
+                    data_offsets.push(b.len());
+                    b.extend_from_slice(&0u32.to_be_bytes());
+                    for sample in run.samples {
+                        b.extend_from_slice(&sample.duration.to_be_bytes());
+                        b.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+                        let flags = if sample.keyframe {
+                            SYNC_SAMPLE_FLAGS
+                        } else {
+                            NON_SYNC_SAMPLE_FLAGS
+                        };
+                        b.extend_from_slice(&flags.to_be_bytes());
+                        if with_offsets {
+                            b.extend_from_slice(&sample.composition_offset.to_be_bytes());
+                        }
+                    }
+                });
+            });
+        }
+    });
+
+    // Sample data starts after the moof and the mdat header.
+    let mut offset = out.len() + 8;
+    for (position, run) in data_offsets.into_iter().zip(runs) {
+        out[position..position + 4].copy_from_slice(&(offset as u32).to_be_bytes());
+        offset += run.samples.iter().map(|s| s.data.len()).sum::<usize>();
+    }
+    write_box(&mut out, b"mdat", |b| {
+        for sample in runs.iter().flat_map(|run| run.samples) {
+            b.extend_from_slice(&sample.data);
+        }
+    });
+    out
+}
+
+fn write_trak(out: &mut Vec<u8>, track: &TrackHeader) {
+    let video = track.entry.is_video();
+    let (width, height) = track.entry.dimensions();
+    write_box(out, b"trak", |b| {
+        // track_enabled | track_in_movie
+        write_full_box(b, b"tkhd", 0, 0x03, |b| {
+            b.extend_from_slice(&[0; 8]); // creation and modification time
+            b.extend_from_slice(&track.id.to_be_bytes());
+            b.extend_from_slice(&[0; 4]);
+            b.extend_from_slice(&0u32.to_be_bytes()); // duration
+            b.extend_from_slice(&[0; 8]);
+            b.extend_from_slice(&0u16.to_be_bytes()); // layer
+            b.extend_from_slice(&0u16.to_be_bytes()); // alternate group
+            let volume: u16 = if video { 0 } else { 0x0100 };
+            b.extend_from_slice(&volume.to_be_bytes());
+            b.extend_from_slice(&[0; 2]);
+            write_matrix(b);
+            b.extend_from_slice(&(width << 16).to_be_bytes());
+            b.extend_from_slice(&(height << 16).to_be_bytes());
+        });
+        write_box(b, b"mdia", |b| {
+            write_full_box(b, b"mdhd", 0, 0, |b| {
+                b.extend_from_slice(&[0; 8]);
+                b.extend_from_slice(&track.timescale.to_be_bytes());
+                b.extend_from_slice(&0u32.to_be_bytes());
+                b.extend_from_slice(&0x55c4u16.to_be_bytes()); // language "und"
+                b.extend_from_slice(&[0; 2]);
+            });
+            write_full_box(b, b"hdlr", 0, 0, |b| {
+                b.extend_from_slice(&[0; 4]);
+                b.extend_from_slice(if video { b"vide" } else { b"soun" });
+                b.extend_from_slice(&[0; 12]);
+                b.extend_from_slice(if video {
+                    b"VideoHandler\0"
+                } else {
+                    b"SoundHandler\0"
+                });
+            });
+            write_box(b, b"minf", |b| {
+                if video {
+                    write_full_box(b, b"vmhd", 0, 1, |b| b.extend_from_slice(&[0; 8]));
+                } else {
+                    write_full_box(b, b"smhd", 0, 0, |b| b.extend_from_slice(&[0; 4]));
+                }
+                write_box(b, b"dinf", |b| {
+                    write_full_box(b, b"dref", 0, 0, |b| {
+                        b.extend_from_slice(&1u32.to_be_bytes());
+                        // Media data in this file.
+                        write_full_box(b, b"url ", 0, 1, |_| {});
+                    });
+                });
+                write_box(b, b"stbl", |b| {
+                    write_full_box(b, b"stsd", 0, 0, |b| {
+                        b.extend_from_slice(&1u32.to_be_bytes());
+                        write_sample_entry(b, &track.entry);
+                    });
+                    for kind in [b"stts", b"stsc", b"stco"] {
+                        write_full_box(b, kind, 0, 0, |b| b.extend_from_slice(&[0; 4]));
+                    }
+                    write_full_box(b, b"stsz", 0, 0, |b| b.extend_from_slice(&[0; 8]));
+                });
+            });
+        });
+    });
+}
+
+fn write_sample_entry(out: &mut Vec<u8>, entry: &SampleEntry) {
+    let kind = match entry {
+        SampleEntry::Avc { .. } => b"avc1",
+        SampleEntry::Hevc { .. } => b"hvc1",
+        SampleEntry::Aac(_) => b"mp4a",
+    };
+    write_box(out, kind, |b| {
+        b.extend_from_slice(&[0; 6]);
+        b.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+        match entry {
+            SampleEntry::Avc { .. } | SampleEntry::Hevc { .. } => {
+                let (width, height) = entry.dimensions();
+                b.extend_from_slice(&[0; 16]);
+                b.extend_from_slice(&(width as u16).to_be_bytes());
+                b.extend_from_slice(&(height as u16).to_be_bytes());
+                b.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+                b.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+                b.extend_from_slice(&[0; 4]);
+                b.extend_from_slice(&1u16.to_be_bytes()); // frame count
+                b.extend_from_slice(&[0; 32]); // compressor name
+                b.extend_from_slice(&0x0018u16.to_be_bytes()); // depth
+                b.extend_from_slice(&(-1i16).to_be_bytes());
+            }
+            SampleEntry::Aac(config) => {
+                b.extend_from_slice(&[0; 8]);
+                b.extend_from_slice(&u16::from(config.channels.max(1)).to_be_bytes());
+                b.extend_from_slice(&16u16.to_be_bytes()); // sample size
+                b.extend_from_slice(&[0; 4]);
+                // 16.16 fixed point; rates above 65535 Hz don't fit and are
+                // taken from the AudioSpecificConfig instead.
+                let rate = if config.sample_rate > 0xffff {
+                    0
+                } else {
+                    config.sample_rate << 16
+                };
+                b.extend_from_slice(&rate.to_be_bytes());
+            }
+        }
+        match entry {
+            SampleEntry::Avc { sps, pps, info } => write_avcc(b, sps, pps, info),
+            SampleEntry::Hevc {
+                vps,
+                sps,
+                pps,
+                info,
+            } => write_hvcc(b, vps, sps, pps, info),
+            SampleEntry::Aac(config) => write_esds(b, config),
+        }
+    });
+}
+
+/// `AVCDecoderConfigurationRecord` (ISO/IEC 14496-15 §5.3.3.1).
+fn write_avcc(out: &mut Vec<u8>, sps: &[u8], pps: &[u8], info: &h264::SequenceParameterSet) {
+    write_box(out, b"avcC", |b| {
+        b.extend_from_slice(&[
+            1,
+            info.profile_idc,
+            info.constraint_flags,
+            info.level_idc,
+            0xfc | 3, // lengthSizeMinusOne: 4-byte NAL lengths
+            0xe0 | 1, // one SPS
+        ]);
+        write_nal(b, sps);
+        b.push(1);
+        write_nal(b, pps);
+        if matches!(info.profile_idc, 100 | 110 | 122 | 144) {
+            b.extend_from_slice(&[
+                0xfc | info.chroma_format_idc,
+                0xf8 | info.bit_depth_luma.saturating_sub(8),
+                0xf8 | info.bit_depth_chroma.saturating_sub(8),
+                0, // no SPS extensions
+            ]);
+        }
+    });
+}
+
+/// `HEVCDecoderConfigurationRecord` (ISO/IEC 14496-15 §8.3.3.1).
+fn write_hvcc(
+    out: &mut Vec<u8>,
+    vps: &[u8],
+    sps: &[u8],
+    pps: &[u8],
+    info: &h265::SequenceParameterSet,
+) {
+    write_box(out, b"hvcC", |b| {
+        b.push(1);
+        b.extend_from_slice(&info.profile_tier_level);
+        b.extend_from_slice(&0xf000u16.to_be_bytes()); // min_spatial_segmentation_idc
+        b.push(0xfc); // parallelismType
+        b.push(0xfc | info.chroma_format_idc);
+        b.push(0xf8 | info.bit_depth_luma.saturating_sub(8));
+        b.push(0xf8 | info.bit_depth_chroma.saturating_sub(8));
+        b.extend_from_slice(&0u16.to_be_bytes()); // avgFrameRate
+        b.push((info.max_sub_layers << 3) | (u8::from(info.temporal_id_nesting) << 2) | 3);
+        b.push(3);
+        for (nal_type, nal) in [(32u8, vps), (33, sps), (34, pps)] {
+            b.push(0x80 | nal_type); // array_completeness
+            b.extend_from_slice(&1u16.to_be_bytes());
+            write_nal(b, nal);
+        }
+    });
+}
+
+/// `ES_Descriptor` with the AAC `DecoderSpecificInfo` (ISO/IEC 14496-1
+/// §7.2.6.5).
+fn write_esds(out: &mut Vec<u8>, config: &AacConfig) {
+    write_full_box(out, b"esds", 0, 0, |b| {
+        let specific_info = config.bytes.len();
+        let decoder_config = 13 + 2 + specific_info;
+        b.extend_from_slice(&[0x03, (3 + 2 + decoder_config + 3) as u8]);
+        b.extend_from_slice(&[0, 1, 0]); // ES_ID, flags
+        b.extend_from_slice(&[0x04, decoder_config as u8]);
+        b.push(0x40); // objectTypeIndication: MPEG-4 Audio
+        b.push(0x15); // streamType: audio, upStream 0, reserved 1
+        b.extend_from_slice(&[0; 3]); // bufferSizeDB
+        b.extend_from_slice(&[0; 8]); // max and average bitrate
+        b.extend_from_slice(&[0x05, specific_info as u8]);
+        b.extend_from_slice(&config.bytes);
+        b.extend_from_slice(&[0x06, 1, 0x02]); // SLConfigDescriptor: MP4
+    });
+}
+
+fn write_nal(out: &mut Vec<u8>, nal: &[u8]) {
+    out.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+    out.extend_from_slice(nal);
+}
+
+/// Identity transformation matrix (§8.2.2.3).
+fn write_matrix(out: &mut Vec<u8>) {
+    for value in [0x0001_0000u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn write_box(out: &mut Vec<u8>, kind: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+    body(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    out: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, kind, |b| {
+        b.extend_from_slice(&((u32::from(version) << 24) | flags).to_be_bytes());
+        body(b);
+    });
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Top-level and nested boxes of `data` as `(path, body)`, depth first.
+    pub(crate) fn walk(data: &[u8]) -> Vec<(String, &[u8])> {
+        const CONTAINERS: [&[u8; 4]; 9] = [
+            b"moov", b"trak", b"mdia", b"minf", b"stbl", b"mvex", b"moof", b"traf", b"dinf",
+        ];
+        let mut boxes = Vec::new();
+        let mut rest = data;
+        while rest.len() >= 8 {
+            let size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8_lossy(&rest[4..8]).into_owned();
+            let body = &rest[8..size];
+            boxes.push((kind.clone(), body));
+            if CONTAINERS.iter().any(|c| &c[..] == kind.as_bytes()) {
+                for (child, child_body) in walk(body) {
+                    boxes.push((format!("{kind}/{child}"), child_body));
+                }
+            }
+            rest = &rest[size..];
+        }
+        boxes
+    }
+
+    fn avc_entry() -> SampleEntry {
+        let sps = vec![
+            0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10,
+        ];
+        SampleEntry::Avc {
+            info: h264::SequenceParameterSet::parse(&sps).unwrap(),
+            sps,
+            pps: vec![0x68, 0xeb, 0xe3, 0xcb],
+        }
+    }
+
+    #[test]
+    fn init_segment_describes_each_track() {
+        let init = init_segment(&[
+            TrackHeader {
+                id: 1,
+                timescale: 90000,
+                entry: avc_entry(),
+            },
+            TrackHeader {
+                id: 2,
+                timescale: 48000,
+                entry: SampleEntry::Aac(AacConfig::new(2, 48000, 2).unwrap()),
+            },
+        ]);
+        let boxes = walk(&init);
+        let paths: Vec<&str> = boxes.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths[0], "ftyp");
+        assert_eq!(paths.iter().filter(|p| **p == "moov/trak").count(), 2);
+        assert_eq!(paths.iter().filter(|p| **p == "moov/mvex/trex").count(), 2);
+
+        let stsd: Vec<&[u8]> = boxes
+            .iter()
+            .filter(|(path, _)| path == "moov/trak/mdia/minf/stbl/stsd")
+            .map(|(_, body)| *body)
+            .collect();
+        // Version/flags and entry count, then the sample entry box.
+        assert_eq!(&stsd[0][12..16], b"avc1");
+        let avc1 = &stsd[0][16..];
+        assert_eq!(&avc1[24..28], &[0x05, 0x00, 0x02, 0xd0], "1280x720");
+        assert_eq!(&avc1[82..86], b"avcC");
+        assert_eq!(&avc1[86..92], &[1, 0x64, 0x00, 0x1f, 0xff, 0xe1]);
+        assert_eq!(&stsd[1][12..16], b"mp4a");
+        assert!(
+            stsd[1].windows(4).any(|w| w == [0x05, 0x02, 0x11, 0x90]),
+            "AudioSpecificConfig in esds"
+        );
+    }
+
+    #[test]
+    fn fragment_points_each_run_at_its_data() {
+        let video = [
+            Sample {
+                data: Bytes::from_static(&[0, 0, 0, 2, 0x65, 0x88]),
+                duration: 3000,
+                composition_offset: 3000,
+                keyframe: true,
+            },
+            Sample {
+                data: Bytes::from_static(&[0, 0, 0, 2, 0x41, 0x9a]),
+                duration: 3000,
+                composition_offset: -3000,
+                keyframe: false,
+            },
+        ];
+        let audio = [Sample {
+            data: Bytes::from_static(&[0x21, 0x10]),
+            duration: 1024,
+            composition_offset: 0,
+            keyframe: true,
+        }];
+        let data = fragment(
+            7,
+            &[
+                TrackRun {
+                    track_id: 1,
+                    decode_time: 90000,
+                    samples: &video,
+                },
+                TrackRun {
+                    track_id: 2,
+                    decode_time: 48000,
+                    samples: &audio,
+                },
+            ],
+        );
+        let boxes = walk(&data);
+        let moof_len = 8 + boxes[0].1.len();
+        assert_eq!(boxes.last().unwrap().0, "mdat");
+
+        let truns: Vec<&[u8]> = boxes
+            .iter()
+            .filter(|(path, _)| path == "moof/traf/trun")
+            .map(|(_, body)| *body)
+            .collect();
+        assert_eq!(&truns[0][1..4], &[0x00, 0x0f, 0x01], "composition offsets");
+        assert_eq!(&truns[1][1..4], &[0x00, 0x07, 0x01]);
+        let data_offset =
+            |trun: &[u8]| u32::from_be_bytes(trun[8..12].try_into().unwrap()) as usize;
+        assert_eq!(&data[data_offset(truns[0])..][..6], &video[0].data[..]);
+        assert_eq!(data_offset(truns[1]), moof_len + 8 + 12);
+        assert_eq!(&data[data_offset(truns[1])..], &[0x21, 0x10]);
+        assert_eq!(&truns[0][40..44], &(-3000i32).to_be_bytes());
+    }
+}
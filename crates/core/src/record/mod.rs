@@ -0,0 +1,686 @@
+//! Recording mounts to fragmented MP4 files.
+//!
+//! [`Mount::start_recording`](crate::Mount::start_recording) taps every
+//! frame the mount packetizes (from `send_frame*`, a send queue or a
+//! seekable mount's player) and writes it to disk on a writer thread, so a
+//! slow disk never stalls delivery; a disk that falls too far behind loses
+//! frames up to the next keyframe. H.264, H.265 and AAC tracks are
+//! recorded; other tracks are left out.
+//!
+//! Each segment is a self-contained fragmented MP4 file: an init segment
+//! whose `avcC`/`hvcC` is built from the parameter sets captured from the
+//! stream, then one `moof` + `mdat` fragment per GOP (at most about a
+//! second long). Recording starts at the first keyframe, and a new segment
+//! starts at the first keyframe after the [`RecordingConfig`] duration or
+//! size limit is reached, or when the parameter sets change.
+//!
+//! Sample times come from the frames' RTP timestamps, so they follow the
+//! producer's timing exactly; tracks are aligned by the arrival time of
+//! their first frame.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rtsp::Server;
+//! use rtsp::record::RecordingConfig;
+//!
+//! let mut server = Server::new("0.0.0.0:8554");
+//! server.start()?;
+//! // Writes archive/cam1-00000.mp4, archive/cam1-00001.mp4, ...
+//! let config = RecordingConfig::new("archive/cam1.mp4")
+//!     .with_segment_duration(Duration::from_secs(600));
+//! server.start_recording("/stream", config)?;
+//! // ...
+//! let segments = server.stop_recording("/stream")?;
+//! # Ok::<(), rtsp::RtspError>(())
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, RtspError};
+use crate::mount::{TappedFrame, Track};
+
+pub(crate) mod fmp4;
+pub(crate) mod track;
+
+use fmp4::{SampleEntry, TrackHeader, TrackRun};
+use track::SampleTrack;
+
+/// Longest fragment; GOPs longer than this are split.
+const FRAGMENT_DURATION: Duration = Duration::from_secs(1);
+
+/// Frames queued for the writer thread, a few seconds of video and audio.
+/// When a slow disk lets the queue fill up, frames are dropped up to the
+/// next keyframe rather than held in memory.
+const WRITER_QUEUE_FRAMES: usize = 256;
+
+/// Where and how to record a mount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingConfig {
+    /// Name pattern of the segment files: `archive/cam1.mp4` records
+    /// `archive/cam1-00000.mp4`, `archive/cam1-00001.mp4`, ... Numbering
+    /// continues after the highest-numbered segment already on disk, and
+    /// existing files are never overwritten.
+    pub path: PathBuf,
+    /// Start a new segment once a segment holds this much media.
+    pub segment_duration: Option<Duration>,
+    /// Start a new segment once a segment has grown to this many bytes.
+    pub segment_size: Option<u64>,
+}
+
+impl RecordingConfig {
+    /// Record to segments named after `path`, without rotation.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            segment_duration: None,
+            segment_size: None,
+        }
+    }
+
+    pub fn with_segment_duration(mut self, duration: Duration) -> Self {
+        self.segment_duration = Some(duration);
+        self
+    }
+
+    pub fn with_segment_size(mut self, bytes: u64) -> Self {
+        self.segment_size = Some(bytes);
+        self
+    }
+
+    /// File name of segment `index`.
+    fn segment_path(&self, index: u32) -> PathBuf {
+        let (stem, extension) = self.name_parts();
+        self.path
+            .with_file_name(format!("{stem}-{index:05}.{extension}"))
+    }
+
+    /// Index after the highest segment already on disk, or 0.
+    fn next_segment_index(&self) -> Result<u32> {
+        let (stem, extension) = self.name_parts();
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let (prefix, suffix) = (format!("{stem}-"), format!(".{extension}"));
+        let mut next = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let index = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(&suffix))
+                .filter(|index| index.len() >= 5 && index.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|index| index.parse::<u32>().ok());
+            if let Some(index) = index {
+                next = next.max(index.saturating_add(1));
+            }
+        }
+        Ok(next)
+    }
+
+    /// File stem and extension segment names are built from.
+    fn name_parts(&self) -> (String, String) {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "recording".to_string());
+        let extension = self
+            .path
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "mp4".to_string());
+        (stem, extension)
+    }
+}
+
+/// A running recording: the channel to its writer thread.
+///
+/// Dropping it stops the recording; the writer flushes what it has and
+/// closes the segment.
+pub(crate) struct Recorder {
+    mount_path: String,
+    frames: Option<SyncSender<TappedFrame>>,
+    writer: Option<JoinHandle<Result<Vec<PathBuf>>>>,
+    /// Mount track index of the writer's lead track, whose keyframes end
+    /// a run of dropped frames.
+    lead: usize,
+    /// Frames dropped since the queue was last full; non-zero while
+    /// dropping.
+    dropped: AtomicU64,
+}
+
+impl Recorder {
+    /// Start recording the recordable `tracks` of mount `mount_path`.
+    pub(crate) fn start(
+        mount_path: &str,
+        tracks: &[Arc<Track>],
+        config: RecordingConfig,
+    ) -> Result<Self> {
+        let tracks = SampleTrack::of_tracks(mount_path, tracks);
+        if tracks.is_empty() {
+            return Err(RtspError::Recording(format!(
+                "{mount_path} has no H.264, H.265 or AAC track"
+            )));
+        }
+        if let Some(parent) = config.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let next_index = config.next_segment_index()?;
+
+        let lead = tracks.iter().position(SampleTrack::is_video).unwrap_or(0);
+        let lead_track = tracks[lead].mount_track;
+        let mut writer = Writer {
+            config,
+            started: Instant::now(),
+            lead,
+            tracks,
+            segment: None,
+            next_index,
+            segments: Vec::new(),
+        };
+        let (frames, received) = mpsc::sync_channel(WRITER_QUEUE_FRAMES);
+        let handle = thread::Builder::new()
+            .name(format!("recorder {mount_path}"))
+            .spawn(move || writer.run(received))?;
+        tracing::info!(mount = mount_path, "recording started");
+        Ok(Self {
+            mount_path: mount_path.to_string(),
+            frames: Some(frames),
+            writer: Some(handle),
+            lead: lead_track,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Queue a frame of `track` for the writer, without waiting. Once the
+    /// queue is full, frames are dropped until a keyframe of the lead
+    /// track fits, so the recording resumes where it can be decoded.
+    pub(crate) fn push(&self, track: &Track, frame: TappedFrame) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+        let dropping = self.dropped.load(Ordering::Relaxed) > 0;
+        if dropping && (track.index() != self.lead || !track.is_keyframe(&frame.data)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match frames.try_send(frame) {
+            Ok(()) => {
+                if dropping {
+                    let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                    tracing::info!(
+                        mount = %self.mount_path,
+                        dropped,
+                        "recording resumed at a keyframe"
+                    );
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    tracing::warn!(
+                        mount = %self.mount_path,
+                        "recording falls behind, dropping frames until the next keyframe"
+                    );
+                }
+            }
+            // The writer only hangs up after a write error, already logged.
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    /// Stop recording. Returns the segments written, or the error that
+    /// stopped the writer.
+    pub(crate) fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.frames.take();
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.frames.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// An open segment file.
+struct Segment {
+    file: BufWriter<File>,
+    bytes: u64,
+    /// Media time of its first lead frame since the recording started.
+    start: Duration,
+    /// Next `moof` sequence number.
+    sequence: u32,
+    /// `stsd` entry of each recorded track; `None` for tracks left out of
+    /// this segment.
+    entries: Vec<Option<SampleEntry>>,
+}
+
+/// The writer thread's state.
+struct Writer {
+    config: RecordingConfig,
+    started: Instant,
+    tracks: Vec<SampleTrack>,
+    /// Track whose keyframes start fragments and segments: the first video
+    /// track, or the first track.
+    lead: usize,
+    segment: Option<Segment>,
+    next_index: u32,
+    segments: Vec<PathBuf>,
+}
+
+impl Writer {
+    fn run(&mut self, frames: Receiver<TappedFrame>) -> Result<Vec<PathBuf>> {
+        let written = frames
+            .iter()
+            .try_for_each(|frame| self.write(frame))
+            .and_then(|()| self.close_segment());
+        match written {
+            Ok(()) => {
+                tracing::info!(segments = self.segments.len(), "recording stopped");
+                Ok(std::mem::take(&mut self.segments))
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "recording failed");
+                Err(e)
+            }
+        }
+    }
+
+    fn write(&mut self, frame: TappedFrame) -> Result<()> {
+        let Some(index) = self
+            .tracks
+            .iter()
+            .position(|t| t.mount_track == frame.track)
+        else {
+            return Ok(());
+        };
+        let since_start = frame.arrival.saturating_duration_since(self.started);
+        let track = &mut self.tracks[index];
+        let Some((data, keyframe)) = track.sample_of(&frame.data) else {
+            return Ok(());
+        };
+        let offset = track.ticks(frame.composition_offset);
+        let timestamp = frame.timestamp.wrapping_sub(offset as u32);
+        track.advance(timestamp, since_start);
+
+        if index == self.lead {
+            // The lead's previous sample ends where this frame starts.
+            let dts = track.dts;
+            let video = track.is_video();
+            track.release(Some(dts));
+            let long = track.pending_span() >= FRAGMENT_DURATION;
+            let time = track.time(dts);
+            let rotated = keyframe && self.on_lead_keyframe(time)?;
+            if !rotated && ((keyframe && video) || long) {
+                self.flush_fragment()?;
+            }
+        }
+
+        let Some(segment) = &self.segment else {
+            return Ok(());
+        };
+        let track = &mut self.tracks[index];
+        if segment.entries[index].is_none() || (!track.synced && !keyframe) {
+            return Ok(());
+        }
+        track.synced = true;
+        track.hold(data, offset, keyframe);
+        Ok(())
+    }
+
+    /// Open a segment at the first lead keyframe, and rotate to a new one
+    /// when a limit is reached or the lead's configuration changed.
+    /// Returns whether a segment was opened.
+    fn on_lead_keyframe(&mut self, time: Duration) -> Result<bool> {
+        let lead_entry = self.tracks[self.lead].sample_entry();
+        let rotate = match &self.segment {
+            None => true,
+            Some(segment) => {
+                segment.entries[self.lead] != lead_entry
+                    || self
+                        .config
+                        .segment_duration
+                        .is_some_and(|limit| time.saturating_sub(segment.start) >= limit)
+                    || self
+                        .config
+                        .segment_size
+                        .is_some_and(|limit| segment.bytes >= limit)
+            }
+        };
+        if !rotate {
+            return Ok(false);
+        }
+        if lead_entry.is_none() {
+            tracing::debug!("waiting for parameter sets to start a segment");
+            return Ok(false);
+        }
+        self.close_segment()?;
+        self.open_segment(time)?;
+        Ok(true)
+    }
+
+    fn open_segment(&mut self, start: Duration) -> Result<()> {
+        let entries: Vec<Option<SampleEntry>> =
+            self.tracks.iter().map(SampleTrack::sample_entry).collect();
+        let headers: Vec<TrackHeader> = entries
+            .iter()
+            .zip(&self.tracks)
+            .zip(1..)
+            .filter_map(|((entry, track), id)| {
+                Some(TrackHeader {
+                    id,
+                    timescale: track.clock_rate,
+                    entry: entry.clone()?,
+                })
+            })
+            .collect();
+        for (entry, track) in entries.iter().zip(&self.tracks) {
+            if entry.is_none() {
+                tracing::warn!(
+                    track = track.mount_track,
+                    "no parameter sets yet, track left out of segment"
+                );
+            }
+        }
+
+        let path = self.config.segment_path(self.next_index);
+        self.next_index += 1;
+        // Never overwrite a segment, e.g. one written meanwhile.
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let mut file = BufWriter::new(file);
+        let init = fmp4::init_segment(&headers);
+        file.write_all(&init)?;
+        tracing::info!(path = %path.display(), "recording segment opened");
+        self.segments.push(path);
+        for track in &mut self.tracks {
+            track.synced = false;
+        }
+        self.segment = Some(Segment {
+            file,
+            bytes: init.len() as u64,
+            start,
+            sequence: 1,
+            entries,
+        });
+        Ok(())
+    }
+
+    fn close_segment(&mut self) -> Result<()> {
+        if self.segment.is_none() {
+            return Ok(());
+        }
+        for track in &mut self.tracks {
+            track.release(None);
+        }
+        self.flush_fragment()?;
+        if let Some(mut segment) = self.segment.take() {
+            segment.file.flush()?;
+            tracing::debug!(bytes = segment.bytes, "recording segment closed");
+        }
+        Ok(())
+    }
+
+    /// Write the pending samples of every track as one fragment. Held
+    /// samples wait for their track's next frame.
+    fn flush_fragment(&mut self) -> Result<()> {
+        let Some(segment) = &mut self.segment else {
+            return Ok(());
+        };
+        let runs: Vec<TrackRun<'_>> = self
+            .tracks
+            .iter()
+            .zip(1..)
+            .filter(|(track, _)| !track.pending.is_empty())
+            .map(|(track, track_id)| {
+                let segment_start = track.ticks(segment.start);
+                TrackRun {
+                    track_id,
+                    decode_time: track.pending_dts.saturating_sub(segment_start),
+                    samples: &track.pending,
+                }
+            })
+            .collect();
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let fragment = fmp4::fragment(segment.sequence, &runs);
+        segment.file.write_all(&fragment)?;
+        segment.sequence += 1;
+        segment.bytes += fragment.len() as u64;
+        for track in &mut self.tracks {
+            track.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::aac::{AacConfig, AacPacketizer};
+    use crate::media::h264::H264Packetizer;
+    use crate::media::opus::OpusPacketizer;
+    use crate::mount::Mount;
+    use crate::send_queue::FrameTiming;
+    use bytes::Bytes;
+
+    const SPS: [u8; 12] = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10,
+    ];
+    const PPS: [u8; 4] = [0x68, 0xeb, 0xe3, 0xcb];
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rtsp-rs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Frame `i` of a 10 fps stream with a keyframe every 10 frames, SPS
+    /// and PPS in front of each keyframe.
+    fn video_frame(i: u8) -> Bytes {
+        let mut frame = Vec::new();
+        if i.is_multiple_of(10) {
+            for nal in [&SPS[..], &PPS[..]] {
+                frame.extend_from_slice(&[0, 0, 0, 1]);
+                frame.extend_from_slice(nal);
+            }
+            frame.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x88, i]);
+        } else {
+            frame.extend_from_slice(&[0, 0, 0, 1, 0x41, 0x9a, i]);
+        }
+        Bytes::from(frame)
+    }
+
+    fn send(mount: &Mount, track: usize, data: Bytes, pts: Duration, dts: Option<Duration>) {
+        let track = mount.track(track).unwrap();
+        FrameTiming::Pts { pts, dts }.packetize(mount, &track, data);
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn records_segments_of_video_and_audio() {
+        let dir = temp_dir("record-segments");
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 1)));
+        let config = AacConfig::new(2, 48000, 2).unwrap();
+        mount.add_track(Box::new(AacPacketizer::new(97, 2, config)));
+        mount
+            .start_recording(
+                RecordingConfig::new(dir.join("cam.mp4"))
+                    .with_segment_duration(Duration::from_secs(1)),
+            )
+            .unwrap();
+        assert!(mount.is_recording());
+
+        for i in 0..30u8 {
+            // Decode order, presented two frames later (B-frame style).
+            let dts = Duration::from_millis(100 * u64::from(i));
+            let pts = dts + Duration::from_millis(200);
+            send(&mount, 0, video_frame(i), pts, Some(dts));
+            send(
+                &mount,
+                1,
+                Bytes::from_static(&[0x21, 0x10, 0x04]),
+                dts,
+                None,
+            );
+        }
+        let segments = mount.stop_recording().unwrap();
+        assert!(!mount.is_recording());
+        assert_eq!(
+            segments,
+            (0..3)
+                .map(|i| dir.join(format!("cam-{i:05}.mp4")))
+                .collect::<Vec<_>>()
+        );
+
+        let mut video_samples = 0;
+        for path in &segments {
+            let data = std::fs::read(path).unwrap();
+            let boxes = fmp4::tests::walk(&data);
+            assert_eq!(boxes[0].0, "ftyp");
+            assert_eq!(boxes[1].0, "moov");
+            let stsd = boxes
+                .iter()
+                .find(|(path, _)| path == "moov/trak/mdia/minf/stbl/stsd")
+                .unwrap()
+                .1;
+            assert_eq!(&stsd[12..16], b"avc1");
+
+            let trafs: Vec<Vec<(String, &[u8])>> = boxes
+                .iter()
+                .filter(|(path, _)| path == "moof/traf")
+                .map(|(_, body)| fmp4::tests::walk(body))
+                .collect();
+            let video: Vec<&Vec<(String, &[u8])>> = trafs
+                .iter()
+                .filter(|traf| u32_at(traf[0].1, 4) == 1)
+                .collect();
+            // The segment starts at its own time zero with a keyframe.
+            let tfdt = video[0][1].1;
+            assert_eq!(&tfdt[4..12], &[0; 8]);
+            let trun = video[0][2].1;
+            assert_eq!(trun[3] & 0x01, 0x01);
+            assert_eq!(trun[2] & 0x08, 0x08, "composition offsets");
+            assert_eq!(u32_at(trun, 12), 9000, "100 ms per frame");
+            assert_eq!(u32_at(trun, 20), 0x0200_0000, "keyframe");
+            assert_eq!(u32_at(trun, 24), 18000);
+            video_samples += video.iter().map(|traf| u32_at(traf[2].1, 4)).sum::<u32>();
+            assert!(trafs.iter().any(|traf| u32_at(traf[0].1, 4) == 2), "audio");
+        }
+        assert_eq!(video_samples, 30);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn starts_at_the_first_keyframe() {
+        let dir = temp_dir("record-keyframe");
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 1)));
+        let config = RecordingConfig::new(dir.join("cam.mp4"));
+        mount.start_recording(config.clone()).unwrap();
+        assert!(matches!(
+            mount.start_recording(config.clone()),
+            Err(RtspError::AlreadyRecording(_))
+        ));
+        for i in 5..15u8 {
+            let pts = Duration::from_millis(100 * u64::from(i));
+            send(&mount, 0, video_frame(i), pts, None);
+        }
+        let segments = mount.stop_recording().unwrap();
+        assert_eq!(segments.len(), 1);
+        let data = std::fs::read(&segments[0]).unwrap();
+        let trun = fmp4::tests::walk(&data)
+            .into_iter()
+            .find(|(path, _)| path == "moof/traf/trun")
+            .unwrap()
+            .1;
+        assert_eq!(u32_at(trun, 4), 5, "frames 10 to 14");
+        assert_eq!(&data[data.len() - 7..], &[0, 0, 0, 3, 0x41, 0x9a, 14]);
+
+        // A new recording numbers its segments after the existing ones,
+        // even with earlier ones deleted.
+        std::fs::write(dir.join("cam-00003.mp4"), b"keep").unwrap();
+        std::fs::remove_file(&segments[0]).unwrap();
+        mount.start_recording(config).unwrap();
+        send(&mount, 0, video_frame(20), Duration::from_secs(2), None);
+        assert_eq!(mount.stop_recording().unwrap(), [dir.join("cam-00004.mp4")]);
+        assert_eq!(std::fs::read(dir.join("cam-00003.mp4")).unwrap(), b"keep");
+        assert!(matches!(
+            mount.stop_recording(),
+            Err(RtspError::NotRecording(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn full_queue_drops_frames_until_the_next_keyframe() {
+        let mount = Mount::new("/cam", Box::new(H264Packetizer::new(96, 1)));
+        let track = mount.track(0).unwrap();
+        // A writer that reads nothing until told to.
+        let (frames, received) = mpsc::sync_channel(WRITER_QUEUE_FRAMES);
+        let recorder = Recorder {
+            mount_path: "/cam".into(),
+            frames: Some(frames),
+            writer: None,
+            lead: 0,
+            dropped: AtomicU64::new(0),
+        };
+        let push = |i: u8| {
+            recorder.push(
+                &track,
+                TappedFrame {
+                    track: 0,
+                    data: video_frame(i),
+                    timestamp: u32::from(i) * 9000,
+                    composition_offset: Duration::ZERO,
+                    arrival: Instant::now(),
+                },
+            )
+        };
+        // Fills the queue, then overflows past the keyframes at 260 and 270.
+        for i in 0..WRITER_QUEUE_FRAMES + 15 {
+            push((i % 250) as u8);
+        }
+        let queued: Vec<TappedFrame> = received.try_iter().collect();
+        assert_eq!(queued.len(), WRITER_QUEUE_FRAMES);
+
+        // Once drained, recording resumes at the next keyframe, 270.
+        for i in 261..275 {
+            push((i % 250) as u8);
+        }
+        let resumed: Vec<u8> = received
+            .try_iter()
+            .map(|frame| *frame.data.last().unwrap())
+            .collect();
+        assert_eq!(resumed, (20..25).collect::<Vec<u8>>());
+        assert_eq!(recorder.dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn rejects_mounts_without_recordable_tracks() {
+        let mount = Mount::new("/talk", Box::new(OpusPacketizer::new(111, 1)));
+        assert!(matches!(
+            mount.start_recording(RecordingConfig::new(temp_dir("record-none").join("a.mp4"))),
+            Err(RtspError::Recording(_))
+        ));
+    }
+}
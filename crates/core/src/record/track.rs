@@ -0,0 +1,257 @@
+//! Turning a mount track's frames into MP4 samples, shared by recording
+//! and [HLS](crate::hls).
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::fmp4::{Sample, SampleEntry};
+use crate::media::aac::{AacConfig, AacPacketizer};
+use crate::media::h264::{self, H264Packetizer};
+use crate::media::h265;
+use crate::mount::Track;
+use crate::playback::VideoCodec;
+
+/// Codec of a track that can be turned into MP4 samples.
+#[derive(Debug, Clone)]
+pub(crate) enum Codec {
+    Video(VideoCodec),
+    Aac(AacConfig),
+}
+
+impl Codec {
+    /// The codec of a mount track, if it is supported.
+    fn of(track: &Track) -> Option<Self> {
+        match track.codec_name() {
+            "H264" => Some(Self::Video(VideoCodec::H264)),
+            "H265" => Some(Self::Video(VideoCodec::H265)),
+            "mpeg4-generic" => {
+                // The AudioSpecificConfig is only known from the SDP.
+                let attributes = track.sdp_attributes();
+                let config = attributes
+                    .iter()
+                    .filter(|a| a.starts_with("a=fmtp:"))
+                    .flat_map(|a| a.split([' ', ';']))
+                    .find_map(|param| param.strip_prefix("config="))?;
+                AacConfig::parse(&decode_hex(config)?).map(Self::Aac)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// A sample whose duration is known once the track's next frame arrives.
+pub(crate) struct HeldSample {
+    data: Bytes,
+    dts: u64,
+    composition_offset: i32,
+    keyframe: bool,
+}
+
+/// Sample state of one mount track: its clock, the parameter sets seen,
+/// and the samples waiting to be written.
+pub(crate) struct SampleTrack {
+    pub mount_track: usize,
+    pub codec: Codec,
+    pub clock_rate: u32,
+    /// Latest VPS, SPS and PPS seen, by NAL type.
+    pub parameter_sets: Vec<(u8, Vec<u8>)>,
+    /// RTP decode timestamp of the previous frame.
+    last_timestamp: Option<u32>,
+    /// Decode time of the latest frame, in clock ticks since the tap
+    /// started.
+    pub dts: u64,
+    held: Option<HeldSample>,
+    pub pending: Vec<Sample>,
+    /// Decode time of the first pending sample.
+    pub pending_dts: u64,
+    pub last_duration: u32,
+    /// Whether the track has reached a keyframe since it was (re)joined.
+    pub synced: bool,
+}
+
+impl SampleTrack {
+    /// Sample tracks for the supported tracks of mount `mount_path`.
+    pub(crate) fn of_tracks(mount_path: &str, tracks: &[Arc<Track>]) -> Vec<Self> {
+        tracks
+            .iter()
+            .filter_map(|track| {
+                let codec = Codec::of(track);
+                if codec.is_none() {
+                    tracing::debug!(
+                        mount = mount_path,
+                        control = %track.control(),
+                        codec = track.codec_name(),
+                        "track left out, codec not supported"
+                    );
+                }
+                Some(Self::new(track, codec?))
+            })
+            .collect()
+    }
+
+    fn new(track: &Track, codec: Codec) -> Self {
+        Self {
+            mount_track: track.index(),
+            codec,
+            clock_rate: track.clock_rate().max(1),
+            parameter_sets: Vec::new(),
+            last_timestamp: None,
+            dts: 0,
+            held: None,
+            pending: Vec::new(),
+            pending_dts: 0,
+            last_duration: 0,
+            synced: false,
+        }
+    }
+
+    pub(crate) fn is_video(&self) -> bool {
+        matches!(self.codec, Codec::Video(_))
+    }
+
+    pub(crate) fn ticks(&self, time: Duration) -> u64 {
+        (time.as_nanos() * u128::from(self.clock_rate) / 1_000_000_000) as u64
+    }
+
+    /// Rounded up, so that [`ticks`](Self::ticks) maps it back exactly.
+    pub(crate) fn time(&self, ticks: u64) -> Duration {
+        let nanos = (u128::from(ticks) * 1_000_000_000).div_ceil(u128::from(self.clock_rate));
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// Convert a frame to an MP4 sample: length-prefixed NAL units without
+    /// access unit delimiters and parameter sets (which are captured), or
+    /// a raw AAC access unit. Returns the sample and whether it is a
+    /// keyframe; `None` if nothing is left.
+    pub(crate) fn sample_of(&mut self, data: &Bytes) -> Option<(Bytes, bool)> {
+        let codec = match &self.codec {
+            Codec::Aac(_) => {
+                let au = AacPacketizer::strip_adts(data);
+                return (!au.is_empty()).then(|| (data.slice_ref(au), true));
+            }
+            Codec::Video(codec) => *codec,
+        };
+        let mut sample = BytesMut::with_capacity(data.len());
+        let mut keyframe = false;
+        for range in H264Packetizer::nal_unit_ranges(data) {
+            let nal = &data[range];
+            let nal_type = codec.nal_type(nal);
+            if codec.is_parameter_set(nal_type) {
+                match self.parameter_sets.iter_mut().find(|(t, _)| *t == nal_type) {
+                    Some((_, set)) if set == nal => {}
+                    Some((_, set)) => *set = nal.to_vec(),
+                    None => self.parameter_sets.push((nal_type, nal.to_vec())),
+                }
+                continue;
+            }
+            let delimiter = match codec {
+                VideoCodec::H264 => nal_type == 9,
+                VideoCodec::H265 => nal_type == 35,
+            };
+            if delimiter || nal.is_empty() {
+                continue;
+            }
+            keyframe |= codec.is_keyframe(nal_type);
+            sample.put_u32(nal.len() as u32);
+            sample.extend_from_slice(nal);
+        }
+        (!sample.is_empty()).then(|| (sample.freeze(), keyframe))
+    }
+
+    /// The `stsd` entry for the track's current configuration; `None` for
+    /// video until the parameter sets have been seen.
+    pub(crate) fn sample_entry(&self) -> Option<SampleEntry> {
+        let set = |nal_type: u8| {
+            self.parameter_sets
+                .iter()
+                .find(|(t, _)| *t == nal_type)
+                .map(|(_, nal)| nal.clone())
+        };
+        match &self.codec {
+            Codec::Aac(config) => Some(SampleEntry::Aac(config.clone())),
+            Codec::Video(VideoCodec::H264) => {
+                let sps = set(7)?;
+                Some(SampleEntry::Avc {
+                    info: h264::SequenceParameterSet::parse(&sps)?,
+                    sps,
+                    pps: set(8)?,
+                })
+            }
+            Codec::Video(VideoCodec::H265) => {
+                let sps = set(33)?;
+                Some(SampleEntry::Hevc {
+                    info: h265::SequenceParameterSet::parse(&sps)?,
+                    vps: set(32)?,
+                    sps,
+                    pps: set(34)?,
+                })
+            }
+        }
+    }
+
+    /// Advance the track's clock to a frame with RTP timestamp `timestamp`.
+    pub(crate) fn advance(&mut self, timestamp: u32, since_start: Duration) {
+        self.dts = match self.last_timestamp {
+            // The first frame starts where it arrived on the wall clock.
+            None => self.ticks(since_start),
+            // B-frames without a known DTS can step back; clamp.
+            Some(last) => self.dts + timestamp.wrapping_sub(last).min(i32::MAX as u32) as u64,
+        };
+        self.last_timestamp = Some(timestamp);
+    }
+
+    /// Hold a sample decoded at the current [`dts`](Self::dts), queueing
+    /// the previously held one.
+    pub(crate) fn hold(&mut self, data: Bytes, composition_offset: u64, keyframe: bool) {
+        let dts = self.dts;
+        self.release(Some(dts));
+        self.held = Some(HeldSample {
+            data,
+            dts,
+            composition_offset: composition_offset.min(i32::MAX as u64) as i32,
+            keyframe,
+        });
+    }
+
+    /// Queue the held sample, now that the next one starts at `next_dts`
+    /// (or, with `None`, the track ends and the last duration is reused).
+    pub(crate) fn release(&mut self, next_dts: Option<u64>) {
+        let Some(held) = self.held.take() else {
+            return;
+        };
+        let duration = match next_dts {
+            Some(next) => u32::try_from(next.saturating_sub(held.dts)).unwrap_or(u32::MAX),
+            None => self.last_duration,
+        };
+        self.last_duration = duration;
+        if self.pending.is_empty() {
+            self.pending_dts = held.dts;
+        }
+        self.pending.push(Sample {
+            data: held.data,
+            duration,
+            composition_offset: held.composition_offset,
+            keyframe: held.keyframe,
+        });
+    }
+
+    /// Media time from the first pending sample to the latest frame.
+    pub(crate) fn pending_span(&self) -> Duration {
+        if self.pending.is_empty() {
+            return Duration::ZERO;
+        }
+        self.time(self.dts.saturating_sub(self.pending_dts))
+    }
+}
@@ -0,0 +1,443 @@
+//! Per-mount frame queues that decouple producers from network I/O.
+//!
+//! By default [`Server::send_frame_to`](crate::Server::send_frame_to)
+//! packetizes and sends on the caller's thread, so a slow socket stalls the
+//! encoder. A mount with a [`SendQueueConfig`] instead hands each frame to
+//! a bounded queue drained by a sender thread; when the queue is full the
+//! [`DropPolicy`] decides what gives. [`SendStats`] counts what happened.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex};
+
+use crate::media::packet::RtpPacket;
+use crate::mount::{Mount, Track};
+use crate::server::SHUTDOWN_TIMEOUT;
+use crate::transport::tcp;
+
+/// What a full send queue does with the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Evict the oldest queued frame that is not a keyframe (the oldest
+    /// frame if all are keyframes) to make room.
+    #[default]
+    DropOldestNonKeyframe,
+    /// Drop the new frame and every later frame of its track until the
+    /// next keyframe, which replaces whatever of that track is still
+    /// queued. Viewers freeze briefly instead of decoding broken
+    /// references.
+    DropUntilKeyframe,
+    /// Block the producer until the sender thread makes room.
+    Block,
+}
+
+/// Send queue settings for a mount (see [`Mount::set_send_queue`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendQueueConfig {
+    /// Frames held before the drop policy applies.
+    pub capacity: usize,
+    pub drop_policy: DropPolicy,
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 8,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+}
+
+/// Frame counters for a mount (see [`Mount::send_stats`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SendStats {
+    /// Frames packetized and handed to the network.
+    pub frames_sent: u64,
+    /// Frames discarded by the [`DropPolicy`].
+    pub frames_dropped: u64,
+    /// Frames waiting in the send queue right now.
+    pub frames_queued: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SendCounters {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    queued: AtomicUsize,
+}
+
+impl SendCounters {
+    pub(crate) fn frame_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn frames_dropped(&self, count: usize) {
+        self.dropped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn set_queued(&self, queued: usize) {
+        self.queued.store(queued, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> SendStats {
+        SendStats {
+            frames_sent: self.sent.load(Ordering::Relaxed),
+            frames_dropped: self.dropped.load(Ordering::Relaxed),
+            frames_queued: self.queued.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// How a frame's RTP timestamp is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameTiming {
+    /// Advance the timestamp by this many clock ticks after the frame.
+    Increment(u32),
+    /// Map a presentation (and optional decode) time through the track's
+    /// [`RtpClock`](crate::media::clock::RtpClock).
+    Pts {
+        pts: Duration,
+        dts: Option<Duration>,
+    },
+}
+
+impl FrameTiming {
+    /// Packetize a frame for one of `mount`'s tracks, and hand it to the
+    /// mount's [recording](Mount::start_recording) and [HLS](crate::hls)
+    /// output if running.
+    pub(crate) fn packetize(self, mount: &Mount, track: &Track, data: Bytes) -> Vec<RtpPacket> {
+        let tapped = mount.is_tapped().then(|| data.clone());
+        let (packets, composition_offset) = match self {
+            Self::Increment(increment) => (track.packetize(data, increment), Duration::ZERO),
+            Self::Pts { pts, dts } => (
+                track.packetize_with_pts(data, pts, dts),
+                dts.map_or(Duration::ZERO, |dts| pts.saturating_sub(dts)),
+            ),
+        };
+        if let Some(data) = tapped {
+            mount.tap(track, data, &packets, composition_offset);
+        }
+        packets
+    }
+}
+
+pub(crate) struct QueuedFrame {
+    pub track: Arc<Track>,
+    pub data: Bytes,
+    pub timing: FrameTiming,
+    pub keyframe: bool,
+}
+
+/// Delivers a track's packets to the mount's viewers, with whether the
+/// frame is a keyframe.
+pub(crate) type Deliver = Arc<dyn Fn(&Mount, usize, Vec<RtpPacket>, bool) + Send + Sync>;
+
+struct State {
+    frames: VecDeque<QueuedFrame>,
+    /// Tracks dropping frames until their next keyframe.
+    awaiting_keyframe: HashSet<usize>,
+    closed: bool,
+}
+
+struct Shared {
+    mount: Arc<Mount>,
+    state: Mutex<State>,
+    /// Signalled when a frame is queued, dequeued, or the queue closes.
+    changed: Condvar,
+}
+
+/// A mount's bounded frame queue and the thread that drains it.
+///
+/// Dropping the queue closes it: frames still queued are discarded, and
+/// the sender thread is given [`SHUTDOWN_TIMEOUT`] to finish the frame it
+/// is sending and exit.
+pub(crate) struct SendQueue {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SendQueue {
+    pub(crate) fn spawn(mount: Arc<Mount>, deliver: Deliver) -> Self {
+        let shared = Arc::new(Shared {
+            mount,
+            state: Mutex::new(State {
+                frames: VecDeque::new(),
+                awaiting_keyframe: HashSet::new(),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let worker_shared = shared.clone();
+        let worker = thread::Builder::new()
+            .name(format!("rtp-sender {}", shared.mount.path()))
+            .spawn(move || send_loop(worker_shared, deliver))
+            .expect("failed to spawn sender thread");
+        Self {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// Queue a frame, applying `config`'s drop policy if the queue is full.
+    /// Returns `false` if the frame itself was dropped.
+    pub(crate) fn push(&self, frame: QueuedFrame, config: SendQueueConfig) -> bool {
+        let shared = &*self.shared;
+        let counters = shared.mount.send_counters();
+        let track = frame.track.index();
+        let capacity = config.capacity.max(1);
+        let mut state = shared.state.lock();
+
+        if config.drop_policy == DropPolicy::DropUntilKeyframe
+            && state.awaiting_keyframe.contains(&track)
+        {
+            if !frame.keyframe {
+                counters.frames_dropped(1);
+                return false;
+            }
+            state.awaiting_keyframe.remove(&track);
+        }
+
+        while state.frames.len() >= capacity && !state.closed {
+            match config.drop_policy {
+                DropPolicy::Block => shared.changed.wait(&mut state),
+                DropPolicy::DropOldestNonKeyframe => {
+                    let victim = state.frames.iter().position(|f| !f.keyframe).unwrap_or(0);
+                    state.frames.remove(victim);
+                    counters.frames_dropped(1);
+                }
+                DropPolicy::DropUntilKeyframe => {
+                    if !frame.keyframe {
+                        state.awaiting_keyframe.insert(track);
+                        counters.frames_dropped(1);
+                        tracing::debug!(
+                            mount = shared.mount.path(),
+                            track,
+                            "send queue full, dropping until next keyframe"
+                        );
+                        return false;
+                    }
+                    // The keyframe supersedes everything of its track.
+                    let before = state.frames.len();
+                    state.frames.retain(|f| f.track.index() != track);
+                    let mut evicted = before - state.frames.len();
+                    if evicted == 0 {
+                        state.frames.pop_front();
+                        evicted = 1;
+                    }
+                    counters.frames_dropped(evicted);
+                }
+            }
+        }
+        if state.closed {
+            return false;
+        }
+
+        state.frames.push_back(frame);
+        counters.set_queued(state.frames.len());
+        shared.changed.notify_all();
+        true
+    }
+}
+
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock();
+            state.closed = true;
+            let discarded = state.frames.len();
+            state.frames.clear();
+            self.shared.mount.send_counters().set_queued(0);
+            if discarded > 0 {
+                tracing::debug!(
+                    mount = self.shared.mount.path(),
+                    discarded,
+                    "send queue closed, queued frames discarded"
+                );
+            }
+        }
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take()
+            && !tcp::join_timeout(worker, SHUTDOWN_TIMEOUT)
+        {
+            tracing::warn!(
+                mount = self.shared.mount.path(),
+                "sender thread did not exit in time, detaching it"
+            );
+        }
+    }
+}
+
+fn send_loop(shared: Arc<Shared>, deliver: Deliver) {
+    let counters = shared.mount.send_counters();
+    loop {
+        let frame = {
+            let mut state = shared.state.lock();
+            while state.frames.is_empty() && !state.closed {
+                shared.changed.wait(&mut state);
+            }
+            let Some(frame) = state.frames.pop_front() else {
+                break;
+            };
+            counters.set_queued(state.frames.len());
+            shared.changed.notify_all();
+            frame
+        };
+
+        let packets = frame
+            .timing
+            .packetize(&shared.mount, &frame.track, frame.data);
+        deliver(&shared.mount, frame.track.index(), packets, frame.keyframe);
+        counters.frame_sent();
+    }
+    tracing::debug!(mount = shared.mount.path(), "sender thread exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::h264::H264Packetizer;
+    use std::sync::mpsc;
+
+    const IDR: &[u8] = &[0, 0, 0, 1, 0x65, 0x88];
+    const P: &[u8] = &[0, 0, 0, 1, 0x41, 0x9a];
+
+    fn frame(mount: &Mount, data: &'static [u8]) -> QueuedFrame {
+        let track = mount.track(0).unwrap();
+        QueuedFrame {
+            keyframe: track.is_keyframe(data),
+            track,
+            data: Bytes::from_static(data),
+            timing: FrameTiming::Increment(3000),
+        }
+    }
+
+    /// A queue whose sender blocks on `gate` before each delivery, and
+    /// reports delivered frames' NAL types on the returned channel.
+    fn gated_queue(mount: &Arc<Mount>) -> (SendQueue, mpsc::Sender<()>, mpsc::Receiver<u8>) {
+        let (open, gate) = mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let (tx, delivered) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let deliver: Deliver = Arc::new(move |_, _, packets: Vec<RtpPacket>, _| {
+            gate.lock().recv().unwrap();
+            tx.lock().send(packets[0].to_vec()[12]).unwrap();
+        });
+        (SendQueue::spawn(mount.clone(), deliver), open, delivered)
+    }
+
+    fn mount() -> Arc<Mount> {
+        Arc::new(Mount::new("/q", Box::new(H264Packetizer::new(96, 1))))
+    }
+
+    /// Queue one frame and wait until the sender has taken it, so it is
+    /// parked on the gate and the queue itself is empty.
+    fn park_sender(queue: &SendQueue, mount: &Arc<Mount>, config: SendQueueConfig) {
+        assert!(queue.push(frame(mount, IDR), config));
+        while mount.send_stats().frames_queued > 0 {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn drop_oldest_non_keyframe_keeps_keyframes() {
+        let mount = mount();
+        let (queue, open, delivered) = gated_queue(&mount);
+        let config = SendQueueConfig {
+            capacity: 2,
+            drop_policy: DropPolicy::DropOldestNonKeyframe,
+        };
+        park_sender(&queue, &mount, config);
+
+        assert!(queue.push(frame(&mount, P), config));
+        assert!(queue.push(frame(&mount, IDR), config));
+        // Full: the queued P-frame makes way.
+        assert!(queue.push(frame(&mount, P), config));
+        assert_eq!(mount.send_stats().frames_dropped, 1);
+
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        let types: Vec<u8> = (0..3).map(|_| delivered.recv().unwrap()).collect();
+        assert_eq!(types, vec![0x65, 0x65, 0x41]);
+        drop(queue);
+        assert_eq!(mount.send_stats().frames_sent, 3);
+    }
+
+    #[test]
+    fn drop_until_keyframe_skips_to_next_idr() {
+        let mount = mount();
+        let (queue, open, delivered) = gated_queue(&mount);
+        let config = SendQueueConfig {
+            capacity: 1,
+            drop_policy: DropPolicy::DropUntilKeyframe,
+        };
+        park_sender(&queue, &mount, config);
+
+        assert!(queue.push(frame(&mount, P), config));
+        assert!(!queue.push(frame(&mount, P), config), "full");
+        // Still waiting for a keyframe even though nothing new is queued.
+        assert!(!queue.push(frame(&mount, P), config));
+        // The keyframe replaces the stale P-frame.
+        assert!(queue.push(frame(&mount, IDR), config));
+        assert_eq!(mount.send_stats().frames_dropped, 3);
+
+        open.send(()).unwrap();
+        open.send(()).unwrap();
+        assert_eq!(delivered.recv().unwrap(), 0x65);
+        assert_eq!(delivered.recv().unwrap(), 0x65);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let mount = mount();
+        let (queue, open, delivered) = gated_queue(&mount);
+        let config = SendQueueConfig {
+            capacity: 1,
+            drop_policy: DropPolicy::Block,
+        };
+        park_sender(&queue, &mount, config);
+        assert!(queue.push(frame(&mount, P), config));
+
+        thread::scope(|s| {
+            let producer = s.spawn(|| queue.push(frame(&mount, P), config));
+            thread::sleep(Duration::from_millis(20));
+            assert!(!producer.is_finished(), "producer blocks while full");
+            open.send(()).unwrap();
+            assert!(producer.join().unwrap());
+        });
+
+        open.send(()).unwrap();
+        open.send(()).unwrap();
+        for _ in 0..3 {
+            delivered.recv().unwrap();
+        }
+        assert_eq!(mount.send_stats().frames_dropped, 0);
+    }
+
+    #[test]
+    fn drop_discards_queued_frames() {
+        let mount = mount();
+        let (queue, open, delivered) = gated_queue(&mount);
+        let config = SendQueueConfig::default();
+        park_sender(&queue, &mount, config);
+        assert!(queue.push(frame(&mount, P), config));
+        assert!(queue.push(frame(&mount, P), config));
+
+        thread::scope(|s| {
+            let closing = s.spawn(|| drop(queue));
+            while mount.send_stats().frames_queued > 0 {
+                thread::yield_now();
+            }
+            // Only the frame being sent is finished.
+            open.send(()).unwrap();
+            closing.join().unwrap();
+        });
+        assert_eq!(delivered.try_iter().collect::<Vec<u8>>(), [0x65]);
+        assert_eq!(mount.send_stats().frames_sent, 1);
+    }
+}
@@ -1,23 +1,51 @@
+use std::collections::HashMap;
 use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
 
 use crate::error::{Result, RtspError};
+use crate::hls::{HlsConfig, HlsServer};
 use crate::media::Packetizer;
 use crate::media::h264::H264Packetizer;
-use crate::mount::{DEFAULT_MOUNT_PATH, MountRegistry};
-use crate::session::SessionManager;
+use crate::media::packet::RtpPacket;
+use crate::media::rtcp;
+use crate::mount::{DEFAULT_MOUNT_PATH, Mount, MountRegistry, Track};
+use crate::playback::{MediaFile, MediaSource};
+use crate::record::RecordingConfig;
+use crate::send_queue::{FrameTiming, QueuedFrame, SendQueue};
+use crate::session::id::DEFAULT_SESSION_ID_LENGTH;
+use crate::session::{RandomSessionIds, Session, SessionIdGenerator, SessionManager, Transport};
 use crate::transport::UdpTransport;
-use crate::transport::tcp;
+use crate::transport::interleaved::{
+    DEFAULT_TCP_SEND_BUFFER, InterleavedSink, OverflowPolicy, TcpViewerStats,
+};
+use crate::transport::pacer::{PacedFrame, Pacer};
+use crate::transport::tcp::{self, Connections};
+
+/// How long [`Server::stop`] waits for connections to flush their output
+/// (including the RTCP BYE) and for threads to exit.
+pub(crate) const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reason carried in the RTCP BYE sent to viewers on shutdown.
+const BYE_REASON: &str = "server shutting down";
 
 /// Server-level configuration used by protocol handlers.
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
-    /// Public host advertised in SDP `o=` and `c=` lines.
+    /// Public host advertised in SDP `o=` and `c=` lines and in the
+    /// `Content-Base`, `a=control` and `RTP-Info` URLs.
     /// When `None`, host is inferred from request URI/client address.
     pub public_host: Option<String>,
-    /// Public RTSP port for future URL-based headers (e.g. RTP-Info).
+    /// Public RTSP port advertised in the `Content-Base`, `a=control` and
+    /// `RTP-Info` URLs, e.g. the external port of a NAT port forward.
+    /// When `None`, the port of the request URI, else the port the server
+    /// listens on.
     pub public_port: Option<u16>,
     /// SDP origin username field (`o=<username> ...`).
     pub sdp_username: String,
@@ -27,6 +55,27 @@ pub struct ServerConfig {
     pub sdp_session_version: String,
     /// SDP session name (`s=`).
     pub sdp_session_name: String,
+    /// Send batched RTP with UDP generic segmentation offload where the
+    /// kernel supports it (Linux 4.18+). Off by default; some tunnels and
+    /// virtual NICs mishandle segmented datagrams.
+    pub udp_gso: bool,
+    /// Outbound buffer per RTSP connection, in bytes, holding responses
+    /// and interleaved RTP for TCP viewers.
+    pub tcp_send_buffer: usize,
+    /// What a TCP viewer whose buffer is full gets.
+    pub tcp_overflow_policy: OverflowPolicy,
+    /// Let any RTSP connection that knows a session's ID control it. Off
+    /// by default: SETUP, PLAY, PAUSE and TEARDOWN of a session from a
+    /// connection other than the one that created it get
+    /// `454 Session Not Found`.
+    pub shared_sessions: bool,
+    /// Length of generated session IDs, in hex characters (at least 8).
+    /// Ignored once a generator is installed with
+    /// [`Server::set_session_id_generator`].
+    pub session_id_length: usize,
+    /// Serve every mount as HLS over HTTP (see [`crate::hls`]). Off by
+    /// default.
+    pub hls: Option<HlsConfig>,
 }
 
 impl Default for ServerConfig {
@@ -38,6 +87,12 @@ impl Default for ServerConfig {
             sdp_session_id: "0".to_string(),
             sdp_session_version: "0".to_string(),
             sdp_session_name: "Stream".to_string(),
+            udp_gso: false,
+            tcp_send_buffer: DEFAULT_TCP_SEND_BUFFER,
+            tcp_overflow_policy: OverflowPolicy::default(),
+            shared_sessions: false,
+            session_id_length: DEFAULT_SESSION_ID_LENGTH,
+            hls: None,
         }
     }
 }
@@ -57,13 +112,23 @@ impl Default for ServerConfig {
 /// // server.send_frame(&h264_data, 3000).unwrap();
 /// ```
 ///
+/// # Shutdown
+///
+/// [`stop`](Self::stop) (also run on drop) sends viewers an RTCP BYE,
+/// closes every RTSP connection, joins the server's threads and removes
+/// all sessions; the server can then be started again.
+///
 /// # Multi-mount usage
 ///
 /// ```no_run
 /// use rtsp::Server;
 /// use rtsp::media::h264::H264Packetizer;
 /// let mut server = Server::new("0.0.0.0:8554");
-/// server.add_mount("/cam1", Box::new(H264Packetizer::with_random_ssrc(96)));
+/// let cam1 = server.add_mount("/cam1", Box::new(H264Packetizer::with_random_ssrc(96)));
+/// cam1.set_keyframe_request_handler(|req| {
+///     // Ask the encoder for an IDR (RTCP PLI/FIR or a new viewer).
+///     println!("keyframe requested on {} ({})", req.mount_path, req.reason);
+/// });
 /// server.start().unwrap();
 /// // server.send_frame_to("/cam1", &data, 3000).unwrap();
 /// ```
@@ -72,15 +137,23 @@ pub struct Server {
     mounts: MountRegistry,
     running: Arc<AtomicBool>,
     bind_addr: String,
-    udp: Option<UdpTransport>,
+    delivery: Option<Arc<Delivery>>,
     config: Arc<ServerConfig>,
+    send_queues: Arc<SendQueues>,
+    /// Address the RTSP listener is bound to while running.
+    local_addr: Option<SocketAddr>,
+    connections: Arc<Connections>,
+    /// Accept and RTCP threads.
+    threads: Vec<JoinHandle<()>>,
+    hls: Option<HlsServer>,
 }
 
 impl Server {
     /// Create a server with a default H.264 mount at `/stream`.
     ///
-    /// `bind_addr` must be `host:port` with an explicit non-zero port (e.g. `127.0.0.1:8554`).
-    /// Port 0 is not allowed; validation happens in [`start`](Self::start).
+    /// `bind_addr` must be `host:port` (e.g. `127.0.0.1:8554`); validation
+    /// happens in [`start`](Self::start). With port 0 the OS picks a free
+    /// port, reported by [`local_addr`](Self::local_addr).
     pub fn new(bind_addr: &str) -> Self {
         Self::with_config(bind_addr, ServerConfig::default())
     }
@@ -88,7 +161,7 @@ impl Server {
     /// Create a server with a single H.264 mount at the given path.
     ///
     /// Use this when the stream path is configurable (e.g. GStreamer `mount-path` property).
-    /// `bind_addr` must be `host:port` with an explicit non-zero port.
+    /// `bind_addr` must be `host:port`.
     pub fn new_with_mount_path(bind_addr: &str, mount_path: &str) -> Self {
         let mounts = MountRegistry::new();
         mounts.add(mount_path, Box::new(H264Packetizer::with_random_ssrc(96)));
@@ -99,7 +172,12 @@ impl Server {
             mounts,
             running: Arc::new(AtomicBool::new(false)),
             bind_addr: bind_addr.to_string(),
-            udp: None,
+            delivery: None,
+            send_queues: Arc::new(SendQueues::default()),
+            local_addr: None,
+            connections: Arc::new(Connections::default()),
+            threads: Vec::new(),
+            hls: None,
             config: Arc::new(ServerConfig::default()),
         }
     }
@@ -115,11 +193,18 @@ impl Server {
         mounts.set_default(DEFAULT_MOUNT_PATH);
 
         Self {
-            session_manager: SessionManager::new(),
+            session_manager: SessionManager::with_id_generator(RandomSessionIds::new(
+                config.session_id_length,
+            )),
             mounts,
             running: Arc::new(AtomicBool::new(false)),
             bind_addr: bind_addr.to_string(),
-            udp: None,
+            delivery: None,
+            send_queues: Arc::new(SendQueues::default()),
+            local_addr: None,
+            connections: Arc::new(Connections::default()),
+            threads: Vec::new(),
+            hls: None,
             config: Arc::new(config),
         }
     }
@@ -140,20 +225,93 @@ impl Server {
         mounts.set_default(DEFAULT_MOUNT_PATH);
 
         Self {
-            session_manager: SessionManager::new(),
+            session_manager: SessionManager::with_id_generator(RandomSessionIds::new(
+                config.session_id_length,
+            )),
             mounts,
             running: Arc::new(AtomicBool::new(false)),
             bind_addr: bind_addr.to_string(),
-            udp: None,
+            delivery: None,
+            send_queues: Arc::new(SendQueues::default()),
+            local_addr: None,
+            connections: Arc::new(Connections::default()),
+            threads: Vec::new(),
+            hls: None,
             config: Arc::new(config),
         }
     }
 
     /// Register a named mount with its own packetizer.
     ///
-    /// Must be called before [`start`](Self::start).
-    pub fn add_mount(&self, path: &str, packetizer: Box<dyn Packetizer>) {
-        self.mounts.add(path, packetizer);
+    /// Must be called before [`start`](Self::start). The returned [`Mount`]
+    /// can be used to install a
+    /// [keyframe request handler](Mount::set_keyframe_request_handler).
+    pub fn add_mount(&self, path: &str, packetizer: Box<dyn Packetizer>) -> Arc<Mount> {
+        self.mounts.add(path, packetizer)
+    }
+
+    /// Register a seekable mount that plays `source` on demand, with
+    /// `packetizer` for its first track (see [`crate::playback`]).
+    ///
+    /// Must be called before [`start`](Self::start), which starts the
+    /// mount's player thread. Further tracks are added with
+    /// [`add_track`](Self::add_track) and fed by the same source.
+    pub fn add_seekable_mount(
+        &self,
+        path: &str,
+        packetizer: Box<dyn Packetizer>,
+        source: Box<dyn MediaSource>,
+    ) -> Arc<Mount> {
+        let mount = self.mounts.add(path, packetizer);
+        mount.set_source(source);
+        mount
+    }
+
+    /// Register a seekable mount that plays a media file (MP4/MOV or an
+    /// H.264/H.265 Annex B stream), one track per file track.
+    ///
+    /// Must be called before [`start`](Self::start). Clients seek with
+    /// `Range` like on any [seekable mount](Self::add_seekable_mount); a
+    /// [looping](MediaFile::with_looping) file starts over at its end.
+    ///
+    /// ```no_run
+    /// use rtsp::Server;
+    /// use rtsp::playback::MediaFile;
+    ///
+    /// let mut server = Server::new("0.0.0.0:8554");
+    /// server.add_file_mount("/clip", MediaFile::open("clip.h264")?.with_frame_rate(25.0));
+    /// server.start()?;
+    /// # Ok::<(), rtsp::RtspError>(())
+    /// ```
+    pub fn add_file_mount(&self, path: &str, file: MediaFile) -> Arc<Mount> {
+        file.mount(&self.mounts, path)
+    }
+
+    /// Add a track (audio, metadata, ...) to an existing mount.
+    ///
+    /// The track appears as an additional `m=` section in the mount's SDP
+    /// and is sent with [`send_track_frame`](Self::send_track_frame) /
+    /// [`send_track_frame_with_pts`](Self::send_track_frame_with_pts). See
+    /// [`Mount::add_track`] for how its timestamps align with the first track.
+    pub fn add_track(
+        &self,
+        mount_path: &str,
+        packetizer: Box<dyn Packetizer>,
+    ) -> Result<Arc<Track>> {
+        Ok(self.mount(mount_path)?.add_track(packetizer))
+    }
+
+    /// Start recording a mount to fragmented MP4 files (see
+    /// [`Mount::start_recording`] and [`crate::record`]). Recording and
+    /// serving are independent: the server need not be running, and
+    /// frames are recorded whether or not anyone watches.
+    pub fn start_recording(&self, mount_path: &str, config: RecordingConfig) -> Result<()> {
+        self.mount(mount_path)?.start_recording(config)
+    }
+
+    /// Stop recording a mount. Returns the segment files written.
+    pub fn stop_recording(&self, mount_path: &str) -> Result<Vec<PathBuf>> {
+        self.mount(mount_path)?.stop_recording()
     }
 
     pub fn start(&mut self) -> Result<()> {
@@ -161,22 +319,16 @@ impl Server {
             return Err(RtspError::AlreadyRunning);
         }
 
-        let addr: SocketAddr = self.bind_addr.parse().map_err(|_| {
-            RtspError::InvalidBindAddress(format!(
-                "expected host:port with explicit port, got {:?}",
-                self.bind_addr
-            ))
-        })?;
-        if addr.port() == 0 {
-            return Err(RtspError::InvalidBindAddress(
-                "port must be explicit (non-zero)".to_string(),
-            ));
-        }
-
-        self.udp = Some(UdpTransport::bind()?);
+        parse_bind_addr(&self.bind_addr)?;
+        let delivery = Delivery::bind(&self.session_manager, &self.config)?;
+        let (server_rtp_port, server_rtcp_port) = delivery.udp.local_ports()?;
 
         let listener = TcpListener::bind(&self.bind_addr)?;
         listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        if let Some(hls) = &self.config.hls {
+            self.hls = Some(HlsServer::start(hls.clone(), self.mounts.clone())?);
+        }
 
         self.running.store(true, Ordering::SeqCst);
 
@@ -185,24 +337,108 @@ impl Server {
         let mounts = self.mounts.clone();
         let config = self.config.clone();
 
-        tracing::info!(addr = %self.bind_addr, "RTSP server listening");
+        tracing::info!(
+            addr = %local_addr,
+            server_rtp_port,
+            server_rtcp_port,
+            "RTSP server listening"
+        );
 
-        thread::spawn(move || {
-            tcp::accept_loop(listener, session_manager, mounts, config, running);
-        });
+        let connections = self.connections.clone();
+        self.threads.push(thread::spawn(move || {
+            tcp::accept_loop(
+                listener,
+                session_manager,
+                mounts,
+                config,
+                running,
+                connections,
+            );
+        }));
+
+        let rtcp_udp = delivery.udp.clone();
+        let running = self.running.clone();
+        let session_manager = self.session_manager.clone();
+        let mounts = self.mounts.clone();
+        self.threads.push(thread::spawn(move || {
+            rtcp_loop(rtcp_udp, session_manager, mounts, running);
+        }));
+
+        self.threads.extend(spawn_players(
+            &self.mounts,
+            &delivery,
+            &self.send_queues,
+            &self.session_manager,
+            self.running.clone(),
+        ));
+
+        self.delivery = Some(delivery);
+        self.local_addr = Some(local_addr);
 
         Ok(())
     }
 
+    /// Shut the server down:
+    ///
+    /// 1. stop accepting connections, receiving RTCP and playing seekable
+    ///    mounts, joining those threads;
+    /// 2. stop send queues and pacers, discarding their queued frames;
+    /// 3. send an RTCP BYE for every track to every subscribed viewer;
+    /// 4. close every RTSP connection after its queued output is written
+    ///    and join the connection threads;
+    /// 5. remove all sessions and mount subscriptions.
+    ///
+    /// Each wait is bounded (about two seconds overall). Does nothing if
+    /// the server is not running; afterwards [`start`](Self::start) may be
+    /// called again.
     pub fn stop(&mut self) {
-        self.running.store(false, Ordering::SeqCst);
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
         tracing::info!("server stopping");
+
+        if let Some(hls) = self.hls.take() {
+            hls.stop();
+        }
+        for thread in self.threads.drain(..) {
+            if !tcp::join_timeout(thread, SHUTDOWN_TIMEOUT) {
+                tracing::warn!("server thread did not exit in time, detaching it");
+            }
+        }
+        if let Some(delivery) = self.delivery.take() {
+            delivery.flush(&self.send_queues);
+            delivery.send_bye(&self.mounts);
+        }
+        let closed = self.connections.close_all(SHUTDOWN_TIMEOUT);
+        self.local_addr = None;
+
+        self.mounts.clear_subscriptions();
+        let removed = self.session_manager.clear();
+        tracing::info!(closed, removed, "server stopped");
     }
 
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
 
+    /// Address the RTSP listener is bound to, with the actual port when
+    /// the server was created with port 0. `None` while not running.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Serve every mount as HLS over HTTP, or stop doing so, from the next
+    /// [`start`](Self::start) on; the same as setting [`ServerConfig::hls`].
+    pub fn set_hls(&mut self, config: Option<HlsConfig>) {
+        Arc::make_mut(&mut self.config).hls = config;
+    }
+
+    /// Address the HLS HTTP server is bound to, when
+    /// [`ServerConfig::hls`] is set. `None` while not running.
+    pub fn hls_addr(&self) -> Option<SocketAddr> {
+        self.hls.as_ref().map(HlsServer::local_addr)
+    }
+
     /// Send a raw encoded frame to the default mount (`/stream`).
     ///
     /// Packetizes the data into RTP packets and delivers them to all
@@ -221,112 +457,180 @@ impl Server {
         data: &[u8],
         timestamp_increment: u32,
     ) -> Result<usize> {
-        let udp = self.udp.as_ref().ok_or(RtspError::NotStarted)?;
-        let mount = self
-            .mounts
-            .get(mount_path)
-            .ok_or_else(|| RtspError::MountNotFound(mount_path.to_string()))?;
-
-        let packets = mount.packetize(data, timestamp_increment);
-        let session_ids = mount.subscribed_session_ids();
-
-        let mut sent = 0;
-        for session_id in &session_ids {
-            let session = match self.session_manager.get_session(session_id) {
-                Some(s) if s.is_playing() => s,
-                _ => continue,
-            };
-            let transport = match session.get_transport() {
-                Some(t) => t,
-                None => continue,
-            };
-            for packet in &packets {
-                match udp.send_to(packet, transport.client_addr) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        tracing::warn!(
-                            session_id,
-                            addr = %transport.client_addr,
-                            error = %e,
-                            "failed to send RTP packet"
-                        );
-                    }
-                }
-            }
-            sent += 1;
-        }
+        self.send_frame_bytes(
+            mount_path,
+            Bytes::copy_from_slice(data),
+            timestamp_increment,
+        )
+    }
 
-        Ok(sent)
+    /// Like [`send_frame_to`](Self::send_frame_to), taking ownership of a
+    /// shared buffer. RTP payloads reference `data` directly, so a frame
+    /// the caller already holds as [`Bytes`] is never copied.
+    pub fn send_frame_bytes(
+        &self,
+        mount_path: &str,
+        data: Bytes,
+        timestamp_increment: u32,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            0,
+            data,
+            FrameTiming::Increment(timestamp_increment),
+        )
+    }
+
+    /// Send a raw encoded frame to one track of a mount (zero-based index;
+    /// track 0 is the one [`send_frame_to`](Self::send_frame_to) uses).
+    pub fn send_track_frame(
+        &self,
+        mount_path: &str,
+        track: usize,
+        data: &[u8],
+        timestamp_increment: u32,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            track,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Increment(timestamp_increment),
+        )
+    }
+
+    /// Send a frame to one track of a mount, stamped with its presentation
+    /// timestamp. Tracks sharing a clock rate share an RTP offset, so
+    /// metadata sent with a video frame's PTS carries that frame's RTP
+    /// timestamp.
+    pub fn send_track_frame_with_pts(
+        &self,
+        mount_path: &str,
+        track: usize,
+        data: &[u8],
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            track,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Pts { pts, dts: None },
+        )
+    }
+
+    /// Send a raw encoded frame to a specific mount, stamped with its
+    /// presentation timestamp.
+    ///
+    /// The PTS is mapped to the codec's RTP clock with a random initial
+    /// offset (see [`RtpClock`](crate::media::clock::RtpClock)), so callers
+    /// never compute increments and dropped frames or variable frame rates
+    /// don't cause drift. Any time base works as long as it is consistent
+    /// per mount (e.g. encoder PTS or capture time since start). The
+    /// mount's packetizer must implement
+    /// [`Packetizer::set_next_rtp_timestamp`](crate::media::Packetizer::set_next_rtp_timestamp),
+    /// as all built-in packetizers do; with one that does not, frames keep
+    /// increment-based timestamps and a warning is logged once per track.
+    pub fn send_frame_with_pts(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send_frame_bytes_with_pts(mount_path, Bytes::copy_from_slice(data), pts)
+    }
+
+    /// Like [`send_frame_with_pts`](Self::send_frame_with_pts), taking
+    /// ownership of a shared buffer (see
+    /// [`send_frame_bytes`](Self::send_frame_bytes)).
+    pub fn send_frame_bytes_with_pts(
+        &self,
+        mount_path: &str,
+        data: Bytes,
+        pts: Duration,
+    ) -> Result<usize> {
+        self.send(mount_path, 0, data, FrameTiming::Pts { pts, dts: None })
+    }
+
+    /// Like [`send_frame_with_pts`](Self::send_frame_with_pts) for streams
+    /// with B-frames, where frames arrive in decode order and the DTS is
+    /// known. The RTP timestamp still follows the PTS (RFC 6184 §5.1); the
+    /// DTS keeps the wall-clock reference monotonic.
+    pub fn send_frame_with_pts_dts(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        pts: Duration,
+        dts: Duration,
+    ) -> Result<usize> {
+        self.send(
+            mount_path,
+            0,
+            Bytes::copy_from_slice(data),
+            FrameTiming::Pts {
+                pts,
+                dts: Some(dts),
+            },
+        )
+    }
+
+    fn mount(&self, mount_path: &str) -> Result<Arc<Mount>> {
+        find_mount(&self.mounts, mount_path)
+    }
+
+    fn delivery(&self) -> Result<&Arc<Delivery>> {
+        self.delivery.as_ref().ok_or(RtspError::NotStarted)
+    }
+
+    fn send(
+        &self,
+        mount_path: &str,
+        track_index: usize,
+        data: Bytes,
+        timing: FrameTiming,
+    ) -> Result<usize> {
+        let delivery = self.delivery()?;
+        let mount = self.mount(mount_path)?;
+        let track = find_track(&mount, track_index)?;
+        Ok(delivery.submit(&self.send_queues, &mount, track, data, timing))
     }
 
     /// Send a pre-packetized RTP packet to a specific session.
     pub fn send_rtp_packet(&self, session_id: &str, payload: &[u8]) -> Result<usize> {
-        let udp = self.udp.as_ref().ok_or(RtspError::NotStarted)?;
-        let session = self
-            .session_manager
-            .get_session(session_id)
-            .ok_or_else(|| RtspError::SessionNotFound(session_id.to_string()))?;
-        if !session.is_playing() {
-            return Err(RtspError::SessionNotPlaying(session_id.to_string()));
-        }
-        let transport = session
-            .get_transport()
-            .ok_or_else(|| RtspError::TransportNotConfigured(session_id.to_string()))?;
-        udp.send_to(payload, transport.client_addr)
+        send_rtp_packet(
+            &self.delivery()?.udp,
+            &self.session_manager,
+            session_id,
+            payload,
+        )
     }
 
     /// Broadcast a pre-packetized RTP packet to all playing sessions
     /// on the default mount.
     pub fn broadcast_rtp_packet(&self, payload: &[u8]) -> Result<usize> {
-        let udp = self.udp.as_ref().ok_or(RtspError::NotStarted)?;
-        let mount = self
-            .mounts
-            .get(DEFAULT_MOUNT_PATH)
-            .ok_or_else(|| RtspError::MountNotFound(DEFAULT_MOUNT_PATH.to_string()))?;
-
-        let session_ids = mount.subscribed_session_ids();
-        let mut sent = 0;
-        for session_id in &session_ids {
-            let session = match self.session_manager.get_session(session_id) {
-                Some(s) if s.is_playing() => s,
-                _ => continue,
-            };
-            if let Some(transport) = session.get_transport() {
-                match udp.send_to(payload, transport.client_addr) {
-                    Ok(_) => sent += 1,
-                    Err(e) => {
-                        tracing::warn!(
-                            session_id,
-                            addr = %transport.client_addr,
-                            error = %e,
-                            "failed to send RTP packet"
-                        );
-                    }
-                }
-            }
-        }
-        Ok(sent)
+        let udp = &self.delivery()?.udp;
+        let mount = self.mount(DEFAULT_MOUNT_PATH)?;
+        Ok(broadcast_rtp_packet(
+            udp,
+            &self.session_manager,
+            &mount,
+            payload,
+        ))
     }
 
     pub fn get_viewers(&self) -> Vec<Viewer> {
-        self.session_manager
-            .get_playing_sessions()
-            .iter()
-            .filter_map(|session| {
-                session.get_transport().map(|transport| Viewer {
-                    session_id: session.id.clone(),
-                    uri: session.uri.clone(),
-                    client_addr: transport.client_addr.to_string(),
-                    client_rtp_port: transport.client_rtp_port,
-                })
-            })
-            .collect()
+        playing_viewers(&self.session_manager)
     }
 
     pub fn session_manager(&self) -> &SessionManager {
         &self.session_manager
     }
 
+    /// Issue session IDs from `generator` instead of random hex IDs, e.g.
+    /// to encode routing information. Applies to sessions created from now
+    /// on.
+    pub fn set_session_id_generator(&self, generator: impl SessionIdGenerator + 'static) {
+        self.session_manager.set_id_generator(generator);
+    }
+
     /// Returns the mount registry (used by adapters that need mount access).
     pub fn mounts(&self) -> &MountRegistry {
         &self.mounts
@@ -338,6 +642,434 @@ impl Server {
     }
 }
 
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Parse and validate a `host:port` bind address. Port 0 asks the OS for
+/// a free port.
+pub(crate) fn parse_bind_addr(bind_addr: &str) -> Result<SocketAddr> {
+    bind_addr.parse().map_err(|_| {
+        RtspError::InvalidBindAddress(format!(
+            "expected host:port with explicit port, got {:?}",
+            bind_addr
+        ))
+    })
+}
+
+pub(crate) fn find_mount(mounts: &MountRegistry, mount_path: &str) -> Result<Arc<Mount>> {
+    mounts
+        .get(mount_path)
+        .ok_or_else(|| RtspError::MountNotFound(mount_path.to_string()))
+}
+
+pub(crate) fn find_track(mount: &Mount, index: usize) -> Result<Arc<Track>> {
+    mount.track(index).ok_or_else(|| RtspError::TrackNotFound {
+        mount: mount.path().to_string(),
+        track: index,
+    })
+}
+
+/// Send a pre-packetized RTP packet to the playing session `session_id`.
+pub(crate) fn send_rtp_packet(
+    udp: &UdpTransport,
+    session_manager: &SessionManager,
+    session_id: &str,
+    payload: &[u8],
+) -> Result<usize> {
+    let session = session_manager
+        .get_session(session_id)
+        .ok_or_else(|| RtspError::SessionNotFound(session_id.to_string()))?;
+    if !session.is_playing() {
+        return Err(RtspError::SessionNotPlaying(session_id.to_string()));
+    }
+    let transport = session
+        .get_transport()
+        .ok_or_else(|| RtspError::TransportNotConfigured(session_id.to_string()))?;
+    match Destination::of(&session, &transport) {
+        Destination::Udp(addr) => udp.send_to(payload, addr),
+        Destination::Interleaved(sink, channel) => {
+            sink.send_packet(channel, RtpPacket::from(payload.to_vec()));
+            Ok(payload.len())
+        }
+    }
+}
+
+/// Send a pre-packetized RTP packet to every playing session subscribed
+/// to `mount`. Returns how many sessions it was sent to.
+pub(crate) fn broadcast_rtp_packet(
+    udp: &UdpTransport,
+    session_manager: &SessionManager,
+    mount: &Mount,
+    payload: &[u8],
+) -> usize {
+    let session_ids = mount.subscribed_session_ids();
+    let mut sent = 0;
+    for session_id in &session_ids {
+        let session = match session_manager.get_session(session_id) {
+            Some(s) if s.is_playing() => s,
+            _ => continue,
+        };
+        if let Some(transport) = session.get_transport() {
+            let result = match Destination::of(&session, &transport) {
+                Destination::Udp(addr) => udp.send_to(payload, addr).map(|_| ()),
+                Destination::Interleaved(sink, channel) => {
+                    sink.send_packet(channel, RtpPacket::from(payload.to_vec()));
+                    Ok(())
+                }
+            };
+            match result {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    tracing::warn!(
+                        session_id,
+                        addr = %transport.client_addr,
+                        error = %e,
+                        "failed to send RTP packet"
+                    );
+                }
+            }
+        }
+    }
+    sent
+}
+
+/// Start a player thread for each seekable mount, feeding its source's
+/// frames to `delivery` until `running` turns `false`.
+pub(crate) fn spawn_players(
+    mounts: &MountRegistry,
+    delivery: &Arc<Delivery>,
+    send_queues: &Arc<SendQueues>,
+    session_manager: &SessionManager,
+    running: Arc<AtomicBool>,
+) -> Vec<JoinHandle<()>> {
+    mounts
+        .all()
+        .into_iter()
+        .filter_map(|mount| {
+            let playback = mount.playback()?;
+            let delivery = delivery.clone();
+            let send_queues = send_queues.clone();
+            let session_manager = session_manager.clone();
+            let running = running.clone();
+            Some(thread::spawn(move || {
+                tracing::debug!(mount = %mount.path(), "player started");
+                playback.run(
+                    || running.load(Ordering::SeqCst),
+                    || {
+                        mount.subscribed_session_ids().iter().any(|id| {
+                            session_manager
+                                .get_session(id)
+                                .is_some_and(|s| s.is_playing())
+                        })
+                    },
+                    |track, data, timing| match mount.track(track) {
+                        Some(track) => {
+                            delivery.submit(&send_queues, &mount, track, data, timing);
+                        }
+                        None => {
+                            tracing::warn!(mount = %mount.path(), track, "source frame for unknown track");
+                        }
+                    },
+                );
+            }))
+        })
+        .collect()
+}
+
+/// Playing sessions as [`Viewer`]s.
+pub(crate) fn playing_viewers(session_manager: &SessionManager) -> Vec<Viewer> {
+    session_manager
+        .get_playing_sessions()
+        .iter()
+        .filter_map(|session| {
+            session.get_transport().map(|transport| Viewer {
+                session_id: session.id.clone(),
+                uri: session.uri.clone(),
+                client_addr: transport.client_addr.to_string(),
+                client_rtp_port: transport.client_rtp_port,
+                tcp_stats: transport.interleaved.and(session.tcp_stats()),
+            })
+        })
+        .collect()
+}
+
+/// Sender threads of mounts with a [send queue](Mount::set_send_queue),
+/// keyed by mount path and started on the first queued frame.
+pub(crate) type SendQueues = Mutex<HashMap<String, Arc<SendQueue>>>;
+
+/// RTP delivery state shared by callers of the `send_*` methods and the
+/// send-queue threads.
+pub(crate) struct Delivery {
+    session_manager: SessionManager,
+    udp: UdpTransport,
+    /// Sender threads of mounts with [pacing](Mount::set_pacing), keyed by
+    /// mount path and started on the first paced frame.
+    pacers: Mutex<HashMap<String, Arc<Pacer>>>,
+}
+
+/// Where one session's packets for a track go.
+enum Destination {
+    Udp(SocketAddr),
+    /// Channel on the session's RTSP connection.
+    Interleaved(Arc<InterleavedSink>, u8),
+}
+
+impl Destination {
+    fn of(session: &Session, transport: &Transport) -> Self {
+        match (transport.interleaved, session.interleaved_sink()) {
+            (Some((channel, _)), Some(sink)) => Self::Interleaved(sink, channel),
+            _ => Self::Udp(transport.client_addr),
+        }
+    }
+}
+
+impl Delivery {
+    /// Bind the RTP/RTCP sockets and pin the ports SETUP advertises.
+    pub(crate) fn bind(
+        session_manager: &SessionManager,
+        config: &ServerConfig,
+    ) -> Result<Arc<Self>> {
+        let udp = UdpTransport::bind()?;
+        if config.udp_gso && !udp.enable_gso() {
+            tracing::info!("UDP GSO not supported, using batched sends without it");
+        }
+        let (server_rtp_port, server_rtcp_port) = udp.local_ports()?;
+        session_manager.set_server_ports(server_rtp_port, server_rtcp_port);
+        Ok(Arc::new(Self {
+            session_manager: session_manager.clone(),
+            udp,
+            pacers: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn udp(&self) -> &UdpTransport {
+        &self.udp
+    }
+
+    /// Packetize and deliver a frame on this thread, or queue it for the
+    /// mount's sender thread if it has a [send queue](Mount::set_send_queue).
+    ///
+    /// Returns the number of viewers the frame is sent to; 0 if the send
+    /// queue dropped it.
+    pub(crate) fn submit(
+        self: &Arc<Self>,
+        send_queues: &SendQueues,
+        mount: &Arc<Mount>,
+        track: Arc<Track>,
+        data: Bytes,
+        timing: FrameTiming,
+    ) -> usize {
+        let track_index = track.index();
+        let keyframe = track.is_keyframe(&data);
+        let Some(config) = mount.send_queue() else {
+            let packets = timing.packetize(mount, &track, data);
+            let sent = self.deliver(mount, track_index, packets, keyframe);
+            mount.send_counters().frame_sent();
+            return sent;
+        };
+
+        let queue = send_queues
+            .lock()
+            .entry(mount.path().to_string())
+            .or_insert_with(|| {
+                let delivery = self.clone();
+                Arc::new(SendQueue::spawn(
+                    mount.clone(),
+                    Arc::new(move |mount: &Mount, track, packets, keyframe| {
+                        delivery.deliver(mount, track, packets, keyframe);
+                    }),
+                ))
+            })
+            .clone();
+
+        let frame = QueuedFrame {
+            track,
+            data,
+            timing,
+            keyframe,
+        };
+        if !queue.push(frame, config) {
+            return 0;
+        }
+        self.destinations(mount, track_index).len()
+    }
+
+    /// Stop the send-queue and pacer threads, discarding the frames still
+    /// queued. Pacers are told first, so that no sender thread stays
+    /// blocked on a full pacer.
+    pub(crate) fn flush(&self, send_queues: &SendQueues) {
+        for pacer in self.pacers.lock().values() {
+            pacer.stop();
+        }
+        send_queues.lock().clear();
+        self.pacers.lock().clear();
+    }
+
+    /// Send an RTCP BYE (RFC 3550 §6.6) for each set-up track to every
+    /// session subscribed to a mount: over UDP to the client's RTCP port,
+    /// or on the RTCP channel of interleaved sessions.
+    pub(crate) fn send_bye(&self, mounts: &MountRegistry) {
+        for mount in mounts.all() {
+            for session_id in mount.subscribed_session_ids() {
+                let Some(session) = self.session_manager.get_session(&session_id) else {
+                    continue;
+                };
+                for (track, transport) in session.transports() {
+                    // A track that never sent a packet has nothing to end.
+                    let Some(ssrc) = mount.track(track).and_then(|t| t.ssrc()) else {
+                        continue;
+                    };
+                    let bye = rtcp::build_bye(ssrc, BYE_REASON);
+                    match (transport.interleaved, session.interleaved_sink()) {
+                        (Some((_, channel)), Some(sink)) => {
+                            sink.send_packet(channel, RtpPacket::from(bye));
+                        }
+                        _ => {
+                            let addr = SocketAddr::new(
+                                transport.client_addr.ip(),
+                                transport.client_rtcp_port,
+                            );
+                            if let Err(e) = self.udp.send_rtcp(&bye, addr) {
+                                tracing::debug!(%session_id, %addr, error = %e, "failed to send RTCP BYE");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Playing sessions subscribed to `mount` that have set up `track`,
+    /// with where their RTP goes.
+    fn destinations(&self, mount: &Mount, track: usize) -> Vec<(String, Destination)> {
+        mount
+            .subscribed_session_ids()
+            .into_iter()
+            .filter_map(|session_id| {
+                let session = self.session_manager.get_session(&session_id)?;
+                if !session.is_playing() {
+                    return None;
+                }
+                let transport = session.track_transport(track)?;
+                let destination = Destination::of(&session, &transport);
+                Some((session_id, destination))
+            })
+            .collect()
+    }
+
+    /// Send a track's packets to every playing session subscribed to `mount`
+    /// that has set up that track. Returns the number of sessions the frame
+    /// was delivered (or, for paced mounts, queued) to.
+    ///
+    /// Packets are built once per frame and sent to each viewer as-is, in
+    /// one batch per viewer (see [`UdpTransport::send_packets`]). Mounts
+    /// with [pacing](Mount::set_pacing) hand the frame to their pacer
+    /// thread instead, waiting while its queue is full.
+    /// TCP viewers get the frame in their connection's buffer, where
+    /// `keyframe` lets a viewer that fell behind resume.
+    fn deliver(
+        &self,
+        mount: &Mount,
+        track: usize,
+        packets: Vec<RtpPacket>,
+        keyframe: bool,
+    ) -> usize {
+        let destinations = self.destinations(mount, track);
+        if destinations.is_empty() || packets.is_empty() {
+            return destinations.len();
+        }
+
+        let mut udp_destinations = Vec::with_capacity(destinations.len());
+        for (session_id, destination) in &destinations {
+            match destination {
+                Destination::Udp(addr) => udp_destinations.push((session_id, *addr)),
+                Destination::Interleaved(sink, channel) => {
+                    sink.send_frame(*channel, &packets, keyframe);
+                }
+            }
+        }
+        if udp_destinations.is_empty() {
+            return destinations.len();
+        }
+
+        if let Some(pacing) = mount.pacing() {
+            let frame = PacedFrame {
+                track,
+                clock_rate: mount.track(track).map_or(90000, |t| t.clock_rate()),
+                pacing,
+                packets,
+                destinations: udp_destinations.iter().map(|(_, addr)| *addr).collect(),
+            };
+            // Submit outside the map lock: a full pacer makes this mount's
+            // producer wait, not every mount's.
+            let pacer = self
+                .pacers
+                .lock()
+                .entry(mount.path().to_string())
+                .or_insert_with(|| Arc::new(Pacer::spawn(self.udp.clone(), mount.path())))
+                .clone();
+            pacer.submit(frame);
+            return destinations.len();
+        }
+
+        for (session_id, addr) in &udp_destinations {
+            if let Err(e) = self.udp.send_packets(&packets, *addr) {
+                tracing::warn!(
+                    session_id,
+                    addr = %addr,
+                    error = %e,
+                    "failed to send RTP packets"
+                );
+            }
+        }
+
+        destinations.len()
+    }
+}
+
+/// Receive RTCP from viewers and forward PLI/FIR to the owning mount.
+///
+/// Polls with a short timeout so it exits promptly after
+/// [`Server::stop`] clears the `running` flag.
+pub(crate) fn rtcp_loop(
+    udp: UdpTransport,
+    session_manager: SessionManager,
+    mounts: MountRegistry,
+    running: Arc<AtomicBool>,
+) {
+    let mut buf = [0u8; 1500];
+    while running.load(Ordering::SeqCst) {
+        let (len, from) = match udp.recv_rtcp(&mut buf) {
+            Ok(Some(received)) => received,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::trace!(error = %e, "RTCP receive error");
+                continue;
+            }
+        };
+
+        handle_rtcp(&buf[..len], from, &session_manager, &mounts);
+    }
+    tracing::debug!("RTCP loop exited");
+}
+
+/// Forward feedback in an RTCP packet received from `from` over UDP.
+pub(crate) fn handle_rtcp(
+    packet: &[u8],
+    from: SocketAddr,
+    session_manager: &SessionManager,
+    mounts: &MountRegistry,
+) {
+    match session_manager.find_by_rtcp_addr(from) {
+        Some(session) => mounts.forward_rtcp_feedback(&session.id, packet),
+        None => tracing::trace!(%from, "RTCP from unknown peer"),
+    }
+}
+
 /// Information about a connected viewer (client in PLAY state).
 #[derive(Debug, Clone)]
 pub struct Viewer {
@@ -345,6 +1077,10 @@ pub struct Viewer {
     pub uri: String,
     pub client_addr: String,
     pub client_rtp_port: u16,
+    /// Delivery counters when media is interleaved on the RTSP connection;
+    /// [`overflows`](TcpViewerStats::overflows) counts how often this
+    /// viewer fell behind.
+    pub tcp_stats: Option<TcpViewerStats>,
 }
 
 #[cfg(test)]
@@ -352,13 +1088,16 @@ mod tests {
     use super::*;
 
     #[test]
-    fn start_rejects_port_zero() {
+    fn start_binds_port_zero_to_a_free_port() {
         let mut server = Server::new("127.0.0.1:0");
-        let err = server.start().unwrap_err();
-        match &err {
-            RtspError::InvalidBindAddress(msg) => assert!(msg.contains("non-zero"), "{}", msg),
-            _ => panic!("expected InvalidBindAddress, got {:?}", err),
-        }
+        assert!(server.local_addr().is_none());
+        server.start().expect("port 0 should be accepted");
+        let addr = server.local_addr().expect("bound address");
+        assert_eq!(addr.ip(), std::net::Ipv4Addr::LOCALHOST);
+        assert_ne!(addr.port(), 0);
+        std::net::TcpStream::connect(addr).expect("listener reachable");
+        server.stop();
+        assert!(server.local_addr().is_none());
     }
 
     #[test]
@@ -373,9 +1112,15 @@ mod tests {
 
     #[test]
     fn start_accepts_explicit_port() {
-        let mut server = Server::new("127.0.0.1:18555");
+        // A port the OS just handed out, so no fixed port can be taken.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port")
+            .port();
+        let mut server = Server::new(&format!("127.0.0.1:{port}"));
         server.start().expect("explicit port should be accepted");
         assert!(server.is_running());
+        assert_eq!(server.local_addr().map(|addr| addr.port()), Some(port));
         server.stop();
     }
 }
@@ -0,0 +1,121 @@
+//! Session ID generation (RFC 2326 §3.4, §12.37).
+//!
+//! A session ID is the only credential a client presents to PLAY, PAUSE or
+//! TEARDOWN a session, so it must not be guessable. The default generator
+//! draws IDs from the thread-local CSPRNG of `rand` (ChaCha, seeded and
+//! periodically reseeded from the OS). Deployments that route requests by
+//! session ID (e.g. behind a load balancer) can install their own
+//! [`SessionIdGenerator`] that embeds routing information.
+
+use rand::RngExt;
+
+/// Default session ID length in characters (64 random bits).
+pub const DEFAULT_SESSION_ID_LENGTH: usize = 16;
+
+/// Shortest session ID [`RandomSessionIds`] generates (RFC 7826 §18.49
+/// asks for at least 8 octets).
+pub const MIN_SESSION_ID_LENGTH: usize = 8;
+
+/// Produces session IDs for new sessions.
+///
+/// IDs must be valid RFC 2326 session identifiers (see
+/// [`is_valid_session_id`]) and should be unpredictable.
+/// [`SessionManager`](super::SessionManager) retries on a collision with
+/// an existing session.
+///
+/// Any `Fn() -> String + Send + Sync` closure is a generator:
+///
+/// ```
+/// use rtsp::session::{RandomSessionIds, SessionIdGenerator, SessionManager};
+///
+/// let random = RandomSessionIds::new(16);
+/// let sessions = SessionManager::new();
+/// sessions.set_id_generator(move || format!("edge1-{}", random.generate()));
+/// let session = sessions.create_session("rtsp://host/stream").unwrap();
+/// assert!(session.id.starts_with("edge1-"));
+/// ```
+pub trait SessionIdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+impl<F> SessionIdGenerator for F
+where
+    F: Fn() -> String + Send + Sync,
+{
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+/// Uppercase hex IDs of a fixed length from a CSPRNG. The default
+/// generator.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomSessionIds {
+    length: usize,
+}
+
+impl RandomSessionIds {
+    /// Generate IDs of `length` hex characters, at least
+    /// [`MIN_SESSION_ID_LENGTH`].
+    pub fn new(length: usize) -> Self {
+        Self {
+            length: length.max(MIN_SESSION_ID_LENGTH),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+impl Default for RandomSessionIds {
+    fn default() -> Self {
+        Self::new(DEFAULT_SESSION_ID_LENGTH)
+    }
+}
+
+impl SessionIdGenerator for RandomSessionIds {
+    fn generate(&self) -> String {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        let mut rng = rand::rng();
+        (0..self.length)
+            .map(|_| HEX[rng.random_range(0..HEX.len())] as char)
+            .collect()
+    }
+}
+
+/// Whether `id` can be sent in a `Session` header: one or more letters,
+/// digits or `$-_.+` (RFC 2326 §3.4).
+pub fn is_valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"$-_.+".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_ids_are_hex_of_the_configured_length() {
+        let ids = RandomSessionIds::new(24);
+        let a = ids.generate();
+        let b = ids.generate();
+        assert_eq!(a.len(), 24);
+        assert!(a.bytes().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+        assert_eq!(
+            RandomSessionIds::new(2).generate().len(),
+            MIN_SESSION_ID_LENGTH
+        );
+    }
+
+    #[test]
+    fn validates_session_id_characters() {
+        assert!(is_valid_session_id("A1b2-C3_d4.e5+$"));
+        assert!(!is_valid_session_id(""));
+        assert!(!is_valid_session_id("abc;timeout=60"));
+        assert!(!is_valid_session_id("abc def"));
+    }
+}
@@ -3,34 +3,48 @@
 //! An RTSP session is a server-side state object created during SETUP and
 //! destroyed by TEARDOWN (or timeout). It tracks:
 //!
-//! - A unique session ID (hex string, returned in the `Session` header).
+//! - A unique, unguessable session ID (returned in the `Session` header;
+//!   see [`id`]).
 //! - The playback state: Ready -> Playing <-> Paused.
-//! - Transport parameters (client/server UDP ports) negotiated during SETUP.
+//! - Transport parameters (client/server UDP ports, or interleaved TCP
+//!   channels) negotiated during SETUP,
+//!   one per track — a client SETUPs each track of a multi-track mount
+//!   within the same session.
 //! - A timeout (default 60s, per RFC 2326 §12.37) — the client must send
 //!   a request (e.g. GET_PARAMETER) before the timeout expires.
 //!
-//! ## Session lifecycle (RFC 2326 §A.1)
+//! ## Session lifecycle (RFC 2326 §A.2)
 //!
 //! ```text
 //! SETUP         -> Ready
-//! PLAY          -> Playing
-//! PAUSE         -> Paused   (from Playing)
-//! PLAY          -> Playing  (from Paused)
+//! PLAY          -> Playing  (from Ready or Paused, once a track is set up)
+//! PAUSE         -> Paused   (from Playing or Paused)
+//! SETUP         -> (unchanged; changes a track's transport, or adds a
+//!                   track unless Playing)
 //! TEARDOWN      -> (removed)
 //! TCP disconnect -> (removed, via cleanup)
 //! ```
+//!
+//! A method the current state does not accept is answered with
+//! `455 Method Not Valid in This State`; see [`SessionState::allows`].
 
+pub mod id;
 pub mod transport;
 
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::error::Result;
+use crate::error::{Result, RtspError};
+use crate::transport::interleaved::{InterleavedSink, TcpViewerStats};
+pub use id::{RandomSessionIds, SessionIdGenerator};
 pub use transport::Transport;
 
-static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// IDs [`SessionManager::create_session`] draws before giving up on
+/// finding one not already in use.
+const SESSION_ID_ATTEMPTS: usize = 8;
 
 const SERVER_PORT_MIN: u64 = 5000;
 const SERVER_PORT_MAX: u64 = 65534;
@@ -49,46 +63,108 @@ pub enum SessionState {
     Paused,
 }
 
+impl SessionState {
+    /// Session methods valid in this state (RFC 2326 §A.2), for the
+    /// `Allow` header of a 455 response.
+    pub fn allowed_methods(&self) -> &'static [&'static str] {
+        match self {
+            SessionState::Ready => &["SETUP", "PLAY", "TEARDOWN", "GET_PARAMETER"],
+            SessionState::Playing | SessionState::Paused => {
+                &["SETUP", "PLAY", "PAUSE", "TEARDOWN", "GET_PARAMETER"]
+            }
+        }
+    }
+
+    /// Whether `method` is valid in this state. PAUSE is not: a session
+    /// that never played has nothing to pause.
+    pub fn allows(&self, method: &str) -> bool {
+        self.allowed_methods().contains(&method)
+    }
+}
+
 /// A single RTSP session (RFC 2326 §3).
 ///
 /// Created during SETUP, destroyed by TEARDOWN or TCP disconnect.
 /// Interior mutability via `RwLock` allows shared references across threads.
 #[derive(Debug)]
 pub struct Session {
-    /// Unique session identifier (16-char hex string).
+    /// Unique session identifier (16-char hex string by default).
     pub id: String,
     /// The RTSP URI this session was created for (from the SETUP request).
     pub uri: String,
-    /// Transport parameters negotiated during SETUP (RFC 2326 §12.39).
-    pub transport: RwLock<Option<Transport>>,
+    /// Transport parameters negotiated during SETUP (RFC 2326 §12.39),
+    /// keyed by zero-based track index.
+    pub transports: RwLock<BTreeMap<usize, Transport>>,
     /// Current playback state.
     pub state: RwLock<SessionState>,
     /// Session timeout in seconds (included in the `Session` response header).
     pub timeout_secs: u64,
+    /// Outbound side of the RTSP connection, for tracks set up with
+    /// interleaved transport.
+    interleaved: RwLock<Option<Arc<InterleavedSink>>>,
 }
 
 impl Session {
-    /// Create a new session with a unique auto-incrementing ID.
+    /// Create a new session with a random ID of the default length.
     pub fn new(uri: &str) -> Self {
-        let id = SESSION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        Self::with_id(RandomSessionIds::default().generate(), uri)
+    }
+
+    /// Create a new session with the given ID.
+    pub fn with_id(id: String, uri: &str) -> Self {
         Session {
-            id: format!("{:016X}", id),
+            id,
             uri: uri.to_string(),
-            transport: RwLock::new(None),
+            transports: RwLock::new(BTreeMap::new()),
             state: RwLock::new(SessionState::Ready),
             timeout_secs: DEFAULT_SESSION_TIMEOUT_SECS,
+            interleaved: RwLock::new(None),
         }
     }
 
-    /// Set the transport parameters (called during SETUP).
+    /// Set the transport parameters of the first track (called during SETUP).
     pub fn set_transport(&self, transport: Transport) {
-        tracing::debug!(session_id = %self.id, client_addr = %transport.client_addr, "transport configured");
-        *self.transport.write() = Some(transport);
+        self.set_track_transport(0, transport);
+    }
+
+    /// Set the transport parameters of a track (called during SETUP).
+    pub fn set_track_transport(&self, track: usize, transport: Transport) {
+        tracing::debug!(session_id = %self.id, track, client_addr = %transport.client_addr, "transport configured");
+        self.transports.write().insert(track, transport);
     }
 
-    /// Returns a clone of the transport parameters, if configured.
+    /// Returns the transport of the lowest set-up track, if any.
     pub fn get_transport(&self) -> Option<Transport> {
-        self.transport.read().clone()
+        self.transports.read().values().next().cloned()
+    }
+
+    /// Returns the transport of a specific track, if it was set up.
+    pub fn track_transport(&self, track: usize) -> Option<Transport> {
+        self.transports.read().get(&track).cloned()
+    }
+
+    /// Returns all configured `(track, transport)` pairs, ordered by track.
+    pub fn transports(&self) -> Vec<(usize, Transport)> {
+        self.transports
+            .read()
+            .iter()
+            .map(|(track, transport)| (*track, transport.clone()))
+            .collect()
+    }
+
+    pub(crate) fn set_interleaved_sink(&self, sink: Arc<InterleavedSink>) {
+        *self.interleaved.write() = Some(sink);
+    }
+
+    pub(crate) fn interleaved_sink(&self) -> Option<Arc<InterleavedSink>> {
+        self.interleaved.read().clone()
+    }
+
+    /// Delivery counters of the RTSP connection, if any track of this
+    /// session is interleaved on it. Sessions sharing a connection share
+    /// its counters.
+    pub fn tcp_stats(&self) -> Option<TcpViewerStats> {
+        self.interleaved.read().as_ref().map(|sink| sink.stats())
     }
 
     /// Transition to a new playback state.
@@ -109,7 +185,7 @@ impl Session {
 
     /// Format the `Session` response header value per RFC 2326 §12.37.
     ///
-    /// Example: `"0000000000000001;timeout=60"`
+    /// Example: `"3F9A0C17B24E8D65;timeout=60"`
     pub fn session_header_value(&self) -> String {
         format!("{};timeout={}", self.id, self.timeout_secs)
     }
@@ -123,26 +199,59 @@ impl Session {
 pub struct SessionManager {
     sessions: Arc<RwLock<HashMap<String, Arc<Session>>>>,
     next_server_port: Arc<AtomicU64>,
+    /// Ports of the sockets actually bound by the UDP transport, if any.
+    bound_server_ports: Arc<RwLock<Option<(u16, u16)>>>,
+    id_generator: Arc<RwLock<Arc<dyn SessionIdGenerator>>>,
 }
 
 impl SessionManager {
+    /// A session manager issuing random IDs of the default length.
     pub fn new() -> Self {
+        Self::with_id_generator(RandomSessionIds::default())
+    }
+
+    /// A session manager issuing IDs from `generator`.
+    pub fn with_id_generator(generator: impl SessionIdGenerator + 'static) -> Self {
         SessionManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             next_server_port: Arc::new(AtomicU64::new(SERVER_PORT_MIN)),
+            bound_server_ports: Arc::new(RwLock::new(None)),
+            id_generator: Arc::new(RwLock::new(Arc::new(generator))),
         }
     }
 
-    /// Create a new session for the given URI and register it.
-    pub fn create_session(&self, uri: &str) -> Arc<Session> {
-        let session = Arc::new(Session::new(uri));
-        let id = session.id.clone();
-        self.sessions.write().insert(id.clone(), session.clone());
+    /// Replace the ID generator for sessions created from now on, e.g. to
+    /// encode routing information in IDs. Shared by all clones.
+    pub fn set_id_generator(&self, generator: impl SessionIdGenerator + 'static) {
+        *self.id_generator.write() = Arc::new(generator);
+    }
 
-        let total = self.sessions.read().len();
-        tracing::debug!(session_id = %id, uri, total_sessions = total, "session created");
+    /// Create a new session for the given URI and register it.
+    ///
+    /// Draws a fresh ID while the generated one is already in use; fails
+    /// with [`RtspError::DuplicateSessionId`] if that keeps happening, and
+    /// with [`RtspError::InvalidSessionId`] if the generator produces an ID
+    /// that cannot go in a `Session` header.
+    pub fn create_session(&self, uri: &str) -> Result<Arc<Session>> {
+        let generator = self.id_generator.read().clone();
+        let mut id = String::new();
+        for _ in 0..SESSION_ID_ATTEMPTS {
+            id = generator.generate();
+            if !id::is_valid_session_id(&id) {
+                return Err(RtspError::InvalidSessionId(id));
+            }
 
-        session
+            let mut sessions = self.sessions.write();
+            if sessions.contains_key(&id) {
+                tracing::warn!(session_id = %id, "generated session ID already in use, retrying");
+                continue;
+            }
+            let session = Arc::new(Session::with_id(id.clone(), uri));
+            sessions.insert(id.clone(), session.clone());
+            tracing::debug!(session_id = %id, uri, total_sessions = sessions.len(), "session created");
+            return Ok(session);
+        }
+        Err(RtspError::DuplicateSessionId(id))
     }
 
     /// Look up a session by ID.
@@ -175,12 +284,34 @@ impl SessionManager {
         removed
     }
 
+    /// Remove every session (used on server shutdown). Returns how many
+    /// were removed.
+    pub fn clear(&self) -> usize {
+        let mut sessions = self.sessions.write();
+        let removed = sessions.len();
+        sessions.clear();
+        removed
+    }
+
+    /// Pin the server port pair advertised in SETUP to the RTP/RTCP sockets
+    /// bound by [`UdpTransport`](crate::transport::UdpTransport), so that
+    /// viewers' RTCP actually reaches the server.
+    pub fn set_server_ports(&self, rtp: u16, rtcp: u16) {
+        *self.bound_server_ports.write() = Some((rtp, rtcp));
+    }
+
     /// Allocate a pair of (RTP, RTCP) server ports.
     ///
-    /// Ports are allocated from a monotonic counter starting at 5000.
+    /// Returns the pair set via [`set_server_ports`](Self::set_server_ports)
+    /// when the server has bound its sockets. Otherwise ports are allocated
+    /// from a monotonic counter starting at 5000.
     /// When the range is exhausted (> 65534), it wraps back to 5000.
     /// Per RFC 3550 §11, RTP ports should be even and RTCP = RTP + 1.
     pub fn allocate_server_ports(&self) -> Result<(u16, u16)> {
+        if let Some(ports) = *self.bound_server_ports.read() {
+            return Ok(ports);
+        }
+
         let rtp = self.next_server_port.fetch_add(2, Ordering::SeqCst);
 
         if rtp > SERVER_PORT_MAX {
@@ -199,6 +330,35 @@ impl SessionManager {
         Ok((rtp as u16, rtp as u16 + 1))
     }
 
+    /// Find the session an RTCP packet from `addr` belongs to.
+    ///
+    /// Matches the negotiated client RTCP address first. Clients behind NAT
+    /// may send from a different port, so a session whose client IP matches
+    /// is used when it is the only one from that host.
+    pub fn find_by_rtcp_addr(&self, addr: SocketAddr) -> Option<Arc<Session>> {
+        let sessions = self.sessions.read();
+        let mut same_host = sessions.values().filter(|s| {
+            s.transports
+                .read()
+                .values()
+                .any(|t| t.interleaved.is_none() && t.client_addr.ip() == addr.ip())
+        });
+
+        if let Some(exact) = same_host.clone().find(|s| {
+            s.transports
+                .read()
+                .values()
+                .any(|t| t.client_rtcp_port == addr.port())
+        }) {
+            return Some(exact.clone());
+        }
+
+        match (same_host.next(), same_host.next()) {
+            (Some(only), None) => Some(only.clone()),
+            _ => None,
+        }
+    }
+
     /// Returns all sessions currently in the [`SessionState::Playing`] state.
     pub fn get_playing_sessions(&self) -> Vec<Arc<Session>> {
         self.sessions
@@ -215,3 +375,49 @@ impl Default for SessionManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_session_retries_colliding_ids() {
+        let sessions = SessionManager::with_id_generator(RandomSessionIds::new(32));
+        let first = sessions.create_session("rtsp://h/stream").unwrap();
+        assert_eq!(first.id.len(), 32);
+
+        let taken = first.id.clone();
+        let next = std::sync::Mutex::new(vec!["FRESH".to_string(), taken.clone()]);
+        sessions.set_id_generator(move || next.lock().unwrap().pop().unwrap());
+        let second = sessions.create_session("rtsp://h/stream").unwrap();
+        assert_eq!(second.id, "FRESH");
+
+        sessions.set_id_generator(move || taken.clone());
+        assert!(matches!(
+            sessions.create_session("rtsp://h/stream"),
+            Err(RtspError::DuplicateSessionId(_))
+        ));
+        sessions.set_id_generator(|| "bad;id".to_string());
+        assert!(matches!(
+            sessions.create_session("rtsp://h/stream"),
+            Err(RtspError::InvalidSessionId(_))
+        ));
+    }
+
+    #[test]
+    fn pause_is_only_valid_after_play() {
+        assert!(!SessionState::Ready.allows("PAUSE"));
+        assert!(SessionState::Ready.allows("PLAY"));
+        assert!(SessionState::Playing.allows("PAUSE"));
+        assert!(SessionState::Paused.allows("PAUSE"));
+        for state in [
+            SessionState::Ready,
+            SessionState::Playing,
+            SessionState::Paused,
+        ] {
+            assert!(state.allows("SETUP"));
+            assert!(state.allows("TEARDOWN"));
+            assert!(!state.allows("RECORD"));
+        }
+    }
+}
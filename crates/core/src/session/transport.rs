@@ -15,39 +15,51 @@ use std::net::SocketAddr;
 ///   Transport: RTP/AVP;unicast;client_port=8000-8001;server_port=5000-5001
 /// ```
 ///
-/// The server sends RTP to `client_addr:client_rtp_port` and (future)
-/// RTCP to `client_addr:client_rtcp_port`.
+/// The server sends RTP to `client_addr:client_rtp_port` and receives the
+/// client's RTCP on `server_rtcp_port`. For interleaved transport
+/// (`RTP/AVP/TCP;interleaved=0-1`) media instead travels on the RTSP
+/// connection and the port fields are 0.
 #[derive(Debug, Clone)]
 pub struct Transport {
     /// Client's RTP receive port.
     pub client_rtp_port: u16,
     /// Client's RTCP receive port (typically `client_rtp_port + 1`).
     pub client_rtcp_port: u16,
-    /// Server's RTP send port (advertised to client, not actually bound).
+    /// Server's RTP send port (advertised to client).
     pub server_rtp_port: u16,
-    /// Server's RTCP port (advertised to client, not actually bound).
+    /// Server's RTCP port (advertised to client; receives PLI/FIR feedback).
     pub server_rtcp_port: u16,
-    /// Full socket address for RTP delivery (`client_ip:client_rtp_port`).
+    /// Full socket address for RTP delivery (`client_ip:client_rtp_port`);
+    /// the connection's peer address for interleaved transport.
     pub client_addr: SocketAddr,
+    /// RTP and RTCP channel ids when media is interleaved on the RTSP
+    /// connection (RFC 2326 §10.12).
+    pub interleaved: Option<(u8, u8)>,
 }
 
 /// Parsed client-side transport info from the RTSP `Transport` header.
 ///
-/// Extracts the `client_port=RTP-RTCP` pair from the header value.
-/// Currently only handles `RTP/AVP;unicast` — interleaved TCP and
-/// multicast are not yet supported (see Issues #14 and RFC 2326 §12.39).
+/// Extracts the `client_port=RTP-RTCP` pair for `RTP/AVP;unicast`, or the
+/// `interleaved=RTP-RTCP` channels for `RTP/AVP/TCP`. Multicast is not yet
+/// supported (see Issues #14 and RFC 2326 §12.39).
 #[derive(Debug, Clone)]
 pub struct TransportHeader {
-    /// Client's requested RTP port.
+    /// Client's requested RTP port (0 for TCP).
     pub client_rtp_port: u16,
-    /// Client's requested RTCP port.
+    /// Client's requested RTCP port (0 for TCP).
     pub client_rtcp_port: u16,
+    /// Whether media should be interleaved on the RTSP connection.
+    pub tcp: bool,
+    /// Channels the client asked for; `None` lets the server choose.
+    pub interleaved: Option<(u8, u8)>,
 }
 
 impl TransportHeader {
     /// Parse the `Transport` header value (RFC 2326 §12.39).
     ///
-    /// Looks for `client_port=RTP-RTCP` among semicolon-separated parameters.
+    /// Looks for `client_port=RTP-RTCP` among semicolon-separated parameters,
+    /// or `RTP/AVP/TCP` with an optional `interleaved=RTP-RTCP` (a single
+    /// channel implies RTCP on the next one).
     ///
     /// ## Examples
     ///
@@ -59,25 +71,52 @@ impl TransportHeader {
     /// assert_eq!(th.client_rtcp_port, 8001);
     ///
     /// assert!(TransportHeader::parse("RTP/AVP;unicast").is_none());
+    ///
+    /// let tcp = TransportHeader::parse("RTP/AVP/TCP;unicast;interleaved=2-3").unwrap();
+    /// assert_eq!(tcp.interleaved, Some((2, 3)));
     /// ```
     pub fn parse(header: &str) -> Option<Self> {
-        for part in header.split(';') {
+        // Only the first transport spec of a comma-separated list is used.
+        let spec = header.split(',').next().unwrap_or(header);
+        let mut tcp = false;
+        let mut interleaved = None;
+
+        for part in spec.split(';') {
             let part = part.trim();
-            if let Some(ports) = part.strip_prefix("client_port=") {
+            if part.eq_ignore_ascii_case("RTP/AVP/TCP") {
+                tcp = true;
+            } else if let Some(channels) = part.strip_prefix("interleaved=") {
+                tcp = true;
+                interleaved = Some(match channels.split_once('-') {
+                    Some((rtp, rtcp)) => (rtp.parse().ok()?, rtcp.parse().ok()?),
+                    None => {
+                        let rtp: u8 = channels.parse().ok()?;
+                        (rtp, rtp.checked_add(1)?)
+                    }
+                });
+            } else if let Some(ports) = part.strip_prefix("client_port=") {
                 let port_parts: Vec<&str> = ports.split('-').collect();
 
-                if port_parts.len() == 2 {
+                if port_parts.len() == 2 && !tcp {
                     let rtp_port: u16 = port_parts[0].parse().ok()?;
                     let rtcp_port: u16 = port_parts[1].parse().ok()?;
 
                     return Some(TransportHeader {
                         client_rtp_port: rtp_port,
                         client_rtcp_port: rtcp_port,
+                        tcp: false,
+                        interleaved: None,
                     });
                 }
             }
         }
-        None
+
+        tcp.then_some(TransportHeader {
+            client_rtp_port: 0,
+            client_rtcp_port: 0,
+            tcp,
+            interleaved,
+        })
     }
 }
 
@@ -96,4 +135,20 @@ mod tests {
     fn parse_no_client_port() {
         assert!(TransportHeader::parse("RTP/AVP;unicast").is_none());
     }
+
+    #[test]
+    fn parse_interleaved_transport() {
+        let th = TransportHeader::parse("RTP/AVP/TCP;unicast;interleaved=0-1").unwrap();
+        assert!(th.tcp);
+        assert_eq!(th.interleaved, Some((0, 1)));
+
+        let th = TransportHeader::parse("RTP/AVP/TCP;unicast;interleaved=4").unwrap();
+        assert_eq!(th.interleaved, Some((4, 5)));
+
+        let th = TransportHeader::parse("RTP/AVP/TCP;unicast").unwrap();
+        assert!(th.tcp);
+        assert_eq!(th.interleaved, None);
+
+        assert!(TransportHeader::parse("RTP/AVP/TCP;interleaved=x-1").is_none());
+    }
 }
@@ -0,0 +1,283 @@
+//! Async counterpart of [`tcp`](super::tcp) for the tokio
+//! [`AsyncServer`](crate::AsyncServer): one task per connection instead of
+//! one thread, sharing the same request handling.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::mount::MountRegistry;
+use crate::server::{SHUTDOWN_TIMEOUT, ServerConfig};
+use crate::session::SessionManager;
+
+use super::http_tunnel::{self, PostDecoder, TunnelRequest, Tunnels};
+use super::interleaved::InterleavedSink;
+use super::tcp::{ConnectionState, Exit};
+
+/// Back-off after a failed accept (e.g. out of file descriptors).
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(50);
+
+/// Accept RTSP connections until `shutdown` turns `true`, then wait for
+/// every connection task to clean up its sessions.
+pub async fn accept_loop(
+    listener: TcpListener,
+    session_manager: SessionManager,
+    mounts: MountRegistry,
+    config: Arc<ServerConfig>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut connections = JoinSet::new();
+    let tunnels = Tunnels::default();
+    let connection_shutdown = shutdown.clone();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer_addr)) => {
+                    connections.spawn(handle_connection(
+                        stream,
+                        peer_addr,
+                        session_manager.clone(),
+                        mounts.clone(),
+                        config.clone(),
+                        tunnels.clone(),
+                        connection_shutdown.clone(),
+                    ));
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "TCP accept error");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                }
+            },
+            // Reap finished connections so the set does not grow.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = raised(&mut shutdown) => break,
+        }
+    }
+    // Connections watch the same signal; let them remove their sessions.
+    while connections.join_next().await.is_some() {}
+    tracing::debug!("accept loop exited");
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    session_manager: SessionManager,
+    mounts: MountRegistry,
+    config: Arc<ServerConfig>,
+    tunnels: Tunnels,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    tracing::info!(%peer_addr, "client connected");
+
+    let local_addr = stream.local_addr();
+    let (read, write) = stream.into_split();
+    let (sink, mut closed) = InterleavedSink::spawn_async(
+        write,
+        peer_addr,
+        config.tcp_send_buffer,
+        config.tcp_overflow_policy,
+    );
+    let mut state =
+        ConnectionState::new(sink, peer_addr, local_addr, session_manager, mounts, config);
+    let mut reader = BufReader::new(read);
+
+    let exit = tokio::select! {
+        exit = run(&mut reader, &mut state) => exit,
+        _ = raised(&mut shutdown) => Exit::Closed("server shutting down"),
+        _ = raised(&mut closed) => Exit::Closed("connection closed by server"),
+    };
+    let reason = match exit {
+        Exit::Closed(reason) => {
+            if *shutdown.borrow() {
+                // Let queued output, such as the RTCP BYE, reach the client.
+                state.finish();
+                let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, raised(&mut closed)).await;
+            }
+            state.cleanup();
+            reason
+        }
+        Exit::Tunnel(request) => {
+            let mut channel = Channel {
+                reader,
+                closed,
+                shutdown,
+            };
+            channel.tunnel(request, state, &tunnels).await
+        }
+    };
+
+    tracing::info!(%peer_addr, reason, "client disconnected");
+}
+
+/// A connection that turned out to be one side of an HTTP tunnel.
+struct Channel {
+    reader: BufReader<OwnedReadHalf>,
+    /// Turns `true` when the connection's own sink closes.
+    closed: watch::Receiver<bool>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Channel {
+    /// Serve one side of an HTTP tunnel. Returns the reason for closing.
+    async fn tunnel(
+        &mut self,
+        request: TunnelRequest,
+        state: ConnectionState,
+        tunnels: &Tunnels,
+    ) -> &'static str {
+        match request {
+            TunnelRequest::Get { cookie } => {
+                let state = match tunnels.open(&cookie, state) {
+                    Ok(state) => state,
+                    Err(state) => return self.refuse(&state, 400, "tunnel cookie in use").await,
+                };
+                state.lock().send_http(http_tunnel::get_response());
+                tracing::debug!(cookie, "HTTP tunnel opened");
+                // Nothing else arrives on the GET channel; wait for it to close.
+                let reason = self.drain().await;
+                tunnels.close(&cookie);
+                if *self.shutdown.borrow() {
+                    // Let queued output, such as the RTCP BYE, reach the client.
+                    state.lock().finish();
+                    let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, raised(&mut self.closed)).await;
+                }
+                state.lock().cleanup();
+                reason
+            }
+            TunnelRequest::Post { cookie } => {
+                let Some(tunnel) = tunnels.get(&cookie) else {
+                    return self.refuse(&state, 404, "no tunnel for cookie").await;
+                };
+                let mut decoder = PostDecoder::default();
+                let reader = &mut self.reader;
+                let post = async {
+                    loop {
+                        let data = match reader.fill_buf().await {
+                            Ok([]) => return "POST channel closed by client",
+                            Ok(data) => data,
+                            Err(_) => return "read error",
+                        };
+                        let len = data.len();
+                        if let Err(reason) = decoder.deliver(data, &tunnel) {
+                            return reason;
+                        }
+                        reader.consume(len);
+                    }
+                };
+                let reason = tokio::select! {
+                    reason = post => reason,
+                    _ = raised(&mut self.shutdown) => "server shutting down",
+                    _ = raised(&mut self.closed) => "connection closed by server",
+                };
+                state.cleanup();
+                reason
+            }
+            TunnelRequest::Invalid => {
+                self.refuse(&state, 400, "HTTP request without tunnel")
+                    .await
+            }
+        }
+    }
+
+    /// Answer an HTTP request with an error and close the connection once
+    /// it is written.
+    async fn refuse(
+        &mut self,
+        state: &ConnectionState,
+        status: u16,
+        reason: &'static str,
+    ) -> &'static str {
+        state.send_http(http_tunnel::error_response(status));
+        state.finish();
+        let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, raised(&mut self.closed)).await;
+        state.cleanup();
+        reason
+    }
+
+    /// Read and discard until the connection closes.
+    async fn drain(&mut self) -> &'static str {
+        let reader = &mut self.reader;
+        let read = async {
+            loop {
+                match reader.fill_buf().await {
+                    Ok([]) => return "connection closed by client",
+                    Ok(data) => {
+                        let len = data.len();
+                        reader.consume(len);
+                    }
+                    Err(_) => return "read error",
+                }
+            }
+        };
+        tokio::select! {
+            reason = read => reason,
+            _ = raised(&mut self.shutdown) => "server shutting down",
+            _ = raised(&mut self.closed) => "connection closed by server",
+        }
+    }
+}
+
+/// RTSP request/response loop. Returns why it exited: the connection is
+/// done, or its first request opens an HTTP tunnel.
+async fn run(reader: &mut BufReader<OwnedReadHalf>, state: &mut ConnectionState) -> Exit {
+    let mut first = true;
+    loop {
+        // Interleaved data (`$`) may arrive between requests.
+        match reader.fill_buf().await {
+            Ok([]) => return Exit::Closed("connection closed by client"),
+            Ok([b'$', ..]) => {
+                let mut prefix = [0u8; 4];
+                if reader.read_exact(&mut prefix).await.is_err() {
+                    return Exit::Closed("read error");
+                }
+                let mut packet = vec![0u8; u16::from_be_bytes([prefix[2], prefix[3]]) as usize];
+                if reader.read_exact(&mut packet).await.is_err() {
+                    return Exit::Closed("read error");
+                }
+                state.handle_interleaved(prefix[1], &packet);
+                continue;
+            }
+            Ok(_) => {}
+            Err(_) => return Exit::Closed("read error"),
+        }
+
+        let mut request_text = String::new();
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line).await {
+                Ok(0) => return Exit::Closed("connection closed by client"),
+                Ok(_) => {
+                    request_text.push_str(&line);
+                    if line == "\r\n" || line == "\n" {
+                        break;
+                    }
+                }
+                Err(_) => return Exit::Closed("read error"),
+            }
+        }
+
+        if std::mem::take(&mut first)
+            && let Some(request) = TunnelRequest::parse(&request_text)
+        {
+            return Exit::Tunnel(request);
+        }
+        if !state.handle_request(&request_text) {
+            return Exit::Closed("write error");
+        }
+    }
+}
+
+/// Resolve once `flag` turns `true` (or its sender is gone).
+///
+/// Unlike awaiting `wait_for` directly inside `select!`, this never holds
+/// the channel's read guard across an await, so the enclosing future stays
+/// `Send`.
+pub(crate) async fn raised(flag: &mut watch::Receiver<bool>) {
+    let _ = flag.wait_for(|raised| *raised).await;
+}
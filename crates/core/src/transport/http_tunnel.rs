@@ -0,0 +1,330 @@
+//! RTSP tunnelled over HTTP, as introduced by Apple QuickTime, for clients
+//! behind proxies that only let HTTP through.
+//!
+//! The client opens two HTTP connections to the RTSP port, tied together
+//! by the `x-sessioncookie` header they both carry:
+//!
+//! - a **GET** channel (`Accept: application/x-rtsp-tunnelled`), answered
+//!   with `200 OK` and then kept open: RTSP responses and interleaved
+//!   RTP/RTCP are written on it exactly as on an RTSP connection;
+//! - a **POST** channel (`Content-Type: application/x-rtsp-tunnelled`),
+//!   never answered, whose body is the client's RTSP requests (and
+//!   interleaved RTCP), base64-encoded. Clients may close it and open a
+//!   new one with the same cookie at any time.
+//!
+//! The GET connection owns the tunnel's connection state, so its
+//! sessions live until the GET channel closes; requests decoded from the
+//! POST channel are handled on that state, unchanged.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use parking_lot::Mutex;
+
+use crate::protocol::response::SERVER_AGENT;
+
+use super::tcp::ConnectionState;
+
+/// Content type of both tunnel channels.
+const TUNNELLED: &str = "application/x-rtsp-tunnelled";
+
+/// Longest request accepted on a POST channel, like the head limit of the
+/// HLS server.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// An HTTP request received where RTSP was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TunnelRequest {
+    /// Opens the tunnel's output channel.
+    Get { cookie: String },
+    /// Carries requests into the tunnel named by `cookie`.
+    Post { cookie: String },
+    /// HTTP, but not a tunnel request.
+    Invalid,
+}
+
+impl TunnelRequest {
+    /// Recognize an HTTP request head; `None` if it is not HTTP (RTSP
+    /// requests end their request line with `RTSP/1.0`).
+    pub(crate) fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.lines();
+        let mut request_line = lines.next()?.split_whitespace();
+        let (method, _target, version) = (
+            request_line.next()?,
+            request_line.next()?,
+            request_line.next()?,
+        );
+        if !version.starts_with("HTTP/") {
+            return None;
+        }
+
+        let mut cookie = None;
+        let mut tunnelled = false;
+        for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("x-sessioncookie") && !value.is_empty() {
+                cookie = Some(value.to_string());
+            } else if (method == "GET" && name.eq_ignore_ascii_case("accept")
+                || method == "POST" && name.eq_ignore_ascii_case("content-type"))
+                && value.eq_ignore_ascii_case(TUNNELLED)
+            {
+                tunnelled = true;
+            }
+        }
+        Some(match (method, cookie) {
+            ("GET", Some(cookie)) if tunnelled => Self::Get { cookie },
+            ("POST", Some(cookie)) if tunnelled => Self::Post { cookie },
+            _ => Self::Invalid,
+        })
+    }
+}
+
+/// The answer to a GET channel: the rest of the connection is RTSP.
+pub(crate) fn get_response() -> Vec<u8> {
+    format!(
+        "HTTP/1.0 200 OK\r\n\
+         Server: {SERVER_AGENT}\r\n\
+         Connection: close\r\n\
+         Cache-Control: no-store\r\n\
+         Pragma: no-cache\r\n\
+         Content-Type: {TUNNELLED}\r\n\r\n"
+    )
+    .into_bytes()
+}
+
+/// An HTTP error answer, after which the connection is closed.
+pub(crate) fn error_response(status: u16) -> Vec<u8> {
+    let reason = match status {
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Error",
+    };
+    format!(
+        "HTTP/1.0 {status} {reason}\r\n\
+         Server: {SERVER_AGENT}\r\n\
+         Connection: close\r\n\
+         Content-Length: 0\r\n\r\n"
+    )
+    .into_bytes()
+}
+
+/// Open tunnels of one listener, by session cookie.
+#[derive(Clone, Default)]
+pub(crate) struct Tunnels {
+    open: Arc<Mutex<HashMap<String, Arc<Mutex<ConnectionState>>>>>,
+}
+
+impl Tunnels {
+    /// Register the state of the GET channel of `cookie`, to be shared
+    /// with its POST channels. Hands it back if the cookie is in use.
+    pub(crate) fn open(
+        &self,
+        cookie: &str,
+        state: ConnectionState,
+    ) -> Result<Arc<Mutex<ConnectionState>>, Box<ConnectionState>> {
+        let mut open = self.open.lock();
+        if open.contains_key(cookie) {
+            return Err(Box::new(state));
+        }
+        let state = Arc::new(Mutex::new(state));
+        open.insert(cookie.to_string(), state.clone());
+        Ok(state)
+    }
+
+    pub(crate) fn get(&self, cookie: &str) -> Option<Arc<Mutex<ConnectionState>>> {
+        self.open.lock().get(cookie).cloned()
+    }
+
+    pub(crate) fn close(&self, cookie: &str) {
+        self.open.lock().remove(cookie);
+    }
+}
+
+/// A message decoded from a POST channel.
+#[derive(Debug, PartialEq, Eq)]
+enum Message {
+    Request(String),
+    Interleaved { channel: u8, packet: Vec<u8> },
+}
+
+/// Decoder of a POST channel's body.
+#[derive(Default)]
+pub(crate) struct PostDecoder {
+    /// Base64 not yet decoded: less than a quantum.
+    base64: Vec<u8>,
+    /// Decoded bytes not yet forming a whole message.
+    decoded: Vec<u8>,
+}
+
+impl PostDecoder {
+    /// Decode `data` from the POST channel and handle the requests it
+    /// completes on the tunnel's `state`. Returns the reason to close the
+    /// POST channel, if any.
+    pub(crate) fn deliver(
+        &mut self,
+        data: &[u8],
+        state: &Mutex<ConnectionState>,
+    ) -> Result<(), &'static str> {
+        self.push(data)
+            .map_err(|_| "invalid base64 on POST channel")?;
+        while let Some(message) = self.next_message() {
+            let mut state = state.lock();
+            match message {
+                Message::Request(text) => {
+                    if !state.handle_request(&text) {
+                        return Err("tunnel closed");
+                    }
+                }
+                Message::Interleaved { channel, packet } => {
+                    state.handle_interleaved(channel, &packet);
+                }
+            }
+        }
+        if self.request_too_long() {
+            return Err("request too long on POST channel");
+        }
+        Ok(())
+    }
+
+    /// Whether the decoded bytes waiting for the end of a request exceed
+    /// [`MAX_REQUEST_SIZE`]. Interleaved packets are bounded by their
+    /// 16-bit length instead.
+    fn request_too_long(&self) -> bool {
+        self.decoded.first() != Some(&b'$') && self.decoded.len() > MAX_REQUEST_SIZE
+    }
+
+    fn push(&mut self, data: &[u8]) -> Result<(), base64::DecodeError> {
+        self.base64
+            .extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+        let complete = self.base64.len() / 4 * 4;
+        let mut start = 0;
+        while start < complete {
+            // Clients encode each message on its own, so padding can
+            // appear mid-stream: decode up to each padded quantum.
+            let end = self.base64[start..complete]
+                .chunks_exact(4)
+                .position(|quantum| quantum[3] == b'=')
+                .map_or(complete, |i| start + (i + 1) * 4);
+            BASE64_STANDARD.decode_vec(&self.base64[start..end], &mut self.decoded)?;
+            start = end;
+        }
+        self.base64.drain(..complete);
+        Ok(())
+    }
+
+    fn next_message(&mut self) -> Option<Message> {
+        if self.decoded.first() == Some(&b'$') {
+            let header = self.decoded.get(..4)?;
+            let (channel, len) = (header[1], u16::from_be_bytes([header[2], header[3]]));
+            let end = 4 + len as usize;
+            if self.decoded.len() < end {
+                return None;
+            }
+            let packet = self.decoded[4..end].to_vec();
+            self.decoded.drain(..end);
+            return Some(Message::Interleaved { channel, packet });
+        }
+        let end = self
+            .decoded
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map(|i| i + 2)
+            .into_iter()
+            .chain(
+                self.decoded
+                    .windows(3)
+                    .position(|w| w == b"\n\r\n")
+                    .map(|i| i + 3),
+            )
+            .min()?;
+        let text = String::from_utf8_lossy(&self.decoded[..end]).into_owned();
+        self.decoded.drain(..end);
+        Some(Message::Request(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_tunnel_requests() {
+        let get = "GET /stream HTTP/1.0\r\nx-sessioncookie: 9a8b7c\r\n\
+                   Accept: application/x-rtsp-tunnelled\r\nPragma: no-cache\r\n\r\n";
+        assert_eq!(
+            TunnelRequest::parse(get),
+            Some(TunnelRequest::Get {
+                cookie: "9a8b7c".into()
+            })
+        );
+        let post = "POST /stream HTTP/1.0\r\nx-sessioncookie: 9a8b7c\r\n\
+                    Content-Type: application/x-rtsp-tunnelled\r\nContent-Length: 32767\r\n\r\n";
+        assert_eq!(
+            TunnelRequest::parse(post),
+            Some(TunnelRequest::Post {
+                cookie: "9a8b7c".into()
+            })
+        );
+        assert_eq!(
+            TunnelRequest::parse("GET /index.html HTTP/1.1\r\nHost: cam\r\n\r\n"),
+            Some(TunnelRequest::Invalid)
+        );
+        assert_eq!(
+            TunnelRequest::parse("GET_PARAMETER rtsp://cam/stream RTSP/1.0\r\nCSeq: 3\r\n\r\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn decodes_separately_encoded_messages_split_anywhere() {
+        let options = "OPTIONS rtsp://cam/stream RTSP/1.0\r\nCSeq: 1\r\n\r\n";
+        let describe = "DESCRIBE rtsp://cam/stream RTSP/1.0\r\nCSeq: 2\r\n\r\n";
+        let rtcp = [b'$', 1, 0, 3, 0x81, 0xc9, 0x00];
+        let mut body = BASE64_STANDARD.encode(options);
+        body.push_str(&BASE64_STANDARD.encode(rtcp));
+        body.push_str("\r\n");
+        body.push_str(&BASE64_STANDARD.encode(describe));
+        // Padding in the middle of the stream.
+        assert_ne!(options.len() % 3, 0);
+
+        let mut decoder = PostDecoder::default();
+        let mut messages = Vec::new();
+        for chunk in body.as_bytes().chunks(5) {
+            decoder.push(chunk).unwrap();
+            messages.extend(std::iter::from_fn(|| decoder.next_message()));
+        }
+        assert_eq!(
+            messages,
+            [
+                Message::Request(options.into()),
+                Message::Interleaved {
+                    channel: 1,
+                    packet: vec![0x81, 0xc9, 0x00]
+                },
+                Message::Request(describe.into()),
+            ]
+        );
+        assert!(decoder.push(b"!!!!").is_err());
+    }
+
+    #[test]
+    fn rejects_requests_that_never_end() {
+        let mut decoder = PostDecoder::default();
+        let line = BASE64_STANDARD.encode("x-padding: 0123456789abcdef\r\n");
+        while decoder.decoded.len() <= MAX_REQUEST_SIZE {
+            assert!(!decoder.request_too_long());
+            decoder.push(line.as_bytes()).unwrap();
+            assert_eq!(decoder.next_message(), None);
+        }
+        assert!(decoder.request_too_long());
+
+        // A large interleaved packet is not a request.
+        let mut decoder = PostDecoder::default();
+        let packet = [&[b'$', 1, 0xff, 0xff][..], &[0; 9000]].concat();
+        decoder
+            .push(BASE64_STANDARD.encode(packet).as_bytes())
+            .unwrap();
+        assert!(!decoder.request_too_long());
+    }
+}
@@ -0,0 +1,717 @@
+//! RTP interleaved on the RTSP TCP connection (RFC 2326 §10.12).
+//!
+//! Each RTP or RTCP packet is prefixed with `$`, a one-byte channel id and
+//! a 16-bit big-endian length:
+//!
+//! ```text
+//! +-----+---------+-----------------+------------------------+
+//! | '$' | channel | length (16 bit) | RTP/RTCP packet        |
+//! +-----+---------+-----------------+------------------------+
+//! ```
+//!
+//! A TCP viewer is only as fast as its socket. Every connection therefore
+//! gets an [`InterleavedSink`]: a bounded outbound buffer drained by its
+//! own writer thread (or task, with the `tokio` feature), so a stalled
+//! viewer fills its own buffer instead of blocking delivery to everyone
+//! else. When the buffer overflows, the [`OverflowPolicy`] decides whether
+//! the viewer skips ahead to the next keyframe or is disconnected.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::media::packet::RtpPacket;
+
+/// Default per-connection outbound buffer for TCP viewers.
+pub const DEFAULT_TCP_SEND_BUFFER: usize = 4 * 1024 * 1024;
+
+/// What happens when a TCP viewer's outbound buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop the viewer's frames on that channel until the next keyframe,
+    /// which replaces whatever of the channel is still buffered. The
+    /// picture freezes briefly, then resumes cleanly.
+    #[default]
+    SkipToKeyframe,
+    /// Close the connection; the client has to reconnect.
+    Disconnect,
+}
+
+/// Delivery counters of one TCP connection (see
+/// [`Viewer::tcp_stats`](crate::Viewer::tcp_stats)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TcpViewerStats {
+    /// Frames written to the socket.
+    pub frames_sent: u64,
+    /// Frames dropped while skipping to a keyframe.
+    pub frames_skipped: u64,
+    /// Times the buffer overflowed and the viewer was degraded.
+    pub overflows: u64,
+    /// Bytes waiting in the outbound buffer right now.
+    pub bytes_queued: usize,
+}
+
+/// One frame's packets on an interleaved channel.
+struct MediaFrame {
+    channel: u8,
+    packets: Vec<RtpPacket>,
+    /// Framed size on the wire.
+    bytes: usize,
+    /// Whether the viewer can resume decoding at this frame.
+    keyframe: bool,
+}
+
+/// Why a frame was not queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rejected {
+    /// Dropped while the channel skips to a keyframe.
+    Skipped,
+    /// The buffer is full and the policy is to disconnect.
+    Overflow,
+    Closed,
+}
+
+/// Outbound buffer state, separated from the socket so the overflow
+/// handling can be tested on its own.
+struct Outbound {
+    limit: usize,
+    policy: OverflowPolicy,
+    /// RTSP responses, written before any queued media.
+    control: VecDeque<Vec<u8>>,
+    control_bytes: usize,
+    media: VecDeque<MediaFrame>,
+    media_bytes: usize,
+    /// Channels dropping frames until their next keyframe.
+    skipping: HashSet<u8>,
+    /// Close once everything queued has been written.
+    finishing: bool,
+    closed: bool,
+    stats: TcpViewerStats,
+}
+
+impl Outbound {
+    fn new(limit: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            limit: limit.max(1),
+            policy,
+            control: VecDeque::new(),
+            control_bytes: 0,
+            media: VecDeque::new(),
+            media_bytes: 0,
+            skipping: HashSet::new(),
+            finishing: false,
+            closed: false,
+            stats: TcpViewerStats::default(),
+        }
+    }
+
+    /// Bytes buffered, responses and media alike.
+    fn queued_bytes(&self) -> usize {
+        self.control_bytes + self.media_bytes
+    }
+
+    /// Queue a response. Responses are never dropped: a client whose
+    /// responses alone overflow the buffer (pipelining requests without
+    /// reading) is rejected with [`Rejected::Overflow`], to be closed.
+    fn push_control(&mut self, response: Vec<u8>) -> Result<(), Rejected> {
+        if self.closed {
+            return Err(Rejected::Closed);
+        }
+        if self.control_bytes + response.len() > self.limit {
+            self.stats.overflows += 1;
+            return Err(Rejected::Overflow);
+        }
+        self.control_bytes += response.len();
+        self.control.push_back(response);
+        Ok(())
+    }
+
+    fn push(&mut self, frame: MediaFrame) -> Result<(), Rejected> {
+        if self.closed {
+            return Err(Rejected::Closed);
+        }
+        if self.skipping.contains(&frame.channel) {
+            if !frame.keyframe {
+                self.stats.frames_skipped += 1;
+                return Err(Rejected::Skipped);
+            }
+            self.skipping.remove(&frame.channel);
+        }
+
+        if self.queued_bytes() + frame.bytes > self.limit {
+            self.stats.overflows += 1;
+            match self.policy {
+                OverflowPolicy::Disconnect => return Err(Rejected::Overflow),
+                OverflowPolicy::SkipToKeyframe if !frame.keyframe => {
+                    self.skipping.insert(frame.channel);
+                    self.stats.frames_skipped += 1;
+                    return Err(Rejected::Skipped);
+                }
+                OverflowPolicy::SkipToKeyframe => {
+                    // The keyframe supersedes its channel's backlog; if that
+                    // is not enough, older frames of other channels go too
+                    // and those channels wait for their own keyframe.
+                    self.evict(|f| f.channel == frame.channel);
+                    while self.queued_bytes() + frame.bytes > self.limit {
+                        let Some(oldest) = self.media.pop_front() else {
+                            break;
+                        };
+                        self.media_bytes -= oldest.bytes;
+                        self.stats.frames_skipped += 1;
+                        self.skipping.insert(oldest.channel);
+                    }
+                }
+            }
+        }
+
+        self.media_bytes += frame.bytes;
+        self.media.push_back(frame);
+        Ok(())
+    }
+
+    /// Drop queued frames matching `pred`, counting them as skipped.
+    fn evict(&mut self, pred: impl Fn(&MediaFrame) -> bool) {
+        let mut evicted_bytes = 0;
+        let before = self.media.len();
+        self.media.retain(|f| {
+            let drop = pred(f);
+            if drop {
+                evicted_bytes += f.bytes;
+            }
+            !drop
+        });
+        self.media_bytes -= evicted_bytes;
+        self.stats.frames_skipped += (before - self.media.len()) as u64;
+    }
+
+    fn stats(&self) -> TcpViewerStats {
+        TcpViewerStats {
+            bytes_queued: self.queued_bytes(),
+            ..self.stats
+        }
+    }
+}
+
+enum Item {
+    Control(Vec<u8>),
+    Media(MediaFrame),
+}
+
+/// How the writer is woken and the connection torn down.
+enum Writer {
+    /// A dedicated thread writing a blocking socket.
+    Thread {
+        /// Handle for shutting the socket down from any thread.
+        stream: TcpStream,
+        /// Signalled when something is queued or the sink closes.
+        changed: Condvar,
+    },
+    /// A tokio task writing the connection's write half.
+    #[cfg(feature = "tokio")]
+    Task {
+        changed: tokio::sync::Notify,
+        /// Tells the connection's read task to stop.
+        closed: tokio::sync::watch::Sender<bool>,
+    },
+}
+
+struct Shared {
+    peer_addr: SocketAddr,
+    state: Mutex<Outbound>,
+    writer: Writer,
+}
+
+/// Outbound side of an RTSP connection: RTSP responses and interleaved
+/// RTP share one socket, written by a dedicated thread or tokio task from
+/// a bounded buffer.
+///
+/// Responses are never dropped and jump ahead of queued media, but count
+/// against the same limit: a client that lets them pile up is
+/// disconnected. Media frames are subject to the [`OverflowPolicy`].
+pub(crate) struct InterleavedSink {
+    shared: Arc<Shared>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl InterleavedSink {
+    pub(crate) fn spawn(
+        stream: TcpStream,
+        limit: usize,
+        policy: OverflowPolicy,
+    ) -> io::Result<Arc<Self>> {
+        let peer_addr = stream.peer_addr()?;
+        let shared = Arc::new(Shared {
+            peer_addr,
+            state: Mutex::new(Outbound::new(limit, policy)),
+            writer: Writer::Thread {
+                stream: stream.try_clone()?,
+                changed: Condvar::new(),
+            },
+        });
+        let writer_shared = shared.clone();
+        let writer = thread::Builder::new()
+            .name(format!("rtsp-writer {peer_addr}"))
+            .spawn(move || write_loop(writer_shared, stream))?;
+        Ok(Arc::new(Self {
+            shared,
+            writer: Mutex::new(Some(writer)),
+        }))
+    }
+
+    /// Like [`spawn`](Self::spawn), writing from a tokio task. The returned
+    /// receiver turns `true` when the sink closes, so the connection's read
+    /// task can stop.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn spawn_async(
+        write: tokio::net::tcp::OwnedWriteHalf,
+        peer_addr: SocketAddr,
+        limit: usize,
+        policy: OverflowPolicy,
+    ) -> (Arc<Self>, tokio::sync::watch::Receiver<bool>) {
+        let (closed, closed_rx) = tokio::sync::watch::channel(false);
+        let shared = Arc::new(Shared {
+            peer_addr,
+            state: Mutex::new(Outbound::new(limit, policy)),
+            writer: Writer::Task {
+                changed: tokio::sync::Notify::new(),
+                closed,
+            },
+        });
+        tokio::spawn(write_task(shared.clone(), write));
+        let sink = Arc::new(Self {
+            shared,
+            writer: Mutex::new(None),
+        });
+        (sink, closed_rx)
+    }
+
+    /// Queue an RTSP response ahead of any buffered media. Returns `false`
+    /// if the sink is closed, or closes it if the client has left more
+    /// than the buffer limit of responses unread.
+    pub(crate) fn send_response(&self, response: Vec<u8>) -> bool {
+        let result = self.shared.state.lock().push_control(response);
+        match result {
+            Ok(()) => {
+                self.shared.wake();
+                true
+            }
+            Err(Rejected::Overflow) => {
+                tracing::warn!(
+                    peer = %self.shared.peer_addr,
+                    "client not reading responses, disconnecting"
+                );
+                self.close();
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Queue a frame's packets on `channel`. Returns `false` if the frame
+    /// was dropped; on overflow with [`OverflowPolicy::Disconnect`] the
+    /// connection is closed.
+    pub(crate) fn send_frame(&self, channel: u8, packets: &[RtpPacket], keyframe: bool) -> bool {
+        let bytes = packets.iter().map(|p| 4 + p.len()).sum();
+        let frame = MediaFrame {
+            channel,
+            packets: packets.to_vec(),
+            bytes,
+            keyframe,
+        };
+        let result = self.shared.state.lock().push(frame);
+        match result {
+            Ok(()) => {
+                self.shared.wake();
+                true
+            }
+            Err(Rejected::Overflow) => {
+                tracing::warn!(
+                    peer = %self.shared.peer_addr,
+                    "TCP viewer too slow, disconnecting"
+                );
+                self.close();
+                false
+            }
+            Err(Rejected::Skipped) => {
+                tracing::trace!(
+                    peer = %self.shared.peer_addr,
+                    channel,
+                    "TCP viewer skipping to next keyframe"
+                );
+                false
+            }
+            Err(Rejected::Closed) => false,
+        }
+    }
+
+    /// Queue a single pre-packetized packet on `channel`. Dropped if it
+    /// does not fit; never changes the channel's keyframe skipping.
+    pub(crate) fn send_packet(&self, channel: u8, packet: RtpPacket) -> bool {
+        let mut state = self.shared.state.lock();
+        let bytes = 4 + packet.len();
+        if state.closed || state.queued_bytes() + bytes > state.limit {
+            state.stats.frames_skipped += 1;
+            return false;
+        }
+        state.media_bytes += bytes;
+        state.media.push_back(MediaFrame {
+            channel,
+            packets: vec![packet],
+            bytes,
+            keyframe: false,
+        });
+        self.shared.wake();
+        true
+    }
+
+    pub(crate) fn stats(&self) -> TcpViewerStats {
+        self.shared.state.lock().stats()
+    }
+
+    /// Write what is already queued, then [`close`](Self::close). Does not
+    /// wait; a viewer that stops reading keeps the sink open until it is
+    /// closed outright.
+    pub(crate) fn finish(&self) {
+        self.shared.state.lock().finishing = true;
+        self.shared.wake();
+    }
+
+    /// Whether the sink is closed: no more output will be written.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.shared.state.lock().closed
+    }
+
+    /// Stop writing and shut the socket down, which also ends the
+    /// connection's request loop. Waits for the writer thread unless
+    /// called from it.
+    pub(crate) fn close(&self) {
+        self.shared.close();
+        let writer = self.writer.lock().take();
+        if let Some(writer) = writer
+            && writer.thread().id() != thread::current().id()
+        {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Shared {
+    /// Wake the writer after queueing or closing.
+    fn wake(&self) {
+        match &self.writer {
+            Writer::Thread { changed, .. } => {
+                changed.notify_all();
+            }
+            #[cfg(feature = "tokio")]
+            Writer::Task { changed, .. } => changed.notify_one(),
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock();
+        if !state.closed {
+            state.closed = true;
+            match &self.writer {
+                Writer::Thread { stream, .. } => {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+                #[cfg(feature = "tokio")]
+                Writer::Task { closed, .. } => {
+                    closed.send_replace(true);
+                }
+            }
+        }
+        drop(state);
+        self.wake();
+    }
+
+    /// Take the next item to write: responses first, then media. `None`
+    /// once the sink is closed, or finishing with nothing left to write.
+    fn next_item(state: &mut Outbound) -> Option<Option<Item>> {
+        if state.closed {
+            return None;
+        }
+        if let Some(response) = state.control.pop_front() {
+            state.control_bytes -= response.len();
+            return Some(Some(Item::Control(response)));
+        }
+        if let Some(frame) = state.media.pop_front() {
+            state.media_bytes -= frame.bytes;
+            return Some(Some(Item::Media(frame)));
+        }
+        if state.finishing {
+            return None;
+        }
+        Some(None)
+    }
+}
+
+impl Item {
+    /// The bytes to write, framing media packets into `buf`.
+    fn bytes<'a>(&'a self, buf: &'a mut Vec<u8>) -> &'a [u8] {
+        match self {
+            Item::Control(response) => response,
+            Item::Media(frame) => {
+                buf.clear();
+                for packet in &frame.packets {
+                    frame_packet(buf, frame.channel, packet);
+                }
+                buf
+            }
+        }
+    }
+
+    fn is_media(&self) -> bool {
+        matches!(self, Item::Media(_))
+    }
+}
+
+impl Drop for InterleavedSink {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for InterleavedSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterleavedSink")
+            .field("peer_addr", &self.shared.peer_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Append `packet` to `buf` with its `$`-channel-length prefix.
+fn frame_packet(buf: &mut Vec<u8>, channel: u8, packet: &RtpPacket) {
+    let len = u16::try_from(packet.len()).unwrap_or(u16::MAX);
+    buf.extend_from_slice(&[b'$', channel]);
+    buf.extend_from_slice(&len.to_be_bytes());
+    packet.write_to(buf);
+}
+
+fn write_loop(shared: Arc<Shared>, mut stream: TcpStream) {
+    let changed = match &shared.writer {
+        Writer::Thread { changed, .. } => changed,
+        #[cfg(feature = "tokio")]
+        Writer::Task { .. } => unreachable!("thread writer for a task sink"),
+    };
+    let mut buf = Vec::new();
+    loop {
+        let item = {
+            let mut state = shared.state.lock();
+            loop {
+                match Shared::next_item(&mut state) {
+                    None => break None,
+                    Some(Some(item)) => break Some(item),
+                    Some(None) => changed.wait(&mut state),
+                }
+            }
+        };
+        let Some(item) = item else {
+            break;
+        };
+
+        if let Err(e) = stream.write_all(item.bytes(&mut buf)) {
+            tracing::debug!(peer = %shared.peer_addr, error = %e, "TCP write failed");
+            shared.close();
+            break;
+        }
+        if item.is_media() {
+            shared.state.lock().stats.frames_sent += 1;
+        }
+    }
+    shared.close();
+    tracing::debug!(peer = %shared.peer_addr, "writer thread exited");
+}
+
+#[cfg(feature = "tokio")]
+async fn write_task(shared: Arc<Shared>, mut write: tokio::net::tcp::OwnedWriteHalf) {
+    use tokio::io::AsyncWriteExt;
+
+    use super::async_tcp::raised;
+
+    let (changed, mut closed) = match &shared.writer {
+        Writer::Task { changed, closed } => (changed, closed.subscribe()),
+        Writer::Thread { .. } => unreachable!("task writer for a thread sink"),
+    };
+    let mut buf = Vec::new();
+    loop {
+        // Bind before matching so the state guard is released before the
+        // await below.
+        let next = Shared::next_item(&mut shared.state.lock());
+        let item = match next {
+            None => break,
+            Some(Some(item)) => item,
+            Some(None) => {
+                changed.notified().await;
+                continue;
+            }
+        };
+
+        // A viewer that stops reading leaves the write pending; closing the
+        // sink (overflow, shutdown) must still end the task.
+        let written = tokio::select! {
+            written = write.write_all(item.bytes(&mut buf)) => written,
+            _ = raised(&mut closed) => break,
+        };
+        if let Err(e) = written {
+            tracing::debug!(peer = %shared.peer_addr, error = %e, "TCP write failed");
+            shared.close();
+            break;
+        }
+        if item.is_media() {
+            shared.state.lock().stats.frames_sent += 1;
+        }
+    }
+    let _ = write.shutdown().await;
+    shared.close();
+    tracing::debug!(peer = %shared.peer_addr, "writer task exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn frame(channel: u8, bytes: usize, keyframe: bool) -> MediaFrame {
+        MediaFrame {
+            channel,
+            packets: Vec::new(),
+            bytes,
+            keyframe,
+        }
+    }
+
+    #[test]
+    fn skip_to_keyframe_drops_until_next_keyframe() {
+        let mut out = Outbound::new(1000, OverflowPolicy::SkipToKeyframe);
+        assert_eq!(out.push(frame(0, 600, true)), Ok(()));
+        assert_eq!(out.push(frame(0, 300, false)), Ok(()));
+        assert_eq!(out.push(frame(0, 300, false)), Err(Rejected::Skipped));
+        // Room again, but the channel waits for a keyframe.
+        out.media.clear();
+        out.media_bytes = 0;
+        assert_eq!(out.push(frame(0, 100, false)), Err(Rejected::Skipped));
+        assert_eq!(out.push(frame(0, 100, true)), Ok(()));
+        assert_eq!(out.push(frame(0, 100, false)), Ok(()));
+
+        let stats = out.stats();
+        assert_eq!(stats.overflows, 1);
+        assert_eq!(stats.frames_skipped, 2);
+        assert_eq!(stats.bytes_queued, 200);
+    }
+
+    #[test]
+    fn keyframe_replaces_backlog_and_stalls_other_channels() {
+        let mut out = Outbound::new(1000, OverflowPolicy::SkipToKeyframe);
+        out.push(frame(2, 400, true)).unwrap();
+        out.push(frame(0, 400, true)).unwrap();
+        // Evicting channel 0's backlog is not enough; channel 2 goes too.
+        assert_eq!(out.push(frame(0, 900, true)), Ok(()));
+        assert_eq!(out.media.len(), 1);
+        assert_eq!(out.stats().frames_skipped, 2);
+        assert_eq!(out.push(frame(2, 10, false)), Err(Rejected::Skipped));
+    }
+
+    #[test]
+    fn disconnect_policy_rejects_overflow() {
+        let mut out = Outbound::new(1000, OverflowPolicy::Disconnect);
+        out.push(frame(0, 800, true)).unwrap();
+        assert_eq!(out.push(frame(0, 800, true)), Err(Rejected::Overflow));
+        assert_eq!(out.stats().overflows, 1);
+    }
+
+    #[test]
+    fn responses_count_against_the_limit() {
+        let mut out = Outbound::new(1000, OverflowPolicy::SkipToKeyframe);
+        out.push_control(vec![0; 600]).unwrap();
+        // Media only gets what responses leave.
+        assert_eq!(out.push(frame(0, 500, false)), Err(Rejected::Skipped));
+        assert_eq!(out.stats().bytes_queued, 600);
+        // Responses piling up past the limit close the connection.
+        assert_eq!(out.push_control(vec![0; 600]), Err(Rejected::Overflow));
+        let Some(Some(Item::Control(_))) = Shared::next_item(&mut out) else {
+            panic!("response queued");
+        };
+        assert_eq!(out.push_control(vec![0; 600]), Ok(()));
+    }
+
+    #[test]
+    fn sink_frames_packets_after_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let sink = InterleavedSink::spawn(server, 1 << 16, OverflowPolicy::default()).unwrap();
+
+        let packet = RtpPacket::from(vec![0x80, 96, 0, 1]);
+        assert!(sink.send_response(b"RTSP/1.0 200 OK\r\n\r\n".to_vec()));
+        assert!(sink.send_frame(0, &[packet.clone(), packet], true));
+
+        let mut buf = [0u8; 19 + 16];
+        client.read_exact(&mut buf).unwrap();
+        assert!(buf.starts_with(b"RTSP/1.0 200 OK\r\n\r\n"));
+        assert_eq!(&buf[19..27], &[b'$', 0, 0, 4, 0x80, 96, 0, 1]);
+        assert_eq!(&buf[27..31], &[b'$', 0, 0, 4]);
+
+        sink.close();
+        assert!(!sink.send_frame(0, &[], true));
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+        assert_eq!(sink.stats().frames_sent, 1);
+    }
+
+    #[test]
+    fn finish_flushes_queue_then_closes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let sink = InterleavedSink::spawn(server, 1 << 16, OverflowPolicy::default()).unwrap();
+
+        assert!(sink.send_packet(1, RtpPacket::from(vec![0x81, 203, 0, 1])));
+        sink.finish();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).unwrap();
+        assert_eq!(received, [b'$', 1, 0, 4, 0x81, 203, 0, 1]);
+        assert!(sink.is_closed());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_writer_exits_when_a_stalled_viewer_overflows() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // A viewer that never reads.
+        let _client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, peer_addr) = listener.accept().await.unwrap();
+        let (_read, write) = server.into_split();
+        let (sink, closed) =
+            InterleavedSink::spawn_async(write, peer_addr, 1 << 18, OverflowPolicy::Disconnect);
+
+        let packet = RtpPacket::from(vec![0u8; 60_000]);
+        for _ in 0..10_000 {
+            if !sink.send_frame(0, std::slice::from_ref(&packet), true) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        assert!(sink.is_closed(), "viewer never overflowed");
+        assert!(*closed.borrow());
+
+        // The writer task, stuck in a write, lets go of the sink.
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
+        while Arc::strong_count(&sink.shared) > 1 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            Arc::strong_count(&sink.shared),
+            1,
+            "writer task still running"
+        );
+    }
+}
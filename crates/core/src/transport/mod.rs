@@ -3,14 +3,28 @@
 //! RTSP uses a split transport model:
 //!
 //! - **TCP** ([`tcp`]): carries RTSP request/response signaling. One TCP
-//!   connection per client, with a thread per connection.
+//!   connection per client, with a thread per connection — or, with the
+//!   `tokio` feature, a task per connection (`async_tcp`).
 //!
-//! - **UDP** ([`udp`]): carries RTP media packets. A single ephemeral
-//!   socket is shared for all outbound RTP delivery.
+//! - **Interleaved** ([`interleaved`]): RTP/RTCP multiplexed onto the RTSP
+//!   connection with `$` framing (RFC 2326 §10.12), through a bounded
+//!   per-connection buffer so slow TCP viewers cannot stall the others.
 //!
-//! Future: interleaved TCP transport (RFC 2326 §10.12) will multiplex
-//! RTP data onto the RTSP TCP connection using `$` framing.
+//! - **HTTP tunneling** ([`http_tunnel`]): RTSP and interleaved RTP carried
+//!   over a pair of HTTP connections (QuickTime style), detected on the
+//!   same TCP listener, for clients behind HTTP-only proxies.
+//!
+//! - **UDP** ([`udp`]): carries RTP media packets and RTCP feedback. A
+//!   single RTP/RTCP socket pair is shared by all sessions.
+//!
+//! - **Pacing** ([`pacer`]): optional per-mount sender thread that spreads
+//!   a frame's packets over time through a token bucket.
 
+#[cfg(feature = "tokio")]
+pub mod async_tcp;
+pub mod http_tunnel;
+pub mod interleaved;
+pub mod pacer;
 pub mod tcp;
 pub mod udp;
 
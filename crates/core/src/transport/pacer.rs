@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::media::packet::RtpPacket;
+use crate::server::SHUTDOWN_TIMEOUT;
+
+use super::{UdpTransport, tcp};
+
+/// Frames a pacer holds before producers wait for it.
+const PACER_QUEUE_FRAMES: usize = 16;
+
+/// Frame interval assumed until two frames of a track have been seen.
+const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Longest gap between frames still treated as a frame interval; longer
+/// gaps (stream pauses, timestamp jumps) fall back to the last interval.
+const MAX_FRAME_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest the pacer thread sleeps between packets before checking
+/// whether it is being stopped.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a mount's packets are spread out on the wire.
+///
+/// Without pacing, every packet of a frame leaves in one burst — for a
+/// large IDR that is hundreds of back-to-back datagrams, which overflows
+/// switch buffers and Wi-Fi queues. A pacer sends them from a dedicated
+/// thread through a token bucket instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Spread each frame's packets evenly over this fraction (0–1] of the
+    /// frame interval, derived from the RTP timestamps of consecutive
+    /// frames. `0.5` finishes every frame in half its display time.
+    FrameInterval(f64),
+    /// Send at a constant rate, allowing bursts of up to `burst_bytes`.
+    /// Frames queue behind each other if the stream exceeds the rate, and
+    /// producers wait once the pacer's queue is full.
+    Bitrate {
+        bits_per_second: u64,
+        burst_bytes: usize,
+    },
+}
+
+/// Byte-granular token bucket (RFC 2697-style single rate).
+///
+/// Tokens accrue at `rate` bytes per second up to `capacity`. A packet may
+/// be sent once the bucket holds `min(len, capacity)` tokens, so packets
+/// larger than the bucket still pass (driving it negative) instead of
+/// stalling forever.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, capacity: usize, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            last: now,
+        }
+    }
+
+    /// Change rate and size, keeping the tokens earned at the old rate.
+    pub(crate) fn set_rate(&mut self, rate: f64, capacity: usize, now: Instant) {
+        self.refill(now);
+        self.rate = rate;
+        self.capacity = capacity as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+
+    /// How long to wait before `len` bytes may be sent.
+    pub(crate) fn delay(&mut self, len: usize, now: Instant) -> Duration {
+        self.refill(now);
+        let need = (len as f64).min(self.capacity);
+        if self.tokens >= need || self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((need - self.tokens) / self.rate)
+    }
+
+    /// Take `len` bytes worth of tokens.
+    pub(crate) fn consume(&mut self, len: usize) {
+        self.tokens -= len as f64;
+    }
+}
+
+/// One frame's packets and the viewers they go to.
+pub(crate) struct PacedFrame {
+    pub track: usize,
+    pub clock_rate: u32,
+    pub pacing: Pacing,
+    pub packets: Vec<RtpPacket>,
+    pub destinations: Vec<SocketAddr>,
+}
+
+/// A mount's pacing sender: a bounded frame queue drained by a dedicated
+/// thread that releases each track's packets through its own
+/// [`TokenBucket`].
+///
+/// Dropping the pacer stops the thread: frames still queued are
+/// discarded, and the thread is given [`SHUTDOWN_TIMEOUT`] to exit.
+pub(crate) struct Pacer {
+    queue: Option<SyncSender<PacedFrame>>,
+    thread: Option<JoinHandle<()>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Pacer {
+    pub(crate) fn spawn(udp: UdpTransport, name: &str) -> Self {
+        let (queue, frames) = mpsc::sync_channel(PACER_QUEUE_FRAMES);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name(format!("rtp-pacer {name}"))
+            .spawn({
+                let stopped = stopped.clone();
+                move || pace_loop(udp, frames, &stopped)
+            })
+            .expect("failed to spawn pacer thread");
+        Self {
+            queue: Some(queue),
+            thread: Some(thread),
+            stopped,
+        }
+    }
+
+    /// Queue a frame for paced delivery, waiting while the queue is full:
+    /// frames never overtake each other, so viewers see RTP sequence
+    /// numbers and timestamps in order.
+    pub(crate) fn submit(&self, frame: PacedFrame) {
+        let queue = self.queue.as_ref().expect("pacer queue open until drop");
+        if queue.send(frame).is_err() && !self.stopped.load(Ordering::Relaxed) {
+            tracing::warn!("pacer thread gone, frame dropped");
+        }
+    }
+
+    /// Make the thread discard the frames queued and exit, releasing
+    /// producers waiting in [`submit`](Self::submit).
+    pub(crate) fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Pacer {
+    fn drop(&mut self) {
+        self.stop();
+        self.queue.take();
+        if let Some(thread) = self.thread.take()
+            && !tcp::join_timeout(thread, SHUTDOWN_TIMEOUT)
+        {
+            tracing::warn!("pacer thread did not exit in time, detaching it");
+        }
+    }
+}
+
+/// Sending rate (bytes/s) and bucket size for one frame.
+fn frame_rate(pacing: Pacing, frame_bytes: usize, interval: Duration, mtu: usize) -> (f64, usize) {
+    match pacing {
+        Pacing::FrameInterval(fraction) => {
+            let window = interval.as_secs_f64() * fraction.clamp(f64::EPSILON, 1.0);
+            (frame_bytes as f64 / window, mtu)
+        }
+        Pacing::Bitrate {
+            bits_per_second,
+            burst_bytes,
+        } => (bits_per_second as f64 / 8.0, burst_bytes.max(1)),
+    }
+}
+
+/// Pacing state of one track: its last RTP timestamp, the frame interval
+/// derived from it, and its own bucket, so that one track's rate (audio)
+/// never replaces another's (video).
+struct TrackPacing {
+    last_timestamp: u32,
+    interval: Duration,
+    bucket: TokenBucket,
+}
+
+fn pace_loop(udp: UdpTransport, frames: Receiver<PacedFrame>, stopped: &AtomicBool) {
+    let mut tracks: HashMap<usize, TrackPacing> = HashMap::new();
+
+    while let Ok(frame) = frames.recv() {
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        let Some(timestamp) = frame.packets.first().and_then(RtpPacket::timestamp) else {
+            continue;
+        };
+        let frame_bytes: usize = frame.packets.iter().map(RtpPacket::len).sum();
+        let largest = frame.packets.iter().map(RtpPacket::len).max().unwrap_or(0);
+        let now = Instant::now();
+
+        let track = match tracks.entry(frame.track) {
+            Entry::Occupied(entry) => {
+                let track = entry.into_mut();
+                let ticks = timestamp.wrapping_sub(track.last_timestamp);
+                let gap = Duration::from_secs_f64(ticks as f64 / frame.clock_rate.max(1) as f64);
+                if !gap.is_zero() && gap <= MAX_FRAME_INTERVAL {
+                    track.interval = gap;
+                }
+                track.last_timestamp = timestamp;
+                let (rate, capacity) =
+                    frame_rate(frame.pacing, frame_bytes, track.interval, largest);
+                track.bucket.set_rate(rate, capacity, now);
+                track
+            }
+            Entry::Vacant(entry) => {
+                let interval = DEFAULT_FRAME_INTERVAL;
+                let (rate, capacity) = frame_rate(frame.pacing, frame_bytes, interval, largest);
+                entry.insert(TrackPacing {
+                    last_timestamp: timestamp,
+                    interval,
+                    bucket: TokenBucket::new(rate, capacity, now),
+                })
+            }
+        };
+        let interval = track.interval;
+        let bucket = &mut track.bucket;
+
+        for packet in &frame.packets {
+            let due = Instant::now() + bucket.delay(packet.len(), Instant::now());
+            while let Some(wait) = due.checked_duration_since(Instant::now())
+                && !wait.is_zero()
+            {
+                if stopped.load(Ordering::Relaxed) {
+                    tracing::debug!("pacer stopped, queued frames discarded");
+                    return;
+                }
+                thread::sleep(wait.min(STOP_POLL_INTERVAL));
+            }
+            bucket.consume(packet.len());
+            for &addr in &frame.destinations {
+                if let Err(e) = udp.send_packet(packet, addr) {
+                    tracing::warn!(%addr, error = %e, "failed to send paced RTP packet");
+                }
+            }
+        }
+
+        tracing::trace!(
+            track = frame.track,
+            packets = frame.packets.len(),
+            frame_bytes,
+            interval_us = interval.as_micros() as u64,
+            "frame paced"
+        );
+    }
+    tracing::debug!("pacer exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn bucket_spaces_packets_at_rate() {
+        let start = Instant::now();
+        // 10 kB/s with room for one 1000-byte packet.
+        let mut bucket = TokenBucket::new(10_000.0, 1000, start);
+        assert_eq!(bucket.delay(1000, start), Duration::ZERO);
+        bucket.consume(1000);
+        assert_eq!(bucket.delay(1000, start), Duration::from_millis(100));
+        let later = start + Duration::from_millis(100);
+        assert_eq!(bucket.delay(1000, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn bucket_lets_oversized_packets_through() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000.0, 500, start);
+        assert_eq!(bucket.delay(1500, start), Duration::ZERO);
+        bucket.consume(1500);
+        // 1000 bytes of debt plus 500 to refill the bucket.
+        assert_eq!(bucket.delay(1500, start), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn frame_interval_rate_finishes_within_fraction() {
+        let (rate, capacity) = frame_rate(
+            Pacing::FrameInterval(0.5),
+            100_000,
+            Duration::from_millis(40),
+            1400,
+        );
+        assert_eq!(capacity, 1400);
+        // 100 kB over 20 ms.
+        assert!((rate - 5_000_000.0).abs() < 1.0);
+
+        let (rate, capacity) = frame_rate(
+            Pacing::Bitrate {
+                bits_per_second: 8_000_000,
+                burst_bytes: 10_000,
+            },
+            100_000,
+            Duration::from_millis(40),
+            1400,
+        );
+        assert_eq!((rate, capacity), (1_000_000.0, 10_000));
+    }
+
+    #[test]
+    fn pacer_spreads_frame_over_interval() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let pacer = Pacer::spawn(UdpTransport::bind().unwrap(), "test");
+
+        let packets: Vec<RtpPacket> = (0..10u8)
+            .map(|i| {
+                RtpPacket::from(
+                    [vec![0x80, 96, 0, i, 0, 0, 0, 0, 0, 0, 0, 1], vec![0; 988]].concat(),
+                )
+            })
+            .collect();
+        let start = Instant::now();
+        // First frame: default interval (33 ms), spread over all of it.
+        pacer.submit(PacedFrame {
+            track: 0,
+            clock_rate: 90000,
+            pacing: Pacing::FrameInterval(1.0),
+            packets,
+            destinations: vec![receiver.local_addr().unwrap()],
+        });
+
+        let mut buf = [0u8; 1500];
+        for i in 0..10u8 {
+            let len = receiver.recv(&mut buf).unwrap();
+            assert_eq!(len, 1000);
+            assert_eq!(buf[3], i);
+        }
+        // Nine gaps of a tenth of the frame each.
+        assert!(
+            start.elapsed() >= Duration::from_millis(25),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn drop_discards_queued_frames() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let pacer = Pacer::spawn(UdpTransport::bind().unwrap(), "test");
+        // 1 kB/s with 1000-byte packets: draining the queue would take
+        // over ten seconds.
+        for seq in 0..PACER_QUEUE_FRAMES as u8 {
+            let packet = RtpPacket::from(
+                [vec![0x80, 96, 0, seq, 0, 0, 0, 0, 0, 0, 0, 1], vec![0; 988]].concat(),
+            );
+            pacer.submit(PacedFrame {
+                track: 0,
+                clock_rate: 90000,
+                pacing: Pacing::Bitrate {
+                    bits_per_second: 8000,
+                    burst_bytes: 1000,
+                },
+                packets: vec![packet],
+                destinations: vec![receiver.local_addr().unwrap()],
+            });
+        }
+        let start = Instant::now();
+        drop(pacer);
+        assert!(
+            start.elapsed() < Duration::from_millis(500),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn full_queue_makes_producer_wait_and_keeps_order() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let pacer = Pacer::spawn(UdpTransport::bind().unwrap(), "test");
+
+        // 100 kB/s with 1000-byte frames: 10 ms each, far slower than
+        // they are submitted, so the queue fills up.
+        let frames = PACER_QUEUE_FRAMES as u16 * 2;
+        for seq in 0..frames {
+            let [hi, lo] = seq.to_be_bytes();
+            let packet = RtpPacket::from(
+                [vec![0x80, 96, hi, lo, 0, 0, 0, 0, 0, 0, 0, 1], vec![0; 988]].concat(),
+            );
+            pacer.submit(PacedFrame {
+                track: 0,
+                clock_rate: 90000,
+                pacing: Pacing::Bitrate {
+                    bits_per_second: 800_000,
+                    burst_bytes: 1000,
+                },
+                packets: vec![packet],
+                destinations: vec![receiver.local_addr().unwrap()],
+            });
+        }
+
+        let mut buf = [0u8; 1500];
+        for seq in 0..frames {
+            receiver.recv(&mut buf).unwrap();
+            assert_eq!(u16::from_be_bytes([buf[2], buf[3]]), seq);
+        }
+    }
+}
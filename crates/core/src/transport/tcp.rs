@@ -1,9 +1,11 @@
-use std::io::{BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Read};
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
 
 use crate::mount::MountRegistry;
 use crate::protocol::MethodHandler;
@@ -11,30 +13,60 @@ use crate::protocol::RtspRequest;
 use crate::server::ServerConfig;
 use crate::session::SessionManager;
 
+use super::http_tunnel::{self, PostDecoder, TunnelRequest, Tunnels};
+use super::interleaved::InterleavedSink;
+
+/// How often [`join_timeout`] and [`Connections::close_all`] check
+/// whether threads have exited.
+const JOIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Non-blocking TCP accept loop for RTSP connections.
 ///
 /// Checks the `running` flag between accepts with a 50ms poll interval
 /// so that [`crate::server::Server::stop`] can terminate it promptly.
+/// Accepted connections are registered in `connections` so the server
+/// can close them on shutdown. Connections that open with an HTTP tunnel
+/// request are paired up by [`http_tunnel`].
 pub fn accept_loop(
     listener: TcpListener,
     session_manager: SessionManager,
     mounts: MountRegistry,
     config: Arc<ServerConfig>,
     running: Arc<AtomicBool>,
+    connections: Arc<Connections>,
 ) {
+    let tunnels = Tunnels::default();
     while running.load(Ordering::SeqCst) {
         match listener.accept() {
-            Ok((stream, _)) => {
+            Ok((stream, peer_addr)) => {
                 if stream.set_nonblocking(false).is_err() {
                     continue;
                 }
+                let reader = match stream.try_clone() {
+                    Ok(reader) => reader,
+                    Err(_) => continue,
+                };
+                let sink = match InterleavedSink::spawn(
+                    stream,
+                    config.tcp_send_buffer,
+                    config.tcp_overflow_policy,
+                ) {
+                    Ok(sink) => sink,
+                    Err(e) => {
+                        tracing::warn!(%peer_addr, error = %e, "failed to start connection writer");
+                        continue;
+                    }
+                };
+
+                let connection_sink = sink.clone();
                 let sm = session_manager.clone();
-                let r = running.clone();
                 let m = mounts.clone();
                 let c = config.clone();
-                thread::spawn(move || {
-                    Connection::handle(stream, sm, m, c, r);
+                let t = tunnels.clone();
+                let thread = thread::spawn(move || {
+                    Connection::handle(reader, peer_addr, connection_sink, sm, m, c, t);
                 });
+                connections.insert(sink, thread);
             }
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 thread::sleep(Duration::from_millis(50));
@@ -49,117 +81,381 @@ pub fn accept_loop(
     tracing::debug!("accept loop exited");
 }
 
+/// RTSP connections accepted by [`accept_loop`], so that
+/// [`Server::stop`](crate::Server::stop) can close them and wait for
+/// their threads.
+#[derive(Default)]
+pub struct Connections {
+    open: Mutex<Vec<OpenConnection>>,
+}
+
+struct OpenConnection {
+    sink: Arc<InterleavedSink>,
+    thread: JoinHandle<()>,
+}
+
+impl Connections {
+    fn insert(&self, sink: Arc<InterleavedSink>, thread: JoinHandle<()>) {
+        let mut open = self.open.lock();
+        open.retain(|c| !c.thread.is_finished());
+        open.push(OpenConnection { sink, thread });
+    }
+
+    /// Close every connection and join its thread.
+    ///
+    /// Output already queued (responses, interleaved RTP, the RTCP BYE
+    /// sent on shutdown) is written first; connections still writing
+    /// after `timeout` are closed without it. Threads that have not exited
+    /// by then are left detached. Returns the number of connections closed.
+    pub(crate) fn close_all(&self, timeout: Duration) -> usize {
+        let open = std::mem::take(&mut *self.open.lock());
+        for connection in &open {
+            connection.sink.finish();
+        }
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && open.iter().any(|c| !c.sink.is_closed()) {
+            thread::sleep(JOIN_POLL_INTERVAL);
+        }
+
+        let closed = open.len();
+        for connection in open {
+            connection.sink.close();
+            if !join_timeout(
+                connection.thread,
+                deadline.saturating_duration_since(Instant::now()),
+            ) {
+                tracing::warn!("connection thread did not exit in time, detaching it");
+            }
+        }
+        closed
+    }
+}
+
+/// Join `thread`, giving up after `timeout`. Returns whether it exited.
+pub(crate) fn join_timeout(thread: JoinHandle<()>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !thread.is_finished() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(JOIN_POLL_INTERVAL);
+    }
+    let _ = thread.join();
+    true
+}
+
+/// Protocol side of an RTSP connection, independent of how its socket is
+/// driven: the method handler, the outbound [`InterleavedSink`], and the
+/// registries requests act on.
+pub(crate) struct ConnectionState {
+    handler: MethodHandler,
+    sink: Arc<InterleavedSink>,
+    peer_addr: SocketAddr,
+    session_manager: SessionManager,
+    mounts: MountRegistry,
+}
+
+impl ConnectionState {
+    pub(crate) fn new(
+        sink: Arc<InterleavedSink>,
+        peer_addr: SocketAddr,
+        local_addr: io::Result<SocketAddr>,
+        session_manager: SessionManager,
+        mounts: MountRegistry,
+        config: Arc<ServerConfig>,
+    ) -> Self {
+        let mut handler =
+            MethodHandler::new(session_manager.clone(), peer_addr, mounts.clone(), config)
+                .with_interleaved_sink(sink.clone());
+        if let Ok(local_addr) = local_addr {
+            handler = handler.with_local_addr(local_addr);
+        }
+        Self {
+            handler,
+            sink,
+            peer_addr,
+            session_manager,
+            mounts,
+        }
+    }
+
+    /// Handle one request and queue its response. Returns `false` once
+    /// the connection can no longer be written to.
+    pub(crate) fn handle_request(&mut self, request_text: &str) -> bool {
+        if request_text.trim().is_empty() {
+            return true;
+        }
+
+        match RtspRequest::parse(request_text) {
+            Ok(request) => {
+                tracing::debug!(
+                    peer = %self.peer_addr,
+                    method = %request.method,
+                    uri = %request.uri,
+                    version = %request.version,
+                    "request"
+                );
+
+                let response = self.handler.handle(&request);
+
+                tracing::debug!(
+                    peer = %self.peer_addr,
+                    status = response.status_code,
+                    "response"
+                );
+
+                self.sink.send_response(response.serialize().into_bytes())
+            }
+            Err(e) => {
+                tracing::warn!(peer = %self.peer_addr, error = %e, "parse error");
+                true
+            }
+        }
+    }
+
+    /// Handle one `$`-framed packet (RFC 2326 §10.12): RTCP on a session's
+    /// RTCP channel has its PLI/FIR forwarded to the session's mount.
+    pub(crate) fn handle_interleaved(&self, channel: u8, packet: &[u8]) {
+        let session = self.handler.session_ids().iter().find_map(|id| {
+            let session = self.session_manager.get_session(id)?;
+            let on_channel = session
+                .transports()
+                .iter()
+                .any(|(_, t)| t.interleaved.is_some_and(|(_, rtcp)| rtcp == channel));
+            on_channel.then_some(session)
+        });
+        match session {
+            Some(session) => self.mounts.forward_rtcp_feedback(&session.id, packet),
+            None => {
+                tracing::trace!(peer = %self.peer_addr, channel, "interleaved data on unknown channel");
+            }
+        }
+    }
+
+    /// Whether the outbound side is closed (by the server, or after a
+    /// write error).
+    pub(crate) fn is_closed(&self) -> bool {
+        self.sink.is_closed()
+    }
+
+    /// Queue raw bytes, such as an HTTP tunnel answer, like a response.
+    pub(crate) fn send_http(&self, response: Vec<u8>) -> bool {
+        self.sink.send_response(response)
+    }
+
+    /// Close the outbound side once its queued output is written.
+    pub(crate) fn finish(&self) {
+        self.sink.finish();
+    }
+
+    /// Close the outbound side, remove the sessions owned by this
+    /// connection and unsubscribe them from their mounts.
+    pub(crate) fn cleanup(&self) {
+        self.sink.close();
+        let orphaned = self.handler.session_ids().to_vec();
+        if !orphaned.is_empty() {
+            for id in &orphaned {
+                self.mounts.unsubscribe_all(id);
+            }
+            let removed = self.session_manager.remove_sessions(&orphaned);
+            tracing::info!(peer = %self.peer_addr, removed, "cleaned up sessions on disconnect");
+        }
+    }
+}
+
 /// A single RTSP client connection with its own lifecycle.
+///
+/// Requests are read on the connection's thread; responses and
+/// interleaved RTP are written by its [`InterleavedSink`], so a viewer
+/// that stops reading only ever backs up its own buffer.
 struct Connection {
     reader: BufReader<TcpStream>,
-    writer: TcpStream,
-    handler: MethodHandler,
-    peer_addr: SocketAddr,
+    state: ConnectionState,
+    tunnels: Tunnels,
+}
+
+/// Why a connection's request loop returned.
+pub(crate) enum Exit {
+    /// The connection is done, for the given reason.
+    Closed(&'static str),
+    /// Its first request was HTTP: it is one side of a tunnel.
+    Tunnel(TunnelRequest),
 }
 
 impl Connection {
-    /// Entry point: set up a connection and run its request loop.
+    /// Entry point: run a connection's request loop on the read side of
+    /// its socket, writing through `sink`.
     pub fn handle(
-        stream: TcpStream,
+        reader: TcpStream,
+        peer_addr: SocketAddr,
+        sink: Arc<InterleavedSink>,
         session_manager: SessionManager,
         mounts: MountRegistry,
         config: Arc<ServerConfig>,
-        running: Arc<AtomicBool>,
+        tunnels: Tunnels,
     ) {
-        let peer_addr = match stream.peer_addr() {
-            Ok(addr) => addr,
-            Err(_) => return,
-        };
-
         tracing::info!(%peer_addr, "client connected");
 
-        let reader_stream = match stream.try_clone() {
-            Ok(s) => s,
-            Err(_) => return,
-        };
-
-        let handler =
-            MethodHandler::new(session_manager.clone(), peer_addr, mounts.clone(), config);
-
+        let local_addr = reader.local_addr();
         let mut conn = Connection {
-            reader: BufReader::new(reader_stream),
-            writer: stream,
-            handler,
-            peer_addr,
+            reader: BufReader::new(reader),
+            state: ConnectionState::new(
+                sink,
+                peer_addr,
+                local_addr,
+                session_manager,
+                mounts,
+                config,
+            ),
+            tunnels,
         };
 
-        let reason = conn.run(&running);
-        conn.cleanup(&session_manager, &mounts);
+        let reason = match conn.run() {
+            Exit::Closed(reason) => {
+                let reason = if conn.state.is_closed() {
+                    "connection closed by server"
+                } else {
+                    reason
+                };
+                conn.state.cleanup();
+                reason
+            }
+            Exit::Tunnel(request) => conn.tunnel(request),
+        };
 
         tracing::info!(%peer_addr, reason, "client disconnected");
     }
 
     /// RTSP request/response loop. Returns the reason for exiting.
-    fn run(&mut self, running: &Arc<AtomicBool>) -> &'static str {
-        while running.load(Ordering::SeqCst) {
+    ///
+    /// Runs until the client disconnects or the sink closes the socket,
+    /// which is how [`Connections::close_all`] ends it on shutdown, or
+    /// until the first request turns out to open an HTTP tunnel.
+    fn run(&mut self) -> Exit {
+        let mut first = true;
+        loop {
+            // Interleaved data (`$`) may arrive between requests.
+            match self.reader.fill_buf() {
+                Ok([]) => return Exit::Closed("connection closed by client"),
+                Ok([b'$', ..]) => {
+                    let mut prefix = [0u8; 4];
+                    if self.reader.read_exact(&mut prefix).is_err() {
+                        return Exit::Closed("read error");
+                    }
+                    let mut packet = vec![0u8; u16::from_be_bytes([prefix[2], prefix[3]]) as usize];
+                    if self.reader.read_exact(&mut packet).is_err() {
+                        return Exit::Closed("read error");
+                    }
+                    self.state.handle_interleaved(prefix[1], &packet);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(_) => return Exit::Closed("read error"),
+            }
+
             let mut request_text = String::new();
             loop {
                 let mut line = String::new();
                 match self.reader.read_line(&mut line) {
-                    Ok(0) => return "connection closed by client",
+                    Ok(0) => return Exit::Closed("connection closed by client"),
                     Ok(_) => {
                         request_text.push_str(&line);
                         if line == "\r\n" || line == "\n" {
                             break;
                         }
                     }
-                    Err(_) => return "read error",
+                    Err(_) => return Exit::Closed("read error"),
                 }
             }
 
-            if request_text.trim().is_empty() {
-                continue;
+            if std::mem::take(&mut first)
+                && let Some(request) = TunnelRequest::parse(&request_text)
+            {
+                return Exit::Tunnel(request);
             }
+            if !self.state.handle_request(&request_text) {
+                return Exit::Closed("write error");
+            }
+        }
+    }
 
-            match RtspRequest::parse(&request_text) {
-                Ok(request) => {
-                    tracing::debug!(
-                        peer = %self.peer_addr,
-                        method = %request.method,
-                        uri = %request.uri,
-                        version = %request.version,
-                        "request"
-                    );
-
-                    let response = self.handler.handle(&request);
-
-                    tracing::debug!(
-                        peer = %self.peer_addr,
-                        status = response.status_code,
-                        "response"
-                    );
-
-                    if self
-                        .writer
-                        .write_all(response.serialize().as_bytes())
-                        .is_err()
-                    {
-                        return "write error";
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!(peer = %self.peer_addr, error = %e, "parse error");
+    /// Serve one side of an HTTP tunnel. Returns the reason for closing.
+    fn tunnel(self, request: TunnelRequest) -> &'static str {
+        let Connection {
+            mut reader,
+            state,
+            tunnels,
+        } = self;
+        match request {
+            TunnelRequest::Get { cookie } => {
+                let state = match tunnels.open(&cookie, state) {
+                    Ok(state) => state,
+                    Err(state) => return refuse(reader, &state, 400, "tunnel cookie in use"),
+                };
+                state.lock().send_http(http_tunnel::get_response());
+                tracing::debug!(cookie, "HTTP tunnel opened");
+                // Nothing else arrives on the GET channel; wait for it to close.
+                let mut reason = drain(&mut reader);
+                tunnels.close(&cookie);
+                let state = state.lock();
+                if state.is_closed() {
+                    reason = "connection closed by server";
                 }
+                state.cleanup();
+                reason
             }
+            TunnelRequest::Post { cookie } => {
+                let Some(tunnel) = tunnels.get(&cookie) else {
+                    return refuse(reader, &state, 404, "no tunnel for cookie");
+                };
+                let mut decoder = PostDecoder::default();
+                let reason = loop {
+                    let data = match reader.fill_buf() {
+                        Ok([]) => break "POST channel closed by client",
+                        Ok(data) => data,
+                        Err(_) => break "read error",
+                    };
+                    let len = data.len();
+                    if let Err(reason) = decoder.deliver(data, &tunnel) {
+                        break reason;
+                    }
+                    reader.consume(len);
+                };
+                state.cleanup();
+                reason
+            }
+            TunnelRequest::Invalid => refuse(reader, &state, 400, "HTTP request without tunnel"),
         }
-
-        "server shutting down"
     }
+}
 
-    /// Clean up sessions owned by this connection and unsubscribe from mounts.
-    fn cleanup(&self, session_manager: &SessionManager, mounts: &MountRegistry) {
-        let orphaned = self.handler.session_ids().to_vec();
-        if !orphaned.is_empty() {
-            for id in &orphaned {
-                mounts.unsubscribe_all(id);
+/// Answer an HTTP request with an error and close the connection once it
+/// is written.
+fn refuse(
+    mut reader: BufReader<TcpStream>,
+    state: &ConnectionState,
+    status: u16,
+    reason: &'static str,
+) -> &'static str {
+    state.send_http(http_tunnel::error_response(status));
+    state.finish();
+    drain(&mut reader);
+    state.cleanup();
+    reason
+}
+
+/// Read and discard until the connection closes, which closing its sink
+/// also causes.
+fn drain(reader: &mut BufReader<TcpStream>) -> &'static str {
+    loop {
+        match reader.fill_buf() {
+            Ok([]) => return "connection closed by client",
+            Ok(data) => {
+                let len = data.len();
+                reader.consume(len);
             }
-            let removed = session_manager.remove_sessions(&orphaned);
-            tracing::info!(peer = %self.peer_addr, removed, "cleaned up sessions on disconnect");
+            Err(_) => return "read error",
         }
     }
 }
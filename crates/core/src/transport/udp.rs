@@ -1,31 +1,236 @@
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::error::Result;
+use crate::media::packet::RtpPacket;
 
-/// UDP transport for outbound RTP packet delivery.
+#[cfg(unix)]
+mod sys;
+
+/// Attempts at finding an even RTP port whose odd neighbour is also free.
+const PORT_PAIR_ATTEMPTS: usize = 16;
+
+/// How long [`UdpTransport::recv_rtcp`] blocks before returning `None`, so
+/// receiver loops can observe shutdown.
+const RTCP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// UDP transport for outbound RTP packet delivery and inbound RTCP.
 ///
-/// Binds a single ephemeral socket (`0.0.0.0:0`) and sends RTP packets
-/// to client addresses resolved by the [`Server`](crate::Server).
+/// Binds an RTP/RTCP socket pair on consecutive ports (RTP even, RTCP =
+/// RTP + 1 per RFC 3550 §11). RTP is sent from the first socket; viewers
+/// send their RTCP (receiver reports, PLI/FIR feedback) to the second.
 ///
 /// This layer is deliberately address-only — it does not know about
 /// sessions or mounts. The caller resolves session state to socket
-/// addresses before calling [`send_to`](Self::send_to).
+/// addresses before calling [`send_to`](Self::send_to), and maps RTCP
+/// source addresses back to sessions after [`recv_rtcp`](Self::recv_rtcp).
+///
+/// ## Send paths
+///
+/// [`send_packets`](Self::send_packets) sends a frame's packets to one
+/// destination. On Linux it batches them into `sendmmsg(2)` calls and, once
+/// [`enable_gso`](Self::enable_gso) has succeeded, hands runs of
+/// equal-sized packets to the kernel as `UDP_SEGMENT` super-datagrams.
+/// Other platforms fall back to one send per packet.
+#[derive(Clone)]
 pub struct UdpTransport {
     socket: Arc<UdpSocket>,
+    rtcp_socket: Arc<UdpSocket>,
+    gso: Arc<AtomicBool>,
 }
 
 impl UdpTransport {
-    /// Bind an ephemeral UDP socket for outbound RTP.
+    /// Bind an RTP/RTCP socket pair on ephemeral ports.
+    ///
+    /// Prefers an even RTP port with RTCP on the next port; if no such pair
+    /// can be found, falls back to two unrelated ephemeral ports.
     pub fn bind() -> Result<Self> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        for _ in 0..PORT_PAIR_ATTEMPTS {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            let port = socket.local_addr()?.port();
+            if port % 2 != 0 || port == u16::MAX {
+                continue;
+            }
+            if let Ok(rtcp_socket) = UdpSocket::bind(("0.0.0.0", port + 1)) {
+                return Self::from_sockets(socket, rtcp_socket);
+            }
+        }
+
+        tracing::debug!("no consecutive RTP/RTCP port pair found, using unrelated ports");
+        Self::from_sockets(UdpSocket::bind("0.0.0.0:0")?, UdpSocket::bind("0.0.0.0:0")?)
+    }
+
+    fn from_sockets(socket: UdpSocket, rtcp_socket: UdpSocket) -> Result<Self> {
+        rtcp_socket.set_read_timeout(Some(RTCP_POLL_INTERVAL))?;
         Ok(Self {
             socket: Arc::new(socket),
+            rtcp_socket: Arc::new(rtcp_socket),
+            gso: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    /// Local (RTP, RTCP) ports, advertised as `server_port` in SETUP.
+    pub fn local_ports(&self) -> Result<(u16, u16)> {
+        Ok((
+            self.socket.local_addr()?.port(),
+            self.rtcp_socket.local_addr()?.port(),
+        ))
+    }
+
     /// Send raw bytes to a specific socket address.
     pub fn send_to(&self, payload: &[u8], addr: SocketAddr) -> Result<usize> {
         Ok(self.socket.send_to(payload, addr)?)
     }
+
+    /// Send an RTCP packet from the RTCP socket, so it arrives from the
+    /// advertised `server_port` RTCP port.
+    pub fn send_rtcp(&self, payload: &[u8], addr: SocketAddr) -> Result<usize> {
+        Ok(self.rtcp_socket.send_to(payload, addr)?)
+    }
+
+    /// Send an [`RtpPacket`] as one datagram without joining its header
+    /// and payload: a vectored `sendmsg` on Unix, a contiguous copy
+    /// elsewhere.
+    pub fn send_packet(&self, packet: &RtpPacket, addr: SocketAddr) -> Result<usize> {
+        #[cfg(unix)]
+        {
+            Ok(sys::send_vectored_to(
+                &self.socket,
+                &packet.io_slices(),
+                addr,
+            )?)
+        }
+        #[cfg(not(unix))]
+        {
+            self.send_to(&packet.to_vec(), addr)
+        }
+    }
+
+    /// Use UDP generic segmentation offload for batched sends if the
+    /// kernel supports it (Linux 4.18+). Returns whether GSO is now on.
+    ///
+    /// GSO switches itself off again if a send reports that the route or
+    /// device can't segment.
+    pub fn enable_gso(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        let supported = sys::gso_supported(&self.socket);
+        #[cfg(not(target_os = "linux"))]
+        let supported = false;
+
+        self.gso.store(supported, Ordering::Relaxed);
+        supported
+    }
+
+    /// Whether batched sends currently use GSO.
+    pub fn gso_enabled(&self) -> bool {
+        self.gso.load(Ordering::Relaxed)
+    }
+
+    /// Send a frame's packets to one destination, in order. Returns the
+    /// number of packets sent.
+    ///
+    /// Uses `sendmmsg` (and GSO when enabled) on Linux, one
+    /// [`send_packet`](Self::send_packet) per packet elsewhere.
+    pub fn send_packets(&self, packets: &[RtpPacket], addr: SocketAddr) -> Result<usize> {
+        #[cfg(target_os = "linux")]
+        {
+            let gso = self.gso_enabled();
+            match sys::send_batch(&self.socket, packets, addr, gso) {
+                Err(e) if gso && sys::is_gso_unsupported(&e) => {
+                    tracing::warn!(error = %e, "UDP GSO send failed, disabling GSO");
+                    self.gso.store(false, Ordering::Relaxed);
+                    Ok(sys::send_batch(&self.socket, packets, addr, false)?)
+                }
+                result => Ok(result?),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            for packet in packets {
+                self.send_packet(packet, addr)?;
+            }
+            Ok(packets.len())
+        }
+    }
+
+    /// Receive one RTCP datagram.
+    ///
+    /// Returns `Ok(None)` when nothing arrived within the poll interval.
+    pub fn recv_rtcp(&self, buf: &mut [u8]) -> Result<Option<(usize, SocketAddr)>> {
+        match self.rtcp_socket.recv_from(buf) {
+            Ok(received) => Ok(Some(received)),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn send_packet_delivers_header_and_payload_as_one_datagram() {
+        let udp = UdpTransport::bind().unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let packet = RtpPacket::new(&[0x80, 0x60, 0, 1], Bytes::from_static(b"payload"));
+        let sent = udp
+            .send_packet(&packet, receiver.local_addr().unwrap())
+            .unwrap();
+        assert_eq!(sent, packet.len());
+
+        let mut buf = [0u8; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], packet.to_vec().as_slice());
+    }
+
+    fn assert_batch_arrives(udp: &UdpTransport) {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        // Three equal fragments and a short tail, like an FU-A NAL.
+        let packets: Vec<RtpPacket> = [1000usize, 1000, 1000, 250]
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| RtpPacket::new(&[0x80, 0x60, 0, i as u8], vec![i as u8; len].into()))
+            .collect();
+        let sent = udp
+            .send_packets(&packets, receiver.local_addr().unwrap())
+            .unwrap();
+        assert_eq!(sent, packets.len());
+
+        let mut buf = [0u8; 2048];
+        for packet in &packets {
+            let len = receiver.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..len], packet.to_vec().as_slice());
+        }
+    }
+
+    #[test]
+    fn send_packets_keeps_datagram_boundaries() {
+        assert_batch_arrives(&UdpTransport::bind().unwrap());
+    }
+
+    #[test]
+    fn send_packets_with_gso_keeps_datagram_boundaries() {
+        let udp = UdpTransport::bind().unwrap();
+        udp.enable_gso();
+        assert_batch_arrives(&udp);
+    }
 }
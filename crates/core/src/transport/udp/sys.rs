@@ -0,0 +1,244 @@
+//! Raw socket calls behind [`UdpTransport`](super::UdpTransport)'s send
+//! paths: vectored `sendmsg` on Unix, plus batched `sendmmsg` and UDP GSO
+//! (`UDP_SEGMENT`) on Linux.
+
+use std::io::{self, IoSlice};
+use std::mem;
+use std::net::{SocketAddr, UdpSocket};
+use std::os::fd::AsRawFd;
+
+#[cfg(target_os = "linux")]
+use crate::media::packet::RtpPacket;
+
+/// Most segments the kernel accepts in one GSO send (`UDP_MAX_SEGMENTS`
+/// before Linux 6.9).
+#[cfg(target_os = "linux")]
+const GSO_MAX_SEGMENTS: usize = 64;
+
+/// Largest GSO super-datagram: the 16-bit IP length minus IPv6 and UDP
+/// headers.
+#[cfg(target_os = "linux")]
+const GSO_MAX_BYTES: usize = 65535 - 40 - 8;
+
+/// `sendmsg(2)` with a gather list. `IoSlice` is ABI-compatible with
+/// `struct iovec` on Unix.
+pub(super) fn send_vectored_to(
+    socket: &UdpSocket,
+    bufs: &[IoSlice<'_>],
+    addr: SocketAddr,
+) -> io::Result<usize> {
+    let (mut name, name_len) = sockaddr(addr);
+    // SAFETY: zeroed msghdr is valid; every pointer stored in it outlives
+    // the call.
+    let sent = unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_name = (&mut name as *mut libc::sockaddr_storage).cast();
+        msg.msg_namelen = name_len;
+        msg.msg_iov = bufs.as_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = bufs.len() as _;
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sent as usize)
+}
+
+/// Whether the kernel supports `UDP_SEGMENT` on this socket (Linux 4.18+).
+#[cfg(target_os = "linux")]
+pub(super) fn gso_supported(socket: &UdpSocket) -> bool {
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: value/len are valid for writes of the sizes passed.
+    let rc = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_UDP,
+            libc::UDP_SEGMENT,
+            (&mut value as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    rc == 0
+}
+
+/// Whether a failed GSO send means the path can't segment (no kernel or
+/// device support) rather than a per-destination error.
+#[cfg(target_os = "linux")]
+pub(super) fn is_gso_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EIO | libc::EINVAL | libc::ENOPROTOOPT | libc::EOPNOTSUPP)
+    )
+}
+
+/// Split `packets` into datagrams: runs of equal-length packets (the last
+/// one may be shorter) that the kernel can segment, or single packets.
+#[cfg(target_os = "linux")]
+fn gso_runs(packets: &[RtpPacket], gso: bool) -> Vec<std::ops::Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < packets.len() {
+        let mut end = start + 1;
+        if gso {
+            let segment = packets[start].len();
+            let mut bytes = segment;
+            while end < packets.len()
+                && end - start < GSO_MAX_SEGMENTS
+                && packets[end].len() <= segment
+                && bytes + packets[end].len() <= GSO_MAX_BYTES
+            {
+                bytes += packets[end].len();
+                end += 1;
+                if packets[end - 1].len() < segment {
+                    break;
+                }
+            }
+        }
+        runs.push(start..end);
+        start = end;
+    }
+    runs
+}
+
+/// Send every packet to `addr` with as few `sendmmsg(2)` calls as the
+/// kernel allows. With `gso`, runs of equal-sized packets (e.g. a frame's
+/// FU-A fragments) go out as one `UDP_SEGMENT` super-datagram each, which
+/// the kernel or NIC splits back into packets.
+///
+/// Returns the number of RTP packets sent.
+#[cfg(target_os = "linux")]
+pub(super) fn send_batch(
+    socket: &UdpSocket,
+    packets: &[RtpPacket],
+    addr: SocketAddr,
+    gso: bool,
+) -> io::Result<usize> {
+    if packets.is_empty() {
+        return Ok(0);
+    }
+
+    let runs = gso_runs(packets, gso);
+    let (mut name, name_len) = sockaddr(addr);
+    let mut iovecs: Vec<libc::iovec> = Vec::with_capacity(packets.len() * 2);
+    for packet in packets {
+        for slice in packet.io_slices() {
+            iovecs.push(libc::iovec {
+                iov_base: slice.as_ptr() as *mut libc::c_void,
+                iov_len: slice.len(),
+            });
+        }
+    }
+
+    // One aligned control buffer per datagram, holding the UDP_SEGMENT cmsg.
+    // SAFETY: CMSG_SPACE is a pure size computation.
+    let cmsg_space = unsafe { libc::CMSG_SPACE(mem::size_of::<u16>() as u32) } as usize;
+    let mut control = vec![[0u64; 4]; runs.len()];
+    debug_assert!(cmsg_space <= mem::size_of::<[u64; 4]>());
+
+    // SAFETY: zeroed mmsghdr is valid; name, iovecs and control are not
+    // touched again until the sends below have finished with them.
+    let mut msgs: Vec<libc::mmsghdr> = runs
+        .iter()
+        .zip(control.iter_mut())
+        .map(|(run, control)| unsafe {
+            let mut msg: libc::mmsghdr = mem::zeroed();
+            let hdr = &mut msg.msg_hdr;
+            hdr.msg_name = (&mut name as *mut libc::sockaddr_storage).cast();
+            hdr.msg_namelen = name_len;
+            hdr.msg_iov = iovecs.as_mut_ptr().add(run.start * 2);
+            hdr.msg_iovlen = (run.len() * 2) as _;
+            if run.len() > 1 {
+                hdr.msg_control = control.as_mut_ptr().cast();
+                hdr.msg_controllen = cmsg_space as _;
+                let cmsg = libc::CMSG_FIRSTHDR(hdr);
+                (*cmsg).cmsg_level = libc::SOL_UDP;
+                (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as u32) as _;
+                let segment = packets[run.start].len() as u16;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<u16>(), segment);
+            }
+            msg
+        })
+        .collect();
+
+    let mut done = 0;
+    while done < msgs.len() {
+        // SAFETY: msgs[done..] is a valid array of initialized mmsghdr.
+        let n = unsafe {
+            libc::sendmmsg(
+                socket.as_raw_fd(),
+                msgs.as_mut_ptr().add(done),
+                (msgs.len() - done) as _,
+                0,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        done += n as usize;
+    }
+
+    Ok(packets.len())
+}
+
+pub(super) fn sockaddr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: sockaddr_storage is plain data and large enough for both
+    // address families.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(v4) => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = v4.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(v4.ip().octets());
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(v6) => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = v6.port().to_be();
+            sin6.sin6_flowinfo = v6.flowinfo();
+            sin6.sin6_addr.s6_addr = v6.ip().octets();
+            sin6.sin6_scope_id = v6.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn packet(len: usize) -> RtpPacket {
+        RtpPacket::new(&[0x80; 12], Bytes::from(vec![0u8; len - 12]))
+    }
+
+    #[test]
+    fn gso_runs_group_equal_sizes_with_short_tail() {
+        let packets: Vec<_> = [1414, 1414, 1414, 300, 200, 1414]
+            .into_iter()
+            .map(packet)
+            .collect();
+        assert_eq!(gso_runs(&packets, true), vec![0..4, 4..5, 5..6]);
+        assert_eq!(gso_runs(&packets, false).len(), packets.len());
+    }
+
+    #[test]
+    fn gso_runs_respect_segment_and_size_limits() {
+        let packets: Vec<_> = (0..100).map(|_| packet(200)).collect();
+        let runs = gso_runs(&packets, true);
+        assert_eq!(runs, vec![0..64, 64..100]);
+
+        let packets: Vec<_> = (0..60).map(|_| packet(1414)).collect();
+        let runs = gso_runs(&packets, true);
+        assert!(runs.iter().all(|r| r.len() * 1414 <= GSO_MAX_BYTES));
+        assert_eq!(runs.iter().map(|r| r.len()).sum::<usize>(), 60);
+    }
+}
@@ -1,13 +1,19 @@
 //! Integration test: full RTSP handshake OPTIONS → DESCRIBE → SETUP → PLAY.
 //!
-//! Starts the server on a fixed port, connects with a TCP client, and
+//! Starts the server on a free port (port 0), connects with a TCP client, and
 //! verifies each response.
 
 use std::io::{BufRead, BufReader, Read, Write};
-use std::net::{TcpStream, ToSocketAddrs};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use rtsp::Server;
+use rtsp::media::h264::H264Packetizer;
+use rtsp::media::onvif::{MetadataFrame, OnvifMetadataPacketizer};
+use rtsp::{
+    HlsConfig, KeyframeRequestPolicy, KeyframeRequestReason, MediaFile, MediaSource, Server,
+    ServerConfig, SourceFrame,
+};
 
 fn rtsp_request(stream: &mut TcpStream, request: &str) -> std::io::Result<String> {
     stream.write_all(request.as_bytes())?;
@@ -32,26 +38,34 @@ fn rtsp_request(stream: &mut TcpStream, request: &str) -> std::io::Result<String
         .find(|l| l.to_lowercase().starts_with("content-length:"))
         .and_then(|l| l.split(':').nth(1))
         .and_then(|v| v.trim().parse::<usize>().ok())
+        && len > 0
     {
-        if len > 0 {
-            let mut body = vec![0u8; len];
-            reader.read_exact(&mut body)?;
-            response.push_str(&String::from_utf8_lossy(&body));
-        }
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        response.push_str(&String::from_utf8_lossy(&body));
     }
 
     Ok(response)
 }
 
-/// Fixed port for integration test. bind_addr must be explicit (no port 0).
-const TEST_BIND: &str = "127.0.0.1:18554";
+/// Every test binds port 0 and connects to [`Server::local_addr`], so
+/// tests can run in parallel without picking ports.
+const TEST_BIND: &str = "127.0.0.1:0";
+
+fn header_value<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let prefix = format!("{}:", name.to_lowercase());
+    response
+        .lines()
+        .find(|l| l.to_lowercase().starts_with(&prefix))
+        .map(|l| l[prefix.len()..].trim())
+}
 
 #[test]
 fn full_handshake_options_describe_setup_play() {
     let mut server = Server::new(TEST_BIND);
     server.start().expect("server start");
 
-    let addr = TEST_BIND.to_socket_addrs().unwrap().next().unwrap();
+    let addr = server.local_addr().expect("bound address");
     let mut stream =
         TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
     stream
@@ -61,7 +75,7 @@ fn full_handshake_options_describe_setup_play() {
         .set_write_timeout(Some(Duration::from_secs(2)))
         .unwrap();
 
-    let base_uri = "rtsp://127.0.0.1:18554/stream".to_string();
+    let base_uri = format!("rtsp://{addr}/stream");
 
     // OPTIONS
     let opt_req = format!("OPTIONS {} RTSP/1.0\r\nCSeq: 1\r\n\r\n", base_uri);
@@ -104,6 +118,22 @@ fn full_handshake_options_describe_setup_play() {
         desc_resp.contains("a=fmtp:96 packetization-mode=1"),
         "DESCRIBE: SDP missing fmtp packetization-mode=1"
     );
+    assert_eq!(
+        header_value(&desc_resp, "Content-Base"),
+        Some(format!("{base_uri}/").as_str())
+    );
+
+    // Content-Base carries the port actually bound, even when the request
+    // URI has none.
+    let desc_resp = rtsp_request(
+        &mut stream,
+        "DESCRIBE rtsp://127.0.0.1/stream RTSP/1.0\r\nCSeq: 2\r\n\r\n",
+    )
+    .expect("DESCRIBE response");
+    assert_eq!(
+        header_value(&desc_resp, "Content-Base"),
+        Some(format!("{base_uri}/").as_str())
+    );
 
     // SETUP (track1)
     let setup_uri = format!("{}/track1", base_uri);
@@ -152,3 +182,965 @@ fn full_handshake_options_describe_setup_play() {
 
     server.stop();
 }
+
+#[test]
+fn keyframe_requested_on_play_and_rtcp_pli() {
+    let mut server = Server::new(TEST_BIND);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let mount = server.mounts().get("/stream").expect("default mount");
+    mount.set_keyframe_request_policy(KeyframeRequestPolicy {
+        min_interval: Duration::ZERO,
+        on_new_viewer: true,
+    });
+    let sink = requests.clone();
+    mount.set_keyframe_request_handler(move |req| {
+        sink.lock()
+            .unwrap()
+            .push((req.reason, req.session_id.clone()));
+    });
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+
+    let rtcp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let rtcp_port = rtcp.local_addr().unwrap().port();
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let setup_req = format!(
+        "SETUP {}/track1 RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        base_uri,
+        rtcp_port - 1,
+        rtcp_port
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+    let server_rtcp_port: u16 = header_value(&setup_resp, "Transport")
+        .and_then(|t| t.split("server_port=").nth(1))
+        .and_then(|p| p.split('-').nth(1))
+        .and_then(|p| p.split(';').next())
+        .and_then(|p| p.parse().ok())
+        .expect("server_port in Transport");
+
+    let play_req = format!(
+        "PLAY {} RTSP/1.0\r\nCSeq: 2\r\nSession: {}\r\n\r\n",
+        base_uri, session_id
+    );
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"));
+
+    // RTCP PLI (RFC 4585 §6.3.1): V=2, FMT=1, PT=206, length=2.
+    let mut pli = vec![0x81, 206, 0, 2];
+    pli.extend_from_slice(&0x1111_1111u32.to_be_bytes());
+    pli.extend_from_slice(&0x2222_2222u32.to_be_bytes());
+    rtcp.send_to(&pli, ("127.0.0.1", server_rtcp_port))
+        .expect("send PLI");
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while requests.lock().unwrap().len() < 2 && std::time::Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        *requests,
+        vec![
+            (KeyframeRequestReason::NewViewer, Some(session_id.clone())),
+            (KeyframeRequestReason::PictureLoss, Some(session_id.clone())),
+        ]
+    );
+
+    server.stop();
+}
+
+#[test]
+fn metadata_track_shares_video_timestamps() {
+    let mut server =
+        Server::with_packetizer(TEST_BIND, Box::new(H264Packetizer::with_random_ssrc(96)));
+    server
+        .add_track(
+            "/stream",
+            Box::new(OnvifMetadataPacketizer::with_random_ssrc(107)),
+        )
+        .expect("add metadata track");
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let desc_req = format!("DESCRIBE {} RTSP/1.0\r\nCSeq: 1\r\n\r\n", base_uri);
+    let desc_resp = rtsp_request(&mut stream, &desc_req).expect("DESCRIBE response");
+    assert!(desc_resp.contains("m=application 0 RTP/AVP 107"));
+    assert!(desc_resp.contains(&format!("a=control:{base_uri}/track2\r\n")));
+
+    let video_rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let metadata_rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for socket in [&video_rtp, &metadata_rtp] {
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+    }
+    let port = |s: &UdpSocket| s.local_addr().unwrap().port();
+
+    let setup_req = format!(
+        "SETUP {}/track1 RTSP/1.0\r\nCSeq: 2\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        base_uri,
+        port(&video_rtp),
+        port(&video_rtp) + 1
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP track1");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    let setup_req = format!(
+        "SETUP {}/track2 RTSP/1.0\r\nCSeq: 3\r\nSession: {}\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        base_uri,
+        session_id,
+        port(&metadata_rtp),
+        port(&metadata_rtp) + 1
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP track2");
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{}", setup_resp);
+    assert!(setup_resp.contains(&session_id), "same session");
+
+    let play_req = format!(
+        "PLAY {} RTSP/1.0\r\nCSeq: 4\r\nSession: {}\r\n\r\n",
+        base_uri, session_id
+    );
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    let rtp_info = header_value(&play_resp, "RTP-Info").expect("RTP-Info");
+    assert!(rtp_info.contains(&format!("url={base_uri}/track1;")));
+    assert!(rtp_info.contains(&format!(",url={base_uri}/track2;")));
+
+    let pts = Duration::from_millis(40);
+    server
+        .send_frame_with_pts("/stream", &[0, 0, 0, 1, 0x65, 0xAA], pts)
+        .expect("send video");
+    let xml = MetadataFrame::new(std::time::SystemTime::now()).to_xml();
+    server
+        .send_track_frame_with_pts("/stream", 1, xml.as_bytes(), pts)
+        .expect("send metadata");
+
+    let mut buf = [0u8; 2048];
+    let rtp_ts = |p: &[u8]| u32::from_be_bytes([p[4], p[5], p[6], p[7]]);
+    let (n, _) = video_rtp.recv_from(&mut buf).expect("video RTP");
+    let video_ts = rtp_ts(&buf[..n]);
+    let (n, _) = metadata_rtp.recv_from(&mut buf).expect("metadata RTP");
+    assert_eq!(buf[1] & 0x7f, 107);
+    assert_eq!(rtp_ts(&buf[..n]), video_ts);
+    assert!(String::from_utf8_lossy(&buf[12..n]).contains("tt:MetadataStream"));
+
+    server.stop();
+}
+
+#[test]
+fn interleaved_tcp_delivers_rtp_and_accepts_rtcp() {
+    let mut server = Server::new(TEST_BIND);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let mount = server.mounts().get("/stream").expect("default mount");
+    mount.set_keyframe_request_policy(KeyframeRequestPolicy {
+        min_interval: Duration::ZERO,
+        on_new_viewer: false,
+    });
+    let sink = requests.clone();
+    mount.set_keyframe_request_handler(move |req| sink.lock().unwrap().push(req.reason));
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let setup_req = format!(
+        "SETUP {}/track1 RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP/TCP;unicast;interleaved=2-3\r\n\r\n",
+        base_uri
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{setup_resp}");
+    assert_eq!(
+        header_value(&setup_resp, "Transport"),
+        Some("RTP/AVP/TCP;unicast;interleaved=2-3")
+    );
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    let play_req = format!(
+        "PLAY {} RTSP/1.0\r\nCSeq: 2\r\nSession: {}\r\n\r\n",
+        base_uri, session_id
+    );
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"));
+
+    let idr = [0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00];
+    assert_eq!(server.send_frame(&idr, 3000).unwrap(), 1);
+
+    let mut prefix = [0u8; 4];
+    stream.read_exact(&mut prefix).expect("interleaved frame");
+    assert_eq!(prefix[..2], [b'$', 2]);
+    let mut packet = vec![0u8; u16::from_be_bytes([prefix[2], prefix[3]]) as usize];
+    stream.read_exact(&mut packet).unwrap();
+    assert_eq!(packet[0] >> 6, 2, "RTP version");
+    assert_eq!(&packet[12..], &idr[4..]);
+
+    // RTCP PLI on the session's RTCP channel.
+    let mut pli = vec![b'$', 3, 0, 12, 0x81, 206, 0, 2];
+    pli.extend_from_slice(&0x1111_1111u32.to_be_bytes());
+    pli.extend_from_slice(&0x2222_2222u32.to_be_bytes());
+    stream.write_all(&pli).unwrap();
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while requests.lock().unwrap().is_empty() && std::time::Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![KeyframeRequestReason::PictureLoss]
+    );
+
+    let viewers = server.get_viewers();
+    let stats = viewers[0].tcp_stats.expect("TCP viewer stats");
+    assert_eq!(stats.frames_sent, 1);
+    assert_eq!(stats.overflows, 0);
+
+    server.stop();
+}
+
+#[test]
+fn stop_sends_bye_closes_connections_and_restarts() {
+    let mut server = Server::new(TEST_BIND);
+    server.start().expect("server start");
+
+    let rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let rtcp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    rtcp.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let rtp_port = rtp.local_addr().unwrap().port();
+    let rtcp_port = rtcp.local_addr().unwrap().port();
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let setup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP;unicast;client_port={rtp_port}-{rtcp_port}\r\n\r\n"
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{setup_resp}");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+    let play_req = format!("PLAY {base_uri} RTSP/1.0\r\nCSeq: 2\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"));
+
+    server.stop();
+    assert!(!server.is_running());
+
+    // RTCP BYE (after the leading empty receiver report).
+    let mut buf = [0u8; 1500];
+    let len = rtcp.recv(&mut buf).expect("RTCP BYE");
+    assert_eq!(buf[1], 201);
+    assert_eq!(buf[9], 203);
+    assert_eq!(buf[4..8], buf[12..16], "BYE names the stream's SSRC");
+    assert!(len > 16);
+
+    // The control connection is closed and the session is gone.
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).expect("connection closed");
+    assert!(server.session_manager().get_session(&session_id).is_none());
+    assert!(server.session_manager().get_session(&session_id).is_none());
+
+    // The listener is closed and the server can start again.
+    drop(TcpListener::bind(addr).expect("port released"));
+    server.start().expect("restart");
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("reconnect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let options = rtsp_request(&mut stream, "OPTIONS * RTSP/1.0\r\nCSeq: 1\r\n\r\n")
+        .expect("OPTIONS response");
+    assert!(options.starts_with("RTSP/1.0 200 OK"));
+    drop(server);
+}
+
+#[test]
+fn urls_advertise_public_host_and_port() {
+    let config = ServerConfig {
+        public_host: Some("203.0.113.7".to_string()),
+        public_port: Some(18554),
+        ..ServerConfig::default()
+    };
+    let mut server = Server::with_config(TEST_BIND, config);
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let public_uri = "rtsp://203.0.113.7:18554/stream";
+
+    // The client reached the server through a forwarded port under another name.
+    let desc_req = format!("DESCRIBE rtsp://{addr}/live RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+    let desc_resp = rtsp_request(&mut stream, &desc_req).expect("DESCRIBE response");
+    assert_eq!(
+        header_value(&desc_resp, "Content-Base"),
+        Some(format!("{public_uri}/").as_str())
+    );
+    assert!(desc_resp.contains(&format!("a=control:{public_uri}/track1\r\n")));
+
+    let rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let rtp_port = rtp.local_addr().unwrap().port();
+    let setup_req = format!(
+        "SETUP {public_uri}/track1 RTSP/1.0\r\nCSeq: 2\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        rtp_port,
+        rtp_port + 1
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{setup_resp}");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    let play_req =
+        format!("PLAY {public_uri}/ RTSP/1.0\r\nCSeq: 3\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    let rtp_info = header_value(&play_resp, "RTP-Info").expect("RTP-Info header");
+    assert!(
+        rtp_info.starts_with(&format!("url={public_uri}/track1;")),
+        "{rtp_info}"
+    );
+
+    server.stop();
+}
+
+#[test]
+fn urls_keep_the_request_port_behind_a_port_forward() {
+    let mut server = Server::new(TEST_BIND);
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+
+    // The client dialled the forwarded port, not the one the server bound.
+    let forwarded_port = if addr.port() == 18554 { 18555 } else { 18554 };
+    let forwarded_uri = format!("rtsp://{}:{forwarded_port}/stream", addr.ip());
+    let desc_req = format!("DESCRIBE {forwarded_uri} RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+    let desc_resp = rtsp_request(&mut stream, &desc_req).expect("DESCRIBE response");
+    assert_eq!(
+        header_value(&desc_resp, "Content-Base"),
+        Some(format!("{forwarded_uri}/").as_str())
+    );
+    assert!(desc_resp.contains(&format!("a=control:{forwarded_uri}/track1\r\n")));
+
+    server.stop();
+}
+
+#[test]
+fn sdp_uses_the_ipv6_host_of_the_request_uri() {
+    let mut server = Server::new(TEST_BIND);
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+
+    let desc_req = "DESCRIBE rtsp://[::1]:8554/stream RTSP/1.0\r\nCSeq: 1\r\n\r\n";
+    let desc_resp = rtsp_request(&mut stream, desc_req).expect("DESCRIBE response");
+    assert!(desc_resp.contains(" IN IP6 ::1\r\n"), "{desc_resp}");
+    assert!(desc_resp.contains("c=IN IP6 ::1\r\n"), "{desc_resp}");
+    assert_eq!(
+        header_value(&desc_resp, "Content-Base"),
+        Some("rtsp://[::1]:8554/stream/")
+    );
+
+    server.stop();
+}
+
+#[test]
+fn session_methods_follow_the_state_machine() {
+    let mut server = Server::new(TEST_BIND);
+    server.add_mount("/other", Box::new(H264Packetizer::with_random_ssrc(96)));
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let connect = || {
+        let stream =
+            TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream
+    };
+    let mut stream = connect();
+    let base_uri = format!("rtsp://{addr}/stream");
+    let status = |response: &str| response.lines().next().unwrap_or("").to_string();
+
+    let setup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP;unicast;client_port=5000-5001\r\n\r\n"
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{setup_resp}");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    // Nothing to pause before PLAY.
+    let pause_req =
+        format!("PAUSE {base_uri} RTSP/1.0\r\nCSeq: 2\r\nSession: {session_id}\r\n\r\n");
+    let pause_resp = rtsp_request(&mut stream, &pause_req).expect("PAUSE response");
+    assert_eq!(
+        status(&pause_resp),
+        "RTSP/1.0 455 Method Not Valid in This State"
+    );
+    assert_eq!(
+        header_value(&pause_resp, "Allow"),
+        Some("SETUP, PLAY, TEARDOWN, GET_PARAMETER")
+    );
+
+    // Re-SETUP changes the transport of the same session.
+    let resetup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 3\r\nSession: {session_id}\r\nTransport: RTP/AVP;unicast;client_port=6000-6001\r\n\r\n"
+    );
+    let resetup_resp = rtsp_request(&mut stream, &resetup_req).expect("re-SETUP response");
+    assert!(
+        resetup_resp.starts_with("RTSP/1.0 200 OK"),
+        "{resetup_resp}"
+    );
+    assert!(
+        header_value(&resetup_resp, "Session").is_some_and(|v| v.starts_with(&session_id)),
+        "{resetup_resp}"
+    );
+    assert!(
+        resetup_resp.contains("client_port=6000-6001"),
+        "{resetup_resp}"
+    );
+    let session = server
+        .session_manager()
+        .get_session(&session_id)
+        .expect("session");
+    assert_eq!(session.transports().len(), 1);
+    assert_eq!(session.track_transport(0).unwrap().client_rtp_port, 6000);
+
+    // PLAY on another mount's URL is not an operation on this session.
+    let play_req =
+        format!("PLAY rtsp://{addr}/other RTSP/1.0\r\nCSeq: 4\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert_eq!(
+        status(&play_resp),
+        "RTSP/1.0 459 Aggregate Operation Not Allowed"
+    );
+
+    let play_req = format!("PLAY {base_uri}/ RTSP/1.0\r\nCSeq: 5\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"), "{play_resp}");
+
+    // Another connection cannot control the session.
+    let mut intruder = connect();
+    for method in ["PAUSE", "TEARDOWN"] {
+        let req =
+            format!("{method} {base_uri} RTSP/1.0\r\nCSeq: 1\r\nSession: {session_id}\r\n\r\n");
+        let resp = rtsp_request(&mut intruder, &req).expect("response");
+        assert_eq!(status(&resp), "RTSP/1.0 454 Session Not Found", "{method}");
+    }
+
+    let pause_req =
+        format!("PAUSE {base_uri} RTSP/1.0\r\nCSeq: 6\r\nSession: {session_id}\r\n\r\n");
+    let pause_resp = rtsp_request(&mut stream, &pause_req).expect("PAUSE response");
+    assert!(pause_resp.starts_with("RTSP/1.0 200 OK"), "{pause_resp}");
+
+    server.stop();
+}
+
+#[test]
+fn shared_sessions_can_be_controlled_from_another_connection() {
+    let config = ServerConfig {
+        shared_sessions: true,
+        ..ServerConfig::default()
+    };
+    let mut server = Server::with_config(TEST_BIND, config);
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut owner =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    owner
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let setup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 1\r\nTransport: RTP/AVP;unicast;client_port=5000-5001\r\n\r\n"
+    );
+    let setup_resp = rtsp_request(&mut owner, &setup_req).expect("SETUP response");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    let mut other =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    other
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let teardown_req =
+        format!("TEARDOWN {base_uri} RTSP/1.0\r\nCSeq: 1\r\nSession: {session_id}\r\n\r\n");
+    let teardown_resp = rtsp_request(&mut other, &teardown_req).expect("TEARDOWN response");
+    assert!(
+        teardown_resp.starts_with("RTSP/1.0 200 OK"),
+        "{teardown_resp}"
+    );
+    assert!(server.session_manager().get_session(&session_id).is_none());
+
+    server.stop();
+}
+
+/// 10 s at 10 fps with a keyframe every second; frame `n` carries `n`.
+struct TestClip {
+    next: u64,
+}
+
+impl MediaSource for TestClip {
+    fn duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs(10))
+    }
+
+    fn seek(&mut self, position: Duration) -> rtsp::Result<Duration> {
+        self.next = position.as_secs() * 10;
+        Ok(Duration::from_millis(self.next * 100))
+    }
+
+    fn next_frame(&mut self) -> rtsp::Result<Option<SourceFrame>> {
+        if self.next >= 100 {
+            return Ok(None);
+        }
+        let nal_type = if self.next.is_multiple_of(10) {
+            0x65
+        } else {
+            0x41
+        };
+        let frame = SourceFrame {
+            track: 0,
+            data: vec![0, 0, 0, 1, nal_type, self.next as u8].into(),
+            pts: Duration::from_millis(self.next * 100),
+            dts: None,
+            keyframe: nal_type == 0x65,
+        };
+        self.next += 1;
+        Ok(Some(frame))
+    }
+}
+
+#[test]
+fn seekable_mount_honours_range_scale_and_pause() {
+    let mut server = Server::new(TEST_BIND);
+    server.add_seekable_mount(
+        "/vod",
+        Box::new(H264Packetizer::with_random_ssrc(96)),
+        Box::new(TestClip { next: 0 }),
+    );
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/vod");
+
+    let desc_req = format!("DESCRIBE {base_uri} RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+    let desc_resp = rtsp_request(&mut stream, &desc_req).expect("DESCRIBE response");
+    assert!(
+        desc_resp.contains("a=range:npt=0-10.000\r\n"),
+        "{desc_resp}"
+    );
+
+    let rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    rtp.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let rtp_port = rtp.local_addr().unwrap().port();
+    let setup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 2\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        rtp_port,
+        rtp_port + 1
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    let play_req = format!(
+        "PLAY {base_uri} RTSP/1.0\r\nCSeq: 3\r\nSession: {session_id}\r\nRange: npt=11-\r\n\r\n"
+    );
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(
+        play_resp.starts_with("RTSP/1.0 457 Invalid Range"),
+        "{play_resp}"
+    );
+
+    for rate in ["Scale: 1e-300", "Scale: -100", "Speed: 0.001"] {
+        let play_req = format!(
+            "PLAY {base_uri} RTSP/1.0\r\nCSeq: 4\r\nSession: {session_id}\r\n{rate}\r\n\r\n"
+        );
+        let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+        assert!(play_resp.starts_with("RTSP/1.0 400"), "{rate}: {play_resp}");
+    }
+
+    let play_req = format!(
+        "PLAY {base_uri} RTSP/1.0\r\nCSeq: 4\r\nSession: {session_id}\r\nRange: npt=3-\r\nScale: 2\r\n\r\n"
+    );
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"), "{play_resp}");
+    assert_eq!(header_value(&play_resp, "Range"), Some("npt=3.000-10.000"));
+    assert_eq!(header_value(&play_resp, "Scale"), Some("2"));
+
+    // Fast-forward: keyframes only, from the seek point on.
+    let mut buf = [0u8; 1500];
+    let mut frames = Vec::new();
+    while frames.len() < 2 {
+        let len = rtp.recv(&mut buf).expect("RTP packet");
+        frames.push((buf[12], buf[len - 1]));
+    }
+    assert_eq!(frames, [(0x65, 30), (0x65, 40)]);
+
+    let pause_req =
+        format!("PAUSE {base_uri} RTSP/1.0\r\nCSeq: 5\r\nSession: {session_id}\r\n\r\n");
+    let pause_resp = rtsp_request(&mut stream, &pause_req).expect("PAUSE response");
+    assert!(pause_resp.starts_with("RTSP/1.0 200 OK"), "{pause_resp}");
+    let paused_at = header_value(&pause_resp, "Range")
+        .expect("Range header")
+        .to_string();
+
+    let play_req = format!("PLAY {base_uri} RTSP/1.0\r\nCSeq: 6\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    let resumed_at = header_value(&play_resp, "Range").expect("Range header");
+    assert!(
+        paused_at.split('-').next() <= resumed_at.split('-').next(),
+        "paused at {paused_at}, resumed at {resumed_at}"
+    );
+
+    server.stop();
+}
+
+#[test]
+fn file_mount_loops_an_annex_b_clip() {
+    let clip: Vec<u8> = [
+        &[0x67, 0x42, 0x00, 0x1e][..],
+        &[0x68, 0xce, 0x38, 0x80],
+        &[0x65, 0x88, 0x84],
+        &[0x41, 0x9a, 0x01],
+        &[0x41, 0x9a, 0x02],
+    ]
+    .iter()
+    .flat_map(|nal| [&[0u8, 0, 0, 1][..], nal].concat())
+    .collect();
+    let path = std::env::temp_dir().join(format!("rtsp-rs-clip-{}.h264", std::process::id()));
+    std::fs::write(&path, clip).unwrap();
+    let file = MediaFile::open(&path)
+        .expect("open clip")
+        .with_frame_rate(100.0)
+        .with_looping(true);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut server = Server::new(TEST_BIND);
+    server.add_file_mount("/clip", file);
+    server.start().expect("server start");
+
+    let addr = server.local_addr().expect("bound address");
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(2)).expect("connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let base_uri = format!("rtsp://{addr}/clip");
+
+    let desc_req = format!("DESCRIBE {base_uri} RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+    let desc_resp = rtsp_request(&mut stream, &desc_req).expect("DESCRIBE response");
+    assert!(
+        desc_resp.contains("profile-level-id=42001e;sprop-parameter-sets="),
+        "{desc_resp}"
+    );
+    assert!(desc_resp.contains("a=range:npt=0-0.030\r\n"), "{desc_resp}");
+
+    let rtp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    rtp.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let rtp_port = rtp.local_addr().unwrap().port();
+    let setup_req = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 2\r\nTransport: RTP/AVP;unicast;client_port={}-{}\r\n\r\n",
+        rtp_port,
+        rtp_port + 1
+    );
+    let setup_resp = rtsp_request(&mut stream, &setup_req).expect("SETUP response");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+    let play_req = format!("PLAY {base_uri} RTSP/1.0\r\nCSeq: 3\r\nSession: {session_id}\r\n\r\n");
+    let play_resp = rtsp_request(&mut stream, &play_req).expect("PLAY response");
+    assert_eq!(header_value(&play_resp, "Range"), Some("npt=0.000-0.030"));
+
+    // Three passes over the clip, on one continuous RTP timeline.
+    let mut buf = [0u8; 1500];
+    let mut timestamps = Vec::new();
+    while timestamps.len() < 3 {
+        let len = rtp.recv(&mut buf).expect("RTP packet");
+        if buf[12] == 0x65 {
+            assert_eq!(&buf[13..len], [0x88, 0x84]);
+            timestamps.push(u32::from_be_bytes(buf[4..8].try_into().unwrap()));
+        }
+    }
+    assert_eq!(timestamps[1].wrapping_sub(timestamps[0]), 2700);
+    assert_eq!(timestamps[2].wrapping_sub(timestamps[1]), 2700);
+
+    server.stop();
+}
+
+/// Send an HTTP GET and read the response: status code, head and body.
+fn http_get(stream: &mut TcpStream, target: &str) -> (u16, String, Vec<u8>) {
+    let request = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    stream.write_all(request.as_bytes()).unwrap();
+    read_http_response(stream)
+}
+
+fn read_http_response(stream: &mut TcpStream) -> (u16, String, Vec<u8>) {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).expect("HTTP response head");
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8(head).unwrap();
+    let status = head[9..12].parse().unwrap();
+    let len: usize = header_value(&head, "Content-Length")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).expect("HTTP response body");
+    (status, head, body)
+}
+
+#[test]
+fn hls_serves_playlists_segments_and_blocking_reloads() {
+    let config = ServerConfig {
+        hls: Some(
+            HlsConfig::new("127.0.0.1:0")
+                .with_segment_duration(Duration::from_secs(1))
+                .with_low_latency(Duration::from_millis(500)),
+        ),
+        ..ServerConfig::default()
+    };
+    let mut server = Server::with_config(TEST_BIND, config);
+    server.start().expect("server start");
+    let addr = server.hls_addr().expect("HLS address");
+    let mut http = TcpStream::connect_timeout(&addr, Duration::from_secs(2)).unwrap();
+    http.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    assert_eq!(http_get(&mut http, "/missing/index.m3u8").0, 404);
+    // The first request starts segmenting the mount; nothing is ready yet.
+    assert_eq!(http_get(&mut http, "/stream/init0.mp4").0, 404);
+
+    let sps = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10,
+    ];
+    let send = |range: std::ops::Range<u64>| {
+        for i in range {
+            let mut frame = Vec::new();
+            if i.is_multiple_of(10) {
+                for nal in [&sps[..], &[0x68, 0xeb, 0xe3, 0xcb]] {
+                    frame.extend_from_slice(&[0, 0, 0, 1]);
+                    frame.extend_from_slice(nal);
+                }
+                frame.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x88, i as u8]);
+            } else {
+                frame.extend_from_slice(&[0, 0, 0, 1, 0x41, 0x9a, i as u8]);
+            }
+            let pts = Duration::from_millis(100 * i);
+            server.send_frame_with_pts("/stream", &frame, pts).unwrap();
+        }
+    };
+    send(0..26);
+
+    let (status, head, body) = http_get(&mut http, "/stream/index.m3u8");
+    assert_eq!(status, 200);
+    assert_eq!(
+        header_value(&head, "Content-Type"),
+        Some("application/vnd.apple.mpegurl")
+    );
+    assert_eq!(
+        header_value(&head, "Access-Control-Allow-Origin"),
+        Some("*")
+    );
+    let playlist = String::from_utf8(body).unwrap();
+    assert!(
+        playlist.contains("#EXT-X-MAP:URI=\"init0.mp4\"\n"),
+        "{playlist}"
+    );
+    assert!(
+        playlist.contains("#EXTINF:1.000,\nseg1.m4s\n"),
+        "{playlist}"
+    );
+    assert!(
+        playlist.contains("#EXT-X-PART:DURATION=0.500,URI=\"part2.0.m4s\",INDEPENDENT=YES\n"),
+        "{playlist}"
+    );
+    assert!(
+        playlist.ends_with("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"part2.1.m4s\"\n"),
+        "{playlist}"
+    );
+
+    let (status, head, init) = http_get(&mut http, "/stream/init0.mp4");
+    assert_eq!(
+        (status, header_value(&head, "Content-Type")),
+        (200, Some("video/mp4"))
+    );
+    assert_eq!(&init[4..8], b"ftyp");
+    let (status, _, segment) = http_get(&mut http, "/stream/seg1.m4s");
+    assert_eq!(status, 200);
+    assert_eq!(&segment[4..8], b"moof");
+
+    // A blocking reload for segment 3 is answered once it completes.
+    http.write_all(b"GET /stream/index.m3u8?_HLS_msn=3 HTTP/1.1\r\n\r\n")
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    send(26..41);
+    let (status, _, body) = read_http_response(&mut http);
+    assert_eq!(status, 200);
+    let playlist = String::from_utf8(body).unwrap();
+    assert!(playlist.contains("seg3.m4s\n"), "{playlist}");
+    assert_eq!(http_get(&mut http, "/stream/index.m3u8?_HLS_msn=9").0, 400);
+
+    server.stop();
+    assert_eq!(server.hls_addr(), None);
+}
+
+/// Read an HTTP or RTSP response head.
+fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).expect("response head");
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+/// Open a POST channel into the tunnel of `cookie` and send `request` on
+/// it, base64-encoded.
+fn tunnel_post(addr: std::net::SocketAddr, cookie: &str, request: &str) -> TcpStream {
+    use base64::prelude::{BASE64_STANDARD, Engine as _};
+
+    let mut post = TcpStream::connect(addr).unwrap();
+    let head = format!(
+        "POST /stream HTTP/1.0\r\nx-sessioncookie: {cookie}\r\n\
+         Content-Type: application/x-rtsp-tunnelled\r\nContent-Length: 32767\r\n\r\n"
+    );
+    post.write_all(head.as_bytes()).unwrap();
+    post.write_all(BASE64_STANDARD.encode(request).as_bytes())
+        .unwrap();
+    post
+}
+
+#[test]
+fn rtsp_over_http_tunnel() {
+    let mut server = Server::new(TEST_BIND);
+    server.start().expect("server start");
+    let addr = server.local_addr().expect("bound address");
+    let base_uri = format!("rtsp://{addr}/stream");
+
+    let mut get = TcpStream::connect(addr).unwrap();
+    get.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    get.write_all(
+        b"GET /stream HTTP/1.0\r\nx-sessioncookie: Zx81cQ\r\n\
+          Accept: application/x-rtsp-tunnelled\r\nPragma: no-cache\r\n\r\n",
+    )
+    .unwrap();
+    let head = read_head(&mut get);
+    assert!(head.starts_with("HTTP/1.0 200 OK"), "{head}");
+    assert_eq!(
+        header_value(&head, "Content-Type"),
+        Some("application/x-rtsp-tunnelled")
+    );
+
+    // Requests go in on POST channels, responses come out on the GET one.
+    let setup = format!(
+        "SETUP {base_uri}/track1 RTSP/1.0\r\nCSeq: 1\r\n\
+         Transport: RTP/AVP/TCP;unicast;interleaved=0-1\r\n\r\n"
+    );
+    let post = tunnel_post(addr, "Zx81cQ", &setup);
+    let setup_resp = read_head(&mut get);
+    assert!(setup_resp.starts_with("RTSP/1.0 200 OK"), "{setup_resp}");
+    let session_id = header_value(&setup_resp, "Session")
+        .and_then(|v| v.split(';').next())
+        .expect("Session header")
+        .to_string();
+
+    // Like QuickTime, send the next request on a new POST channel.
+    post.shutdown(std::net::Shutdown::Both).unwrap();
+    let play = format!("PLAY {base_uri} RTSP/1.0\r\nCSeq: 2\r\nSession: {session_id}\r\n\r\n");
+    let _post = tunnel_post(addr, "Zx81cQ", &play);
+    let play_resp = read_head(&mut get);
+    assert!(play_resp.starts_with("RTSP/1.0 200 OK"), "{play_resp}");
+
+    let idr = [0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00];
+    assert_eq!(server.send_frame(&idr, 3000).unwrap(), 1);
+    let mut prefix = [0u8; 4];
+    get.read_exact(&mut prefix).expect("interleaved frame");
+    assert_eq!(prefix[..2], [b'$', 0]);
+    let mut packet = vec![0u8; u16::from_be_bytes([prefix[2], prefix[3]]) as usize];
+    get.read_exact(&mut packet).unwrap();
+    assert_eq!(&packet[12..], &idr[4..]);
+
+    // A POST without its GET channel is refused.
+    let mut stray = tunnel_post(addr, "unknown", "OPTIONS * RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+    stray
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    assert!(read_head(&mut stray).starts_with("HTTP/1.0 404 Not Found"));
+
+    // Closing the GET channel ends the tunnel's sessions.
+    drop(get);
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while !server.get_viewers().is_empty() && std::time::Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(server.get_viewers().is_empty());
+
+    server.stop();
+}
@@ -1,11 +1,12 @@
 use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
 
 use gstreamer::glib;
 use gstreamer::prelude::*;
 use gstreamer::subclass::prelude::*;
 use gstreamer_base::subclass::prelude::*;
 
-use rtsp::Server;
+use rtsp::{DropPolicy, SendQueueConfig, Server};
 
 static CAT: LazyLock<gstreamer::DebugCategory> = LazyLock::new(|| {
     gstreamer::DebugCategory::new(
@@ -18,12 +19,45 @@ static CAT: LazyLock<gstreamer::DebugCategory> = LazyLock::new(|| {
 const DEFAULT_ADDRESS: &str = "0.0.0.0";
 const DEFAULT_PORT: u32 = 8554;
 const DEFAULT_MOUNT_PATH: &str = "/stream";
+const DEFAULT_SEND_QUEUE: u32 = 0;
+
+/// What the sink's send queue does when it is full (see [`DropPolicy`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, glib::Enum)]
+#[enum_type(name = "GstRtspServerSinkDropPolicy")]
+pub enum SinkDropPolicy {
+    #[default]
+    #[enum_value(
+        name = "Drop the oldest queued frame that is not a keyframe",
+        nick = "drop-oldest-non-keyframe"
+    )]
+    DropOldestNonKeyframe,
+    #[enum_value(
+        name = "Drop frames until the next keyframe",
+        nick = "drop-until-keyframe"
+    )]
+    DropUntilKeyframe,
+    #[enum_value(name = "Block the pipeline until there is room", nick = "block")]
+    Block,
+}
+
+impl From<SinkDropPolicy> for DropPolicy {
+    fn from(policy: SinkDropPolicy) -> Self {
+        match policy {
+            SinkDropPolicy::DropOldestNonKeyframe => DropPolicy::DropOldestNonKeyframe,
+            SinkDropPolicy::DropUntilKeyframe => DropPolicy::DropUntilKeyframe,
+            SinkDropPolicy::Block => DropPolicy::Block,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 struct Settings {
     address: String,
     port: u32,
     mount_path: String,
+    /// Frames the send queue holds; 0 sends from the streaming thread.
+    send_queue: u32,
+    drop_policy: SinkDropPolicy,
 }
 
 impl Default for Settings {
@@ -32,6 +66,8 @@ impl Default for Settings {
             address: DEFAULT_ADDRESS.to_string(),
             port: DEFAULT_PORT,
             mount_path: DEFAULT_MOUNT_PATH.to_string(),
+            send_queue: DEFAULT_SEND_QUEUE,
+            drop_policy: SinkDropPolicy::default(),
         }
     }
 }
@@ -84,6 +120,18 @@ impl ObjectImpl for RtspServerSink {
                     .blurb("RTSP stream path (e.g. /stream or /cam1)")
                     .default_value(Some(DEFAULT_MOUNT_PATH))
                     .build(),
+                glib::ParamSpecUInt::builder("send-queue")
+                    .nick("Send Queue")
+                    .blurb(
+                        "Frames to queue for a sender thread so a slow viewer can't stall \
+                         the pipeline (0 = send from the streaming thread)",
+                    )
+                    .default_value(DEFAULT_SEND_QUEUE)
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("drop-policy", SinkDropPolicy::default())
+                    .nick("Drop Policy")
+                    .blurb("What a full send queue does with the next frame")
+                    .build(),
             ]
         })
     }
@@ -106,6 +154,16 @@ impl ObjectImpl for RtspServerSink {
                     settings.mount_path = s;
                 }
             }
+            "send-queue" => {
+                if let Ok(n) = value.get::<u32>() {
+                    settings.send_queue = n;
+                }
+            }
+            "drop-policy" => {
+                if let Ok(p) = value.get::<SinkDropPolicy>() {
+                    settings.drop_policy = p;
+                }
+            }
             _ => unimplemented!(),
         }
     }
@@ -116,6 +174,8 @@ impl ObjectImpl for RtspServerSink {
             "address" => settings.address.to_value(),
             "port" => settings.port.to_value(),
             "mount-path" => settings.mount_path.to_value(),
+            "send-queue" => settings.send_queue.to_value(),
+            "drop-policy" => settings.drop_policy.to_value(),
             _ => unimplemented!(),
         }
     }
@@ -172,6 +232,17 @@ impl BaseSinkImpl for RtspServerSink {
             )
         })?;
 
+        // Optionally keep network I/O off the streaming thread so a slow
+        // viewer can't stall the pipeline.
+        if settings.send_queue > 0
+            && let Some(mount) = server.mounts().get(&settings.mount_path)
+        {
+            mount.set_send_queue(Some(SendQueueConfig {
+                capacity: settings.send_queue as usize,
+                drop_policy: settings.drop_policy.into(),
+            }));
+        }
+
         let mount_path = settings.mount_path.clone();
         *self.state.lock().unwrap() = Some(State {
             server,
@@ -206,21 +277,38 @@ impl BaseSinkImpl for RtspServerSink {
             gstreamer::FlowError::Error
         })?;
 
-        let ts_increment = buffer
-            .duration()
-            .map(|d| ((d.nseconds() * 90000 + 500_000_000) / 1_000_000_000) as u32)
-            .unwrap_or(3000);
-
         let state_guard = self.state.lock().unwrap();
         let state = state_guard.as_ref().ok_or_else(|| {
             gstreamer::error!(CAT, imp = self, "Element not started");
             gstreamer::FlowError::Error
         })?;
 
-        if let Err(e) = state
-            .server
-            .send_frame_to(&state.mount_path, map.as_slice(), ts_increment)
-        {
+        // Prefer buffer timestamps: the core maps PTS onto the RTP clock, and
+        // DTS keeps the wall-clock reference monotonic when B-frames reorder.
+        let result = match (buffer.pts(), buffer.dts()) {
+            (Some(pts), Some(dts)) => state.server.send_frame_with_pts_dts(
+                &state.mount_path,
+                map.as_slice(),
+                Duration::from_nanos(pts.nseconds()),
+                Duration::from_nanos(dts.nseconds()),
+            ),
+            (Some(pts), None) => state.server.send_frame_with_pts(
+                &state.mount_path,
+                map.as_slice(),
+                Duration::from_nanos(pts.nseconds()),
+            ),
+            (None, _) => {
+                let ts_increment = buffer
+                    .duration()
+                    .map(|d| ((d.nseconds() * 90000 + 500_000_000) / 1_000_000_000) as u32)
+                    .unwrap_or(3000);
+                state
+                    .server
+                    .send_frame_to(&state.mount_path, map.as_slice(), ts_increment)
+            }
+        };
+
+        if let Err(e) = result {
             gstreamer::warning!(CAT, imp = self, "send_frame failed: {}", e);
         }
 
@@ -19,6 +19,7 @@
 //! ```text
 //! gst-launch-1.0 videotestsrc ! x264enc ! rtspserversink address=0.0.0.0 port=8554
 //! gst-launch-1.0 videotestsrc ! x264enc ! rtspserversink port=8554 mount-path=/cam1
+//! gst-launch-1.0 videotestsrc ! x264enc ! rtspserversink send-queue=8 drop-policy=drop-until-keyframe
 //! ```
 //!
 //! ## Properties
@@ -28,6 +29,8 @@
 //! | `address`     | String | `0.0.0.0` | Address to bind the RTSP server to   |
 //! | `port`        | u32    | `8554`    | Port for the RTSP server             |
 //! | `mount-path`  | String | `/stream` | RTSP stream path (e.g. /stream, /cam1) |
+//! | `send-queue`  | u32    | `0`       | Frames queued for a sender thread; 0 sends from the streaming thread |
+//! | `drop-policy` | enum   | `drop-oldest-non-keyframe` | What a full send queue does: `drop-oldest-non-keyframe`, `drop-until-keyframe` or `block` |
 
 mod imp;
 
@@ -10,5 +10,6 @@ fn rtsp_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<server::PyServer>()?;
     m.add_class::<packetizer::PyH264Packetizer>()?;
     m.add_class::<types::PyViewer>()?;
+    m.add_class::<types::PySendStats>()?;
     Ok(())
 }
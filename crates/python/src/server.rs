@@ -1,10 +1,16 @@
 use parking_lot::Mutex;
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::types::PyViewer;
-use rtsp::{Server, ServerConfig};
+use crate::types::{PySendStats, PyViewer};
+use rtsp::media::onvif::OnvifMetadataPacketizer;
+use rtsp::{
+    DropPolicy, HlsConfig, HlsFormat, KeyframeRequestPolicy, MediaFile, Mount, RecordingConfig,
+    RtspError, SendQueueConfig, Server, ServerConfig,
+};
 
 #[pyclass(name = "Server")]
 pub struct PyServer {
@@ -18,6 +24,12 @@ impl PyServer {
     {
         Ok(f(&mut self.inner.lock()))
     }
+
+    fn mount(&self, mount_path: &str) -> PyResult<Arc<Mount>> {
+        self.inner.lock().mounts().get(mount_path).ok_or_else(|| {
+            PyRuntimeError::new_err(RtspError::MountNotFound(mount_path.to_string()).to_string())
+        })
+    }
 }
 
 #[pymethods]
@@ -61,6 +73,59 @@ impl PyServer {
         self.with_server(|s| s.is_running())
     }
 
+    /// Address the server listens on as `"host:port"`, with the actual
+    /// port when created with port 0. `None` while not running.
+    fn local_addr(&self) -> PyResult<Option<String>> {
+        self.with_server(|s| s.local_addr().map(|addr| addr.to_string()))
+    }
+
+    /// Serve every mount as HLS at `http://<addr>/<mount>/index.m3u8`
+    /// from the next `start()`. `format` is `"fmp4"` or `"ts"`;
+    /// `part_duration` (seconds) turns on Low-Latency HLS.
+    #[pyo3(signature = (
+        addr = "0.0.0.0:8888",
+        format = "fmp4",
+        segment_duration = 2.0,
+        playlist_length = 6,
+        part_duration = None,
+    ))]
+    fn enable_hls(
+        &self,
+        addr: &str,
+        format: &str,
+        segment_duration: f64,
+        playlist_length: usize,
+        part_duration: Option<f64>,
+    ) -> PyResult<()> {
+        let format = match format {
+            "fmp4" => HlsFormat::Fmp4,
+            "ts" => HlsFormat::Ts,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown HLS format '{other}'"
+                )));
+            }
+        };
+        let seconds = |value: f64| {
+            Duration::try_from_secs_f64(value)
+                .map_err(|_| PyValueError::new_err(format!("invalid duration {value}")))
+        };
+        let mut config = HlsConfig::new(addr)
+            .with_format(format)
+            .with_segment_duration(seconds(segment_duration)?)
+            .with_playlist_length(playlist_length);
+        if let Some(part) = part_duration {
+            config = config.with_low_latency(seconds(part)?);
+        }
+        self.with_server(|s| s.set_hls(Some(config)))
+    }
+
+    /// Address the HLS server listens on as `"host:port"`; `None` without
+    /// `enable_hls()` or while not running.
+    fn hls_addr(&self) -> PyResult<Option<String>> {
+        self.with_server(|s| s.hls_addr().map(|addr| addr.to_string()))
+    }
+
     /// Send a raw encoded frame to the default mount (`/stream`).
     /// Handles packetization and delivery internally.
     fn send_frame(&self, data: &[u8], timestamp_increment: u32) -> PyResult<usize> {
@@ -83,6 +148,93 @@ impl PyServer {
             .map_err(|e| PyRuntimeError::new_err(e.to_string()))
     }
 
+    /// Send a raw encoded frame to a specific mount, stamped with its
+    /// presentation timestamp in seconds.
+    ///
+    /// The PTS is mapped to the codec clock internally, so no timestamp
+    /// increments are needed. Pass `dts` (seconds, same time base) for
+    /// streams with B-frames that arrive in decode order.
+    #[pyo3(signature = (mount_path, data, pts, dts = None))]
+    fn send_frame_with_pts(
+        &self,
+        mount_path: &str,
+        data: &[u8],
+        pts: f64,
+        dts: Option<f64>,
+    ) -> PyResult<usize> {
+        let pts = seconds_to_duration("pts", pts)?;
+        let server = self.inner.lock();
+        let result = match dts {
+            Some(dts) => server.send_frame_with_pts_dts(
+                mount_path,
+                data,
+                pts,
+                seconds_to_duration("dts", dts)?,
+            ),
+            None => server.send_frame_with_pts(mount_path, data, pts),
+        };
+        result.map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Serve a media file (MP4/MOV, or a raw H.264/H.265 Annex B stream)
+    /// at `mount_path`, played on demand with seeking.
+    ///
+    /// `loop` starts the file over at its end. `frame_rate` spaces the
+    /// frames of Annex B streams, which carry no timing (default 30).
+    /// Must be called before `start`.
+    #[pyo3(signature = (mount_path, file_path, r#loop = false, frame_rate = None))]
+    fn add_file_mount(
+        &self,
+        mount_path: &str,
+        file_path: PathBuf,
+        r#loop: bool,
+        frame_rate: Option<f64>,
+    ) -> PyResult<()> {
+        let mut file = MediaFile::open(&file_path)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?
+            .with_looping(r#loop);
+        if let Some(fps) = frame_rate {
+            if !(fps.is_finite() && fps > 0.0) {
+                return Err(PyValueError::new_err(format!("invalid frame_rate: {fps}")));
+            }
+            file = file.with_frame_rate(fps);
+        }
+        self.inner.lock().add_file_mount(mount_path, file);
+        Ok(())
+    }
+
+    /// Add an ONVIF metadata track (`vnd.onvif.metadata/90000`) to a mount.
+    ///
+    /// Returns the track index to pass to `send_track_frame_with_pts`.
+    #[pyo3(signature = (mount_path = "/stream", payload_type = 107))]
+    fn add_metadata_track(&self, mount_path: &str, payload_type: u8) -> PyResult<usize> {
+        self.inner
+            .lock()
+            .add_track(
+                mount_path,
+                Box::new(OnvifMetadataPacketizer::with_random_ssrc(payload_type)),
+            )
+            .map(|track| track.index())
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Send data (e.g. a `tt:MetadataStream` XML document) to one track of
+    /// a mount, stamped with its presentation timestamp in seconds. Use the
+    /// video frame's PTS to give metadata the frame's RTP timestamp.
+    fn send_track_frame_with_pts(
+        &self,
+        mount_path: &str,
+        track: usize,
+        data: &[u8],
+        pts: f64,
+    ) -> PyResult<usize> {
+        let pts = seconds_to_duration("pts", pts)?;
+        self.inner
+            .lock()
+            .send_track_frame_with_pts(mount_path, track, data, pts)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
     /// Send a pre-packetized RTP packet to a specific session.
     fn send_rtp_packet(&self, session_id: &str, payload: &[u8]) -> PyResult<usize> {
         self.inner
@@ -100,8 +252,118 @@ impl PyServer {
             .map_err(|e| PyRuntimeError::new_err(e.to_string()))
     }
 
+    /// Register a callable invoked when a mount's encoder should produce a keyframe.
+    ///
+    /// Called as `callback(mount_path, reason, session_id)` where `reason` is
+    /// `"pli"`, `"fir"` or `"new-viewer"` and `session_id` may be `None`.
+    /// Requests closer together than `min_interval` seconds are dropped.
+    #[pyo3(signature = (callback, mount_path = "/stream", min_interval = 1.0, on_new_viewer = true))]
+    fn set_keyframe_request_handler(
+        &self,
+        callback: Py<PyAny>,
+        mount_path: &str,
+        min_interval: f64,
+        on_new_viewer: bool,
+    ) -> PyResult<()> {
+        let min_interval = seconds_to_duration("min_interval", min_interval)?;
+        let mount = self.mount(mount_path)?;
+
+        mount.set_keyframe_request_policy(KeyframeRequestPolicy {
+            min_interval,
+            on_new_viewer,
+        });
+        mount.set_keyframe_request_handler(move |req| {
+            Python::attach(|py| {
+                let args = (
+                    req.mount_path.as_str(),
+                    req.reason.to_string(),
+                    req.session_id.as_deref(),
+                );
+                if let Err(e) = callback.call1(py, args) {
+                    e.write_unraisable(py, Some(callback.bind(py)));
+                }
+            });
+        });
+        Ok(())
+    }
+
+    /// Queue a mount's frames for a sender thread so `send_frame*` never
+    /// blocks on the network.
+    ///
+    /// `drop_policy` is `"drop-oldest-non-keyframe"`, `"drop-until-keyframe"`
+    /// or `"block"`. A `capacity` of 0 turns the queue off.
+    #[pyo3(signature = (mount_path = "/stream", capacity = 8, drop_policy = "drop-oldest-non-keyframe"))]
+    fn set_send_queue(&self, mount_path: &str, capacity: usize, drop_policy: &str) -> PyResult<()> {
+        let drop_policy = match drop_policy {
+            "drop-oldest-non-keyframe" => DropPolicy::DropOldestNonKeyframe,
+            "drop-until-keyframe" => DropPolicy::DropUntilKeyframe,
+            "block" => DropPolicy::Block,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown drop policy '{other}'"
+                )));
+            }
+        };
+        let config = (capacity > 0).then_some(SendQueueConfig {
+            capacity,
+            drop_policy,
+        });
+        self.mount(mount_path)?.set_send_queue(config);
+        Ok(())
+    }
+
+    /// Frame counters (sent, dropped, queued) for a mount.
+    #[pyo3(signature = (mount_path = "/stream"))]
+    fn send_stats(&self, mount_path: &str) -> PyResult<PySendStats> {
+        Ok(self.mount(mount_path)?.send_stats().into())
+    }
+
+    /// Start recording a mount to fragmented MP4 files.
+    ///
+    /// `path` names the segments: `archive/cam1.mp4` records
+    /// `archive/cam1-00000.mp4`, `archive/cam1-00001.mp4`, ... A new segment
+    /// starts at the first keyframe after `segment_duration` seconds or
+    /// `segment_size` bytes.
+    #[pyo3(signature = (mount_path, path, segment_duration = None, segment_size = None))]
+    fn start_recording(
+        &self,
+        mount_path: &str,
+        path: PathBuf,
+        segment_duration: Option<f64>,
+        segment_size: Option<u64>,
+    ) -> PyResult<()> {
+        let mut config = RecordingConfig::new(path);
+        if let Some(seconds) = segment_duration {
+            config =
+                config.with_segment_duration(seconds_to_duration("segment_duration", seconds)?);
+        }
+        if let Some(bytes) = segment_size {
+            config = config.with_segment_size(bytes);
+        }
+        self.mount(mount_path)?
+            .start_recording(config)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Stop recording a mount. Returns the paths of the segments written.
+    fn stop_recording(&self, mount_path: &str) -> PyResult<Vec<PathBuf>> {
+        self.mount(mount_path)?
+            .stop_recording()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    fn is_recording(&self, mount_path: &str) -> PyResult<bool> {
+        Ok(self.mount(mount_path)?.is_recording())
+    }
+
     fn get_viewers(&self) -> PyResult<Vec<PyViewer>> {
         let viewers = self.inner.lock().get_viewers();
         Ok(viewers.into_iter().map(PyViewer::from).collect())
     }
 }
+
+/// Convert a non-negative number of seconds from Python into a [`Duration`].
+fn seconds_to_duration(name: &str, seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|e| PyValueError::new_err(format!("invalid {name}: {e}")))
+}
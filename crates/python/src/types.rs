@@ -1,6 +1,6 @@
 use pyo3::prelude::*;
 
-use rtsp::Viewer;
+use rtsp::{SendStats, Viewer};
 
 #[pyclass(name = "Viewer", skip_from_py_object)]
 #[derive(Clone)]
@@ -35,3 +35,34 @@ impl PyViewer {
         )
     }
 }
+
+#[pyclass(name = "SendStats", skip_from_py_object)]
+#[derive(Clone)]
+pub struct PySendStats {
+    #[pyo3(get)]
+    pub frames_sent: u64,
+    #[pyo3(get)]
+    pub frames_dropped: u64,
+    #[pyo3(get)]
+    pub frames_queued: usize,
+}
+
+impl From<SendStats> for PySendStats {
+    fn from(s: SendStats) -> Self {
+        PySendStats {
+            frames_sent: s.frames_sent,
+            frames_dropped: s.frames_dropped,
+            frames_queued: s.frames_queued,
+        }
+    }
+}
+
+#[pymethods]
+impl PySendStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "SendStats(frames_sent={}, frames_dropped={}, frames_queued={})",
+            self.frames_sent, self.frames_dropped, self.frames_queued
+        )
+    }
+}